@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use async_std::fs;
+use serde::Deserialize;
+
+/// 商品的模拟数据, 见 fixtures/catalog.json
+#[derive(Deserialize, Debug, Clone)]
+pub struct Goods {
+    pub name: String,
+    // 逗号分隔
+    pub category: String,
+    // normal、secKill、yuyue、preSale, 转成小写后决定用哪个 item_*.html
+    pub purchase_type: String,
+    pub price: String,
+    pub ori_price: String,
+    // 33 有货, 34 无货, 36 预订
+    pub stock: u32,
+    // 提交订单的结果: success、out_of_stock、fail
+    pub submit: String,
+    // 搜索结果里显示的店铺
+    #[serde(default = "default_shop")]
+    pub shop: String,
+}
+
+fn default_shop() -> String {
+    "京东自营旗舰店".to_string()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Catalog {
+    // 没在 goods 里的 sku 都用 default
+    default: Goods,
+    goods: HashMap<String, Goods>,
+}
+
+impl Catalog {
+    pub fn get(&self, sku: &str) -> Goods {
+        let mut goods = self
+            .goods
+            .get(sku)
+            .cloned()
+            .unwrap_or_else(|| self.default.clone());
+        goods.name = goods.name.replace("{{sku}}", sku);
+        goods
+    }
+
+    /// 名称里包含关键字的商品, 按 sku 排序
+    pub fn search(&self, keyword: &str) -> Vec<(String, Goods)> {
+        let mut ret: Vec<_> = self
+            .goods
+            .iter()
+            .filter(|(_, g)| g.name.contains(keyword))
+            .map(|(sku, g)| (sku.clone(), g.clone()))
+            .collect();
+        ret.sort_by(|a, b| a.0.cmp(&b.0));
+        ret
+    }
+}
+
+/// fixtures 目录, 每次请求都重新读取, 改了文件不用重启
+#[derive(Clone)]
+pub struct Fixtures {
+    dir: PathBuf,
+}
+
+impl Fixtures {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub async fn read(&self, name: &str) -> tide::Result<String> {
+        Ok(fs::read_to_string(self.dir.join(name)).await?)
+    }
+
+    pub async fn catalog(&self) -> tide::Result<Catalog> {
+        Ok(serde_json::from_str(self.read("catalog.json").await?.as_str())?)
+    }
+
+    /// 读取模板并替换其中的 {{key}}
+    pub async fn render(&self, name: &str, vars: &[(&str, &str)]) -> tide::Result<String> {
+        Ok(render(self.read(name).await?, vars))
+    }
+}
+
+pub fn render(mut tpl: String, vars: &[(&str, &str)]) -> String {
+    for (k, v) in vars {
+        tpl = tpl.replace(format!("{{{{{}}}}}", k).as_str(), v);
+    }
+    tpl
+}
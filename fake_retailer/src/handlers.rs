@@ -0,0 +1,748 @@
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+use chrono::{Duration, Local, NaiveDateTime};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tide::http::mime;
+use tide::{Body, Response, StatusCode};
+
+use crate::fixtures::render;
+use crate::{Address, CartItem, FRequest, Order};
+
+const DT_FMT: &str = "%Y-%m-%d %H:%M:%S";
+// 下单后多少分钟内要付款
+const PAY_MINUTES: i64 = 30;
+
+// cookie 里带上这个标记就模拟 cookie 失效
+const EXPIRED_MARK: &str = "fake_expired";
+
+fn html(body: String) -> Response {
+    Response::builder(StatusCode::Ok)
+        .body(body)
+        .content_type(mime::HTML)
+        .build()
+}
+
+fn json_resp(v: Value) -> tide::Result {
+    Ok(Response::builder(StatusCode::Ok)
+        .body(Body::from_json(&v)?)
+        .build())
+}
+
+fn redirect(location: String) -> Response {
+    Response::builder(StatusCode::Found)
+        .header("location", location)
+        .build()
+}
+
+fn cookie(req: &FRequest) -> String {
+    req.header("cookie")
+        .map(|c| c.last().as_str().to_string())
+        .unwrap_or_default()
+}
+
+fn is_expired(req: &FRequest) -> bool {
+    cookie(req).contains(EXPIRED_MARK)
+}
+
+/// 购物车按账号区分, 优先用 cookie 里的 pt_pin
+fn cart_key(req: &FRequest) -> String {
+    let ck = cookie(req);
+    ck.split(';')
+        .map(|s| s.trim())
+        .find(|s| s.starts_with("pt_pin="))
+        .map(|s| s.to_string())
+        .unwrap_or(ck)
+}
+
+/// cookie 失效时京东会跳转到登录页
+fn login_redirect(req: &FRequest) -> Response {
+    redirect(format!("{}/new/login.aspx", req.state().base_url))
+}
+
+fn query_map(req: &FRequest) -> HashMap<String, String> {
+    req.url().query_pairs().into_owned().collect()
+}
+
+// 购物车接口请求里的 sku, 在 operations[].ThePacks[].TheSkus[] 里
+fn collect_skus(v: &Value, skus: &mut Vec<String>) {
+    match v {
+        Value::Object(obj) => {
+            if let Some(Value::Array(items)) = obj.get("TheSkus") {
+                for item in items {
+                    let id = item.get("Id").or_else(|| item.get("id"));
+                    if let Some(id) = id.and_then(|id| id.as_str()) {
+                        skus.push(id.to_string());
+                    }
+                }
+            }
+            obj.values().for_each(|v| collect_skus(v, skus));
+        }
+        Value::Array(items) => items.iter().for_each(|v| collect_skus(v, skus)),
+        _ => {}
+    }
+}
+
+pub async fn login(req: FRequest) -> tide::Result {
+    Ok(html(req.state().fixtures.read("login.html").await?))
+}
+
+pub async fn item_page(req: FRequest) -> tide::Result {
+    let sku = req.param("page")?.trim_end_matches(".html").to_string();
+    let state = req.state();
+    let goods = state.fixtures.catalog().await?.get(&sku);
+    let category = goods
+        .category
+        .split(',')
+        .map(|c| format!("\"{}\"", c.trim()))
+        .collect::<Vec<_>>()
+        .join(",");
+    let page = state
+        .fixtures
+        .render(
+            format!("item_{}.html", goods.purchase_type.to_lowercase()).as_str(),
+            &[
+                ("name", goods.name.as_str()),
+                ("sku", sku.as_str()),
+                ("category", category.as_str()),
+                ("base", state.base_url.as_str()),
+            ],
+        )
+        .await?;
+    Ok(html(page))
+}
+
+pub async fn item_m_page(req: FRequest) -> tide::Result {
+    let sku = req.param("page")?.trim_end_matches(".html").to_string();
+    let state = req.state();
+    let goods = state.fixtures.catalog().await?.get(&sku);
+    // 预约已经开始, 一小时后结束, 两小时后开抢
+    let now = Local::now();
+    let yuyue_start = (now - Duration::hours(1)).format(DT_FMT).to_string();
+    let yuyue_end = (now + Duration::hours(1)).format(DT_FMT).to_string();
+    let qiang_start = (now + Duration::hours(2)).format(DT_FMT).to_string();
+    let page = state
+        .fixtures
+        .render(
+            "item_m.html",
+            &[
+                ("name", goods.name.as_str()),
+                ("sku", sku.as_str()),
+                ("base", state.base_url.as_str()),
+                ("stock", goods.stock.to_string().as_str()),
+                ("yuyue_start", yuyue_start.as_str()),
+                ("yuyue_end", yuyue_end.as_str()),
+                ("qiang_start", qiang_start.as_str()),
+            ],
+        )
+        .await?;
+    Ok(html(page))
+}
+
+#[derive(Deserialize)]
+struct SkusQuery {
+    #[serde(rename = "skuIds", default)]
+    sku_ids: String,
+}
+
+pub async fn stocks(req: FRequest) -> tide::Result {
+    let q: SkusQuery = req.query()?;
+    let catalog = req.state().fixtures.catalog().await?;
+    let mut ret = json!({});
+    for sku in q.sku_ids.split(',').filter(|s| !s.is_empty()) {
+        ret[sku] = json!({"StockState": catalog.get(sku).stock});
+    }
+    json_resp(ret)
+}
+
+pub async fn prices(req: FRequest) -> tide::Result {
+    let q: SkusQuery = req.query()?;
+    let catalog = req.state().fixtures.catalog().await?;
+    let ret = q
+        .sku_ids
+        .split(',')
+        .filter(|s| !s.is_empty())
+        .map(|id| {
+            let goods = catalog.get(id.trim_start_matches("J_"));
+            json!({"id": id, "p": goods.price, "op": goods.ori_price})
+        })
+        .collect::<Vec<_>>();
+    json_resp(Value::from(ret))
+}
+
+// 搜索结果每页的商品数
+const SEARCH_PAGE_SIZE: usize = 30;
+
+pub async fn search(req: FRequest) -> tide::Result {
+    let q = query_map(&req);
+    let keyword = q.get("keyword").map(|s| s.trim()).unwrap_or_default();
+    // 京东的 page 参数是 1、3、5 ... 对应第 1、2、3 ... 页
+    let page = q.get("page").and_then(|p| p.parse::<usize>().ok()).unwrap_or(1).max(1) / 2 + 1;
+    let state = req.state();
+    let found = state.fixtures.catalog().await?.search(keyword);
+    if found.is_empty() {
+        return Ok(html(state.fixtures.render("search_empty.html", &[("keyword", keyword)]).await?));
+    }
+    let pages = found.len().div_ceil(SEARCH_PAGE_SIZE);
+    let page = page.min(pages);
+    let item_tpl = state.fixtures.read("search_item.html").await?;
+    let items = found
+        .iter()
+        .skip((page - 1) * SEARCH_PAGE_SIZE)
+        .take(SEARCH_PAGE_SIZE)
+        .map(|(sku, g)| {
+            // 和京东一样高亮名称里的关键字
+            let name_html = if keyword.is_empty() {
+                g.name.clone()
+            } else {
+                g.name.replace(keyword, format!("<font class=\"skcolor_ljg\">{}</font>", keyword).as_str())
+            };
+            render(
+                item_tpl.clone(),
+                &[
+                    ("sku", sku.as_str()),
+                    ("name", g.name.as_str()),
+                    ("name_html", name_html.as_str()),
+                    ("price", g.price.as_str()),
+                    ("shop", g.shop.as_str()),
+                ],
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let body = state
+        .fixtures
+        .render(
+            "search.html",
+            &[
+                ("keyword", keyword),
+                ("items", items.as_str()),
+                ("page", page.to_string().as_str()),
+                ("pages", pages.to_string().as_str()),
+            ],
+        )
+        .await?;
+    Ok(html(body))
+}
+
+pub async fn item_show_btn(req: FRequest) -> tide::Result {
+    let q = query_map(&req);
+    let callback = q.get("callback").cloned().unwrap_or_default();
+    let sku = q.get("skuId").cloned().unwrap_or_default();
+    let url = format!("{}/captcha.html?skuId={}", req.state().base_url, sku);
+    Ok(Response::builder(StatusCode::Ok)
+        .body(format!("{}({})", callback, json!({"type": "3", "url": url})))
+        .content_type(mime::JAVASCRIPT)
+        .build())
+}
+
+pub async fn add_to_cart(req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    let q = query_map(&req);
+    let sku = q.get("pid").cloned().unwrap_or_default();
+    let num = q.get("pcount").and_then(|n| n.parse().ok()).unwrap_or(1);
+    let key = cart_key(&req);
+    let state = req.state();
+    {
+        let mut carts = state.carts.lock().await;
+        let cart = carts.entry(key).or_default();
+        match cart.iter_mut().find(|item| item.sku == sku) {
+            Some(item) => {
+                item.num += num;
+                item.checked = true;
+            }
+            None => cart.push(CartItem {
+                sku,
+                num,
+                checked: true,
+            }),
+        }
+    }
+    Ok(redirect(format!("{}/addToCart.html", state.base_url)))
+}
+
+pub async fn add_to_cart_done(_req: FRequest) -> tide::Result {
+    Ok(html("<html>商品已成功加入购物车</html>".to_string()))
+}
+
+/// 购物车接口, 通过 functionId 区分, functionId 可能在 url 里也可能在表单里
+pub async fn cart_api(mut req: FRequest) -> tide::Result {
+    let mut params = query_map(&req);
+    let body = req.body_string().await?;
+    params.extend(url::form_urlencoded::parse(body.as_bytes()).into_owned());
+    if is_expired(&req) {
+        return json_resp(json!({"success": false, "message": "未登录"}));
+    }
+    let function_id = params.get("functionId").cloned().unwrap_or_default();
+    let mut skus = vec![];
+    if let Some(body) = params.get("body") {
+        collect_skus(&serde_json::from_str(body).unwrap_or_default(), &mut skus);
+    }
+    let key = cart_key(&req);
+    let mut carts = req.state().carts.lock().await;
+    let cart = carts.entry(key).or_default();
+    match function_id.as_str() {
+        "pcCart_jc_cartUnCheckAll" => cart.iter_mut().for_each(|item| item.checked = false),
+        "pcCart_jc_cartCheckSingle" => cart
+            .iter_mut()
+            .filter(|item| skus.contains(&item.sku))
+            .for_each(|item| item.checked = true),
+        "pcCart_jc_cartRemove" => cart.retain(|item| !skus.contains(&item.sku)),
+        "pcCart_jc_getCurrentCart" => {
+            let sorted = cart
+                .iter()
+                .map(|item| {
+                    json!({"item": {
+                        "Id": item.sku,
+                        "skuUuid": format!("uuid-{}", item.sku),
+                        "num": item.num,
+                        "checked": item.checked,
+                        "items": []
+                    }})
+                })
+                .collect::<Vec<_>>();
+            return json_resp(json!({
+                "success": true,
+                "resultData": {"cartInfo": {"vendors": [{"sorted": sorted}]}}
+            }));
+        }
+        _ => {}
+    }
+    json_resp(json!({"success": true, "message": ""}))
+}
+
+pub async fn yuyue(req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    Ok(html("<html>预约成功</html>".to_string()))
+}
+
+/// 预售商品支付定金, 只结算这一个商品
+pub async fn presale_gate(req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    let q = query_map(&req);
+    let sku = q.get("wids").cloned().unwrap_or_default();
+    let num = q.get("nums").and_then(|n| n.parse().ok()).unwrap_or(1);
+    let key = cart_key(&req);
+    let state = req.state();
+    {
+        let mut carts = state.carts.lock().await;
+        let cart = carts.entry(key).or_default();
+        cart.retain(|item| item.sku != sku);
+        cart.iter_mut().for_each(|item| item.checked = false);
+        cart.push(CartItem {
+            sku,
+            num,
+            checked: true,
+        });
+    }
+    Ok(redirect(format!(
+        "{}/shopping/order/getOrderInfo.action",
+        state.base_url
+    )))
+}
+
+pub async fn order_info(req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    Ok(html("<html>订单结算页</html>".to_string()))
+}
+
+/// 下单结果由勾选的第一个商品在 catalog.json 里的 submit 决定
+pub async fn submit_order(req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    let key = cart_key(&req);
+    let state = req.state();
+    let catalog = state.fixtures.catalog().await?;
+    let mut carts = state.carts.lock().await;
+    let cart = carts.entry(key).or_default();
+    let checked = cart
+        .iter()
+        .filter(|item| item.checked)
+        .cloned()
+        .collect::<Vec<_>>();
+    let first = match checked.first() {
+        Some(item) => catalog.get(&item.sku),
+        None => {
+            return json_resp(
+                json!({"success": false, "resultCode": 0, "message": "购物车里没有勾选的商品"}),
+            )
+        }
+    };
+    match first.submit.as_str() {
+        "success" => {
+            let mut order_id = 0;
+            for item in checked.iter() {
+                let goods = catalog.get(&item.sku);
+                order_id = state.create_order(&item.sku, &goods, item.num).await;
+            }
+            cart.retain(|item| !item.checked);
+            json_resp(json!({"success": true, "orderId": order_id, "message": ""}))
+        }
+        "out_of_stock" => json_resp(
+            json!({"success": false, "resultCode": 600158, "message": "抱歉，您购买的商品无货"}),
+        ),
+        _ => json_resp(json!({"success": false, "resultCode": 0, "message": "系统繁忙，请稍后再试"})),
+    }
+}
+
+pub async fn save_consignee(req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    json_resp(json!({"success": true}))
+}
+
+pub async fn captcha(req: FRequest) -> tide::Result {
+    let sku = query_map(&req).get("skuId").cloned().unwrap_or_default();
+    Ok(redirect(format!(
+        "{}/seckill/seckill.action?skuId={}",
+        req.state().base_url,
+        sku
+    )))
+}
+
+pub async fn seckill_page(_req: FRequest) -> tide::Result {
+    Ok(html("<html>秒杀结算页</html>".to_string()))
+}
+
+pub async fn seckill_init(req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    json_resp(json!({
+        "addressList": [{"id": 1, "name": "张三", "provinceId": 1, "cityId": 72, "countyId": 2819, "townId": 0, "addressDetail": "建国路88号", "mobile": "138****0000", "mobileKey": "fake"}],
+        "invoiceInfo": {},
+        "seckillSkuVO": {"extMap": {"YuShou": "0"}},
+        "token": "fake"
+    }))
+}
+
+pub async fn seckill_submit(req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    let sku = query_map(&req).get("skuId").cloned().unwrap_or_default();
+    let state = req.state();
+    let goods = state.fixtures.catalog().await?.get(&sku);
+    if goods.submit != "success" {
+        return json_resp(json!({"success": false, "errorMessage": "很遗憾没有抢到，再接再厉哦"}));
+    }
+    let order_id = state.create_order(&sku, &goods, 1).await;
+    json_resp(json!({
+        "success": true,
+        "orderId": order_id,
+        "totalMoney": goods.price,
+        "pcUrl": format!("{}/pay.html", state.base_url)
+    }))
+}
+
+async fn render_orders(req: &FRequest, keyword: Option<&str>) -> tide::Result {
+    let state = req.state();
+    // s=1 只看待付款的订单
+    let unpaid_only = query_map(req).get("s").map(|s| s == "1").unwrap_or(false);
+    let row_tpl = state.fixtures.read("order_row.html").await?;
+    let rows = state
+        .orders
+        .lock()
+        .await
+        .iter()
+        .rev()
+        .filter(|o| keyword.map(|kw| o.name.contains(kw)).unwrap_or(true))
+        .filter(|o| !unpaid_only || !o.paid)
+        .map(|o| {
+            // 模拟的订单 30 分钟内付款
+            let pay_deadline = NaiveDateTime::parse_from_str(o.create_dt.as_str(), DT_FMT)
+                .map(|dt| (dt + Duration::minutes(PAY_MINUTES)).format(DT_FMT).to_string())
+                .unwrap_or_default();
+            let pay_url = format!("{}/cashier/index.action?orderId={}", state.base_url, o.order_id);
+            render(
+                row_tpl.clone(),
+                &[
+                    ("order_id", o.order_id.to_string().as_str()),
+                    ("pay_deadline", pay_deadline.as_str()),
+                    ("pay_url", pay_url.as_str()),
+                    ("sku", o.sku.as_str()),
+                    ("name", o.name.as_str()),
+                    ("num", o.num.to_string().as_str()),
+                    ("total", o.total.as_str()),
+                    ("receiver", "张三"),
+                    ("status", if o.paid { "等待收货" } else { "等待付款" }),
+                    ("create_dt", o.create_dt.as_str()),
+                ],
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let page = state
+        .fixtures
+        .render("order_list.html", &[("orders", rows.as_str())])
+        .await?;
+    Ok(html(page))
+}
+
+pub async fn order_list(req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    render_orders(&req, None).await
+}
+
+pub async fn order_search(req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    let keyword = query_map(&req).get("keyword").cloned().unwrap_or_default();
+    render_orders(&req, Some(keyword.as_str())).await
+}
+
+/// 订单物流, 模拟的订单只有下单这一条记录; 不认识的订单返回空列表
+pub async fn order_track(req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    let order_id = query_map(&req).get("orderId").cloned().unwrap_or_default();
+    let orders = req.state().orders.lock().await;
+    let packages: Vec<Value> = orders
+        .iter()
+        .filter(|o| o.order_id.to_string() == order_id)
+        .map(|o| {
+            json!({
+                "carrier": "京东快递",
+                "waybillCode": format!("JDV{}", o.order_id),
+                "isSign": false,
+                "trackList": [{"msgTime": o.create_dt, "content": "您提交了订单，请等待系统确认"}]
+            })
+        })
+        .collect();
+    json_resp(json!({"orderId": order_id, "multiPackageTrackList": packages}))
+}
+
+/// 模拟收银台, 点付款后订单变成等待收货
+pub async fn cashier(req: FRequest) -> tide::Result {
+    let order_id = query_map(&req).get("orderId").cloned().unwrap_or_default();
+    Ok(html(format!(
+        r#"<html><head><meta charset="utf-8"><title>京东收银台</title></head><body>
+<p>订单号: {0}</p><a href="{1}/cashier/pay.action?orderId={0}">付款</a></body></html>"#,
+        order_id,
+        req.state().base_url
+    )))
+}
+
+pub async fn cashier_pay(req: FRequest) -> tide::Result {
+    let order_id = query_map(&req).get("orderId").cloned().unwrap_or_default();
+    let mut paid = false;
+    for o in req.state().orders.lock().await.iter_mut() {
+        if o.order_id.to_string() == order_id {
+            o.paid = true;
+            paid = true;
+        }
+    }
+    Ok(html(format!(
+        r#"<html><head><meta charset="utf-8"></head><body>{}</body></html>"#,
+        if paid { "付款成功" } else { "订单不存在" }
+    )))
+}
+
+// 最多能保存的收货地址数, 和京东一样
+const MAX_ADDRESSES: usize = 20;
+
+fn default_addresses() -> Vec<Address> {
+    vec![
+        Address {
+            id: 3759910743,
+            receiver: "张三".to_string(),
+            area: "北京朝阳区三环以内".to_string(),
+            address: "建国路88号SOHO现代城A座1001".to_string(),
+            mobile: "13800000000".to_string(),
+            phone: String::new(),
+            email: "zhangsan@example.com".to_string(),
+        },
+        Address {
+            id: 3759910888,
+            receiver: "李四".to_string(),
+            area: "上海浦东新区外环以内".to_string(),
+            address: "世纪大道100号".to_string(),
+            mobile: "13900001111".to_string(),
+            phone: String::new(),
+            email: String::new(),
+        },
+    ]
+}
+
+// 列表页上的手机号中间四位打码
+fn mask_mobile(mobile: &str) -> String {
+    if mobile.len() == 11 {
+        format!("{}****{}", &mobile[..3], &mobile[7..])
+    } else {
+        mobile.to_string()
+    }
+}
+
+async fn address_params(req: &mut FRequest) -> tide::Result<HashMap<String, String>> {
+    let body = req.body_string().await?;
+    Ok(url::form_urlencoded::parse(body.as_bytes()).into_owned().collect())
+}
+
+// 新增和修改地址的表单, 校验不通过时返回提示
+fn address_from_params(id: u64, params: &HashMap<String, String>) -> Result<Address, String> {
+    let param = |k: &str| {
+        params
+            .get(format!("addressInfoParam.{}", k).as_str())
+            .map(|v| v.trim().to_string())
+            .unwrap_or_default()
+    };
+    let addr = Address {
+        id,
+        receiver: param("consigneeName"),
+        area: param("areaName"),
+        address: param("consigneeAddress"),
+        mobile: param("mobile"),
+        phone: param("phone"),
+        email: param("email"),
+    };
+    if addr.receiver.is_empty() {
+        return Err("收货人不能为空".to_string());
+    }
+    if param("countyId").parse::<u64>().unwrap_or(0) == 0 {
+        return Err("请选择所在地区".to_string());
+    }
+    if addr.address.is_empty() {
+        return Err("详细地址不能为空".to_string());
+    }
+    if addr.mobile.len() != 11 {
+        return Err("手机号不正确".to_string());
+    }
+    Ok(addr)
+}
+
+pub async fn address_list(req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    let state = req.state();
+    let row_tpl = state.fixtures.read("address_row.html").await?;
+    let rows = state
+        .addresses
+        .lock()
+        .await
+        .entry(cart_key(&req))
+        .or_insert_with(default_addresses)
+        .iter()
+        .map(|a| {
+            render(
+                row_tpl.clone(),
+                &[
+                    ("id", a.id.to_string().as_str()),
+                    ("receiver", a.receiver.as_str()),
+                    ("area", a.area.as_str()),
+                    ("address", a.address.as_str()),
+                    ("mobile", mask_mobile(a.mobile.as_str()).as_str()),
+                    ("phone", a.phone.as_str()),
+                    ("email", a.email.as_str()),
+                ],
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let page = state
+        .fixtures
+        .render("address_list.html", &[("addresses", rows.as_str())])
+        .await?;
+    Ok(html(page))
+}
+
+pub async fn address_add(mut req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    let params = address_params(&mut req).await?;
+    let state = req.state();
+    let mut addresses = state.addresses.lock().await;
+    let list = addresses.entry(cart_key(&req)).or_insert_with(default_addresses);
+    if list.len() >= MAX_ADDRESSES {
+        return json_resp(json!({"success": false, "message": format!("收货地址最多{}个", MAX_ADDRESSES)}));
+    }
+    match address_from_params(state.next_address_id.fetch_add(1, Ordering::SeqCst), &params) {
+        Ok(addr) => {
+            let id = addr.id;
+            list.push(addr);
+            json_resp(json!({"success": true, "addressId": id}))
+        }
+        Err(msg) => json_resp(json!({"success": false, "message": msg})),
+    }
+}
+
+pub async fn address_update(mut req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    let params = address_params(&mut req).await?;
+    let id = params
+        .get("addressInfoParam.id")
+        .and_then(|id| id.parse::<u64>().ok())
+        .unwrap_or_default();
+    let mut addresses = req.state().addresses.lock().await;
+    let list = addresses.entry(cart_key(&req)).or_insert_with(default_addresses);
+    let resp = match (list.iter_mut().find(|a| a.id == id), address_from_params(id, &params)) {
+        (None, _) => json!({"success": false, "message": "地址不存在"}),
+        (Some(_), Err(msg)) => json!({"success": false, "message": msg}),
+        (Some(old), Ok(addr)) => {
+            *old = addr;
+            json!({"success": true, "addressId": id})
+        }
+    };
+    json_resp(resp)
+}
+
+pub async fn address_delete(mut req: FRequest) -> tide::Result {
+    if is_expired(&req) {
+        return Ok(login_redirect(&req));
+    }
+    let params = address_params(&mut req).await?;
+    let id = params.get("addressId").cloned().unwrap_or_default();
+    let mut addresses = req.state().addresses.lock().await;
+    let list = addresses.entry(cart_key(&req)).or_insert_with(default_addresses);
+    let len = list.len();
+    list.retain(|a| a.id.to_string() != id);
+    if list.len() == len {
+        return json_resp(json!({"success": false, "message": "地址不存在"}));
+    }
+    json_resp(json!({"success": true}))
+}
+
+pub async fn areas(req: FRequest) -> tide::Result {
+    let fid = query_map(&req).get("fid").cloned().unwrap_or_default();
+    let areas: Value = serde_json::from_str(req.state().fixtures.read("areas.json").await?.as_str())?;
+    json_resp(areas.get(fid.as_str()).cloned().unwrap_or_else(|| json!([])))
+}
+
+pub async fn coupons(_req: FRequest) -> tide::Result {
+    json_resp(json!({"skuConpons": [], "currentSkuConpons": []}))
+}
+
+impl crate::State {
+    async fn create_order(&self, sku: &str, goods: &crate::fixtures::Goods, num: u32) -> u64 {
+        let order_id = self.next_order_id.fetch_add(1, Ordering::SeqCst);
+        let total = goods.price.parse::<f64>().unwrap_or_default() * num as f64;
+        self.orders.lock().await.push(Order {
+            order_id,
+            sku: sku.to_string(),
+            name: goods.name.clone(),
+            num,
+            total: format!("{:.2}", total),
+            create_dt: Local::now().format(DT_FMT).to_string(),
+            paid: false,
+        });
+        order_id
+    }
+}
@@ -0,0 +1,127 @@
+//! 离线测试用的假京东: 商品、搜索、库存、价格、购物车、订单列表、收货地址和提交订单等接口,
+//! 都按 fixtures 里的页面和 catalog.json 返回响应。
+//! 把客户端 conf.toml 里 [endpoints] 的地址都指向这里, 就可以在没有外网的机器上把整个流程跑一遍。
+use std::collections::HashMap;
+use std::io;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+
+use async_std::sync::Mutex;
+
+mod fixtures;
+mod handlers;
+
+pub use fixtures::render;
+use fixtures::Fixtures;
+
+pub type AppState = Arc<State>;
+pub type FRequest = tide::Request<AppState>;
+
+#[derive(Clone, Debug)]
+pub struct CartItem {
+    pub sku: String,
+    pub num: u32,
+    pub checked: bool,
+}
+
+// 提交成功的订单, 在订单列表里展示
+pub struct Order {
+    pub order_id: u64,
+    pub sku: String,
+    pub name: String,
+    pub num: u32,
+    pub total: String,
+    pub create_dt: String,
+    // 在模拟收银台点了付款
+    pub paid: bool,
+}
+
+// 账号的收货地址, 第一次查询时是两个默认地址
+#[derive(Clone, Debug)]
+pub struct Address {
+    pub id: u64,
+    pub receiver: String,
+    pub area: String,
+    pub address: String,
+    pub mobile: String,
+    pub phone: String,
+    pub email: String,
+}
+
+pub struct State {
+    pub base_url: String,
+    pub fixtures: Fixtures,
+    // 购物车, key 是账号的 pt_pin
+    pub carts: Mutex<HashMap<String, Vec<CartItem>>>,
+    pub orders: Mutex<Vec<Order>>,
+    pub next_order_id: AtomicU64,
+    // 收货地址, key 和购物车一样
+    pub addresses: Mutex<HashMap<String, Vec<Address>>>,
+    pub next_address_id: AtomicU64,
+}
+
+/// 页面里的链接都指向 base_url, 模板和 catalog.json 从 fixtures 目录读取
+pub fn server(base_url: &str, fixtures: PathBuf) -> tide::Server<AppState> {
+    let state = Arc::new(State {
+        base_url: base_url.trim_end_matches('/').to_string(),
+        fixtures: Fixtures::new(fixtures),
+        carts: Mutex::new(HashMap::new()),
+        orders: Mutex::new(Vec::new()),
+        next_order_id: AtomicU64::new(200000000001),
+        addresses: Mutex::new(HashMap::new()),
+        next_address_id: AtomicU64::new(3759911001),
+    });
+    let mut app = tide::with_state(state);
+    // 商品详情、库存、价格
+    app.at("/:page").get(handlers::item_page);
+    app.at("/product/:page").get(handlers::item_m_page);
+    app.at("/stocks").get(handlers::stocks);
+    app.at("/prices/mgets").get(handlers::prices);
+    app.at("/itemShowBtn").get(handlers::item_show_btn);
+    app.at("/Search").get(handlers::search);
+    // 购物车
+    app.at("/gate.action").get(handlers::add_to_cart);
+    app.at("/addToCart.html").get(handlers::add_to_cart_done);
+    app.at("/api").get(handlers::cart_api).post(handlers::cart_api);
+    // 预约、预售、结算和提交订单
+    app.at("/toYuyue.action").get(handlers::yuyue);
+    app.at("/cart/dynamic/gateForSubFlow.action").get(handlers::presale_gate);
+    app.at("/shopping/order/getOrderInfo.action").get(handlers::order_info);
+    app.at("/shopping/order/submitOrder.action").post(handlers::submit_order);
+    app.at("/shopping/dynamic/consignee/saveConsignee.action").post(handlers::save_consignee);
+    // 秒杀
+    app.at("/captcha.html").get(handlers::captcha);
+    app.at("/seckill/seckill.action").get(handlers::seckill_page);
+    app.at("/seckillnew/orderService/pc/init.action").post(handlers::seckill_init);
+    app.at("/seckillnew/orderService/pc/submitOrder.action").post(handlers::seckill_submit);
+    // 订单、收货地址、地区、优惠券、登录
+    app.at("/center/list.action").get(handlers::order_list);
+    app.at("/center/search.action").get(handlers::order_search);
+    app.at("/lazy/getOrderTrackInfoMultiPackage.action").get(handlers::order_track);
+    app.at("/address/getEasyBuyList.action").get(handlers::address_list);
+    app.at("/address/addAddress.action").post(handlers::address_add);
+    app.at("/address/updateAddress.action").post(handlers::address_update);
+    app.at("/address/deleteAddress.action").post(handlers::address_delete);
+    app.at("/area/get").get(handlers::areas);
+    app.at("/coupon/service").get(handlers::coupons);
+    app.at("/new/login.aspx").get(handlers::login);
+    // 收银台
+    app.at("/cashier/index.action").get(handlers::cashier);
+    app.at("/cashier/pay.action").get(handlers::cashier_pay);
+    app
+}
+
+/// 在本机随机端口上启动, 返回 base_url, 测试里用
+pub fn start(fixtures: PathBuf) -> io::Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let base_url = format!("http://{}", listener.local_addr()?);
+    let app = server(base_url.as_str(), fixtures);
+    async_std::task::spawn(async move {
+        if let Err(e) = app.listen(listener).await {
+            eprintln!("fake_retailer 退出:{:?}", e);
+        }
+    });
+    Ok(base_url)
+}
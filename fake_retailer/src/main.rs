@@ -0,0 +1,23 @@
+use std::env;
+
+use async_std::fs;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Config {
+    ip: String,
+    port: u16,
+    base_url: String,
+    fixtures: String,
+}
+
+#[async_std::main]
+async fn main() -> tide::Result<()> {
+    let exe_dir = env::current_exe()?.parent().unwrap().to_owned();
+    let conf_file = exe_dir.join("config/conf.toml");
+    let config: Config = toml::from_slice(fs::read(conf_file).await?.as_slice())?;
+    tide::log::start();
+    let app = fake_retailer::server(config.base_url.as_str(), exe_dir.join(config.fixtures.as_str()));
+    app.listen(format!("{}:{}", config.ip, config.port)).await?;
+    Ok(())
+}
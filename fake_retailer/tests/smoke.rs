@@ -0,0 +1,98 @@
+//! 在随机端口上启动假京东, 按客户端的方式请求各个接口
+use std::path::PathBuf;
+
+use async_std::net::TcpStream;
+use async_std::prelude::*;
+
+fn fixtures() -> PathBuf {
+    PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../fixtures"))
+}
+
+// 发一个 HTTP/1.1 请求, 返回状态码、响应头和响应体
+async fn request(base_url: &str, path: &str, cookie: &str) -> (u16, String, String) {
+    let host = base_url.trim_start_matches("http://");
+    let mut stream = TcpStream::connect(host).await.unwrap();
+    let req = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nCookie: {}\r\nConnection: close\r\n\r\n",
+        path, host, cookie
+    );
+    stream.write_all(req.as_bytes()).await.unwrap();
+    let mut resp = String::new();
+    stream.read_to_string(&mut resp).await.unwrap();
+    let (head, body) = resp.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, head.to_string(), body.to_string())
+}
+
+async fn get(base_url: &str, path: &str) -> String {
+    let (status, _, body) = request(base_url, path, "pt_key=fake; pt_pin=fake").await;
+    assert_eq!(status, 200, "{}", path);
+    body
+}
+
+#[async_std::test]
+async fn test_item_pages() {
+    let base = fake_retailer::start(fixtures()).unwrap();
+    let page = get(&base, "/100009077475.html").await;
+    assert!(page.contains("name: 'Apple iPhone 12 (A2404) 128GB 蓝色 支持移动联通电信5G 双卡双待手机'"));
+    assert!(page.contains(r#"catName: ["手机通讯","手机","手机"]"#));
+    assert!(page.contains(format!("{}/gate.action?pid=100009077475&pcount=1&ptype=1", base).as_str()));
+    // 秒杀、预约、预售的商品用各自的页面
+    assert!(get(&base, "/100012043978.html").await.contains("choose-btn-ko"));
+    assert!(get(&base, "/100010104457.html").await.contains("立即预约"));
+    assert!(get(&base, "/100016777664.html").await.contains("gateForSubFlow.action?wids=100016777664"));
+    // 没有配置的 sku 用 default
+    assert!(get(&base, "/123.html").await.contains("name: '测试商品123'"));
+    let page = get(&base, "/product/100010104457.html").await;
+    assert!(page.contains(r#""skuId":"100010104457""#));
+    assert!(page.contains(r#""yuyue":{"#));
+}
+
+#[async_std::test]
+async fn test_stocks_and_prices() {
+    let base = fake_retailer::start(fixtures()).unwrap();
+    let stocks: serde_json::Value = serde_json::from_str(
+        get(&base, "/stocks?type=getstocks&skuIds=100009077475,100000000001&area=1_72_2819_0")
+            .await
+            .as_str(),
+    )
+    .unwrap();
+    assert_eq!(stocks["100009077475"]["StockState"], 33);
+    assert_eq!(stocks["100000000001"]["StockState"], 34);
+    let prices: serde_json::Value =
+        serde_json::from_str(get(&base, "/prices/mgets?type=1&skuIds=J_100009077475").await.as_str()).unwrap();
+    assert_eq!(prices[0]["id"], "J_100009077475");
+    assert_eq!((prices[0]["p"].as_str(), prices[0]["op"].as_str()), (Some("5999.00"), Some("6299.00")));
+}
+
+#[async_std::test]
+async fn test_search() {
+    let base = fake_retailer::start(fixtures()).unwrap();
+    // keyword=手机
+    let page = get(&base, "/Search?keyword=%E6%89%8B%E6%9C%BA&enc=utf-8&page=1").await;
+    assert!(page.contains("<title>手机 - 商品搜索 - 京东</title>"));
+    assert!(page.contains(r#"data-sku="100009077475""#));
+    assert!(page.contains(r#"双卡双待<font class="skcolor_ljg">手机</font></em>"#));
+    assert!(page.contains("<b>1</b><em>/</em><i>1</i>"));
+    let page = get(&base, "/Search?keyword=%E6%B2%A1%E6%9C%89&enc=utf-8&page=1").await;
+    assert!(page.contains("没有找到"));
+}
+
+#[async_std::test]
+async fn test_orders_and_addresses() {
+    let base = fake_retailer::start(fixtures()).unwrap();
+    let page = get(&base, "/center/list.action").await;
+    assert!(page.contains("我的订单"));
+    assert!(!page.contains("tr-bd"));
+    // 第一次查询时是两个默认地址, 手机号打码
+    let page = get(&base, "/address/getEasyBuyList.action").await;
+    assert!(page.contains(r#"id="addresssDiv-3759910743""#));
+    assert!(page.contains(r#"id="addresssDiv-3759910888""#));
+    assert!(page.contains("138****0000"));
+
+    // cookie 失效时跳转到登录页
+    let (status, head, _) = request(&base, "/center/list.action", "pt_pin=fake; fake_expired=1").await;
+    assert_eq!(status, 302);
+    assert!(head.to_lowercase().contains(format!("location: {}/new/login.aspx", base).as_str()));
+    assert!(get(&base, "/new/login.aspx").await.contains("<title>"));
+}
@@ -0,0 +1,780 @@
+//! 命令行模式, 不启动界面, 可以在没有桌面的 linux 服务器上执行定时抢购或者写脚本
+//!
+//! 命令和参数只在 [`USAGE`] 里写一份, 命令不对时打印出来, 加命令时改那里
+mod output;
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use futures::future::join_all;
+use log::{error, info};
+use serde_json::json;
+
+use crate::error::{OpError, Result};
+use crate::models::*;
+use crate::services::area::AreaService;
+use crate::services::budget::{self, remaining_amount, remaining_num, Spend, BLOCKED};
+use crate::services::dry_run::{self, DryRunOrder, DRY_RUN};
+use crate::services::notify::{self, Event, EventKind};
+use crate::services::order::QueryCondition;
+use crate::services::order_export::{self, DateRange, ExportFormat};
+use crate::services::retailer::{
+    check_max_price, plan_submit_orders, retailer, submit_within_budget, SubmitOrderReq,
+    PRICE_TOO_HIGH,
+};
+use crate::services::scheduler::scheduler;
+use crate::services::shopping_cart::ShoppingCartService;
+use crate::services::store;
+use crate::services::user::UserService;
+use crate::utils::{datetime_fmt_option, greater_than_now, parse_amount};
+use crate::{PKLocal, CONFIG};
+use output::{print_rows, OutputFormat};
+
+/// 命令行的用法, 也是这里支持的命令的唯一说明
+const USAGE: &'static str = "usage: jd_miaosha_rs cli [--format table|json] <command>
+  accounts list
+  accounts check-cookie [--account ACCOUNT]
+  cart list [--keyword KEYWORD] [--category CATEGORY]
+  cart add --sku SKU [--num NUM] [--area AREA]
+  cart remove --id ID
+  cart dry-run --id ID [--off]
+  cart max-price --id ID [--price PRICE]
+  orders list --account ACCOUNT [--status unpaid|receiving|finish|all]
+  orders export [--out FILE.csv|FILE.json] [--from YYYY-MM-DD] [--to YYYY-MM-DD] [--status STATUS]
+  stock check --sku SKU [--area AREA]
+  budget show
+  jobs list
+  jobs cancel --id ID
+  journal [--limit N]
+  run [--area AREA] [--keep] [--reload SECONDS] [--dry-run]
+AREA is an area id path like 1_72_2839_0 or a name like \"北京 朝阳\" searched in the local area tree";
+
+// 不带值的参数
+const SWITCHES: [&'static str; 3] = ["keep", "dry-run", "off"];
+
+#[derive(Debug, Default)]
+struct Args {
+    commands: Vec<String>,
+    options: HashMap<String, String>,
+    switches: HashSet<String>,
+}
+
+impl Args {
+    fn parse(args: Vec<String>) -> Result<Self> {
+        let mut ret = Args::default();
+        let mut iter = args.into_iter();
+        while let Some(arg) = iter.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                if let Some(idx) = name.find('=') {
+                    ret.options
+                        .insert(name[..idx].to_string(), name[idx + 1..].to_string());
+                } else if SWITCHES.contains(&name) {
+                    ret.switches.insert(name.to_string());
+                } else {
+                    match iter.next() {
+                        Some(v) => {
+                            ret.options.insert(name.to_string(), v);
+                        }
+                        None => return Err(invalid_args(format!("--{} needs a value", name))),
+                    }
+                }
+            } else {
+                ret.commands.push(arg);
+            }
+        }
+        Ok(ret)
+    }
+
+    fn opt(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(|s| s.as_str())
+    }
+
+    fn required(&self, name: &str) -> Result<&str> {
+        self.opt(name)
+            .ok_or_else(|| invalid_args(format!("--{} is required", name)))
+    }
+
+    fn parse_opt<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>> {
+        match self.opt(name) {
+            Some(v) => v
+                .parse::<T>()
+                .map(Some)
+                .map_err(|_| invalid_args(format!("--{} is invalid: {}", name, v))),
+            None => Ok(None),
+        }
+    }
+
+    fn area(&self) -> Result<String> {
+        match self.opt("area") {
+            Some(area) => AreaService::resolve(area)
+                .map(|m| m.ids)
+                .ok_or_else(|| invalid_args(format!("unknown area: {}", area))),
+            None => Ok(DEFAULT_ADDR.to_string()),
+        }
+    }
+
+    fn status(&self, default: &str) -> Result<QueryCondition> {
+        match self.opt("status").unwrap_or(default) {
+            "unpaid" => Ok(QueryCondition::Unpaid),
+            "receiving" => Ok(QueryCondition::WaitForReceiving),
+            "finish" => Ok(QueryCondition::Finish),
+            "all" => Ok(QueryCondition::All),
+            s => Err(invalid_args(format!("unknown status: {}", s))),
+        }
+    }
+}
+
+fn invalid_args(msg: String) -> crate::error::JdMiaoshaError {
+    OpError::InvalidArgs(msg).into()
+}
+
+/// 命令行入口, 返回进程的退出码
+pub fn run(args: Vec<String>) -> i32 {
+    let rt = match tokio::runtime::Runtime::new() {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    match rt.block_on(dispatch(args)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("{:?}", e);
+            eprintln!("{}", e);
+            if let crate::error::JdMiaoshaError::OpError(OpError::InvalidArgs(_)) = e {
+                eprintln!("{}", USAGE);
+            }
+            1
+        }
+    }
+}
+
+async fn dispatch(args: Vec<String>) -> Result<()> {
+    let args = Args::parse(args)?;
+    let format = OutputFormat::parse(args.opt("format").unwrap_or("table"))?;
+    UserService::load_config().await?;
+    if args.opt("area").is_some() {
+        // 按名称解析地区要用本地的地区树
+        if let Err(e) = AreaService::init_tree().await {
+            error!("读取地区树失败:{:?}", e);
+        }
+    }
+    let cmds: Vec<&str> = args.commands.iter().map(|s| s.as_str()).collect();
+    match cmds.as_slice() {
+        ["accounts", "list"] => accounts_list(&args, format).await,
+        ["accounts", "check-cookie"] => accounts_check_cookie(&args, format).await,
+        ["cart", "list"] => cart_list(&args, format).await,
+        ["cart", "add"] => cart_add(&args, format).await,
+        ["cart", "remove"] => cart_remove(&args, format).await,
+        ["cart", "dry-run"] => cart_dry_run(&args, format).await,
+        ["cart", "max-price"] => cart_max_price(&args, format).await,
+        ["orders", "list"] => orders_list(&args, format).await,
+        ["orders", "export"] => orders_export(&args, format).await,
+        ["stock", "check"] => stock_check(&args, format).await,
+        ["budget", "show"] => budget_show(&args, format).await,
+        ["jobs", "list"] => jobs_list(&args, format).await,
+        ["jobs", "cancel"] => jobs_cancel(&args, format).await,
+        ["journal"] => journal_list(&args, format).await,
+        ["run"] => run_scheduled(&args, format).await,
+        _ => Err(invalid_args(format!("unknown command: {}", cmds.join(" ")))),
+    }
+}
+
+// 使用界面上保存的激活码
+async fn activate_code() -> Result<String> {
+    match UserService::load_user_activate_info().await? {
+        Some(info) if !info.activate_code.is_empty() => Ok(info.activate_code),
+        _ => Err(OpError::Authorization.into()),
+    }
+}
+
+async fn fetch_all_accounts(code: &str) -> Result<Vec<UserState>> {
+    let mut ret = vec![];
+    let mut page_no = 1;
+    while let Some(page) = UserService::list_accounts(code.to_string(), String::new(), page_no).await? {
+        ret.extend(page.records);
+        if page.page_no >= page.pages {
+            break;
+        }
+        page_no += 1;
+    }
+    Ok(ret)
+}
+
+async fn fetch_all_cart_goods(
+    code: &str,
+    key_word: String,
+    category: Option<u8>,
+) -> Result<Vec<CartProdState>> {
+    let mut ret = vec![];
+    let mut page_no = 1;
+    while let Some(page) =
+        ShoppingCartService::list_cart_goods(code.to_string(), key_word.clone(), page_no, category)
+            .await?
+    {
+        ret.extend(page.records);
+        if page.page_no >= page.pages {
+            break;
+        }
+        page_no += 1;
+    }
+    Ok(ret)
+}
+
+async fn find_account(code: &str, account: &str) -> Result<UserState> {
+    fetch_all_accounts(code)
+        .await?
+        .into_iter()
+        .find(|u| u.account == account)
+        .ok_or_else(|| invalid_args(format!("account not found: {}", account)))
+}
+
+async fn accounts_list(_args: &Args, format: OutputFormat) -> Result<()> {
+    let code = activate_code().await?;
+    let rows: Vec<Vec<String>> = fetch_all_accounts(code.as_str())
+        .await?
+        .iter()
+        .map(|u| {
+            vec![
+                u.id.to_string(),
+                format!("{:?}", u.platform),
+                u.account.clone(),
+                datetime_fmt_option(&u.cookie_last_update_dt),
+            ]
+        })
+        .collect();
+    print_rows(format, &["id", "platform", "account", "cookie_update_dt"], &rows);
+    Ok(())
+}
+
+async fn accounts_check_cookie(args: &Args, format: OutputFormat) -> Result<()> {
+    let code = activate_code().await?;
+    let accounts: Vec<UserState> = fetch_all_accounts(code.as_str())
+        .await?
+        .into_iter()
+        .filter(|u| args.opt("account").map(|a| a == u.account).unwrap_or(true))
+        .collect();
+    let checks = accounts
+        .iter()
+        .map(|u| UserService::is_valid_cookie(u.account.clone(), u.cookie.clone()));
+    let rows: Vec<Vec<String>> = join_all(checks)
+        .await
+        .into_iter()
+        .zip(accounts.iter())
+        .map(|(r, u)| {
+            let valid = match r {
+                Ok((_, valid)) => valid.to_string(),
+                Err(e) => {
+                    error!("{}:{:?}", u.account, e);
+                    "error".to_string()
+                }
+            };
+            vec![u.account.clone(), valid]
+        })
+        .collect();
+    for row in rows.iter().filter(|row| row[1] == "false") {
+        notify::notify(Event::new(
+            EventKind::CookieExpired,
+            "cookie 失效",
+            format!("账号 {} 的 cookie 已失效, 请重新登录", row[0]),
+        ))
+        .await;
+    }
+    print_rows(format, &["account", "valid"], &rows);
+    Ok(())
+}
+
+fn cart_row(item: &CartProdState) -> Vec<String> {
+    vec![
+        item.id.to_string(),
+        item.sku.clone(),
+        item.name.clone(),
+        item.category.to_string(),
+        item.purchase_type.clone(),
+        item.purchase_num.to_string(),
+        item.cur_price.clone(),
+        item.purchase_status.clone(),
+        datetime_fmt_option(&item.yuyue_dt),
+        item.max_price.clone().unwrap_or_default(),
+    ]
+}
+
+const CART_HEADERS: [&'static str; 10] = [
+    "id", "sku", "name", "category", "purchase_type", "num", "price", "status", "yuyue_dt",
+    "max_price",
+];
+
+async fn cart_list(args: &Args, format: OutputFormat) -> Result<()> {
+    let code = activate_code().await?;
+    let key_word = args.opt("keyword").unwrap_or("").to_string();
+    let category = args.parse_opt::<u8>("category")?;
+    let rows: Vec<Vec<String>> = fetch_all_cart_goods(code.as_str(), key_word, category)
+        .await?
+        .iter()
+        .map(cart_row)
+        .collect();
+    print_rows(format, &CART_HEADERS, &rows);
+    Ok(())
+}
+
+async fn cart_add(args: &Args, format: OutputFormat) -> Result<()> {
+    let code = activate_code().await?;
+    let sku = args.required("sku")?;
+    let area = args.area()?;
+    let mut goods = retailer(ProdPlatform::default())
+        .get_prod_info(sku, area.as_str())
+        .await?;
+    goods.purchase_num = args.parse_opt::<u8>("num")?.unwrap_or(1);
+    ShoppingCartService::create_cart_goods(code, goods.cart_body()).await?;
+    print_rows(
+        format,
+        &["sku", "name", "price", "stock", "purchase_type"],
+        &[vec![
+            goods.sku.clone(),
+            goods.name.clone(),
+            goods.cur_price.clone(),
+            goods.status.to_string(),
+            goods.purchase_type.clone(),
+        ]],
+    );
+    Ok(())
+}
+
+async fn cart_remove(args: &Args, format: OutputFormat) -> Result<()> {
+    let code = activate_code().await?;
+    let id = args
+        .parse_opt::<crate::IDType>("id")?
+        .ok_or_else(|| invalid_args("--id is required".to_string()))?;
+    ShoppingCartService::delete_one_cart_goods(code, id).await?;
+    print_rows(format, &["id", "removed"], &[vec![id.to_string(), true.to_string()]]);
+    Ok(())
+}
+
+// 单个商品的试运行开关
+async fn cart_dry_run(args: &Args, format: OutputFormat) -> Result<()> {
+    let code = activate_code().await?;
+    let id = args
+        .parse_opt::<crate::IDType>("id")?
+        .ok_or_else(|| invalid_args("--id is required".to_string()))?;
+    let on = !args.switches.contains("off");
+    let body = json!({
+        "op": if on { 11 } else { 12 },
+        "yuyue_dt":null,
+    })
+    .to_string();
+    ShoppingCartService::update_cart_goods(code, id, body).await?;
+    print_rows(format, &["id", "dry_run"], &[vec![id.to_string(), on.to_string()]]);
+    Ok(())
+}
+
+// 单个商品的最高价格, 不带 --price 就是不限制
+async fn cart_max_price(args: &Args, format: OutputFormat) -> Result<()> {
+    let code = activate_code().await?;
+    let id = args
+        .parse_opt::<crate::IDType>("id")?
+        .ok_or_else(|| invalid_args("--id is required".to_string()))?;
+    let max_price = match args.opt("price") {
+        Some(v) => match parse_amount(v) {
+            Some(p) if p > 0.0 => Some(format!("{:.2}", p)),
+            _ => return Err(invalid_args(format!("--price is invalid: {}", v))),
+        },
+        None => None,
+    };
+    let body = json!({
+        "op":13,
+        "yuyue_dt":null,
+        "max_price":max_price,
+    })
+    .to_string();
+    ShoppingCartService::update_cart_goods(code, id, body).await?;
+    print_rows(
+        format,
+        &["id", "max_price"],
+        &[vec![id.to_string(), max_price.unwrap_or_default()]],
+    );
+    Ok(())
+}
+
+async fn orders_list(args: &Args, format: OutputFormat) -> Result<()> {
+    let code = activate_code().await?;
+    let account = find_account(code.as_str(), args.required("account")?).await?;
+    let cond = args.status("unpaid")?;
+    let since = PKLocal::now();
+    let orders = retailer(account.platform)
+        .get_orders(account.account.clone(), account.cookie.clone(), cond.clone())
+        .await?;
+    budget::sync_orders(account.account.as_str(), &orders, &cond, since).await;
+    let rows: Vec<Vec<String>> = orders
+        .values()
+        .map(|o| {
+            vec![
+                o.order_no.clone(),
+                o.name.clone(),
+                o.purchase_num.clone(),
+                o.total_price.clone(),
+                o.status.clone(),
+                o.create_dt.clone(),
+            ]
+        })
+        .collect();
+    print_rows(
+        format,
+        &["order_no", "name", "num", "total_price", "status", "create_dt"],
+        &rows,
+    );
+    Ok(())
+}
+
+// 所有账号的订单导出到文件, 每个账号后面一行合计; 默认导出全部状态的订单
+async fn orders_export(args: &Args, format: OutputFormat) -> Result<()> {
+    let code = activate_code().await?;
+    let range = DateRange::parse(args.opt("from").unwrap_or(""), args.opt("to").unwrap_or(""))?;
+    let cond = args.status("all")?;
+    let path = args
+        .opt("out")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| order_export::default_path(ExportFormat::Csv));
+    let users = fetch_all_accounts(code.as_str()).await?;
+    let report = order_export::export_orders(users, cond, range, path).await?;
+    let rows: Vec<Vec<String>> = report
+        .totals
+        .iter()
+        .map(|t| {
+            vec![
+                t.account.clone(),
+                t.orders.to_string(),
+                t.num.to_string(),
+                format!("{:.2}", t.amount),
+            ]
+        })
+        .chain(report.failed.iter().map(|a| {
+            vec![a.clone(), "error".to_string(), String::new(), String::new()]
+        }))
+        .collect();
+    print_rows(format, &["account", "orders", "num", "amount"], &rows);
+    // 文件路径输出到 stderr, 不影响 json 输出的解析
+    eprintln!("{}", report.path.display());
+    Ok(())
+}
+
+async fn stock_check(args: &Args, format: OutputFormat) -> Result<()> {
+    let sku = args.required("sku")?.to_string();
+    let area = args.area()?;
+    let (_, status) = retailer(ProdPlatform::default())
+        .get_prod_stock(0, sku.clone(), area.clone())
+        .await?;
+    print_rows(
+        format,
+        &["sku", "area", "stock", "is_stock"],
+        &[vec![sku, area, status.to_string(), status.is_stock().to_string()]],
+    );
+    Ok(())
+}
+
+fn fmt_limit<T: ToString>(v: Option<T>) -> String {
+    v.map(|v| v.to_string()).unwrap_or("-".to_string())
+}
+
+fn budget_row(name: &str, spent: Spend, limit: f64, num_limit: u32) -> Vec<String> {
+    vec![
+        name.to_string(),
+        format!("{:.2}", spent.amount),
+        fmt_limit(remaining_amount(limit, spent.amount).map(|v| format!("{:.2}", v))),
+        spent.num.to_string(),
+        fmt_limit(remaining_num(num_limit, spent.num)),
+    ]
+}
+
+const BLOCKED_HEADERS: [&'static str; 6] = ["create_dt", "account", "id", "sku", "amount", "reason"];
+
+fn print_blocked(format: OutputFormat, blocked: &[budget::BlockedSubmit]) {
+    let rows: Vec<Vec<String>> = blocked
+        .iter()
+        .map(|b| {
+            vec![
+                b.create_dt.clone(),
+                b.account.clone(),
+                b.cart_goods_id.to_string(),
+                b.sku.clone(),
+                format!("{:.2}", b.spend.amount),
+                b.reason.clone(),
+            ]
+        })
+        .collect();
+    print_rows(format, &BLOCKED_HEADERS, &rows);
+}
+
+// 界面上保存的定时任务
+async fn jobs_list(_args: &Args, format: OutputFormat) -> Result<()> {
+    let rows: Vec<Vec<String>> = scheduler()
+        .jobs()
+        .await
+        .into_iter()
+        .map(|j| {
+            vec![
+                j.cart_goods_id.to_string(),
+                j.sku,
+                j.name,
+                j.yuyue_dt,
+                j.fire_dt,
+                format!("{:?}", j.status),
+            ]
+        })
+        .collect();
+    print_rows(
+        format,
+        &["id", "sku", "name", "yuyue_dt", "fire_dt", "status"],
+        &rows,
+    );
+    Ok(())
+}
+
+async fn jobs_cancel(args: &Args, format: OutputFormat) -> Result<()> {
+    let id = args
+        .parse_opt::<crate::IDType>("id")?
+        .ok_or_else(|| invalid_args("--id is required".to_string()))?;
+    let cancelled = scheduler().cancel(id).await;
+    print_rows(format, &["id", "cancelled"], &[vec![id.to_string(), cancelled.to_string()]]);
+    Ok(())
+}
+
+// 本地数据库里最近的下单流水, 默认 50 条
+async fn journal_list(args: &Args, format: OutputFormat) -> Result<()> {
+    let limit = args.parse_opt::<usize>("limit")?.unwrap_or(50);
+    let rows: Vec<Vec<String>> = store::run(move |s| s.journal(limit))
+        .await?
+        .into_iter()
+        .map(|e| {
+            vec![
+                e.id.to_string(),
+                e.create_dt,
+                e.account,
+                e.cart_goods_id.to_string(),
+                e.sku,
+                e.num.to_string(),
+                e.price,
+                e.status,
+            ]
+        })
+        .collect();
+    print_rows(
+        format,
+        &["id", "create_dt", "account", "cart_goods_id", "sku", "num", "price", "status"],
+        &rows,
+    );
+    Ok(())
+}
+
+// 今天的花费、剩余的预算和被拦下的下单
+async fn budget_show(_args: &Args, format: OutputFormat) -> Result<()> {
+    let code = activate_code().await?;
+    budget::sync_accounts(fetch_all_accounts(code.as_str()).await?).await;
+    let summary = budget::summary().await;
+    let b = &summary.budget;
+    let mut rows = vec![budget_row(
+        "*",
+        summary.global,
+        b.max_global_daily,
+        b.max_num_global_daily,
+    )];
+    rows.extend(summary.accounts.iter().map(|(account, spent)| {
+        budget_row(account, *spent, b.max_per_account_daily, b.max_num_per_account_daily)
+    }));
+    print_rows(
+        format,
+        &["account", "spent", "remaining", "num", "remaining_num"],
+        &rows,
+    );
+    print_blocked(format, summary.blocked.as_slice());
+    Ok(())
+}
+
+// 设置了预约时间, 且还没有执行的商品
+fn is_scheduled(item: &CartProdState) -> bool {
+    item.yuyue_dt.is_some()
+        && (item.purchase_status.eq("ready") || item.purchase_status.eq("yuyueing"))
+}
+
+// 和界面上的 SubmitOrderFinish 一致: 有一个成功就是成功, 都没成功才是失败
+fn merge_submit_status(status: &[&'static str]) -> (u8, &'static str) {
+    match ShoppingCartService::merge_status(status) {
+        "success" => (5, "success"),
+        "yuyueing" => (7, "yuyueing"),
+        DRY_RUN => (10, DRY_RUN),
+        _ => (6, "fail"),
+    }
+}
+
+fn dry_run_row(order: &DryRunOrder) -> Vec<String> {
+    vec![
+        order.cart_goods_id.to_string(),
+        order.account_id.to_string(),
+        order.sku.clone(),
+        order.method.clone(),
+        order.url.clone(),
+        order.body.clone(),
+    ]
+}
+
+const DRY_RUN_HEADERS: [&'static str; 6] = ["id", "account_id", "sku", "method", "url", "body"];
+
+async fn submit_cart_goods(
+    code: &str,
+    item: &CartProdState,
+    accounts: &[UserState],
+    area: &str,
+) -> Result<&'static str> {
+    if let Some(max_price) = item.max_price_value() {
+        // 提交前查询最新价格, 超过最高价格就不购买
+        let (cur_price, ok) = check_max_price(item.platform, item.sku.clone(), max_price).await?;
+        let body = if parse_amount(cur_price.as_str()).is_none() {
+            // 查不到价格, 按失败处理
+            json!({"op":6, "yuyue_dt":null})
+        } else {
+            json!({
+                "op": if ok { 14 } else { 15 },
+                "yuyue_dt":null,
+                "cur_price":cur_price,
+            })
+        };
+        ShoppingCartService::update_cart_goods(code.to_string(), item.id, body.to_string()).await?;
+        if !ok {
+            return Ok(if body["op"] == 6 { "fail" } else { PRICE_TOO_HIGH });
+        }
+    }
+    let reqs = plan_submit_orders(item, accounts, area);
+    if reqs.is_empty() {
+        return Ok("skipped");
+    }
+    // 分发给各个账号之前获取一次抢购租约, 不管怎么结束都要释放, 否则 run --keep 会一直续约
+    ShoppingCartService::claim_cart_goods(code, item.id).await?;
+    let r = submit_claimed_goods(code, item, reqs).await;
+    let released = ShoppingCartService::release_cart_goods_claim(code.to_string(), item.id).await;
+    let status = r?;
+    released?;
+    Ok(status)
+}
+
+async fn submit_claimed_goods(
+    code: &str,
+    item: &CartProdState,
+    reqs: Vec<SubmitOrderReq>,
+) -> Result<&'static str> {
+    info!("命令行购买商品:{}-{}, 总次数:{}", item.id, item.sku, reqs.len());
+    let results = join_all(
+        reqs.into_iter()
+            .map(|req| submit_within_budget(item.platform, req)),
+    )
+    .await;
+    let status: Vec<&'static str> = results
+        .into_iter()
+        .filter_map(|r| match r {
+            Ok((_, _, status)) => Some(status),
+            Err(e) => {
+                error!("提交订单失败！{:?}", e);
+                None
+            }
+        })
+        .collect();
+    if !status.is_empty() && status.iter().all(|s| *s == BLOCKED) {
+        // 都超出了预算, 没有提交, 商品保持原来的状态
+        return Ok(BLOCKED);
+    }
+    let (op, status) = merge_submit_status(status.as_slice());
+    let body = json!({
+        "op":op,
+        "yuyue_dt":null,
+    })
+    .to_string();
+    ShoppingCartService::update_cart_goods(code.to_string(), item.id, body).await?;
+    Ok(status)
+}
+
+async fn run_scheduled(args: &Args, format: OutputFormat) -> Result<()> {
+    let code = activate_code().await?;
+    let area = args.area()?;
+    let keep = args.switches.contains("keep");
+    if args.switches.contains("dry-run") {
+        // 只记录要提交的订单, 不真正提交
+        CONFIG.write().await.set_dry_run(true);
+    }
+    // 多久从服务端重新加载一次购物车
+    let reload = Duration::from_secs(args.parse_opt::<u64>("reload")?.unwrap_or(30));
+    let accounts = fetch_all_accounts(code.as_str()).await?;
+    // 先按订单列表统计今天已经花了多少
+    budget::sync_accounts(accounts.clone()).await;
+    let mut executed = HashSet::new();
+    let mut rows = vec![];
+    let mut dry_run_rows = vec![];
+    loop {
+        let items: Vec<CartProdState> = fetch_all_cart_goods(code.as_str(), String::new(), None)
+            .await?
+            .into_iter()
+            .filter(|item| is_scheduled(item) && !executed.contains(&item.id))
+            .collect();
+        if items.is_empty() && !keep {
+            break;
+        }
+        info!("命令行定时购买, 待执行的商品:{}个", items.len());
+        let deadline = PKLocal::now() + chrono::Duration::from_std(reload).unwrap();
+        let mut pending = items;
+        while !pending.is_empty() && PKLocal::now() < deadline {
+            let (due, rest): (Vec<_>, Vec<_>) = pending
+                .into_iter()
+                .partition(|item| !greater_than_now(item.yuyue_dt.as_ref().unwrap()));
+            pending = rest;
+            for item in due.iter() {
+                executed.insert(item.id);
+                let status = match submit_cart_goods(code.as_str(), item, &accounts, area.as_str()).await {
+                    Ok(status) => status,
+                    Err(e) => {
+                        error!("{}-{}:{:?}", item.id, item.sku, e);
+                        "error"
+                    }
+                };
+                if let Some(kind) = EventKind::from_purchase_status(status) {
+                    notify::notify(Event::new(
+                        kind,
+                        format!("{}: {}", status, item.name),
+                        format!("商品:{}, sku:{}, 价格:{}", item.name, item.sku, item.cur_price),
+                    ))
+                    .await;
+                }
+                let mut row = cart_row(item);
+                row[7] = status.to_string();
+                let dry_rows: Vec<Vec<String>> = if status == DRY_RUN {
+                    dry_run::orders(item.id).await.iter().map(dry_run_row).collect()
+                } else {
+                    vec![]
+                };
+                if keep {
+                    // 一直运行时, 执行完一个就输出一个
+                    print_rows(format, &CART_HEADERS, &[row]);
+                    if !dry_rows.is_empty() {
+                        print_rows(format, &DRY_RUN_HEADERS, &dry_rows);
+                    }
+                } else {
+                    rows.push(row);
+                    dry_run_rows.extend(dry_rows);
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        if let Err(e) = ShoppingCartService::renew_cart_goods_claims().await {
+            error!("{:?}", e);
+        }
+        if keep && PKLocal::now() < deadline {
+            tokio::time::sleep((deadline - PKLocal::now()).to_std().unwrap_or_default()).await;
+        }
+    }
+    print_rows(format, &CART_HEADERS, &rows);
+    if !dry_run_rows.is_empty() {
+        print_rows(format, &DRY_RUN_HEADERS, &dry_run_rows);
+    }
+    let blocked = budget::summary().await.blocked;
+    if !blocked.is_empty() {
+        print_blocked(format, blocked.as_slice());
+    }
+    Ok(())
+}
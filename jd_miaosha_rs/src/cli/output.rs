@@ -0,0 +1,82 @@
+use serde_json::{Map, Value};
+
+use crate::error::{OpError, Result};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(OpError::InvalidArgs(format!("unknown format: {}", s)).into()),
+        }
+    }
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::Table
+    }
+}
+
+// 中文等宽字符按两个字符宽度计算, 保证表格能对齐
+fn display_width(s: &str) -> usize {
+    s.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum()
+}
+
+fn pad(s: &str, width: usize) -> String {
+    let mut ret = s.to_string();
+    for _ in display_width(s)..width {
+        ret.push(' ');
+    }
+    ret
+}
+
+/// 按表格或者 json 输出, json 时每一行是一个以表头为 key 的对象
+pub fn print_rows(format: OutputFormat, headers: &[&str], rows: &[Vec<String>]) {
+    match format {
+        OutputFormat::Json => {
+            let data: Vec<Value> = rows
+                .iter()
+                .map(|row| {
+                    let mut m = Map::new();
+                    for (h, v) in headers.iter().zip(row.iter()) {
+                        m.insert(h.to_string(), Value::String(v.clone()));
+                    }
+                    Value::Object(m)
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&data).unwrap_or_default());
+        }
+        OutputFormat::Table => {
+            let mut widths: Vec<usize> = headers.iter().map(|h| display_width(h)).collect();
+            for row in rows.iter() {
+                for (idx, v) in row.iter().enumerate() {
+                    if idx < widths.len() {
+                        widths[idx] = std::cmp::max(widths[idx], display_width(v));
+                    }
+                }
+            }
+            let line = |cols: Vec<String>| cols.join("  ").trim_end().to_string();
+            println!(
+                "{}",
+                line(headers.iter().zip(widths.iter()).map(|(h, w)| pad(h, *w)).collect())
+            );
+            println!(
+                "{}",
+                line(widths.iter().map(|w| "-".repeat(*w)).collect())
+            );
+            for row in rows.iter() {
+                println!(
+                    "{}",
+                    line(row.iter().zip(widths.iter()).map(|(v, w)| pad(v, *w)).collect())
+                );
+            }
+        }
+    }
+}
@@ -27,6 +27,14 @@ pub enum JdMiaoshaError {
     // 程序内部的错误
     SerdeJsonError(#[from] serde_json::Error),
 
+    #[error(transparent)]
+    // 解析平台返回的页面出错
+    ParseError(#[from] ParseError),
+
+    #[error(transparent)]
+    // 本地数据库的错误
+    SqliteError(#[from] rusqlite::Error),
+
     #[error("unknown data store error")]
     Other(#[from] anyhow::Error),
 }
@@ -71,4 +79,43 @@ pub enum OpError {
 
     #[error("Get shoppincart info error!")]
     GetCartInfo,
+
+    #[error("Cart goods {0} is being purchased by another client!")]
+    CartGoodsClaimed(IDType), // 商品的抢购租约被其他客户端持有
+
+    #[error("Invalid arguments: {0}")]
+    InvalidArgs(String), // 命令行参数错误
+
+    #[error("Notify error: {0}")]
+    Notify(String), // 发送通知失败
+
+    #[error("Invalid settings: {0}")]
+    InvalidSettings(String), // 设置的值不合法
+
+    #[error("Save address error: {0}")]
+    SaveAddress(String), // 平台拒绝了新增、修改或删除收货地址
+
+    #[error("Get sub areas of {0} error!")]
+    GetAreas(i64), // 所有地区接口都请求失败
+
+    #[error("Query {0} error: http status {1}!")]
+    QueryFailed(&'static str, u16), // 库存、价格接口返回的状态码不对
+
+    #[error("Query {0} backing off, retry after {1} ms!")]
+    BackingOff(&'static str, i64), // 接口连续出错, 等一会儿再查
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ParseError {
+    #[error("{0}: need to login!")]
+    LoginRequired(&'static str), // 跳转到了登录页, cookie 失效了
+
+    #[error("{0}: element {1} not found!")]
+    MissingElement(&'static str, String), // 页面结构变了
+
+    #[error("{0}: field {1} not found!")]
+    MissingField(&'static str, String),
+
+    #[error("{0}: invalid json, {1}")]
+    InvalidJson(&'static str, String),
 }
@@ -13,16 +13,25 @@ use serde::Deserialize;
 use tokio::sync::RwLock;
 
 use platform::Platform;
+use services::budget::Budget;
+use services::notify::NotifyConfig;
+use services::scheduler::SchedulerConfig;
+use services::endpoints::Endpoints;
+use settings::SettingsLayer;
 
 
 pub mod error;
 pub mod models;
 pub mod platform;
 pub mod services;
+pub mod settings;
 mod ui;
 pub mod utils;
 pub mod types;
 pub mod executor;
+mod cli;
+#[cfg(test)]
+mod testing;
 
 lazy_static! {
     pub static ref CONFIG: Arc<RwLock<Config>> = Arc::new(RwLock::new(Config::default()));
@@ -44,6 +53,23 @@ const HOME: &'static str = "HOME";
 pub struct Config {
     addr: String,
     proxy_ip_pool_url: Option<String>,
+    #[serde(default)]
+    endpoints: Endpoints,
+    // 全局试运行: 所有商品都只走下单流程, 不真正提交订单
+    #[serde(default)]
+    dry_run: bool,
+    // 预算上限
+    #[serde(default)]
+    budget: Budget,
+    // 通知
+    #[serde(default)]
+    notify: NotifyConfig,
+    // 定时任务
+    #[serde(default)]
+    scheduler: SchedulerConfig,
+    // 并发数、各种检查间隔、日志级别等, 可以被环境变量和设置页覆盖
+    #[serde(default)]
+    settings: SettingsLayer,
 }
 
 impl Config {
@@ -54,15 +80,45 @@ impl Config {
     pub fn account_proxy_ip_pool_url(&self) -> Option<String> {
         self.proxy_ip_pool_url.clone()
     }
+
+    pub fn endpoints(&self) -> Endpoints {
+        self.endpoints.clone()
+    }
+
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    pub fn budget(&self) -> Budget {
+        self.budget.clone()
+    }
+
+    pub fn notify(&self) -> NotifyConfig {
+        self.notify.clone()
+    }
+
+    pub fn scheduler(&self) -> SchedulerConfig {
+        self.scheduler.clone()
+    }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let exe_dir = env::current_exe()?.parent().unwrap().to_owned();
     let home = exe_dir.to_str().unwrap();
     env::set_var(HOME, home);
-    let log_yaml = exe_dir.join("config/log.yml");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let is_cli = args.first().map(|a| a == "cli").unwrap_or(false);
+    // 命令行模式的日志只写文件, 避免和命令的输出混在一起
+    let log_yaml = exe_dir.join(if is_cli { "config/log_cli.yml" } else { "config/log.yml" });
     info!("目录:{}, \n日志配置文件:{}", home, log_yaml.display());
-    log4rs::init_file(log_yaml, Default::default()).unwrap();
+    settings::init_logger(log_yaml).unwrap();
+    if is_cli {
+        std::process::exit(cli::run(args[1..].to_vec()));
+    }
     ui::run_app(exe_dir);
     Ok(())
 }
@@ -2,10 +2,13 @@ use iced::{button, text_input};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp::Ordering;
 use std::sync::Arc;
+use super::ProdPlatform;
 
 
 #[derive(Debug, Clone, Default)]
 pub struct AddressInfo {
+    // 账号所在的平台
+    pub platform: ProdPlatform,
     pub account: String,
     pub cookie: Arc<String>,
     pub addr_id: String,
@@ -23,6 +26,8 @@ pub struct AddressInfo {
     pub is_latest_receive_addr: bool,
     // 设置收货地址按钮
     pub set_addr_button_state: button::State,
+    pub edit_button_state: button::State,
+    pub delete_button_state: button::State,
 }
 
 
@@ -43,3 +48,55 @@ impl PartialOrd for AddressInfo {
         }
     }
 }
+
+/// 新增、修改收货地址时填写的内容, 批量设置时作为模板
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct AddressForm {
+    pub receiver: String,
+    // 省、市、区、街道的 id, 没有街道时为 0
+    pub area_ids: [i64; 4],
+    // 地区名称, 如 北京朝阳区三环以内
+    pub area_zone: String,
+    pub address: String,
+    pub mobile_phone: String,
+    pub fixed_line_phone: String,
+    pub email: String,
+}
+
+impl AddressForm {
+    /// 修改已有的地址: 列表页上没有地区 id, 手机号也是打码的, 需要重新填
+    pub fn from_info(addr: &AddressInfo) -> Self {
+        Self {
+            receiver: addr.receiver.clone(),
+            area_zone: addr.area_zone.clone(),
+            address: addr.address.clone(),
+            fixed_line_phone: addr.fixed_line_phone.clone(),
+            email: addr.email.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// 检查必填项, 返回不合法的原因
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.receiver.trim().is_empty() {
+            return Err("收货人不能为空".to_string());
+        }
+        // 省市区三级是必须的
+        if self.area_ids[..3].iter().any(|id| *id <= 0) {
+            return Err("请选择所在地区".to_string());
+        }
+        if self.address.trim().is_empty() {
+            return Err("详细地址不能为空".to_string());
+        }
+        let mobile = self.mobile_phone.trim();
+        if mobile.len() != 11 || !mobile.starts_with('1') || !mobile.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("手机号不正确: {}", mobile));
+        }
+        Ok(())
+    }
+
+    /// 账号里已经有的同一个地址, 批量设置时修改它而不是重复新增
+    pub fn same_as(&self, addr: &AddressInfo) -> bool {
+        self.receiver.trim() == addr.receiver && self.address.trim() == addr.address
+    }
+}
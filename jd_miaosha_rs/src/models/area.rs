@@ -1,4 +1,7 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::utils::parse_datetime;
+use crate::PKDateTime;
 
 pub type Area = BTreeMap<i64, String>;
 pub static DEFAULT_ADDR:&'static str = "1_72_2839_0"; // 北京(1), 朝阳区(72), 四环到五环之间(2839);
@@ -44,4 +47,155 @@ lazy_static!{
     ps
     };
     pub static ref PROVINCE_NAMES:Vec<String> = PROVINCES.values().map(|s|s.to_owned()).collect::<Vec<String>>();
-}
\ No newline at end of file
+}
+
+// 省、市、区县、乡镇四级
+pub const AREA_LEVELS: usize = 4;
+
+/// 搜索到的地区, ids 是四级的完整路径, 如 1_72_2839_0
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct AreaMatch {
+    pub ids: String,
+    // 从省开始的各级名称
+    pub names: Vec<String>,
+}
+
+impl AreaMatch {
+    pub fn display(&self) -> String {
+        self.names.join(" ")
+    }
+
+    /// 四级的 id, 没有的级别是 0
+    pub fn id_list(&self) -> Vec<i64> {
+        self.ids.split('_').map(|id| id.parse().unwrap_or(0)).collect()
+    }
+}
+
+/// 完整的地区树, 存在本地数据库里, 离线时也能选地区和搜索
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AreaTree {
+    // 上级 id -> 下级地区, 0 下面是省
+    children: BTreeMap<i64, Area>,
+    // id -> 上级 id
+    parents: HashMap<i64, i64>,
+    // 最近一次完整刷新的时间, 没有完整刷新过时为空
+    pub version: String,
+}
+
+impl AreaTree {
+    pub fn new(children: BTreeMap<i64, Area>, version: String) -> Self {
+        let parents = children
+            .iter()
+            .flat_map(|(pid, area)| area.keys().map(move |id| (*id, *pid)))
+            .collect();
+        Self {
+            children,
+            parents,
+            version,
+        }
+    }
+
+    /// 还没有缓存时只有写死的省份
+    pub fn provinces_only() -> Self {
+        let mut children = BTreeMap::new();
+        children.insert(0, PROVINCES.clone());
+        Self::new(children, String::new())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// 地区的个数
+    pub fn len(&self) -> usize {
+        self.parents.len()
+    }
+
+    pub fn children(&self, parent_id: i64) -> Option<&Area> {
+        self.children.get(&parent_id).filter(|area| !area.is_empty())
+    }
+
+    /// 所有的 上级 id -> 下级地区, 保存时用
+    pub fn all(&self) -> &BTreeMap<i64, Area> {
+        &self.children
+    }
+
+    pub fn name(&self, id: i64) -> Option<&str> {
+        let pid = self.parents.get(&id)?;
+        self.children.get(pid)?.get(&id).map(|s| s.as_str())
+    }
+
+    /// 从省到这个地区的路径
+    pub fn path(&self, id: i64) -> Vec<(i64, String)> {
+        let mut ret = vec![];
+        let mut cur = id;
+        while cur != 0 && ret.len() < AREA_LEVELS {
+            match self.name(cur) {
+                Some(name) => ret.push((cur, name.to_string())),
+                None => break,
+            }
+            cur = self.parents[&cur];
+        }
+        ret.reverse();
+        ret
+    }
+
+    pub fn to_match(&self, id: i64) -> Option<AreaMatch> {
+        let path = self.path(id);
+        if path.is_empty() {
+            return None;
+        }
+        let mut ids: Vec<String> = path.iter().map(|(id, _)| id.to_string()).collect();
+        ids.resize(AREA_LEVELS, "0".to_string());
+        Some(AreaMatch {
+            ids: ids.join("_"),
+            names: path.into_iter().map(|(_, name)| name).collect(),
+        })
+    }
+
+    /// 按名称搜索, 空格分隔的每个词都要出现在路径上的某一级;
+    /// 返回的都是最后一级, 搜 "朝阳" 会列出朝阳区下面的各个区域。
+    /// 默认地址排在最前面, 其他的按匹配到的级别排, 匹配到区县的排在匹配到街道的前面
+    pub fn search(&self, keyword: &str, limit: usize) -> Vec<AreaMatch> {
+        let words: Vec<&str> = keyword.split_whitespace().collect();
+        if words.is_empty() || limit == 0 {
+            return vec![];
+        }
+        let mut found = vec![];
+        let mut path = vec![];
+        self.search_in(0, &words, &mut path, &mut found);
+        // 排序是稳定的, 同一级的保持地区树里的顺序
+        found.sort_by_key(|(level, m)| (m.ids != DEFAULT_ADDR, *level));
+        found.into_iter().take(limit).map(|(_, m)| m).collect()
+    }
+
+    // 找到的地区和所有词都匹配上时的最深的级别
+    fn search_in(&self, parent_id: i64, words: &[&str], path: &mut Vec<i64>, found: &mut Vec<(usize, AreaMatch)>) {
+        let area = match self.children(parent_id) {
+            Some(area) if path.len() < AREA_LEVELS => area,
+            _ => {
+                let names: Vec<&str> = path.iter().filter_map(|id| self.name(*id)).collect();
+                let levels: Option<Vec<usize>> = words
+                    .iter()
+                    .map(|w| names.iter().position(|n| n.contains(w)))
+                    .collect();
+                if let Some(level) = levels.and_then(|l| l.into_iter().max()) {
+                    found.extend(self.to_match(parent_id).map(|m| (level, m)));
+                }
+                return;
+            }
+        };
+        for id in area.keys() {
+            path.push(*id);
+            self.search_in(*id, words, path, found);
+            path.pop();
+        }
+    }
+
+    /// 超过 days 天没有完整刷新过
+    pub fn is_stale(&self, now: PKDateTime, days: u32) -> bool {
+        parse_datetime(self.version.as_str())
+            .map(|dt| now.signed_duration_since(dt) > chrono::Duration::days(days as i64))
+            .unwrap_or(true)
+    }
+}
@@ -1,10 +1,15 @@
 use crate::{PurchaseType, PKDateTime};
+use super::ProdPlatform;
+use crate::utils::datetime_fmt_option;
+use serde_json::json;
 use iced::button;
 use std::cmp::Ordering;
 use std::fmt::Formatter;
 
 #[derive(Debug, Clone, Default)]
 pub struct GoodsState {
+    // 商品所在的平台
+    pub platform: ProdPlatform,
     pub name: String,
     pub sku: String,
     pub ori_price: String,
@@ -15,6 +20,8 @@ pub struct GoodsState {
     pub purchase_url: String,
     pub yuyue_dt: Option<PKDateTime>,
     pub purchase_type: PurchaseType,
+    // 商品详情页上的分类名称, 逗号分隔, 用于服务端推断商品分类
+    pub category_hint: String,
     pub add_num_button_state: button::State,
     pub sub_num_button_state: button::State,
     pub immediately_buy_button_state: button::State,
@@ -37,6 +44,26 @@ impl Default for StockStatus {
     }
 }
 
+impl GoodsState {
+    // 加入购物车时提交给服务端的数据
+    pub fn cart_body(&self) -> String {
+        json!({
+            "sku":&self.sku,
+            "name":&self.name,
+            "purchase_num":self.purchase_num,
+            "yuyue_dt":datetime_fmt_option(&self.yuyue_dt),
+            "ori_price":&self.ori_price,
+            "cur_price":&self.cur_price,
+            "purchase_type":&self.purchase_type,
+            "is_stock":self.status.is_stock_u8(),
+            "purchase_url":&self.purchase_url,
+            "category_hint":&self.category_hint,
+            "platform":u8::from(self.platform),
+        })
+        .to_string()
+    }
+}
+
 impl StockStatus {
     pub fn is_stock(&self) -> bool {
         // 判定有货无货
@@ -45,6 +72,16 @@ impl StockStatus {
             _ => false,
         }
     }
+    // 库存接口里的状态码, 保存到本地时用, 未知时为空
+    pub fn code(&self) -> &'static str {
+        match self {
+            StockStatus::Distribution => "40",
+            StockStatus::OnSale => "33",
+            StockStatus::OutOfStock => "34",
+            StockStatus::PreSell => "36",
+            StockStatus::Unknown => "",
+        }
+    }
     pub fn is_stock_u8(&self) -> u8 {
         if self.is_stock(){
             1
@@ -118,3 +155,36 @@ impl PartialOrd for GoodsState {
         }
     }
 }
+
+/// 关键字搜索结果里的一个商品
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct SearchItem {
+    pub sku: String,
+    pub name: String,
+    pub price: String,
+    pub shop: String,
+    // 在选中地区的库存
+    pub status: StockStatus,
+}
+
+/// 一页搜索结果, page 从 1 开始
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct SearchPage {
+    pub keyword: String,
+    pub items: Vec<SearchItem>,
+    pub page: u32,
+    pub total_pages: u32,
+}
+
+impl SearchPage {
+    pub fn has_prev(&self) -> bool {
+        self.page > 1
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.page < self.total_pages
+    }
+}
+
+#[cfg(test)]
+mod tests;
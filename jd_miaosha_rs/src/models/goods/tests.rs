@@ -0,0 +1,8 @@
+use super::StockStatus;
+
+#[test]
+fn test_stock_status_code() {
+    for status in &[StockStatus::Distribution, StockStatus::OnSale, StockStatus::OutOfStock, StockStatus::PreSell, StockStatus::Unknown] {
+        assert_eq!(StockStatus::from(status.code()).to_string(), status.to_string());
+    }
+}
@@ -4,11 +4,11 @@ mod user;
 mod address;
 mod shopping_cart;
 mod order;
-pub use goods::{GoodsState, StockStatus};
-pub use area::{Area, PROVINCES, PROVINCE_NAMES, DEFAULT_ADDR, DEFAULT_ADDR_NAMES};
+pub use goods::{GoodsState, SearchItem, SearchPage, StockStatus};
+pub use area::{Area, AreaMatch, AreaTree, AREA_LEVELS, PROVINCES, PROVINCE_NAMES, DEFAULT_ADDR, DEFAULT_ADDR_NAMES};
 pub use user::{UserState, UserInfo, UserInfoStatus, AccountsPageState};
-pub use address::{AddressInfo};
-pub use shopping_cart::{CartProdState, CartProdStatus, ShoppingCartPageState, PInfo, YuyueInfo};
-pub use order::OrderInfo;
+pub use address::{AddressForm, AddressInfo};
+pub use shopping_cart::{CartProdState, CartProdStatus, ShoppingCartPageState, PInfo, YuyueInfo, CategoryInfo};
+pub use order::{OrderInfo, OrderTrack, TrackEvent, TrackPackage};
 
 pub use crate::types::*;
\ No newline at end of file
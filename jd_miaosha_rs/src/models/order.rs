@@ -5,6 +5,8 @@ use std::cmp::Ordering;
 use std::collections::LinkedList;
 use std::sync::Arc;
 use super::ProdPlatform;
+use crate::utils::parse_amount;
+use crate::PKDateTime;
 
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
@@ -17,12 +19,33 @@ pub struct OrderInfo {
     pub total_price: String,
     pub receiver: String,
     pub create_dt: String,
+    // 待付款订单的付款截止时间和付款链接, 页面上没有时为空
+    #[serde(default)]
+    pub pay_deadline: String,
+    #[serde(default)]
+    pub pay_url: String,
     #[serde(with="ProdPlatform")]
     pub platform: ProdPlatform,
     #[serde(skip)]
     pub cookie: Arc<String>, // todo cookie 使用读写锁要做到整个app共享
     #[serde(skip)]
     pub express_button_state: button::State,
+    #[serde(skip)]
+    pub pay_button_state: button::State,
+}
+
+impl OrderInfo {
+    /// 订单金额, "¥5999.00" 这样的
+    pub fn amount(&self) -> f64 {
+        parse_amount(self.total_price.as_str()).unwrap_or_default()
+    }
+
+    /// 商品数量, "x1" 这样的, 取不到时算 1 件
+    pub fn num(&self) -> u32 {
+        parse_amount(self.purchase_num.as_str())
+            .map(|n| n as u32)
+            .unwrap_or(1)
+    }
 }
 
 impl PartialEq for OrderInfo {
@@ -43,3 +66,53 @@ impl PartialOrd for OrderInfo {
     }
 }
 
+
+/// 物流轨迹里的一条记录
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct TrackEvent {
+    pub dt: Option<PKDateTime>,
+    pub content: String,
+}
+
+/// 订单里的一个包裹, events 按时间从新到旧排
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct TrackPackage {
+    pub carrier: String,
+    pub tracking_no: String,
+    pub events: Vec<TrackEvent>,
+    pub delivered: bool,
+}
+
+/// 订单的物流信息, 一个订单可能拆成多个包裹
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct OrderTrack {
+    pub order_no: String,
+    pub packages: Vec<TrackPackage>,
+}
+
+impl OrderTrack {
+    /// 所有包裹都签收了才算签收
+    pub fn delivered(&self) -> bool {
+        !self.packages.is_empty() && self.packages.iter().all(|p| p.delivered)
+    }
+
+    /// 最近一条物流记录的时间
+    pub fn last_update(&self) -> Option<PKDateTime> {
+        self.packages
+            .iter()
+            .flat_map(|p| p.events.iter())
+            .filter_map(|e| e.dt)
+            .max()
+    }
+
+    /// 没有签收, 并且超过 days 天物流没有更新
+    pub fn is_stale(&self, now: PKDateTime, days: u32) -> bool {
+        if self.delivered() {
+            return false;
+        }
+        match self.last_update() {
+            Some(dt) => now.signed_duration_since(dt) > chrono::Duration::days(days as i64),
+            None => false,
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::utils::{datetime_fmt, update_create_dt_date_format};
+use crate::utils::{datetime_fmt, parse_amount, update_create_dt_date_format};
 use crate::*;
 use iced::{button, text_input};
 use serde::ser::SerializeStruct;
@@ -6,13 +6,15 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp::Ordering;
 use std::collections::LinkedList;
 use std::sync::Arc;
-use super::{ProdPlatform, Category};
+use super::ProdPlatform;
 
 
 #[derive(Debug,Copy, Clone, PartialOrd, PartialEq, Deserialize, Serialize)]
 pub enum CartProdStatus{
     Done,
     Editing,
+    // 编辑最高价格
+    EditingMaxPrice,
 }
 
 impl Default for CartProdStatus{
@@ -34,8 +36,9 @@ pub struct CartProdState {
     pub purchase_type: PurchaseType,
     #[serde(with = "ProdPlatform")]
     pub platform: ProdPlatform,
-    #[serde(with = "Category")]
-    pub category: Category,
+    // 商品分类, 对应服务端 goods_category 的 id
+    #[serde(default)]
+    pub category: u8,
     #[serde(
     serialize_with = "update_create_dt_date_format::serialize_none",
     deserialize_with = "update_create_dt_date_format::deserialize_pk_dt")
@@ -54,8 +57,15 @@ pub struct CartProdState {
     #[serde(rename="status")]
     pub purchase_status: String,
     pub is_stock: u8,  // 是否有货
+    // 试运行: 走完下单流程但不提交订单
+    #[serde(default, skip_serializing)]
+    pub dry_run: u8,
     pub ori_price: String,
+    // 最近一次查询到的价格
     pub cur_price: String,
+    // 可接受的最高价格, None 表示不限制
+    #[serde(default)]
+    pub max_price: Option<String>,
     #[serde(skip_serializing,with = "update_create_dt_date_format")]
     pub create_time: Option<PKDateTime>,
     #[serde(skip_serializing,with = "update_create_dt_date_format")]
@@ -87,10 +97,33 @@ pub struct CartProdState {
     // 删除
     pub delete_button_state: button::State,
     #[serde(skip)]
+    // 试运行开关
+    pub dry_run_button_state: button::State,
+    // 最近一次试运行要提交的订单
+    #[serde(skip)]
+    pub dry_run_payload: String,
+    // 最高价格
+    #[serde(skip)]
+    pub max_price_txt: String,
+    #[serde(skip)]
+    pub max_price_input_state: text_input::State,
+    #[serde(skip)]
+    pub max_price_button_state: button::State,
+    #[serde(skip)]
     pub status: CartProdStatus,
 }
 
 
+impl CartProdState {
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run == 1
+    }
+
+    pub fn max_price_value(&self) -> Option<f64> {
+        self.max_price.as_deref().and_then(parse_amount)
+    }
+}
+
 impl PartialEq for CartProdState {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -169,6 +202,13 @@ pub struct PInfo{
 
 }
 
+// 商品分类
+#[derive(Debug, Clone, Default, PartialOrd, PartialEq, Deserialize, Serialize)]
+pub struct CategoryInfo {
+    pub id: u8,
+    pub name: String,
+}
+
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[allow(non_snake_case)]
 pub struct YuyueInfo{
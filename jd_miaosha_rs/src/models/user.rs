@@ -7,6 +7,7 @@ use std::cmp::Ordering;
 use std::collections::LinkedList;
 use std::sync::Arc;
 use mac_address::get_mac_address;
+use super::ProdPlatform;
 
 
 #[derive(Debug, Clone, Deserialize)]
@@ -55,6 +56,9 @@ impl PartialOrd for AccountsPageState{
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct UserState {
     pub id: IDType,
+    // 账号所属的平台
+    #[serde(default, with = "ProdPlatform")]
+    pub platform: ProdPlatform,
     pub account: String,
     pub pwd: String,
     #[serde(serialize_with = "cookie_serialize", deserialize_with="cookie_deserialize")]
@@ -0,0 +1,55 @@
+use std::time::Instant;
+
+use http::Method;
+use log::{error, info};
+use serde_json::json;
+
+use crate::error::Result;
+use crate::services::transport::{HttpClient, HttpResponse};
+
+pub const REQUEST_ID_HEADER: &'static str = "X-Request-Id";
+
+pub fn new_request_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+/// 请求服务端接口: 每个请求都带上 X-Request-Id, 并以 json 记录请求id、耗时和状态码,
+/// 服务端的访问日志用同一个请求id, 出问题时可以从客户端日志一直查到服务端日志
+pub async fn call_api(
+    method: Method,
+    url: String,
+    code: &str,
+    body: Option<String>,
+) -> Result<HttpResponse> {
+    let request_id = new_request_id();
+    let start = Instant::now();
+    let mut builder = HttpClient::new()
+        .request(method.clone(), url.as_str())
+        .header("token", code)
+        .header(REQUEST_ID_HEADER, request_id.as_str());
+    if let Some(body) = body {
+        builder = builder.body(body);
+    }
+    match builder.send().await {
+        Ok(resp) => {
+            info!(target: "api", "{}", json!({
+                "request_id": request_id,
+                "method": method.as_str(),
+                "url": url,
+                "status": resp.status().as_u16(),
+                "latency_ms": start.elapsed().as_millis() as u64,
+            }));
+            Ok(resp)
+        }
+        Err(e) => {
+            error!(target: "api", "{}", json!({
+                "request_id": request_id,
+                "method": method.as_str(),
+                "url": url,
+                "latency_ms": start.elapsed().as_millis() as u64,
+                "error": e.to_string(),
+            }));
+            Err(e)
+        }
+    }
+}
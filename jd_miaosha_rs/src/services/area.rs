@@ -1,12 +1,36 @@
-use crate::error::Result;
-use crate::models::Area;
-use log::{error, debug, info};
+use crate::error::{OpError, Result};
+use crate::models::{Area, AreaMatch, AreaTree, AREA_LEVELS};
+use crate::services::endpoints::endpoints;
+use crate::services::store::{self, AREA_KEY};
+use crate::services::transport::HttpClient;
+use crate::utils::{datetime_fmt, now};
+use futures::future::join_all;
+use log::{error, debug, info, warn};
 use reqwest::StatusCode;
-use serde_json::{to_string, Value};
-use std::env;
-use std::path::PathBuf;
-use tokio::fs::{create_dir_all, File, OpenOptions};
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+// 地区树超过这么多天没有完整刷新, 启动后在后台刷新
+pub const AREA_REFRESH_DAYS: u32 = 30;
+// 刷新地区树时同时请求的个数
+const AREA_FETCH_CONCURRENCY: usize = 8;
+// 刷新地区树时单个地区请求失败后重试的次数
+const AREA_FETCH_RETRIES: usize = 2;
+
+lazy_static! {
+    // 界面上搜索地区是同步的, 用 std 的锁
+    static ref AREA_TREE: RwLock<Arc<AreaTree>> = RwLock::new(Arc::new(AreaTree::provinces_only()));
+}
+
+/// 当前的地区树
+pub fn area_tree() -> Arc<AreaTree> {
+    AREA_TREE.read().unwrap().clone()
+}
+
+fn set_area_tree(tree: AreaTree) {
+    *AREA_TREE.write().unwrap() = Arc::new(tree);
+}
 
 pub struct AreaService;
 
@@ -25,78 +49,147 @@ impl AreaService {
         ret
     }
 
+    /// 先查本地的地区树, 没有时再请求平台
     pub async fn get_sub_areas(parent_area_id: i64) -> Result<Area> {
-        for url in &[
-            format!("https://fts.jd.com/area/get?fid={}", parent_area_id),
-            format!("https://d.jd.com/area/get?fid={}", parent_area_id),
-        ] {
-            let resp = reqwest::get(url.as_str()).await?;
+        if let Some(area) = area_tree().children(parent_area_id) {
+            return Ok(area.clone());
+        }
+        match Self::fetch_sub_areas(parent_area_id).await {
+            Ok(area) => {
+                // 地区也存到本地数据库里
+                let areas = area.clone();
+                if let Err(e) = store::run(move |s| s.store_areas(parent_area_id, &areas)).await {
+                    error!("保存地区失败:{}, {:?}", parent_area_id, e);
+                }
+                Ok(area)
+            }
+            Err(e) => {
+                error!("{:?}", e);
+                Ok(Area::new())
+            }
+        }
+    }
+
+    /// 请求平台的子地区, 所有地址都失败时返回错误
+    pub async fn fetch_sub_areas(parent_area_id: i64) -> Result<Area> {
+        for url in &endpoints().await.areas(parent_area_id.to_string().as_str()) {
+            let resp = HttpClient::new().get(url.as_str()).send().await?;
             let status = resp.status();
-            if status == StatusCode::OK {
-                let r: Value = resp.json().await?;
-                let mut area = Area::new();
-                if r.is_array() {
+            if status != StatusCode::OK {
+                error!("请求地区失败:{} - {}", url.as_str(), status);
+                continue;
+            }
+            let r: Value = resp.json().await?;
+            let mut area = Area::new();
+            match r.as_array() {
+                Some(items) => {
                     let x: &[_] = &['"'];
-                    for v in r.as_array().unwrap() {
-                        area.insert(
-                            v["id"].as_i64().unwrap(),
-                            v["name"].to_string().trim_matches(x).to_string(),
-                        );
+                    for v in items {
+                        if let Some(id) = v["id"].as_i64() {
+                            area.insert(id, v["name"].to_string().trim_matches(x).to_string());
+                        }
                     }
-                } else {
-                    error!(
-                        "Get area error:{} - {} - {}",
-                        url.as_str(),
-                        status,
-                        r.to_string()
-                    );
+                    return Ok(area);
                 }
-                return Ok(area);
-            } else {
-                error!("请求地区失败:{} - {}", url.as_str(), status);
+                None => error!("Get area error:{} - {} - {}", url.as_str(), status, r.to_string()),
             }
         }
-        return Ok(Area::new());
+        Err(OpError::GetAreas(parent_area_id).into())
     }
 
-    fn area_file_path() -> PathBuf {
-        let mut home = PathBuf::from(env::var(crate::HOME).unwrap());
-        let file_path = home.join("db/area.txt");
-        info!("{:?}", file_path);
-        file_path
+    async fn fetch_sub_areas_retry(parent_area_id: i64) -> Result<Area> {
+        let mut r = Self::fetch_sub_areas(parent_area_id).await;
+        for _ in 0..AREA_FETCH_RETRIES {
+            if r.is_ok() {
+                break;
+            }
+            r = Self::fetch_sub_areas(parent_area_id).await;
+        }
+        r
     }
 
-    pub async fn load_area() -> Result<String> {
-        let db_file_path = Self::area_file_path();
-        match File::open(&db_file_path).await {
-            Ok(db_file) => {
-                let mut reader = BufReader::new(db_file);
-                let mut ret = String::with_capacity(32);
-                let _ = reader.read_to_string(&mut ret).await?;
-                Ok(ret)
-            }
-            Err(e) => {
-                use std::io::ErrorKind::NotFound;
-                if e.kind() == NotFound {
-                    let parent = db_file_path.parent().unwrap();
-                    create_dir_all(parent).await?;
-                    File::create(db_file_path.as_path()).await?;
-                    return Ok("".to_string());
+    /// 一级一级请求完整的地区树; 单个地区重试后还是失败时用本地原来的, 本地也没有就跳过,
+    /// 省份请求失败时整个失败
+    pub async fn fetch_tree() -> Result<AreaTree> {
+        let old = area_tree();
+        let mut children = BTreeMap::new();
+        let mut parents = vec![0i64];
+        let mut failed = 0;
+        for _ in 0..AREA_LEVELS {
+            let mut next = vec![];
+            for chunk in parents.chunks(AREA_FETCH_CONCURRENCY) {
+                let results = join_all(chunk.iter().map(|id| Self::fetch_sub_areas_retry(*id))).await;
+                for (id, r) in chunk.iter().zip(results) {
+                    let area = match r {
+                        Ok(area) => area,
+                        Err(e) if *id == 0 => return Err(e),
+                        Err(e) => {
+                            failed += 1;
+                            warn!("请求地区失败, 用本地原来的:{}, {:?}", id, e);
+                            match old.all().get(id) {
+                                Some(area) => area.clone(),
+                                None => continue,
+                            }
+                        }
+                    };
+                    next.extend(area.keys().cloned());
+                    children.insert(*id, area);
                 }
-                return Err(e.into());
             }
+            debug!("请求地区树, 下一级有 {} 个地区", next.len());
+            parents = next;
         }
+        if failed > 0 {
+            warn!("刷新地区树时有 {} 个地区请求失败", failed);
+        }
+        Ok(AreaTree::new(children, datetime_fmt(now())))
+    }
+
+    /// 启动时读本地的地区树, 返回是否需要刷新
+    pub async fn init_tree() -> Result<bool> {
+        let tree = store::run(|s| s.load_area_tree()).await?;
+        let tree = if tree.is_empty() { AreaTree::provinces_only() } else { tree };
+        let stale = tree.is_stale(now(), AREA_REFRESH_DAYS);
+        set_area_tree(tree);
+        Ok(stale)
+    }
+
+    /// 刷新完整的地区树并保存, 失败时保留原来的, 返回地区个数
+    pub async fn refresh_tree() -> Result<usize> {
+        let tree = Self::fetch_tree().await?;
+        let len = tree.len();
+        let stored = tree.clone();
+        store::run(move |s| s.store_area_tree(&stored)).await?;
+        set_area_tree(tree);
+        info!("地区树已刷新, 共 {} 个地区", len);
+        Ok(len)
+    }
+
+    /// 把 1_72_2839_0 或 "朝阳" 这样的输入解析成完整的地区路径
+    pub fn resolve(text: &str) -> Option<AreaMatch> {
+        let text = text.trim();
+        let tree = area_tree();
+        let ids: Option<Vec<i64>> = text.split('_').map(|id| id.parse().ok()).collect();
+        if let Some(ids) = ids {
+            // 已经是 id 路径了, 本地有这个地区时补上名称
+            let last = ids.iter().rev().find(|id| **id != 0).cloned().unwrap_or(0);
+            return Some(tree.to_match(last).filter(|m| m.ids == text).unwrap_or(AreaMatch {
+                ids: text.to_string(),
+                names: vec![],
+            }));
+        }
+        tree.search(text, 1).into_iter().next()
+    }
+
+    // 选中的收货地区, 如 1_72_2819_0
+    pub async fn load_area() -> Result<String> {
+        Ok(store::run(|s| s.get_setting(AREA_KEY)).await?.unwrap_or_default())
     }
 
     pub async fn store_area(data: String) -> Result<()> {
-        let db_file_path = Self::area_file_path();
-        let mut db_file = OpenOptions::new()
-            .write(true)
-            .append(false)
-            .truncate(true)
-            .open(db_file_path)
-            .await?;
-        db_file.write_all(data.as_bytes()).await?;
-        Ok(())
+        store::run(move |s| s.set_setting(AREA_KEY, data.as_str())).await
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,103 @@
+use http::StatusCode;
+
+use crate::error::{JdMiaoshaError, OpError};
+use crate::models::AreaTree;
+use crate::services::area::{AreaService, area_tree};
+use crate::services::transport::{HttpResponse, JdScenario};
+use crate::testing::{area_children, install};
+
+#[test]
+fn test_area_tree_search() {
+    let tree = AreaTree::new(area_children(), String::new());
+    assert_eq!(tree.len(), 7);
+    let ids = |keyword: &str, limit: usize| -> Vec<String> {
+        tree.search(keyword, limit).into_iter().map(|m| m.ids).collect()
+    };
+    // 匹配到区县时展开到最后一级, 默认地址排在最前面
+    assert_eq!(ids("朝阳", 10), vec!["1_72_2839_0", "1_72_2819_0"]);
+    assert_eq!(ids("朝阳", 1), vec!["1_72_2839_0"]);
+    assert_eq!(ids("北京 四环", 10), vec!["1_72_2839_0"]);
+    assert_eq!(ids("海淀", 10), vec!["1_2800_0_0"]);
+    assert_eq!(ids("上海", 10), vec!["2_2813_0_0"]);
+    assert!(ids("上海 朝阳", 10).is_empty());
+    assert!(ids("  ", 10).is_empty());
+
+    // 匹配到上一级的排在匹配到下一级的前面
+    let mut children = area_children();
+    children.get_mut(&0).unwrap().insert(5, "辽宁".to_string());
+    children.insert(5, vec![(6, "沈阳市".to_string()), (7, "朝阳市".to_string())].into_iter().collect());
+    children.insert(6, vec![(8, "朝阳街道".to_string())].into_iter().collect());
+    let ranked = AreaTree::new(children, String::new());
+    let ids: Vec<String> = ranked.search("朝阳", 10).into_iter().map(|m| m.ids).collect();
+    assert_eq!(ids, vec!["1_72_2839_0", "1_72_2819_0", "5_7_0_0", "5_6_8_0"]);
+
+    let m = tree.to_match(2839).unwrap();
+    assert_eq!(m.display(), "北京 朝阳区 三环到四环之间");
+    assert_eq!(m.id_list(), vec![1, 72, 2839, 0]);
+    assert_eq!(tree.to_match(72).unwrap().ids, "1_72_0_0");
+    assert!(tree.to_match(999).is_none());
+    assert!(tree.children(2800).is_none());
+
+    // 没有完整刷新过或者太旧了要刷新
+    let now = crate::utils::now();
+    assert!(tree.is_stale(now, 30));
+    let tree = AreaTree::new(area_children(), crate::utils::datetime_fmt(now - chrono::Duration::days(1)));
+    assert!(!tree.is_stale(now, 30));
+    assert!(tree.is_stale(now + chrono::Duration::days(31), 30));
+}
+
+#[tokio::test]
+async fn test_refresh_area_tree() {
+    let g = install(JdScenario::Success).await;
+    for (fid, area) in area_children() {
+        let items: Vec<_> = area.iter().map(|(id, name)| serde_json::json!({"id": id, "name": name})).collect();
+        g.mock.on(format!("area/get?fid={} ", fid).as_str(), HttpResponse::json_ok(serde_json::json!(items)));
+    }
+    for fid in &[2813, 2819, 2839] {
+        g.mock.on(format!("area/get?fid={} ", fid).as_str(), HttpResponse::json_ok(serde_json::json!([])));
+    }
+    let tree = AreaService::fetch_tree().await.unwrap();
+    assert_eq!(tree.len(), 7);
+    assert_eq!(tree.search("朝阳", 10), AreaTree::new(area_children(), String::new()).search("朝阳", 10));
+    // 每个地区只请求一次
+    assert_eq!(g.mock.hits("fid=2819 "), 1);
+
+    assert_eq!(AreaService::refresh_tree().await.unwrap(), 7);
+    assert!(!area_tree().version.is_empty());
+    assert_eq!(AreaService::resolve("朝阳").unwrap().ids, "1_72_2839_0");
+    assert_eq!(AreaService::resolve(" 北京 四环 ").unwrap().ids, "1_72_2839_0");
+    let m = AreaService::resolve("1_72_2839_0").unwrap();
+    assert_eq!(m.names, vec!["北京", "朝阳区", "三环到四环之间"]);
+    // 本地没有的 id 原样使用
+    assert_eq!(AreaService::resolve("5_142_143_0").unwrap().ids, "5_142_143_0");
+    assert!(AreaService::resolve("火星").is_none());
+    // 本地有的地区不再请求
+    let hits = g.mock.hits("fid=72 ");
+    assert_eq!(AreaService::get_sub_areas(72).await.unwrap().len(), 2);
+    assert_eq!(g.mock.hits("fid=72 "), hits);
+
+    // 单个地区失败时重试, 还是失败就用本地原来的
+    g.mock.replace("area/get?fid=72 ", HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR, ""));
+    let hits = g.mock.hits("fid=72 ");
+    assert_eq!(AreaService::refresh_tree().await.unwrap(), 7);
+    assert!(g.mock.hits("fid=72 ") >= hits + 3);
+    assert_eq!(AreaService::resolve("朝阳").unwrap().ids, "1_72_2839_0");
+
+    // 本地也没有的就跳过
+    g.mock.replace("area/get?fid=1 ", HttpResponse::json_ok(serde_json::json!([
+        {"id": 72, "name": "朝阳区"}, {"id": 2800, "name": "海淀区"}, {"id": 2801, "name": "东城区"}
+    ])));
+    g.mock.on("area/get?fid=2801 ", HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR, ""));
+    assert_eq!(AreaService::refresh_tree().await.unwrap(), 8);
+    assert_eq!(AreaService::resolve("东城").unwrap().ids, "1_2801_0_0");
+
+    // 省份都请求不到时整个失败, 保留原来的地区树
+    g.mock.replace("area/get?fid=0 ", HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR, ""));
+    let version = area_tree().version.clone();
+    assert!(matches!(
+        AreaService::refresh_tree().await,
+        Err(JdMiaoshaError::OpError(OpError::GetAreas(0)))
+    ));
+    assert_eq!(area_tree().version, version);
+    assert_eq!(area_tree().len(), 8);
+}
@@ -0,0 +1,377 @@
+//! 预算: 提交订单前按 价格 × 数量 检查单笔、单个账号每天、所有账号每天的金额和数量上限,
+//! 超出的不提交并记录原因。已经下单的花费优先按订单列表里的订单金额统计, 当天的花费和拦截记录存在本地数据库里,
+//! 重启后不会清零
+use std::collections::{BTreeMap, HashMap};
+
+use futures::future::join_all;
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{RwLock, RwLockWriteGuard};
+
+use crate::models::{OrderInfo, UserState};
+use crate::services::order::QueryCondition;
+use crate::services::retailer::retailer;
+use crate::services::store;
+use crate::utils::{date_fmt, datetime_fmt, parse_amount};
+use crate::{IDType, PKDateTime, PKLocal, CONFIG};
+
+// 超出预算, 没有提交订单
+pub const BLOCKED: &'static str = "blocked";
+// 最多保留的拦截记录
+const MAX_BLOCKED: usize = 100;
+
+/// 预算上限, 在 conf.toml 的 [budget] 里配置, 0 表示不限制
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, PartialOrd)]
+#[serde(default)]
+pub struct Budget {
+    // 单笔订单的金额
+    pub max_per_order: f64,
+    // 单个账号每天的金额
+    pub max_per_account_daily: f64,
+    // 所有账号每天的金额
+    pub max_global_daily: f64,
+    // 单笔订单的数量
+    pub max_num_per_order: u32,
+    // 单个账号每天的数量
+    pub max_num_per_account_daily: u32,
+    // 所有账号每天的数量
+    pub max_num_global_daily: u32,
+}
+
+/// 花费的金额和数量
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct Spend {
+    pub amount: f64,
+    pub num: u32,
+}
+
+impl Spend {
+    pub fn new(amount: f64, num: u32) -> Self {
+        Self { amount, num }
+    }
+
+    fn add(self, other: Spend) -> Spend {
+        Spend::new(self.amount + other.amount, self.num + other.num)
+    }
+}
+
+impl Budget {
+    /// 检查这次下单会不会超出预算, 超出时返回原因
+    pub fn check(&self, order: Spend, account_spent: Spend, global_spent: Spend) -> Option<String> {
+        let account = account_spent.add(order);
+        let global = global_spent.add(order);
+        if self.max_per_order > 0.0 && order.amount > self.max_per_order {
+            Some(format!("单笔金额{:.2}超过上限{:.2}", order.amount, self.max_per_order))
+        } else if self.max_num_per_order > 0 && order.num > self.max_num_per_order {
+            Some(format!("单笔数量{}超过上限{}", order.num, self.max_num_per_order))
+        } else if self.max_per_account_daily > 0.0 && account.amount > self.max_per_account_daily {
+            Some(format!(
+                "账号今天的金额{:.2}超过上限{:.2}",
+                account.amount, self.max_per_account_daily
+            ))
+        } else if self.max_num_per_account_daily > 0 && account.num > self.max_num_per_account_daily {
+            Some(format!(
+                "账号今天的数量{}超过上限{}",
+                account.num, self.max_num_per_account_daily
+            ))
+        } else if self.max_global_daily > 0.0 && global.amount > self.max_global_daily {
+            Some(format!(
+                "今天的总金额{:.2}超过上限{:.2}",
+                global.amount, self.max_global_daily
+            ))
+        } else if self.max_num_global_daily > 0 && global.num > self.max_num_global_daily {
+            Some(format!(
+                "今天的总数量{}超过上限{}",
+                global.num, self.max_num_global_daily
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// 剩余的金额, 不限制时返回 None
+pub fn remaining_amount(limit: f64, spent: f64) -> Option<f64> {
+    if limit > 0.0 {
+        Some((limit - spent).max(0.0))
+    } else {
+        None
+    }
+}
+
+/// 剩余的数量, 不限制时返回 None
+pub fn remaining_num(limit: u32, spent: u32) -> Option<u32> {
+    if limit > 0 {
+        Some(limit.saturating_sub(spent))
+    } else {
+        None
+    }
+}
+
+/// 被预算拦下的下单
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct BlockedSubmit {
+    pub account: String,
+    pub cart_goods_id: IDType,
+    pub sku: String,
+    pub spend: Spend,
+    pub reason: String,
+    pub create_dt: String,
+}
+
+// 已经预留了预算的下单
+struct Pending {
+    spend: Spend,
+    // 共用这个预留的并发下单个数
+    refs: usize,
+}
+
+/// 下单成功但还没在订单列表里看到的
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Submitted {
+    pub spend: Spend,
+    pub at: PKDateTime,
+}
+
+/// 账号一天的花费, 保存在数据库里
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DaySpend {
+    // 订单列表里当天的订单: 订单号 -> 订单金额
+    pub orders: HashMap<String, Spend>,
+    pub submitted: Vec<Submitted>,
+}
+
+#[derive(Default)]
+struct AccountLedger {
+    spend: DaySpend,
+    // 正在下单的, 购物车商品id -> 预留的预算
+    pending: HashMap<IDType, Pending>,
+}
+
+impl AccountLedger {
+    fn spent(&self) -> Spend {
+        self.spend
+            .orders
+            .values()
+            .chain(self.pending.values().map(|p| &p.spend))
+            .chain(self.spend.submitted.iter().map(|p| &p.spend))
+            .fold(Spend::default(), |acc, s| acc.add(*s))
+    }
+}
+
+#[derive(Default)]
+struct Ledger {
+    // 按天统计, 换天了就清空
+    date: String,
+    accounts: HashMap<String, AccountLedger>,
+}
+
+impl Ledger {
+    fn account_spent(&self, account: &str) -> Spend {
+        self.accounts
+            .get(account)
+            .map(|a| a.spent())
+            .unwrap_or_default()
+    }
+
+    fn global_spent(&self) -> Spend {
+        self.accounts
+            .values()
+            .fold(Spend::default(), |acc, a| acc.add(a.spent()))
+    }
+}
+
+lazy_static! {
+    static ref LEDGER: RwLock<Ledger> = Default::default();
+}
+
+/// 换天或者刚启动时从数据库读出当天的花费
+async fn ledger() -> RwLockWriteGuard<'static, Ledger> {
+    let mut guard = LEDGER.write().await;
+    let today = date_fmt(PKLocal::today());
+    if guard.date != today {
+        let date = today.clone();
+        let saved = store::run(move |s| s.budget_spend(date.as_str()))
+            .await
+            .unwrap_or_else(|e| {
+                error!("读取今天的花费失败:{:?}", e);
+                Default::default()
+            });
+        guard.date = today;
+        guard.accounts = saved
+            .into_iter()
+            .map(|(account, spend)| {
+                (
+                    account,
+                    AccountLedger {
+                        spend,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+    }
+    guard
+}
+
+// 账号的花费有变化时保存, 失败只记日志
+async fn save(guard: &Ledger, account: &str) {
+    let spend = match guard.accounts.get(account) {
+        Some(a) => a.spend.clone(),
+        None => return,
+    };
+    let (date, account) = (guard.date.clone(), account.to_string());
+    if let Err(e) = store::run(move |s| s.save_budget_spend(date.as_str(), account.as_str(), &spend)).await {
+        error!("保存花费失败:{:?}", e);
+    }
+}
+
+// 拦截记录存到数据库里, 失败只记日志
+async fn block(blocked: BlockedSubmit) {
+    if let Err(e) = store::run(move |s| s.append_budget_blocked(&blocked, MAX_BLOCKED)).await {
+        error!("保存拦截记录失败:{:?}", e);
+    }
+}
+
+pub async fn budget() -> Budget {
+    CONFIG.read().await.budget()
+}
+
+/// 提交订单前预留预算, 同一个账号同一个商品并发的多次下单只预留一次;
+/// 超出预算时记录原因并返回 false
+pub async fn reserve(account: &str, cart_goods_id: IDType, sku: &str, price: &str, num: u32) -> bool {
+    let budget = budget().await;
+    let mut guard = ledger().await;
+    if let Some(p) = guard
+        .accounts
+        .get_mut(account)
+        .and_then(|a| a.pending.get_mut(&cart_goods_id))
+    {
+        p.refs += 1;
+        return true;
+    }
+    let price = parse_amount(price);
+    let spend = Spend::new(price.unwrap_or_default() * num as f64, num);
+    let reason = if price.is_none() && budget != Budget::default() {
+        Some("商品价格未知".to_string())
+    } else {
+        budget.check(spend, guard.account_spent(account), guard.global_spent())
+    };
+    if let Some(reason) = reason {
+        warn!("超出预算, 不提交订单:{}-{}-{}, {}", account, cart_goods_id, sku, reason);
+        block(BlockedSubmit {
+            account: account.to_string(),
+            cart_goods_id,
+            sku: sku.to_string(),
+            spend,
+            reason,
+            create_dt: datetime_fmt(&PKLocal::now()),
+        })
+        .await;
+        return false;
+    }
+    guard.accounts.entry(account.to_string()).or_default().pending.insert(
+        cart_goods_id,
+        Pending { spend, refs: 1 },
+    );
+    true
+}
+
+/// 下单结束: 每次成功都单独计入花费(并发的下单可能都成功了), 并发的下单都结束了才释放预留的预算
+pub async fn settle(account: &str, cart_goods_id: IDType, status: &str) {
+    let mut guard = ledger().await;
+    let mut changed = false;
+    if let Some(a) = guard.accounts.get_mut(account) {
+        if let Some(p) = a.pending.get_mut(&cart_goods_id) {
+            if status == "success" {
+                a.spend.submitted.push(Submitted {
+                    spend: p.spend,
+                    at: PKLocal::now(),
+                });
+                changed = true;
+            }
+            p.refs -= 1;
+            if p.refs == 0 {
+                a.pending.remove(&cart_goods_id);
+            }
+        }
+    }
+    if changed {
+        save(&guard, account).await;
+    }
+}
+
+/// 用订单列表里今天的订单金额更新账号的花费, since 是开始查询订单的时间,
+/// 在这之前下单成功的都已经在订单列表里了。
+/// 只有全部订单才能这样算, 待付款等列表里看不到已经付款的订单, 不用来统计
+pub async fn sync_orders(
+    account: &str,
+    orders: &BTreeMap<String, OrderInfo>,
+    cond: &QueryCondition,
+    since: PKDateTime,
+) {
+    if !matches!(cond, QueryCondition::All) {
+        debug!("不是全部订单, 不统计花费:{}", account);
+        return;
+    }
+    let mut guard = ledger().await;
+    let today = guard.date.clone();
+    let a = guard.accounts.entry(account.to_string()).or_default();
+    for o in orders.values().filter(|o| o.create_dt.starts_with(today.as_str())) {
+        a.spend.orders.insert(o.order_no.clone(), Spend::new(o.amount(), o.num()));
+    }
+    a.spend.submitted.retain(|p| p.at > since);
+    save(&guard, account).await;
+}
+
+/// 查询各个账号的订单, 更新今天的花费; 订单多的账号往后翻页, 翻到今天之前的订单为止
+pub async fn sync_accounts(accounts: Vec<UserState>) {
+    join_all(accounts.into_iter().map(|u| async move {
+        let since = PKLocal::now();
+        let today = date_fmt(PKLocal::today());
+        match retailer(u.platform)
+            .get_orders_since(u.account.clone(), u.cookie.clone(), QueryCondition::All, today)
+            .await
+        {
+            Ok(orders) => sync_orders(u.account.as_str(), &orders, &QueryCondition::All, since).await,
+            Err(e) => error!("查询订单失败, 不能统计花费:{}, {:?}", u.account, e),
+        }
+    }))
+    .await;
+}
+
+/// 预算面板上展示的内容
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct BudgetSummary {
+    pub budget: Budget,
+    pub global: Spend,
+    pub accounts: Vec<(String, Spend)>,
+    pub blocked: Vec<BlockedSubmit>,
+}
+
+pub async fn summary() -> BudgetSummary {
+    let budget = budget().await;
+    let blocked = store::run(|s| s.budget_blocked(MAX_BLOCKED))
+        .await
+        .unwrap_or_else(|e| {
+            error!("读取拦截记录失败:{:?}", e);
+            Default::default()
+        });
+    let guard = ledger().await;
+    let mut accounts: Vec<(String, Spend)> = guard
+        .accounts
+        .iter()
+        .map(|(k, v)| (k.clone(), v.spent()))
+        .collect();
+    accounts.sort_by(|a, b| a.0.cmp(&b.0));
+    BudgetSummary {
+        budget,
+        global: guard.global_spent(),
+        accounts,
+        // 最近的在前面
+        blocked,
+    }
+}
+
+#[cfg(test)]
+mod tests;
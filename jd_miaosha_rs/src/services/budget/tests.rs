@@ -0,0 +1,91 @@
+use crate::models::OrderInfo;
+use crate::services::budget::{self, Budget, Spend};
+use crate::services::order::QueryCondition;
+use crate::testing::SKU;
+
+#[test]
+fn test_budget_check() {
+    let b = Budget {
+        max_per_order: 100.0,
+        max_per_account_daily: 150.0,
+        max_global_daily: 200.0,
+        max_num_per_order: 2,
+        ..Default::default()
+    };
+    assert_eq!(b.check(Spend::new(99.0, 1), Spend::default(), Spend::default()), None);
+    assert!(b.check(Spend::new(101.0, 1), Spend::default(), Spend::default()).is_some());
+    assert!(b.check(Spend::new(30.0, 3), Spend::default(), Spend::default()).is_some());
+    assert!(b.check(Spend::new(60.0, 1), Spend::new(100.0, 1), Spend::new(100.0, 1)).is_some());
+    assert!(b.check(Spend::new(60.0, 1), Spend::new(50.0, 1), Spend::new(150.0, 2)).is_some());
+    // 不配置就不限制
+    assert_eq!(
+        Budget::default().check(Spend::new(99999.0, 99), Spend::default(), Spend::default()),
+        None
+    );
+    assert_eq!(crate::utils::parse_amount("¥5999.00"), Some(5999.0));
+    assert_eq!(crate::utils::parse_amount(""), None);
+}
+
+#[tokio::test]
+async fn test_budget_spent() {
+    let account = "budget_mock";
+    assert!(budget::reserve(account, 21, SKU, "99.00", 2).await);
+    // 同一个商品并发的下单只预留一次
+    assert!(budget::reserve(account, 21, SKU, "99.00", 2).await);
+    budget::settle(account, 21, "fail").await;
+    budget::settle(account, 21, "success").await;
+    assert!(budget::reserve(account, 22, SKU, "10.00", 1).await);
+    budget::settle(account, 22, "fail").await;
+    let spent = |s: budget::BudgetSummary| {
+        s.accounts
+            .into_iter()
+            .find(|(a, _)| a == account)
+            .map(|(_, s)| s)
+            .unwrap_or_default()
+    };
+    assert_eq!(spent(budget::summary().await), Spend::new(198.0, 2));
+
+    // 订单列表里有了之后, 按订单金额统计
+    let mut orders = std::collections::BTreeMap::new();
+    orders.insert(
+        "1".to_string(),
+        OrderInfo {
+            account: account.to_string(),
+            order_no: "1".to_string(),
+            purchase_num: "x2".to_string(),
+            total_price: "¥188.00".to_string(),
+            create_dt: crate::utils::datetime_fmt(&crate::PKLocal::now()),
+            ..Default::default()
+        },
+    );
+    // 待付款列表里看不到已经付款的订单, 不用来统计
+    budget::sync_orders(account, &orders, &QueryCondition::Unpaid, crate::PKLocal::now()).await;
+    assert_eq!(spent(budget::summary().await), Spend::new(198.0, 2));
+    budget::sync_orders(account, &orders, &QueryCondition::All, crate::PKLocal::now()).await;
+    assert_eq!(spent(budget::summary().await), Spend::new(188.0, 2));
+}
+
+#[tokio::test]
+async fn test_budget_settle_concurrent() {
+    let account = "budget_concurrent";
+    let spent = |s: budget::BudgetSummary| {
+        s.accounts
+            .into_iter()
+            .find(|(a, _)| a == account)
+            .map(|(_, s)| s)
+            .unwrap_or_default()
+    };
+    for _ in 0..3 {
+        assert!(budget::reserve(account, 31, SKU, "50.00", 1).await);
+    }
+    // 并发的下单每次成功都是一个订单, 各算一次
+    budget::settle(account, 31, "success").await;
+    assert_eq!(spent(budget::summary().await), Spend::new(100.0, 2));
+    budget::settle(account, 31, "success").await;
+    assert_eq!(spent(budget::summary().await), Spend::new(150.0, 3));
+    // 最后一个失败了, 只释放预留的
+    budget::settle(account, 31, "fail").await;
+    assert_eq!(spent(budget::summary().await), Spend::new(100.0, 2));
+    budget::settle(account, 31, "success").await;
+    assert_eq!(spent(budget::summary().await), Spend::new(100.0, 2));
+}
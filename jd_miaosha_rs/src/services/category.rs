@@ -0,0 +1,24 @@
+use http::{Method, StatusCode};
+use log::error;
+
+use crate::*;
+use crate::error::Result;
+use crate::models::CategoryInfo;
+use crate::services::api::call_api;
+
+pub struct CategoryService;
+
+impl CategoryService {
+    // 商品分类, 按服务端的排序返回
+    pub async fn list_categories(code: String) -> Result<Vec<CategoryInfo>> {
+        let addr_prefix = CONFIG.read().await.server_addr();
+        let url = format!("{}/api/category", addr_prefix.as_str());
+        let resp = call_api(Method::GET, url, code.as_str(), None).await?;
+        if resp.status() != StatusCode::OK {
+            error!("code:{}, {}", code, resp.status().as_str());
+            return Ok(vec![]);
+        }
+        let data: Vec<CategoryInfo> = serde_json::from_value(resp.json().await?)?;
+        Ok(data)
+    }
+}
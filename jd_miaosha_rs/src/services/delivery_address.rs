@@ -1,5 +1,9 @@
-use crate::error::{JdMiaoshaError, OpError, Result};
-use crate::models::AddressInfo;
+use crate::error::{JdMiaoshaError, OpError, ParseError, Result};
+use crate::models::{AddressForm, AddressInfo, UserState};
+use crate::services::parser::{parse_address_result, parse_addresses};
+use crate::services::endpoints::endpoints;
+use crate::services::retailer::retailer;
+use crate::services::transport::HttpClient;
 use http::{Method, StatusCode};
 use log::{debug, error, info, warn};
 use rand::prelude::*;
@@ -23,9 +27,21 @@ use thirtyfour::GenericWebDriver;
 use thirtyfour::{By, WebDriverCommands};
 use tokio::fs::{create_dir_all, File, OpenOptions};
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+use futures::future::join_all;
 use tokio::sync::RwLock;
 use url::form_urlencoded;
 
+/// 批量设置收货地址时每个账号的结果
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum ApplyOutcome {
+    // 新增了地址, 新地址的 id
+    Added(String),
+    // 已经有这个地址, 修改了它
+    Updated(String),
+    // 失败的原因
+    Failed(String),
+}
+
 #[derive(Clone)]
 pub struct DeliveryAddressService;
 
@@ -34,8 +50,8 @@ impl DeliveryAddressService {
         account: String,
         cookie_str: Arc<String>,
     ) -> Result<LinkedList<AddressInfo>> {
-        let url = "https://easybuy.jd.com/address/getEasyBuyList.action";
-        let resp = reqwest::Client::new()
+        let url = endpoints().await.address_list();
+        let resp = HttpClient::new()
             .get(url)
             .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.9")
             .header("Accept-Language", "zh-CN,zh;q=0.9")
@@ -54,59 +70,12 @@ impl DeliveryAddressService {
             .header("Cookie", cookie_str.as_str())
             .send()
             .await?;
-        let resp = resp.text().await?;
-        // let body = to_bytes(resp.into_body()).await?;
-        // let resp_html = ::std::str::from_utf8(&to_bytes(resp.into_body()).await?).unwrap().to_string();
-        // let resp_html = String::from_utf8(body.chunk().to_vec()).unwrap();
-        let document = Html::parse_document(resp.as_str());
-        let addr_selector = Selector::parse("div#addressList div.easebuy-m").unwrap(); // 找到商品名称
-        let left_col_selector = Selector::parse("div.item-lcol").unwrap();
-        let item_selector = Selector::parse("div.item").unwrap();
-        let label_selector = Selector::parse("span.label").unwrap();
-        let fr_selector = Selector::parse("div.fl").unwrap();
+        let resp_html = resp.text().await?;
         let mut ret = LinkedList::new();
-        let mut i = 0;
-        for element in document.select(&addr_selector) {
-            let mut addr = AddressInfo::default();
+        for mut addr in parse_addresses(resp_html.as_str())?.into_iter() {
             addr.account = account.as_str().to_owned();
             addr.cookie = cookie_str.clone();
             addr.is_latest_receive_addr = false;
-            i += 1;
-            if let Some(eid) = element.value().attr("id") {
-                // eid 的格式 addresssDiv-3759910743
-                addr.addr_id = eid.split("-").skip(1).next().unwrap().to_owned();
-            }
-            if let Some(left_div) = element.select(&left_col_selector).next() {
-                for item in left_div.select(&item_selector) {
-                    if let Some(label) = item.select(&label_selector).next() {
-                        if let Some(mut txt) = label.text().next() {
-                            txt = txt.trim();
-                            let sibling_txt = item
-                                .select(&fr_selector)
-                                .next()
-                                .unwrap()
-                                .text()
-                                .next()
-                                .unwrap_or("")
-                                .trim()
-                                .to_owned();
-                            match txt {
-                                "收货人：" => addr.receiver = sibling_txt,
-                                "所在地区：" => addr.area_zone = sibling_txt,
-                                "地址：" => addr.address = sibling_txt,
-                                "手机：" => addr.mobile_phone = sibling_txt,
-                                "固定电话：" => addr.fixed_line_phone = sibling_txt,
-                                "电子邮箱：" => addr.email = sibling_txt,
-                                _ => {
-                                    warn!("Unknown text")
-                                }
-                            }
-                        }
-                    } else {
-                        error!("error document!")
-                    }
-                }
-            }
             ret.push_back(addr);
         }
         Ok(ret)
@@ -119,7 +88,7 @@ impl DeliveryAddressService {
     ) -> Result<Option<(String, String)>> {
         // 设置结算时订单的收货地址
         // 京东在展示商品的时候，是按照最近一次订单（不管订单有没有支付或者完成、失败）的收货地址来展示商品的库存的
-        let url = "https://trade.jd.com/shopping/dynamic/consignee/saveConsignee.action";
+        let url = endpoints().await.save_consignee();
         let encoded = form_urlencoded::Serializer::new(String::new())
             .append_pair("consigneeParam.newId", addr_id.as_str()) // 设置地址id, 通过 get_all_address 获取
             .append_pair("consigneeParam.type", "null")
@@ -133,7 +102,7 @@ impl DeliveryAddressService {
             .append_pair("consigneeParam.pickType", "0")
             .append_pair("presaleStockSign", "0") // 这个参数没得什么实际作用， 只需要有就行了
             .finish();
-        let resp = reqwest::Client::new().post(url)
+        let resp = HttpClient::new().post(url)
             .header("authority", "trade.jd.com")
             .header("sec-ch-ua", r#""Google Chrome";v="89", "Chromium";v="89", ";Not A Brand";v="99""#)
             .header("Accept", "application/json, text/javascript, */*; q=0.01")
@@ -160,4 +129,136 @@ impl DeliveryAddressService {
             Ok(None)
         }
     }
+
+    // 新增和修改地址的表单参数
+    fn address_params(form: &AddressForm, addr_id: Option<&str>) -> String {
+        let mut params = form_urlencoded::Serializer::new(String::new());
+        if let Some(id) = addr_id {
+            params.append_pair("addressInfoParam.id", id);
+        }
+        params
+            .append_pair("addressInfoParam.consigneeName", form.receiver.trim())
+            .append_pair("addressInfoParam.provinceId", form.area_ids[0].to_string().as_str())
+            .append_pair("addressInfoParam.cityId", form.area_ids[1].to_string().as_str())
+            .append_pair("addressInfoParam.countyId", form.area_ids[2].to_string().as_str())
+            .append_pair("addressInfoParam.townId", form.area_ids[3].to_string().as_str())
+            .append_pair("addressInfoParam.areaName", form.area_zone.as_str())
+            .append_pair("addressInfoParam.consigneeAddress", form.address.trim())
+            .append_pair("addressInfoParam.mobile", form.mobile_phone.trim())
+            .append_pair("addressInfoParam.phone", form.fixed_line_phone.trim())
+            .append_pair("addressInfoParam.email", form.email.trim())
+            .finish()
+    }
+
+    // 提交地址的修改, 成功时返回地址 id
+    async fn post_address(
+        url: String,
+        body: String,
+        op: &'static str,
+        account: String,
+        cookie_str: Arc<String>,
+    ) -> Result<String> {
+        let ep = endpoints().await;
+        let resp = HttpClient::new()
+            .post(url)
+            .header("cookie", cookie_str.as_str())
+            .header("user-agent", crate::utils::get_useragent())
+            .header("accept", "application/json, text/javascript, */*; q=0.01")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .header("x-requested-with", "XMLHttpRequest")
+            .header("origin", ep.easybuy.as_str())
+            .header("referer", ep.address_list())
+            .header("accept-language", "zh-CN,zh;q=0.9")
+            .body(body)
+            .send()
+            .await?;
+        // cookie 失效时跳转到登录页
+        let to_login = resp
+            .headers()
+            .get("location")
+            .map(|u| u.to_str().unwrap_or_default().contains("login"))
+            .unwrap_or(false);
+        if to_login {
+            return Err(OpError::CookieExpired(account).into());
+        }
+        let body = resp.text().await?;
+        match parse_address_result(body.as_str(), op) {
+            Ok(Ok(addr_id)) => Ok(addr_id),
+            Ok(Err(msg)) => {
+                error!("{}:{} 失败, {}", account, op, msg);
+                Err(OpError::SaveAddress(msg).into())
+            }
+            Err(ParseError::LoginRequired(_)) => Err(OpError::CookieExpired(account).into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 新增收货地址, 返回新地址的 id
+    pub async fn add_address(account: String, cookie_str: Arc<String>, form: AddressForm) -> Result<String> {
+        form.validate().map_err(OpError::InvalidArgs)?;
+        let url = endpoints().await.address_add();
+        let body = Self::address_params(&form, None);
+        Self::post_address(url, body, "address_add", account, cookie_str).await
+    }
+
+    /// 修改收货地址
+    pub async fn update_address(
+        account: String,
+        cookie_str: Arc<String>,
+        addr_id: String,
+        form: AddressForm,
+    ) -> Result<String> {
+        form.validate().map_err(OpError::InvalidArgs)?;
+        let url = endpoints().await.address_update();
+        let body = Self::address_params(&form, Some(addr_id.as_str()));
+        Self::post_address(url, body, "address_update", account, cookie_str)
+            .await
+            // 修改接口不一定返回 id
+            .map(|id| if id.is_empty() { addr_id } else { id })
+    }
+
+    /// 删除收货地址
+    pub async fn delete_address(account: String, cookie_str: Arc<String>, addr_id: String) -> Result<()> {
+        let url = endpoints().await.address_delete();
+        let body = form_urlencoded::Serializer::new(String::new())
+            .append_pair("addressId", addr_id.as_str())
+            .finish();
+        Self::post_address(url, body, "address_delete", account, cookie_str).await?;
+        Ok(())
+    }
 }
+
+/// 把同一个地址设置到多个账号: 已经有这个地址的账号修改它, 没有的新增
+pub async fn apply_address(users: Vec<UserState>, form: AddressForm) -> Vec<(String, ApplyOutcome)> {
+    if let Err(e) = form.validate() {
+        return users.into_iter().map(|u| (u.account, ApplyOutcome::Failed(e.clone()))).collect();
+    }
+    join_all(users.into_iter().map(|u| {
+        let form = form.clone();
+        async move {
+            let r = retailer(u.platform);
+            let outcome = match r.get_addresses(u.account.clone(), u.cookie.clone()).await {
+                Ok(addrs) => match addrs.iter().find(|a| form.same_as(a)) {
+                    Some(addr) => r
+                        .update_address(u.account.clone(), u.cookie.clone(), addr.addr_id.clone(), form)
+                        .await
+                        .map(ApplyOutcome::Updated),
+                    None => r
+                        .add_address(u.account.clone(), u.cookie.clone(), form)
+                        .await
+                        .map(ApplyOutcome::Added),
+                },
+                Err(e) => Err(e),
+            };
+            let outcome = outcome.unwrap_or_else(|e| {
+                error!("设置收货地址失败:{}, {:?}", u.account, e);
+                ApplyOutcome::Failed(e.to_string())
+            });
+            (u.account, outcome)
+        }
+    }))
+    .await
+}
+
+#[cfg(test)]
+mod tests;
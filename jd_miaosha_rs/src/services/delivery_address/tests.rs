@@ -0,0 +1,113 @@
+use crate::error::{JdMiaoshaError, OpError};
+use crate::models::AddressForm;
+use crate::services::delivery_address::{self, ApplyOutcome, DeliveryAddressService};
+use crate::services::transport::{HttpResponse, JdScenario};
+use crate::testing::{address_form, address_list_page, cookie, install};
+
+#[test]
+fn test_address_form_validate() {
+    assert!(address_form().validate().is_ok());
+    let invalid = |f: fn(&mut AddressForm)| {
+        let mut form = address_form();
+        f(&mut form);
+        form.validate().is_err()
+    };
+    assert!(invalid(|f| f.receiver = " ".to_string()));
+    assert!(invalid(|f| f.area_ids = [1, 72, 0, 0]));
+    assert!(invalid(|f| f.address.clear()));
+    assert!(invalid(|f| f.mobile_phone = "138****0000".to_string()));
+    assert!(invalid(|f| f.mobile_phone = "1380000000".to_string()));
+
+    // 修改已有地址时地区和手机号要重新填
+    let addrs = crate::services::parser::parse_addresses(address_list_page().as_str()).unwrap();
+    let form = AddressForm::from_info(&addrs[0]);
+    assert!(form.same_as(&addrs[0]));
+    assert!(!form.same_as(&addrs[1]));
+    assert!(form.validate().is_err());
+}
+
+#[tokio::test]
+async fn test_address_crud() {
+    let g = install(JdScenario::Success).await;
+    let id = DeliveryAddressService::add_address("a".to_string(), cookie(), address_form())
+        .await
+        .unwrap();
+    assert_eq!(id, "3759910999");
+    assert_eq!(g.mock.hits("addressInfoParam.countyId=2819"), 1);
+    // 修改接口没有返回 id 时用原来的 id
+    let id = DeliveryAddressService::update_address("a".to_string(), cookie(), "3759910743".to_string(), address_form())
+        .await
+        .unwrap();
+    assert_eq!(id, "3759910743");
+    assert_eq!(g.mock.hits("addressInfoParam.id=3759910743"), 1);
+    DeliveryAddressService::delete_address("a".to_string(), cookie(), "3759910743".to_string())
+        .await
+        .unwrap();
+    assert_eq!(g.mock.hits("addressId=3759910743"), 1);
+
+    // 表单不对时不发请求
+    let mut form = address_form();
+    form.mobile_phone.clear();
+    assert!(matches!(
+        DeliveryAddressService::add_address("a".to_string(), cookie(), form).await,
+        Err(JdMiaoshaError::OpError(OpError::InvalidArgs(_)))
+    ));
+    assert_eq!(g.mock.hits("addAddress.action"), 1);
+
+    g.mock.replace(
+        "easybuy.jd.com/address/addAddress.action",
+        HttpResponse::json_ok(serde_json::json!({"success": false, "message": "收货地址最多20个"})),
+    );
+    match DeliveryAddressService::add_address("a".to_string(), cookie(), address_form()).await {
+        Err(JdMiaoshaError::OpError(OpError::SaveAddress(msg))) => assert_eq!(msg, "收货地址最多20个"),
+        r => panic!("unexpected: {:?}", r),
+    }
+}
+
+#[tokio::test]
+async fn test_apply_address() {
+    let g = install(JdScenario::Success).await;
+    g.mock.on(
+        "easybuy.jd.com/address/getEasyBuyList.action",
+        HttpResponse::ok(address_list_page()),
+    );
+    let user = |account: &str| crate::models::UserState {
+        account: account.to_string(),
+        cookie: cookie(),
+        ..Default::default()
+    };
+    // 已经有相同收货人和地址的修改原来的地址
+    let report = delivery_address::apply_address(vec![user("a"), user("b")], address_form()).await;
+    assert_eq!(
+        report,
+        vec![
+            ("a".to_string(), ApplyOutcome::Updated("3759910743".to_string())),
+            ("b".to_string(), ApplyOutcome::Updated("3759910743".to_string())),
+        ]
+    );
+    assert_eq!(g.mock.hits("addAddress.action"), 0);
+
+    let mut form = address_form();
+    form.address = "建国路99号".to_string();
+    let report = delivery_address::apply_address(vec![user("a")], form.clone()).await;
+    assert_eq!(report, vec![("a".to_string(), ApplyOutcome::Added("3759910999".to_string()))]);
+
+    // 表单不对时每个账号都失败, 不发请求
+    form.area_ids = [0; 4];
+    let report = delivery_address::apply_address(vec![user("a"), user("b")], form).await;
+    assert!(report.iter().all(|(_, outcome)| matches!(outcome, ApplyOutcome::Failed(_))));
+    assert_eq!(g.mock.hits("addAddress.action"), 1);
+}
+
+#[tokio::test]
+async fn test_apply_address_expired_cookie() {
+    let _g = install(JdScenario::ExpiredCookie).await;
+    let user = crate::models::UserState {
+        account: "a".to_string(),
+        cookie: cookie(),
+        ..Default::default()
+    };
+    let report = delivery_address::apply_address(vec![user], address_form()).await;
+    assert_eq!(report.len(), 1);
+    assert!(matches!(report[0].1, ApplyOutcome::Failed(_)));
+}
@@ -0,0 +1,102 @@
+//! 试运行: 下单前的步骤(加购物车、勾选、结算页、秒杀初始化等)照常执行,
+//! 只把最后提交订单的请求记录下来, 不真正发出去
+use std::collections::HashMap;
+
+use log::info;
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use crate::services::transport::HttpRequest;
+use crate::utils::datetime_fmt;
+use crate::{IDType, PKLocal, CONFIG};
+
+pub const DRY_RUN: &'static str = "dry-run";
+
+/// 试运行时本来要提交的订单
+#[derive(Debug, Clone, Serialize)]
+pub struct DryRunOrder {
+    pub account_id: IDType,
+    pub cart_goods_id: IDType,
+    pub sku: String,
+    pub method: String,
+    pub url: String,
+    pub body: String,
+    pub create_dt: String,
+}
+
+impl DryRunOrder {
+    pub fn payload(&self) -> String {
+        if self.body.is_empty() {
+            format!("{} {}", self.method, self.url)
+        } else {
+            format!("{} {}\n{}", self.method, self.url, self.body)
+        }
+    }
+}
+
+lazy_static! {
+    // 购物车商品id -> 各个账号最近一次试运行的订单
+    static ref DRY_RUN_ORDERS: RwLock<HashMap<IDType, Vec<DryRunOrder>>> = Default::default();
+}
+
+/// 全局的试运行开关, 在 conf.toml 里配置
+pub async fn is_global_dry_run() -> bool {
+    CONFIG.read().await.dry_run()
+}
+
+async fn save(order: DryRunOrder) {
+    info!(
+        "试运行, 不提交订单:{}",
+        serde_json::to_string(&order).unwrap_or_default()
+    );
+    let mut guard = DRY_RUN_ORDERS.write().await;
+    let orders = guard.entry(order.cart_goods_id).or_default();
+    orders.retain(|o| o.account_id != order.account_id);
+    orders.push(order);
+}
+
+/// 记录本来要发出的提交订单请求
+pub async fn record(account_id: IDType, cart_goods_id: IDType, sku: &str, req: &HttpRequest) {
+    save(DryRunOrder {
+        account_id,
+        cart_goods_id,
+        sku: sku.to_string(),
+        method: req.method.to_string(),
+        url: req.url.clone(),
+        body: req.body.clone().unwrap_or_default(),
+        create_dt: datetime_fmt(&PKLocal::now()),
+    })
+    .await
+}
+
+/// 用 webdriver 下单时, 记录本来要点击的按钮
+pub async fn record_click(
+    account_id: IDType,
+    cart_goods_id: IDType,
+    sku: &str,
+    page_url: String,
+    button: &str,
+) {
+    save(DryRunOrder {
+        account_id,
+        cart_goods_id,
+        sku: sku.to_string(),
+        method: "CLICK".to_string(),
+        url: page_url,
+        body: button.to_string(),
+        create_dt: datetime_fmt(&PKLocal::now()),
+    })
+    .await
+}
+
+pub async fn orders(cart_goods_id: IDType) -> Vec<DryRunOrder> {
+    DRY_RUN_ORDERS
+        .read()
+        .await
+        .get(&cart_goods_id)
+        .cloned()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,76 @@
+use crate::services::dry_run::{self, DRY_RUN};
+use crate::services::shopping_cart::ShoppingCartService;
+use crate::services::transport::JdScenario;
+use crate::testing::{AREA, SKU, cookie, install};
+
+#[tokio::test]
+async fn test_submit_order_dry_run() {
+    let g = install(JdScenario::Success).await;
+    let r = ShoppingCartService::submit_order_wrapper(
+        1011,
+        cookie(),
+        11,
+        SKU.to_string(),
+        1,
+        AREA.to_string(),
+        true,
+        1,
+        true,
+    )
+    .await
+    .unwrap();
+    assert_eq!(r, (11, 1, DRY_RUN));
+    // 加购物车和结算页照常执行, 只是不提交订单
+    assert_eq!(g.mock.hits("cart.jd.com/gate.action"), 1);
+    assert_eq!(g.mock.hits("getOrderInfo.action"), 1);
+    assert_eq!(g.mock.hits("submitOrder.action"), 0);
+    let orders = dry_run::orders(11).await;
+    assert_eq!(orders.len(), 1);
+    assert_eq!(orders[0].account_id, 1011);
+    assert_eq!(orders[0].method, "POST");
+    assert!(orders[0].url.contains("submitOrder.action"));
+    assert!(orders[0].body.contains("submitOrderParam"));
+}
+
+#[tokio::test]
+async fn test_submit_presale_order_dry_run() {
+    let g = install(JdScenario::Success).await;
+    let r = ShoppingCartService::submit_presale_order(
+        1012,
+        cookie(),
+        12,
+        SKU.to_string(),
+        "".to_string(),
+        "".to_string(),
+        1,
+        true,
+    )
+    .await
+    .unwrap();
+    assert_eq!(r, (12, 1, DRY_RUN));
+    assert_eq!(g.mock.hits("getOrderInfo.action"), 1);
+    assert_eq!(g.mock.hits("submitOrder.action"), 0);
+    let orders = dry_run::orders(12).await;
+    assert!(orders[0].body.contains("flowType=15"));
+}
+
+#[tokio::test]
+async fn test_submit_seckill_order_dry_run() {
+    let g = install(JdScenario::Success).await;
+    let r = ShoppingCartService::submit_seckill_order(
+        1013,
+        cookie(),
+        13,
+        SKU.to_string(),
+        "".to_string(),
+        "".to_string(),
+        true,
+    )
+    .await
+    .unwrap();
+    assert_eq!(r, (13, 1, DRY_RUN));
+    assert_eq!(g.mock.hits("seckillnew/orderService/pc/init.action"), 1);
+    assert_eq!(g.mock.hits("seckillnew/orderService/pc/submitOrder.action"), 0);
+    let orders = dry_run::orders(13).await;
+    assert!(orders[0].body.contains("skuId=100012043978"));
+}
@@ -0,0 +1,215 @@
+use serde::Deserialize;
+
+use crate::CONFIG;
+
+/// 京东各个服务的地址, 可以在 conf.toml 的 [endpoints] 里覆盖,
+/// 比如全部指向 fake_retailer 做离线测试
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Endpoints {
+    pub item: String,
+    pub item_m: String,
+    pub itemko: String,
+    pub stock: String,
+    pub price: String,
+    pub cart: String,
+    pub cart_api: String,
+    pub trade: String,
+    pub marathon: String,
+    pub order: String,
+    pub details: String,
+    pub cashier: String,
+    pub easybuy: String,
+    pub area: String,
+    pub area_backup: String,
+    pub coupon: String,
+    pub coupon_center: String,
+    pub passport: String,
+    pub search: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            item: "https://item.jd.com".to_string(),
+            item_m: "https://item.m.jd.com".to_string(),
+            itemko: "https://itemko.jd.com".to_string(),
+            stock: "https://c0.3.cn".to_string(),
+            price: "http://p.3.cn".to_string(),
+            cart: "https://cart.jd.com".to_string(),
+            cart_api: "https://api.m.jd.com".to_string(),
+            trade: "https://trade.jd.com".to_string(),
+            marathon: "https://marathon.jd.com".to_string(),
+            order: "https://order.jd.com".to_string(),
+            details: "https://details.jd.com".to_string(),
+            cashier: "https://pcashier.jd.com".to_string(),
+            easybuy: "https://easybuy.jd.com".to_string(),
+            area: "https://fts.jd.com".to_string(),
+            area_backup: "https://d.jd.com".to_string(),
+            coupon: "https://cd.jd.com".to_string(),
+            coupon_center: "https://a.jd.com".to_string(),
+            passport: "https://passport.jd.com".to_string(),
+            search: "https://search.jd.com".to_string(),
+        }
+    }
+}
+
+impl Endpoints {
+    // 商品详情页
+    pub fn item_page(&self, sku: &str) -> String {
+        format!("{}/{}.html", self.item, sku)
+    }
+
+    // 移动端商品详情页, 预约信息从这里取
+    pub fn item_m_page(&self, sku: &str) -> String {
+        format!("{}/product/{}.html", self.item_m, sku)
+    }
+
+    // 秒杀按钮
+    pub fn item_show_btn(&self) -> String {
+        format!("{}/itemShowBtn", self.itemko)
+    }
+
+    pub fn stocks(&self) -> String {
+        format!("{}/stocks", self.stock)
+    }
+
+    pub fn prices(&self) -> String {
+        format!("{}/prices/mgets", self.price)
+    }
+
+    // 按关键字搜索商品, 京东的 page 参数是 1、3、5 ... 对应第 1、2、3 ... 页
+    pub fn search(&self, keyword: &str, page: u32) -> String {
+        let keyword: String = url::form_urlencoded::byte_serialize(keyword.as_bytes()).collect();
+        format!(
+            "{}/Search?keyword={}&enc=utf-8&page={}",
+            self.search,
+            keyword,
+            page.max(1) * 2 - 1
+        )
+    }
+
+    // 加入购物车
+    pub fn add_to_cart(&self, sku: &str, num: u32) -> String {
+        format!("{}/gate.action?pid={}&pcount={}&ptype=1", self.cart, sku, num)
+    }
+
+    // 预售商品的定金下单入口
+    pub fn presale_gate(&self, sku: &str, num: u32) -> String {
+        format!(
+            "{}/cart/dynamic/gateForSubFlow.action?wids={}&nums={}&subType=32",
+            self.cart, sku, num
+        )
+    }
+
+    // 购物车接口, 通过 functionId 区分
+    pub fn cart_api(&self) -> String {
+        format!("{}/api", self.cart_api)
+    }
+
+    pub fn cart_function(&self, function_id: &str) -> String {
+        format!(
+            "{}?functionId={}&appid=JDC_mall_cart&loginType=3",
+            self.cart_api(),
+            function_id
+        )
+    }
+
+    // 订单结算页
+    pub fn order_info(&self) -> String {
+        format!("{}/shopping/order/getOrderInfo.action", self.trade)
+    }
+
+    pub fn submit_order(&self) -> String {
+        format!("{}/shopping/order/submitOrder.action", self.trade)
+    }
+
+    pub fn save_consignee(&self) -> String {
+        format!("{}/shopping/dynamic/consignee/saveConsignee.action", self.trade)
+    }
+
+    pub fn seckill_init(&self) -> String {
+        format!("{}/seckillnew/orderService/pc/init.action", self.marathon)
+    }
+
+    pub fn seckill_submit(&self, sku: &str) -> String {
+        format!(
+            "{}/seckillnew/orderService/pc/submitOrder.action?skuId={}",
+            self.marathon, sku
+        )
+    }
+
+    pub fn seckill_page(&self) -> String {
+        format!("{}/seckill/seckill.action", self.marathon)
+    }
+
+    // 订单列表
+    pub fn order_list(&self) -> String {
+        format!("{}/center/list.action", self.order)
+    }
+
+    pub fn order_search(&self, keyword: &str) -> String {
+        format!("{}/center/search.action?keyword={}", self.order, keyword)
+    }
+
+    // 订单的物流轨迹
+    pub fn order_track(&self, order_no: &str) -> String {
+        format!(
+            "{}/lazy/getOrderTrackInfoMultiPackage.action?orderId={}",
+            self.details, order_no
+        )
+    }
+
+    // 收银台, 订单列表里没有付款链接时用
+    pub fn pay_page(&self, order_no: &str) -> String {
+        format!("{}/cashier/index.action?orderId={}", self.cashier, order_no)
+    }
+
+    // 收货地址列表
+    pub fn address_list(&self) -> String {
+        format!("{}/address/getEasyBuyList.action", self.easybuy)
+    }
+
+    pub fn address_add(&self) -> String {
+        format!("{}/address/addAddress.action", self.easybuy)
+    }
+
+    pub fn address_update(&self) -> String {
+        format!("{}/address/updateAddress.action", self.easybuy)
+    }
+
+    pub fn address_delete(&self) -> String {
+        format!("{}/address/deleteAddress.action", self.easybuy)
+    }
+
+    // 地区, 主地址不可用时用备用地址
+    pub fn areas(&self, fid: &str) -> Vec<String> {
+        vec![
+            format!("{}/area/get?fid={}", self.area, fid),
+            format!("{}/area/get?fid={}", self.area_backup, fid),
+        ]
+    }
+
+    pub fn coupon_service(&self) -> String {
+        format!("{}/coupon/service", self.coupon)
+    }
+
+    pub fn coupon_search(&self, text: &str) -> String {
+        format!("{}/search.html?searchText={}", self.coupon_center, text)
+    }
+
+    pub fn coupon_get(&self, key: &str) -> String {
+        format!("{}/ajax/freeGetCoupon.html?key={}", self.coupon_center, key)
+    }
+
+    pub fn login(&self) -> String {
+        format!("{}/new/login.aspx", self.passport)
+    }
+}
+
+pub async fn endpoints() -> Endpoints {
+    CONFIG.read().await.endpoints()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,77 @@
+use crate::models::StockStatus;
+use crate::services::delivery_address::DeliveryAddressService;
+use crate::services::endpoints::{endpoints, Endpoints};
+use crate::services::goods::GoodsService;
+use crate::services::inventory;
+use crate::services::order::{OrderService, QueryCondition};
+use crate::testing::{AREA, cookie, fixtures_dir, serial};
+use crate::{CONFIG, NORMAL, SECOND_KILL};
+
+// 所有地址都指向 fake_retailer
+fn all_of(base: &str) -> Endpoints {
+    let base = base.to_string();
+    Endpoints {
+        item: base.clone(),
+        item_m: base.clone(),
+        itemko: base.clone(),
+        stock: base.clone(),
+        price: base.clone(),
+        cart: base.clone(),
+        cart_api: base.clone(),
+        trade: base.clone(),
+        marathon: base.clone(),
+        order: base.clone(),
+        details: base.clone(),
+        cashier: base.clone(),
+        easybuy: base.clone(),
+        area: base.clone(),
+        area_backup: base.clone(),
+        coupon: base.clone(),
+        coupon_center: base.clone(),
+        passport: base.clone(),
+        search: base,
+    }
+}
+
+#[tokio::test]
+async fn test_against_fake_retailer() {
+    let _g = serial().await;
+    let base = fake_retailer::start(fixtures_dir()).unwrap();
+    CONFIG.write().await.endpoints = all_of(base.as_str());
+    inventory::clear();
+    assert_eq!(endpoints().await.stocks(), format!("{}/stocks", base));
+
+    let normal = GoodsService::get_prod_info("100009077475", AREA).await;
+    let seckill = GoodsService::get_prod_info("100012043978", AREA).await;
+    let out_of_stock = GoodsService::get_prod_info("100000000001", AREA).await;
+    let search = GoodsService::search_goods("手机", 1, AREA, "").await;
+    let addrs = DeliveryAddressService::get_all_address_by_user("a".to_string(), cookie()).await;
+    let orders = OrderService::get_orders_by_user("a".to_string(), cookie(), QueryCondition::All).await;
+    // 先恢复再检查结果, 失败了也不影响别的测试
+    CONFIG.write().await.endpoints = Endpoints::default();
+    inventory::clear();
+
+    let normal = normal.unwrap();
+    assert_eq!(normal.name, "Apple iPhone 12 (A2404) 128GB 蓝色 支持移动联通电信5G 双卡双待手机");
+    assert_eq!(normal.category_hint, "手机通讯,手机,手机");
+    assert_eq!(normal.purchase_type, NORMAL);
+    assert_eq!(normal.purchase_url, format!("{}/gate.action?pid=100009077475&pcount=1&ptype=1", base));
+    assert_eq!((normal.cur_price.as_str(), normal.ori_price.as_str()), ("5999.00", "6299.00"));
+    assert_eq!(normal.status, StockStatus::OnSale);
+    let seckill = seckill.unwrap();
+    assert_eq!(seckill.purchase_type, SECOND_KILL);
+    assert_eq!(seckill.purchase_url, "");
+    assert_eq!(out_of_stock.unwrap().status, StockStatus::OutOfStock);
+
+    let search = search.unwrap();
+    let skus: Vec<&str> = search.items.iter().map(|i| i.sku.as_str()).collect();
+    assert_eq!(skus, vec!["100009077475"]);
+    assert_eq!(search.items[0].name, normal.name);
+    assert_eq!(search.items[0].status, StockStatus::OnSale);
+
+    let addrs: Vec<_> = addrs.unwrap().into_iter().collect();
+    assert_eq!(addrs.len(), 2);
+    assert_eq!((addrs[0].receiver.as_str(), addrs[0].mobile_phone.as_str()), ("张三", "138****0000"));
+    // 还没下过单
+    assert!(orders.unwrap().is_empty());
+}
@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::time::Duration;
 
 use chrono::Datelike;
@@ -17,10 +17,18 @@ use url::form_urlencoded;
 
 use crate::*;
 use crate::error::{JdMiaoshaError, OpError, Result};
-use crate::models::{GoodsState, StockStatus};
+use crate::models::{GoodsState, ProdPlatform, SearchPage, StockStatus};
 use crate::services::driver::*;
+use crate::services::endpoints::endpoints;
+use crate::services::inventory;
+use crate::services::parser::*;
+use crate::services::transport::HttpClient;
 use crate::utils::*;
 
+// 库存、价格接口一次最多查询的商品数
+pub const STOCK_BATCH_SIZE: usize = 50;
+pub const PRICE_BATCH_SIZE: usize = 50;
+
 #[derive(Clone)]
 pub struct GoodsService {
     http_client: Client,
@@ -48,8 +56,6 @@ lazy_static! {
 }
 
 impl GoodsService {
-    const STOCK_URL: &'static str = "https://c0.3.cn/stocks";
-    const PRICE_URL: &'static str = "http://p.3.cn/prices/mgets";
     // const ADD_TO_SHOPPING_CART_URL: &'static str = "https//cart.jd.com/gate.action?pid={}&pcount={}&ptype=1&gs=2";
 
     fn new() -> Self {
@@ -64,120 +70,91 @@ impl GoodsService {
             http_client: client,
         }
     }
-    pub async fn get_prod_stock(
-        cart_goods_id: IDType,
-        sku: String,
-        area_id: String,
-    ) -> Result<(IDType, StockStatus)> {
-        let r = Self::inner_get_prod_stock(sku.as_str(), area_id.as_str()).await?;
-        Ok((cart_goods_id, r))
+    // 一次查询多个商品的库存, 顺序和 skus 一致, 解析不出来的是 Unknown
+    pub async fn get_prod_stocks(skus: &[String], area_id: &str) -> Result<Vec<StockStatus>> {
+        if skus.is_empty() {
+            return Ok(vec![]);
+        }
+        let stocks = match Self::fetch_stocks(skus, area_id).await {
+            Err(JdMiaoshaError::OpError(OpError::QueryFailed(..))) | Err(JdMiaoshaError::ParseError(_)) => {
+                Default::default()
+            }
+            r => r?,
+        };
+        Ok(skus
+            .iter()
+            .map(|sku| stocks.get(sku).cloned().unwrap_or(StockStatus::Unknown))
+            .collect())
     }
 
-    async fn inner_get_prod_stock(prod_id: &str, area_id: &str) -> Result<StockStatus> {
+    /// 一次请求查询多个商品的库存, 最多 STOCK_BATCH_SIZE 个; 返回里没有的商品不在结果里
+    pub async fn fetch_stocks(skus: &[String], area_id: &str) -> Result<HashMap<String, StockStatus>> {
         let encoded = form_urlencoded::Serializer::new(String::new())
             .append_pair("type", "getstocks")
-            .append_pair("skuIds", prod_id)
+            .append_pair("skuIds", skus.join(",").as_str())
             .append_pair("area", area_id)
             .finish();
-        let resp = reqwest::get(format!("{}?{}", Self::STOCK_URL, encoded)).await?;
+        let resp = HttpClient::new()
+            .get(format!("{}?{}", endpoints().await.stocks(), encoded))
+            .send()
+            .await?;
         let status = resp.status();
-        if status == StatusCode::OK {
-            // 只能通过下面的方式，使用两次变化，才能正常使用 --begin
-            let v = resp.text().await?;
-            let v_json: Value = serde_json::from_str(v.as_str())?;
-            // 只能通过上面的方式，使用两次变化，才能正常使用 --end
-            let stock = v_json[prod_id]["StockState"].to_string();
-            debug!(
-                "stock:{}, StockState:{}",
-                stock.as_str(),
-                v_json.to_string()
-            );
-            return Ok(StockStatus::from(stock.as_str()));
-        } else {
-            info!("请求商品库存失败:{}-{}", status, &prod_id);
-            return Ok(StockStatus::Unknown);
+        if status != StatusCode::OK {
+            info!("请求商品库存失败:{}-{}", status, skus.join(","));
+            return Err(OpError::QueryFailed("stock", status.as_u16()).into());
         }
+        let txt = resp.text().await?;
+        debug!("stocks:{}", txt);
+        Ok(parse_stocks(txt.as_str())?)
     }
 
-    async fn get_prod_price(sku: &str) -> Result<(String, String)> {
+    /// 一次请求查询多个商品的 (现价, 原价), 最多 PRICE_BATCH_SIZE 个; 查不到价格的商品不在结果里
+    pub async fn fetch_prices(skus: &[String]) -> Result<HashMap<String, (String, String)>> {
+        let ids: Vec<String> = skus.iter().map(|sku| format!("J_{}", sku)).collect();
         let encoded = form_urlencoded::Serializer::new(String::new())
             .append_pair("type", "1")
-            .append_pair("skuIds", format!("J_{}", sku).as_str())
+            .append_pair("skuIds", ids.join(",").as_str())
             .finish();
-        let resp = reqwest::get(format!("{}?{}", Self::PRICE_URL, encoded)).await?;
+        let resp = HttpClient::new()
+            .get(format!("{}?{}", endpoints().await.prices(), encoded))
+            .send()
+            .await?;
         let status = resp.status();
-        let r: Value = resp.json::<Value>().await?;
-        if r.is_array() && r.as_array().unwrap().len() > 0 {
-            let d = &r[0];
-            debug!("price:{}-{}", sku, r.to_string());
-            let has_data = d.get("p").is_some();
-            if has_data {
-                return Ok((
-                    d["p"]
-                        .as_str()
-                        .unwrap_or("")
-                        .trim_matches(|c| c == '\"')
-                        .to_string(),
-                    d["op"]
-                        .as_str()
-                        .unwrap_or("")
-                        .trim_matches(|c| c == '\"')
-                        .to_string(),
-                ));
-            }
+        if status != StatusCode::OK {
+            info!("请求商品价格失败:{}-{}", status, skus.join(","));
+            return Err(OpError::QueryFailed("price", status.as_u16()).into());
         }
-        warn!("Can't get price info: {}-{}", sku, status);
-        Ok(("".to_string(), "".to_string()))
+        let txt = resp.text().await?;
+        debug!("prices:{}", txt);
+        Ok(parse_prices(txt.as_str())?)
     }
 
-    pub fn get_purchase_info(html: &Html) -> Result<(String, String)> {
-        let mut purchase_type = NORMAL.to_string();
-        let mut cart_link = String::default();
-        'selectors: for selector in &["a#choose-btn-ko", "a#btn-reservation", "a#InitCartUrl"] {
-            let purchase_selector = Selector::parse(*selector).unwrap();
-            'finder: for element in html.select(&purchase_selector) {
-                if let Some(link) = element.value().attr("href") {
-                    if let Some(_id) = element.value().attr("id") {
-                        // 秒杀的商品
-                        if _id.contains("choose-btn-ko") {
-                            purchase_type = SECOND_KILL.to_string();
-                        } else if _id.contains("btn-reservation") {
-                            let txt = element.inner_html();
-                            let _txt = txt.trim();
-                            if _txt.contains("立即预约") {
-                                purchase_type = YUYUE.to_string();
-                            } else if _txt.contains("支付定金")
-                                || _txt.contains("立即购买")
-                            {
-                                // https://item.jd.com/100016777664.html  支付定金的预售商品
-                                // https://item.jd.com/31335300862.html  立即购买的预售商品
-                                purchase_type = PRESALE.to_string();
-                            }else if _txt.contains("抢购"){
-                                purchase_type = SECOND_KILL.to_string();
-                            }
-                        }
-                    }
-                    let link = link.trim();
-                    if link.contains("none") {
-                        continue 'finder;
-                    }
-                    if link.starts_with("//") {
-                        cart_link = format!("https:{}", link);
-                    } else {
-                        cart_link = link.to_string();
-                    }
-                    info!("Found purchase link: {}", &cart_link);
-                    break 'selectors;
-                }
-            }
+    /// 按关键字搜索商品, shop 不为空时只保留店铺名称包含它的商品, 并查询在 area_id 的库存
+    pub async fn search_goods(keyword: &str, page: u32, area_id: &str, shop: &str) -> Result<SearchPage> {
+        let url = endpoints().await.search(keyword, page);
+        let resp = HttpClient::build(default_client(""))?.get(&url).send().await?;
+        let txt = resp.text().await?;
+        let mut ret = parse_search_page(txt.as_str())?;
+        ret.keyword = keyword.to_string();
+        let shop = shop.trim();
+        if !shop.is_empty() {
+            ret.items.retain(|item| item.shop.contains(shop));
+        }
+        let skus: Vec<String> = ret.items.iter().map(|item| item.sku.clone()).collect();
+        let stocks = Self::get_prod_stocks(&skus, area_id).await?;
+        for (item, status) in ret.items.iter_mut().zip(stocks) {
+            item.status = status;
         }
-        Ok((purchase_type, cart_link))
+        Ok(ret)
+    }
+
+    pub fn get_purchase_info(html: &Html) -> Result<(String, String)> {
+        Ok(parse_purchase_info(html)?)
     }
 
     pub async fn get_goods_info(sku: &str) -> Result<String>{
-        let prod_url = format!("https://item.jd.com/{}.html", &sku);
-        let resp = default_client("")
-            .build()?
+        let prod_url = endpoints().await.item_page(sku);
+        let resp = HttpClient::build(default_client(""))?
             .get(&prod_url)
             .send()
             .await?;
@@ -186,54 +163,21 @@ impl GoodsService {
     }
 
     pub async fn get_prod_info(sku: &str, area_id: &str) -> Result<GoodsState> {
+        // 库存和价格走 inventory, 和其他地方的查询合并、共用缓存
         let (goods_info, stock_info, price_info) = join!(
             Self::get_goods_info(sku),
-            Self::inner_get_prod_stock(sku, area_id),
-            Self::get_prod_price(sku)
+            inventory::stocks(vec![sku.to_string()], area_id.to_string()),
+            inventory::prices(vec![sku.to_string()])
         );
-        let resp_html = goods_info?;
-        let stock_info = stock_info?;
-        let price_info = price_info?;
-        let mut purchase_type = NORMAL.to_string();
-        let mut cart_link = String::new();
-        let mut area_id = String::with_capacity(20);
-        let mut title = String::with_capacity(60);
-        let mut name = String::new();
-        {
-            let pname = "name:";
-            if let Some(idx) = resp_html.find(pname) {
-                let txt = resp_html.as_str()[idx + pname.len()..].trim_start();
-                let end = "',";
-                if let Some(endIdx) = txt.find(end) {
-                    let x: &[_] = &['\'', '"', ' '];
-                    name = txt[..endIdx].trim_matches(x).to_string();
-                }
-            }
-            let document = Html::parse_document(&resp_html);
-            if name.is_empty() {
-                let mut name_selector = Selector::parse("div#name h1").unwrap(); // 找到商品名称
-                for element in document.select(&name_selector) {
-                    name = element.inner_html().trim().to_string();
-                    break;
-                }
-                if name.is_empty() {
-                    name_selector = Selector::parse("div.sku-name").unwrap(); // 找到商品名称
-                    for element in document.select(&name_selector) {
-                        name = element.inner_html().trim().to_string();
-                        info!("sku{}, inner_html: {}", sku, &name);
-                        if let Some(idx) = name.find(r#">"#) {
-                            name = name.split_off(idx + r#">"#.len());
-                        }
-                        break;
-                    }
-                }
-            }
-            // 地区需要根据当前的收货的地址来传
-            // 显示商品有货无货需要调用接口
-            let info = Self::get_purchase_info(&document)?;
-            purchase_type = info.0;
-            cart_link = info.1;
-        }
+        let page = parse_item_page(goods_info?.as_str())?;
+        let stock_info = stock_info?.remove(sku).unwrap_or(StockStatus::Unknown);
+        // 下架或者还没开卖的商品没有价格, 照常返回商品信息, 价格为空
+        let price_info = price_info?.remove(sku).unwrap_or_else(|| {
+            warn!("查不到商品价格:{}", sku);
+            Default::default()
+        });
+        let purchase_type = page.purchase_type;
+        let mut cart_link = page.purchase_url;
 
         if cart_link.is_empty() {
             if purchase_type.eq(SECOND_KILL) {
@@ -242,14 +186,12 @@ impl GoodsService {
                 // 预约的商品在加入购物车的时候，才会去获取到预约 开始时间
                 // cart_link = format!("https://cart.jd.com/gate.action?pid={}&pcount=1&ptype=1", sku);
             } else {
-                cart_link = format!(
-                    "https://cart.jd.com/gate.action?pid={}&pcount=1&ptype=1",
-                    sku
-                );
+                cart_link = endpoints().await.add_to_cart(sku, 1);
             }
         }
         let goods_data = GoodsState {
-            name: name.trim().to_owned(),
+            platform: ProdPlatform::JD,
+            name: page.name,
             sku: sku.to_string(),
             cur_price: price_info.0,
             ori_price: price_info.1,
@@ -258,22 +200,25 @@ impl GoodsService {
             purchase_num: 1,
             purchase_url: cart_link,
             purchase_type,
+            category_hint: page.category_hint,
             ..Default::default()
         };
         Ok(goods_data)
     }
 
     pub async fn get_goods_seckill_link(sku: String) -> Result<String> {
-        let client = default_client("").build()?;
+        let client = HttpClient::build(default_client(""))?;
         Self::get_goods_seckill_link2(sku, client).await
     }
 
-    pub async fn get_goods_seckill_link2(sku: String, client: reqwest::Client) -> Result<String> {
+    pub async fn get_goods_seckill_link2(sku: String, client: HttpClient) -> Result<String> {
+        let ep = endpoints().await;
         let seckill_url = {
             let mut rng = thread_rng();
             let jqn: u32 = rng.gen_range(1000000..=9999999);
             format!(
-                "https://itemko.jd.com/itemShowBtn?callback=jQuery{}&skuId={}&from=pc&_={}",
+                "{}?callback=jQuery{}&skuId={}&from=pc&_={}",
+                ep.item_show_btn(),
                 jqn,
                 sku.as_str(),
                 PKLocal::now().timestamp()
@@ -282,10 +227,7 @@ impl GoodsService {
         let resp = client
             .get(seckill_url)
             .header("Host", "itemko.jd.com")
-            .header(
-                "Referer",
-                format!("https://item.jd.com/{}.html", sku.as_str()),
-            )
+            .header("Referer", ep.item_page(sku.as_str()))
             .send()
             .await?;
         let status = resp.status();
@@ -307,7 +249,7 @@ impl GoodsService {
     }
 
     pub async fn get_presale_info(sku: String) -> Result<(String, Option<PKDateTime>, String)> {
-        let url = format!("https://item.jd.com/{}.html", &sku);
+        let url = endpoints().await.item_page(&sku);
         let mut href = String::new();
         let mut yuyue_dt = None;
         {
@@ -353,3 +295,6 @@ impl GoodsService {
         Ok((sku, yuyue_dt, href))
     }
 }
+
+#[cfg(test)]
+mod tests;
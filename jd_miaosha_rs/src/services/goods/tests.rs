@@ -0,0 +1,78 @@
+use crate::error::{JdMiaoshaError, ParseError};
+use crate::models::{ProdPlatform, StockStatus};
+use crate::services::goods::GoodsService;
+use crate::services::retailer::retailer;
+use crate::services::transport::{HttpResponse, JdScenario};
+use crate::testing::{AREA, SKU, fixture, install, search_page};
+
+#[tokio::test]
+async fn test_get_prod_info() {
+    let _g = install(JdScenario::Success).await;
+    let info = GoodsService::get_prod_info(SKU, AREA).await.unwrap();
+    assert_eq!(info.name, "模拟商品100012043978");
+    assert_eq!(info.cur_price, "99.00");
+    assert_eq!(info.ori_price, "129.00");
+    assert_eq!(info.status, StockStatus::OnSale);
+}
+
+#[tokio::test]
+async fn test_get_prod_info_without_price() {
+    let g = install(JdScenario::Success).await;
+    g.mock.replace(
+        "p.3.cn/prices/mgets",
+        HttpResponse::ok(fixture("price_missing.json")),
+    );
+    // 没有价格时照常返回商品, 价格为空
+    let info = GoodsService::get_prod_info(SKU, AREA).await.unwrap();
+    assert_eq!(info.name, "模拟商品100012043978");
+    assert_eq!((info.cur_price.as_str(), info.ori_price.as_str()), ("", ""));
+    // 只查价格时还是报错
+    assert!(matches!(
+        retailer(ProdPlatform::JD).get_prod_price(SKU.to_string()).await,
+        Err(JdMiaoshaError::ParseError(ParseError::MissingField("price", _)))
+    ));
+}
+
+#[tokio::test]
+async fn test_get_prod_stock_out_of_stock() {
+    let _g = install(JdScenario::OutOfStock).await;
+    let (id, status) = retailer(ProdPlatform::JD)
+        .get_prod_stock(11, SKU.to_string(), AREA.to_string())
+        .await
+        .unwrap();
+    assert_eq!(id, 11);
+    assert_eq!(status, StockStatus::OutOfStock);
+}
+
+#[tokio::test]
+async fn test_search_goods() {
+    let g = install(JdScenario::Success).await;
+    g.mock.on(
+        "search.jd.com/Search",
+        HttpResponse::ok(search_page()),
+    );
+    g.mock.replace(
+        "c0.3.cn/stocks",
+        HttpResponse::json_ok(serde_json::json!({
+            "100009077475": {"StockState": 33},
+            "100016777664": {"StockState": 34}
+        })),
+    );
+    let page = GoodsService::search_goods("手机", 2, AREA, "").await.unwrap();
+    assert_eq!(page.keyword, "手机");
+    // 第 2 页对应京东的 page=3
+    assert_eq!(g.mock.hits("Search?keyword=%E6%89%8B%E6%9C%BA&enc=utf-8&page=3 "), 1);
+    // 一次请求查询所有商品的库存
+    assert_eq!(g.mock.hits("skuIds=100009077475%2C100016777664%2C10025346234651"), 1);
+    let status: Vec<String> = page.items.iter().map(|i| i.status.to_string()).collect();
+    assert_eq!(status, vec!["有货", "无货", "未知"]);
+
+    // 只看自营店铺
+    let page = GoodsService::search_goods("手机", 2, AREA, "自营").await.unwrap();
+    assert_eq!(page.items.len(), 2);
+    assert_eq!(g.mock.hits("skuIds=100009077475%2C100016777664&"), 1);
+
+    // 页面结构变了时返回错误
+    g.mock.replace("search.jd.com/Search", HttpResponse::ok("<html></html>"));
+    assert!(GoodsService::search_goods("手机", 1, AREA, "").await.is_err());
+}
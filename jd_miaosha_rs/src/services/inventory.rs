@@ -0,0 +1,199 @@
+//! 库存和价格查询: 多个商品合并成一次请求, 重复的商品只查一次, 查到的结果缓存一小会儿;
+//! 接口连续出错时等待的时间逐次翻倍, 不再每次都去请求
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use log::warn;
+
+use crate::error::{OpError, Result};
+use crate::models::StockStatus;
+use crate::services::goods::{GoodsService, PRICE_BATCH_SIZE, STOCK_BATCH_SIZE};
+use crate::utils::now;
+
+// 查到的库存和价格在这段时间内直接用, 要小于最短的库存检查间隔
+pub const CACHE_TTL_MILLIS: i64 = 800;
+// 第一次出错后等多久, 之后每次翻倍
+pub const BACKOFF_BASE_MILLIS: i64 = 1000;
+pub const BACKOFF_MAX_MILLIS: i64 = 30_000;
+
+/// 一种查询的缓存和退避状态, 时间都是毫秒时间戳
+#[derive(Debug, Clone, Default)]
+pub struct QueryCache<K, V> {
+    entries: HashMap<K, (i64, V)>,
+    // 连续出错的次数
+    failures: u32,
+    retry_at: i64,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> QueryCache<K, V> {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            failures: 0,
+            retry_at: 0,
+        }
+    }
+
+    /// 没过期的缓存
+    pub fn get(&self, key: &K, now: i64) -> Option<V> {
+        self.entries
+            .get(key)
+            .filter(|(at, _)| now - at < CACHE_TTL_MILLIS)
+            .map(|(_, v)| v.clone())
+    }
+
+    pub fn put(&mut self, key: K, value: V, now: i64) {
+        self.entries.retain(|_, (at, _)| now - *at < CACHE_TTL_MILLIS);
+        self.entries.insert(key, (now, value));
+    }
+
+    /// 正在退避时返回还要等的毫秒数
+    pub fn backoff_remaining(&self, now: i64) -> Option<i64> {
+        Some(self.retry_at - now).filter(|ms| *ms > 0)
+    }
+
+    pub fn succeed(&mut self) {
+        self.failures = 0;
+        self.retry_at = 0;
+    }
+
+    /// 记一次出错, 返回下次可以请求之前要等的毫秒数
+    pub fn fail(&mut self, now: i64) -> i64 {
+        let wait = (BACKOFF_BASE_MILLIS << self.failures.min(16)).min(BACKOFF_MAX_MILLIS);
+        self.failures += 1;
+        self.retry_at = now + wait;
+        wait
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+lazy_static! {
+    static ref STOCKS: Mutex<QueryCache<(String, String), StockStatus>> = Mutex::new(QueryCache::new());
+    static ref PRICES: Mutex<QueryCache<String, (String, String)>> = Mutex::new(QueryCache::new());
+    // 同一种查询同时只有一批请求在路上, 后来的等它查完先看缓存, 重复的商品不会再查
+    static ref STOCKS_FETCH: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+    static ref PRICES_FETCH: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
+
+fn now_millis() -> i64 {
+    now().timestamp_millis()
+}
+
+/// 先用缓存, 缓存里没有的去重后按 batch_size 分批查询;
+/// 后面的批次出错时返回前面查到的, 一个都没查到才返回错误
+async fn query<K, V, F, Fut>(
+    name: &'static str,
+    cache: &Mutex<QueryCache<K, V>>,
+    fetch_lock: &tokio::sync::Mutex<()>,
+    keys: Vec<K>,
+    batch_size: usize,
+    fetch: F,
+) -> Result<HashMap<K, V>>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    F: Fn(Vec<K>) -> Fut,
+    Fut: Future<Output = Result<HashMap<K, V>>>,
+{
+    let mut ret = HashMap::new();
+    let lookup = |ret: &mut HashMap<K, V>| -> Vec<K> {
+        let cache = cache.lock().unwrap();
+        let now = now_millis();
+        let mut missing: Vec<K> = vec![];
+        for key in keys.iter() {
+            if ret.contains_key(key) || missing.contains(key) {
+                continue;
+            }
+            match cache.get(key, now) {
+                Some(v) => {
+                    ret.insert(key.clone(), v);
+                }
+                None => missing.push(key.clone()),
+            }
+        }
+        missing
+    };
+    if lookup(&mut ret).is_empty() {
+        return Ok(ret);
+    }
+    let _fetching = fetch_lock.lock().await;
+    // 等锁的时候别人可能已经查过了
+    let missing = lookup(&mut ret);
+    for batch in missing.chunks(batch_size) {
+        let backoff = cache.lock().unwrap().backoff_remaining(now_millis());
+        if let Some(ms) = backoff {
+            if ret.is_empty() {
+                return Err(OpError::BackingOff(name, ms).into());
+            }
+            break;
+        }
+        match fetch(batch.to_vec()).await {
+            Ok(found) => {
+                let mut cache = cache.lock().unwrap();
+                let now = now_millis();
+                cache.succeed();
+                for (k, v) in found {
+                    cache.put(k.clone(), v.clone(), now);
+                    ret.insert(k, v);
+                }
+            }
+            Err(e) => {
+                let wait = cache.lock().unwrap().fail(now_millis());
+                warn!("查询{}出错, {}毫秒后再试:{:?}", name, wait, e);
+                if ret.is_empty() {
+                    return Err(e);
+                }
+                break;
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// 商品在某个地区的库存, 返回里没有的商品是接口没有返回
+pub async fn stocks(skus: Vec<String>, area: String) -> Result<HashMap<String, StockStatus>> {
+    let keys = skus.into_iter().map(|sku| (sku, area.clone())).collect();
+    let found = query("stock", &STOCKS, &STOCKS_FETCH, keys, STOCK_BATCH_SIZE, |batch| {
+        let area = area.clone();
+        async move {
+            let skus: Vec<String> = batch.into_iter().map(|(sku, _)| sku).collect();
+            let stocks = GoodsService::fetch_stocks(&skus, area.as_str()).await?;
+            Ok(stocks.into_iter().map(|(sku, s)| ((sku, area.clone()), s)).collect())
+        }
+    })
+    .await?;
+    Ok(found.into_iter().map(|((sku, _), s)| (sku, s)).collect())
+}
+
+/// 商品的 (现价, 原价), 返回里没有的商品是查不到价格
+pub async fn prices(skus: Vec<String>) -> Result<HashMap<String, (String, String)>> {
+    query("price", &PRICES, &PRICES_FETCH, skus, PRICE_BATCH_SIZE, |batch| async move {
+        GoodsService::fetch_prices(&batch).await
+    })
+    .await
+}
+
+/// 不用缓存、不管退避直接查询价格, 下单前核对价格时用, 查到的结果放进缓存
+pub async fn prices_now(skus: Vec<String>) -> Result<HashMap<String, (String, String)>> {
+    let found = GoodsService::fetch_prices(&skus).await?;
+    let mut cache = PRICES.lock().unwrap();
+    let now = now_millis();
+    for (sku, price) in found.iter() {
+        cache.put(sku.clone(), price.clone(), now);
+    }
+    Ok(found)
+}
+
+/// 清掉缓存和退避状态, 切换接口地址或测试时用
+pub fn clear() {
+    STOCKS.lock().unwrap().clear();
+    PRICES.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests;
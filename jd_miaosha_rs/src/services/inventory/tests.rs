@@ -0,0 +1,100 @@
+use http::StatusCode;
+
+use crate::error::{JdMiaoshaError, OpError};
+use crate::models::ProdPlatform;
+use crate::services::inventory::{
+    self, QueryCache, BACKOFF_BASE_MILLIS, BACKOFF_MAX_MILLIS, CACHE_TTL_MILLIS,
+};
+use crate::services::retailer::check_max_price;
+use crate::services::transport::{HttpResponse, JdScenario};
+use crate::testing::{AREA, SKU, install};
+
+#[test]
+fn test_query_cache() {
+    let mut cache: QueryCache<String, u32> = QueryCache::new();
+    cache.put("a".to_string(), 1, 1000);
+    assert_eq!(cache.get(&"a".to_string(), 1000 + CACHE_TTL_MILLIS - 1), Some(1));
+    assert_eq!(cache.get(&"a".to_string(), 1000 + CACHE_TTL_MILLIS), None);
+    assert_eq!(cache.get(&"b".to_string(), 1000), None);
+
+    // 连续出错时等待时间翻倍, 不超过上限, 成功后恢复
+    assert_eq!(cache.backoff_remaining(0), None);
+    assert_eq!(cache.fail(0), BACKOFF_BASE_MILLIS);
+    assert_eq!(cache.backoff_remaining(0), Some(BACKOFF_BASE_MILLIS));
+    assert_eq!(cache.backoff_remaining(BACKOFF_BASE_MILLIS), None);
+    assert_eq!(cache.fail(0), BACKOFF_BASE_MILLIS * 2);
+    for _ in 0..20 {
+        cache.fail(0);
+    }
+    assert_eq!(cache.fail(0), BACKOFF_MAX_MILLIS);
+    cache.succeed();
+    assert_eq!(cache.backoff_remaining(0), None);
+    assert_eq!(cache.fail(0), BACKOFF_BASE_MILLIS);
+}
+
+#[tokio::test]
+async fn test_inventory_batch() {
+    let g = install(JdScenario::Success).await;
+    let other = "100009077475".to_string();
+    // 重复的商品只查一次, 多个商品合并成一次请求
+    let skus = vec![SKU.to_string(), other.clone(), SKU.to_string()];
+    let stocks = inventory::stocks(skus.clone(), AREA.to_string()).await.unwrap();
+    assert_eq!(stocks[SKU].to_string(), "有货");
+    // 接口没有返回的商品不在结果里
+    assert!(!stocks.contains_key(&other));
+    assert_eq!(g.mock.hits("c0.3.cn/stocks"), 1);
+    assert_eq!(g.mock.hits(format!("skuIds={}%2C{}&", SKU, other).as_str()), 1);
+    let prices = inventory::prices(skus.clone()).await.unwrap();
+    assert_eq!(prices[SKU], ("99.00".to_string(), "129.00".to_string()));
+    assert_eq!(g.mock.hits(format!("skuIds=J_{}%2CJ_{}", SKU, other).as_str()), 1);
+
+    // 缓存有效期内不再请求, 换了地区要重新查
+    inventory::stocks(skus.clone(), AREA.to_string()).await.unwrap();
+    inventory::prices(vec![SKU.to_string()]).await.unwrap();
+    assert_eq!(g.mock.hits("c0.3.cn/stocks"), 1);
+    assert_eq!(g.mock.hits("p.3.cn/prices/mgets"), 1);
+    inventory::stocks(vec![SKU.to_string()], "1_72_2799_0".to_string()).await.unwrap();
+    assert_eq!(g.mock.hits("c0.3.cn/stocks"), 2);
+
+    // 出错后退避, 这段时间内不再请求
+    inventory::clear();
+    g.mock.replace("c0.3.cn/stocks", HttpResponse::new(StatusCode::SERVICE_UNAVAILABLE, ""));
+    assert!(matches!(
+        inventory::stocks(vec![SKU.to_string()], AREA.to_string()).await.unwrap_err(),
+        JdMiaoshaError::OpError(OpError::QueryFailed("stock", 503))
+    ));
+    assert!(matches!(
+        inventory::stocks(vec![SKU.to_string()], AREA.to_string()).await.unwrap_err(),
+        JdMiaoshaError::OpError(OpError::BackingOff("stock", _))
+    ));
+    assert_eq!(g.mock.hits("c0.3.cn/stocks"), 3);
+    // 下单前核对价格不受退避影响
+    inventory::clear();
+    g.mock.replace("p.3.cn/prices/mgets", HttpResponse::new(StatusCode::SERVICE_UNAVAILABLE, ""));
+    assert!(inventory::prices(vec![SKU.to_string()]).await.is_err());
+    assert!(matches!(
+        inventory::prices(vec![SKU.to_string()]).await.unwrap_err(),
+        JdMiaoshaError::OpError(OpError::BackingOff("price", _))
+    ));
+    g.mock.replace(
+        "p.3.cn/prices/mgets",
+        HttpResponse::json_ok(serde_json::json!([{"id": format!("J_{}", SKU), "p": "89.00", "op": "129.00"}])),
+    );
+    assert_eq!(
+        check_max_price(ProdPlatform::JD, SKU.to_string(), 90.0).await.unwrap(),
+        ("89.00".to_string(), true)
+    );
+
+    // 后面的批次出错时保留前面批次查到的
+    inventory::clear();
+    let many: Vec<String> = std::iter::once(SKU.to_string())
+        .chain((0..STOCK_BATCH_SIZE).map(|i| format!("9{:011}", i)))
+        .collect();
+    let mut stock = serde_json::json!({});
+    stock[SKU] = serde_json::json!({"StockState": 33});
+    g.mock.replace("c0.3.cn/stocks", HttpResponse::json_ok(stock));
+    g.mock.on("c0.3.cn/stocks", HttpResponse::new(StatusCode::SERVICE_UNAVAILABLE, ""));
+    let stocks = inventory::stocks(many, AREA.to_string()).await.unwrap();
+    assert_eq!(stocks.len(), 1);
+    assert_eq!(stocks[SKU].to_string(), "有货");
+}
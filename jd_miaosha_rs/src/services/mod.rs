@@ -1,11 +1,27 @@
+pub mod api;
 pub mod area;
+pub mod budget;
+pub mod category;
 pub mod delivery_address;
 pub mod driver;
+pub mod dry_run;
+pub mod endpoints;
 pub mod goods;
+pub mod inventory;
+pub mod notify;
 pub mod order;
+pub mod order_export;
+pub mod parser;
+pub mod price_history;
 pub mod reqwest_async;
+pub mod retailer;
+pub mod scheduler;
 pub mod shopping_cart;
+pub mod store;
+pub mod transport;
+pub mod unpaid;
 pub mod user;
+pub mod watch;
 
 use std::sync::Arc;
 use tokio::sync::RwLock;
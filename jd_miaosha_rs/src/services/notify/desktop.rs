@@ -0,0 +1,58 @@
+//! 桌面通知: linux 下调用 gdbus 走 freedesktop 的 org.freedesktop.Notifications D-Bus 接口
+use async_trait::async_trait;
+
+use crate::error::{OpError, Result};
+
+use super::{Event, NotifySink, SinkKind};
+
+// 通知显示的毫秒数
+#[cfg(target_os = "linux")]
+const EXPIRE_TIMEOUT: i32 = 10000;
+
+pub struct DesktopSink;
+
+/// gdbus 的参数是 GVariant 文本格式, 字符串要加引号并转义
+#[cfg(target_os = "linux")]
+fn gvariant_str(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[async_trait]
+impl NotifySink for DesktopSink {
+    fn kind(&self) -> SinkKind {
+        SinkKind::Desktop
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn send(&self, event: &Event) -> Result<()> {
+        let output = tokio::process::Command::new("gdbus")
+            .args(&[
+                "call",
+                "--session",
+                "--dest",
+                "org.freedesktop.Notifications",
+                "--object-path",
+                "/org/freedesktop/Notifications",
+                "--method",
+                "org.freedesktop.Notifications.Notify",
+                "'jd_miaosha_rs'",
+                "0",
+                "''",
+            ])
+            .arg(gvariant_str(event.title.as_str()))
+            .arg(gvariant_str(event.body.as_str()))
+            .args(&["[]", "{}"])
+            .arg(EXPIRE_TIMEOUT.to_string())
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(OpError::Notify(String::from_utf8_lossy(&output.stderr).to_string()).into());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn send(&self, _event: &Event) -> Result<()> {
+        Err(OpError::Notify("desktop notification is only supported on linux".to_string()).into())
+    }
+}
@@ -0,0 +1,141 @@
+//! 邮件: 最简单的 SMTP 客户端, 不支持 TLS, 需要发到本机或内网的邮件中继;
+//! 配置了用户名密码时用 AUTH PLAIN 登录
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::error::{OpError, Result};
+
+use super::{Event, NotifySink, SinkKind};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+fn default_port() -> u16 {
+    25
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct EmailConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+pub struct EmailSink {
+    config: EmailConfig,
+}
+
+impl EmailSink {
+    pub fn new(config: EmailConfig) -> Self {
+        Self { config }
+    }
+}
+
+fn base64(input: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity((input.len() + 2) / 3 * 4);
+    for chunk in input.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// 邮件内容, 标题按 RFC 2047 编码, 正文用 base64 避免中文和以 . 开头的行
+pub fn build_message(from: &str, to: &[String], event: &Event) -> String {
+    let body = base64(event.body.as_bytes());
+    let lines: Vec<&str> = body
+        .as_bytes()
+        .chunks(76)
+        .map(|c| std::str::from_utf8(c).unwrap_or_default())
+        .collect();
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: =?UTF-8?B?{}?=\r\nMIME-Version: 1.0\r\n\
+         Content-Type: text/plain; charset=UTF-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+        from,
+        to.join(", "),
+        base64(event.title.as_bytes()),
+        lines.join("\r\n")
+    )
+}
+
+struct SmtpConn {
+    reader: BufReader<TcpStream>,
+}
+
+impl SmtpConn {
+    // 读一个应答, 多行应答以 "250-" 这样的开头, 最后一行是 "250 "
+    async fn reply(&mut self, expect: &str) -> Result<()> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).await? == 0 {
+                return Err(OpError::Notify("smtp connection closed".to_string()).into());
+            }
+            if line.len() < 4 || !line.starts_with(expect) {
+                return Err(OpError::Notify(format!("smtp expect {}, got: {}", expect, line.trim_end())).into());
+            }
+            if line.as_bytes()[3] == b' ' {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn cmd(&mut self, cmd: &str, expect: &str) -> Result<()> {
+        self.reader.get_mut().write_all(format!("{}\r\n", cmd).as_bytes()).await?;
+        self.reply(expect).await
+    }
+}
+
+impl EmailSink {
+    async fn deliver(&self, event: &Event) -> Result<()> {
+        let c = &self.config;
+        let stream = TcpStream::connect((c.host.as_str(), c.port)).await?;
+        let mut conn = SmtpConn {
+            reader: BufReader::new(stream),
+        };
+        conn.reply("220").await?;
+        conn.cmd("EHLO jd_miaosha_rs", "250").await?;
+        if let (Some(username), Some(password)) = (c.username.as_ref(), c.password.as_ref()) {
+            let token = base64(format!("\0{}\0{}", username, password).as_bytes());
+            conn.cmd(format!("AUTH PLAIN {}", token).as_str(), "235").await?;
+        }
+        conn.cmd(format!("MAIL FROM:<{}>", c.from).as_str(), "250").await?;
+        for to in c.to.iter() {
+            conn.cmd(format!("RCPT TO:<{}>", to).as_str(), "250").await?;
+        }
+        conn.cmd("DATA", "354").await?;
+        let message = build_message(c.from.as_str(), c.to.as_slice(), event);
+        conn.cmd(format!("{}.", message).as_str(), "250").await?;
+        conn.cmd("QUIT", "221").await
+    }
+}
+
+#[async_trait]
+impl NotifySink for EmailSink {
+    fn kind(&self) -> SinkKind {
+        SinkKind::Email
+    }
+
+    async fn send(&self, event: &Event) -> Result<()> {
+        if self.config.to.is_empty() {
+            return Err(OpError::Notify("email has no recipients".to_string()).into());
+        }
+        tokio::time::timeout(TIMEOUT, self.deliver(event))
+            .await
+            .map_err(|_| OpError::Notify(format!("smtp {} timeout", self.config.host)))?
+    }
+}
@@ -0,0 +1,160 @@
+//! 通知: 抢购结果、cookie 失效、激活码失效、有货、降价等事件按 conf.toml 里 [notify.routes] 配置的规则,
+//! 发到桌面通知、提示音、webhook 和邮件; 没有配置规则的事件只写日志
+mod desktop;
+mod email;
+mod sound;
+mod webhook;
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::utils::datetime_fmt;
+use crate::{PKLocal, CONFIG};
+
+pub use desktop::DesktopSink;
+pub use email::{build_message, EmailConfig, EmailSink};
+pub use sound::{SoundConfig, SoundSink};
+pub use webhook::{WebhookConfig, WebhookSink};
+
+/// 要通知的事件类型, 也是路由规则的 key
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    // 抢购成功
+    PurchaseSuccess,
+    // 抢购失败, 包括超过最高价格
+    PurchaseFail,
+    // 账号的 cookie 失效
+    CookieExpired,
+    // 激活码失效
+    LicenseExpired,
+    // 监控的商品有货了
+    StockAvailable,
+    // 到货提醒里的商品低于提醒价格
+    PriceDrop,
+    // 待付款订单快到付款截止时间
+    PaymentDue,
+    // 待付款订单只剩最后几分钟
+    PaymentUrgent,
+}
+
+impl EventKind {
+    /// 抢购结束时的状态对应的事件, 预约中、试运行等不通知
+    pub fn from_purchase_status(status: &str) -> Option<Self> {
+        match status {
+            "success" => Some(EventKind::PurchaseSuccess),
+            "fail" | "price-high" => Some(EventKind::PurchaseFail),
+            _ => None,
+        }
+    }
+}
+
+/// 通知的方式
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkKind {
+    // 系统桌面通知, linux 下走 freedesktop 的 D-Bus 接口
+    Desktop,
+    // 提示音
+    Sound,
+    // http webhook
+    Webhook,
+    // 邮件
+    Email,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Event {
+    pub kind: EventKind,
+    pub title: String,
+    pub body: String,
+    pub create_dt: String,
+}
+
+impl Event {
+    pub fn new<T: Into<String>, B: Into<String>>(kind: EventKind, title: T, body: B) -> Self {
+        Self {
+            kind,
+            title: title.into(),
+            body: body.into(),
+            create_dt: datetime_fmt(&PKLocal::now()),
+        }
+    }
+}
+
+#[async_trait]
+pub trait NotifySink: Send + Sync {
+    fn kind(&self) -> SinkKind;
+
+    async fn send(&self, event: &Event) -> Result<()>;
+}
+
+/// 通知配置, 在 conf.toml 的 [notify] 里配置
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct NotifyConfig {
+    pub sound: SoundConfig,
+    pub webhook: Option<WebhookConfig>,
+    pub email: Option<EmailConfig>,
+    // 事件类型 -> 通知方式
+    pub routes: HashMap<EventKind, Vec<SinkKind>>,
+}
+
+impl NotifyConfig {
+    /// 事件要发到的通知方式, 没有配置 webhook 或邮件的跳过
+    pub fn sinks(&self, kind: EventKind) -> Vec<Box<dyn NotifySink>> {
+        self.routes
+            .get(&kind)
+            .map(|kinds| kinds.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|k| -> Option<Box<dyn NotifySink>> {
+                match k {
+                    SinkKind::Desktop => Some(Box::new(DesktopSink)),
+                    SinkKind::Sound => Some(Box::new(SoundSink::new(self.sound.clone()))),
+                    SinkKind::Webhook => self
+                        .webhook
+                        .clone()
+                        .map(|c| Box::new(WebhookSink::new(c)) as Box<dyn NotifySink>),
+                    SinkKind::Email => self
+                        .email
+                        .clone()
+                        .map(|c| Box::new(EmailSink::new(c)) as Box<dyn NotifySink>),
+                }
+            })
+            .collect()
+    }
+}
+
+/// 按配置的规则发送通知, 返回发送成功的通知方式; 发送失败只记日志
+pub async fn dispatch(config: &NotifyConfig, event: &Event) -> Vec<SinkKind> {
+    info!("通知:{:?}, {}, {}", event.kind, event.title, event.body);
+    let sinks = config.sinks(event.kind);
+    join_all(sinks.iter().map(|sink| async move {
+        match sink.send(event).await {
+            Ok(_) => Some(sink.kind()),
+            Err(e) => {
+                error!("发送通知失败:{:?}, {:?}", sink.kind(), e);
+                None
+            }
+        }
+    }))
+    .await
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// 用 conf.toml 里的配置发送通知
+pub async fn notify(event: Event) {
+    let config = CONFIG.read().await.notify();
+    dispatch(&config, &event).await;
+}
+
+#[cfg(test)]
+mod tests;
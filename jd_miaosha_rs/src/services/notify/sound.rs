@@ -0,0 +1,58 @@
+//! 提示音: 配置了声音文件就用系统的播放器播放, 否则让终端响铃
+use std::io::Write;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{OpError, Result};
+
+use super::{Event, NotifySink, SinkKind};
+
+#[cfg(target_os = "macos")]
+const DEFAULT_PLAYER: &'static str = "afplay";
+#[cfg(not(target_os = "macos"))]
+const DEFAULT_PLAYER: &'static str = "paplay";
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SoundConfig {
+    // 声音文件, 如 /usr/share/sounds/freedesktop/stereo/complete.oga
+    pub file: Option<String>,
+    // 播放声音文件的命令, 默认 linux 用 paplay, mac 用 afplay
+    pub player: Option<String>,
+}
+
+pub struct SoundSink {
+    config: SoundConfig,
+}
+
+impl SoundSink {
+    pub fn new(config: SoundConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl NotifySink for SoundSink {
+    fn kind(&self) -> SinkKind {
+        SinkKind::Sound
+    }
+
+    async fn send(&self, _event: &Event) -> Result<()> {
+        match self.config.file.as_ref() {
+            Some(file) => {
+                let player = self.config.player.as_deref().unwrap_or(DEFAULT_PLAYER);
+                let status = tokio::process::Command::new(player).arg(file).status().await?;
+                if !status.success() {
+                    return Err(OpError::Notify(format!("{} {} exit with {}", player, file, status)).into());
+                }
+            }
+            None => {
+                let mut stderr = std::io::stderr();
+                stderr.write_all(b"\x07")?;
+                stderr.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
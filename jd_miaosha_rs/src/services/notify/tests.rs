@@ -0,0 +1,139 @@
+use crate::services::dry_run::DRY_RUN;
+use crate::services::notify::{self, Event, EventKind, NotifyConfig, SinkKind};
+use crate::services::transport::{HttpResponse, JdScenario};
+use crate::testing::{SKU, install};
+
+#[test]
+fn test_notify_routes() {
+    let config: NotifyConfig = toml::from_str(
+        r#"
+        [routes]
+        purchase_success = ["desktop", "webhook", "email"]
+        cookie_expired = ["sound"]
+        [webhook]
+        url = "http://127.0.0.1:48199/hook"
+        "#,
+    )
+    .unwrap();
+    let kinds = |kind| {
+        config
+            .sinks(kind)
+            .iter()
+            .map(|s| s.kind())
+            .collect::<Vec<_>>()
+    };
+    // 没有配置邮件的跳过
+    assert_eq!(kinds(EventKind::PurchaseSuccess), vec![SinkKind::Desktop, SinkKind::Webhook]);
+    assert_eq!(kinds(EventKind::CookieExpired), vec![SinkKind::Sound]);
+    assert!(kinds(EventKind::StockAvailable).is_empty());
+    assert_eq!(EventKind::from_purchase_status("price-high"), Some(EventKind::PurchaseFail));
+    assert_eq!(EventKind::from_purchase_status(DRY_RUN), None);
+}
+
+#[tokio::test]
+async fn test_notify_webhook() {
+    let g = install(JdScenario::Success).await;
+    g.mock.on("127.0.0.1:48199/hook", HttpResponse::ok(""));
+    let config: NotifyConfig = toml::from_str(
+        r#"
+        [routes]
+        purchase_success = ["webhook"]
+        [webhook]
+        url = "http://127.0.0.1:48199/hook"
+        headers = { Authorization = "Bearer mock" }
+        "#,
+    )
+    .unwrap();
+    let event = Event::new(EventKind::PurchaseSuccess, "抢购成功", SKU);
+    assert_eq!(notify::dispatch(&config, &event).await, vec![SinkKind::Webhook]);
+    let req = g.mock.requests().into_iter().last().unwrap();
+    assert_eq!(req.header("authorization"), Some("Bearer mock"));
+    assert!(req.body.unwrap().contains("\"purchase_success\""));
+    // 没有配置规则的事件不发送
+    let event = Event::new(EventKind::PurchaseFail, "抢购失败", SKU);
+    assert!(notify::dispatch(&config, &event).await.is_empty());
+    assert_eq!(g.mock.hits("/hook"), 1);
+}
+
+#[tokio::test]
+async fn test_notify_email() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    // 本地的 SMTP 替身, 收到的 DATA 通过返回值拿到
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut stream = BufReader::new(stream);
+        stream.get_mut().write_all(b"220 mock\r\n").await.unwrap();
+        let (mut commands, mut data) = (vec![], String::new());
+        let mut in_data = false;
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.unwrap() == 0 {
+                break;
+            }
+            let reply: &[u8] = if in_data {
+                if line == ".\r\n" {
+                    in_data = false;
+                    b"250 queued\r\n"
+                } else {
+                    data.push_str(line.as_str());
+                    continue;
+                }
+            } else {
+                commands.push(line.trim_end().to_string());
+                match line.split_whitespace().next().unwrap_or_default() {
+                    "EHLO" => b"250-mock\r\n250 AUTH PLAIN\r\n",
+                    "AUTH" => b"235 ok\r\n",
+                    "DATA" => {
+                        in_data = true;
+                        b"354 go ahead\r\n"
+                    }
+                    "QUIT" => b"221 bye\r\n",
+                    _ => b"250 ok\r\n",
+                }
+            };
+            stream.get_mut().write_all(reply).await.unwrap();
+        }
+        (commands, data)
+    });
+    let config: NotifyConfig = toml::from_str(
+        format!(
+            r#"
+            [routes]
+            cookie_expired = ["email"]
+            [email]
+            host = "127.0.0.1"
+            port = {}
+            username = "u"
+            password = "p"
+            from = "miaosha@localhost"
+            to = ["a@localhost", "b@localhost"]
+            "#,
+            port
+        )
+        .as_str(),
+    )
+    .unwrap();
+    let event = Event::new(EventKind::CookieExpired, "cookie 失效", "账号 mock 的 cookie 已失效");
+    assert_eq!(notify::dispatch(&config, &event).await, vec![SinkKind::Email]);
+    let (commands, data) = server.await.unwrap();
+    assert_eq!(
+        commands,
+        vec![
+            "EHLO jd_miaosha_rs",
+            "AUTH PLAIN AHUAcA==",
+            "MAIL FROM:<miaosha@localhost>",
+            "RCPT TO:<a@localhost>",
+            "RCPT TO:<b@localhost>",
+            "DATA",
+            "QUIT",
+        ]
+    );
+    assert_eq!(
+        data,
+        notify::build_message("miaosha@localhost", &["a@localhost".to_string(), "b@localhost".to_string()], &event)
+    );
+    assert!(data.contains("Subject: =?UTF-8?B?Y29va2llIOWkseaViA==?=\r\n"));
+}
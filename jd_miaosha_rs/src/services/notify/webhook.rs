@@ -0,0 +1,49 @@
+//! webhook: 把事件以 json POST 到配置的地址
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{OpError, Result};
+use crate::services::transport::HttpClient;
+
+use super::{Event, NotifySink, SinkKind};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct WebhookConfig {
+    pub url: String,
+    // 额外的请求头, 如鉴权用的 token
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+pub struct WebhookSink {
+    config: WebhookConfig,
+}
+
+impl WebhookSink {
+    pub fn new(config: WebhookConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl NotifySink for WebhookSink {
+    fn kind(&self) -> SinkKind {
+        SinkKind::Webhook
+    }
+
+    async fn send(&self, event: &Event) -> Result<()> {
+        let mut builder = HttpClient::new()
+            .post(self.config.url.as_str())
+            .header("Content-Type", "application/json");
+        for (k, v) in self.config.headers.iter() {
+            builder = builder.header(k, v);
+        }
+        let resp = builder.body(serde_json::to_string(event)?).send().await?;
+        if !resp.status().is_success() {
+            return Err(OpError::Notify(format!("webhook {} returns {}", self.config.url, resp.status())).into());
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,9 @@
-use crate::error::{JdMiaoshaError, OpError, Result};
-use crate::models::{OrderInfo, ProdPlatform};
+use crate::error::{JdMiaoshaError, OpError, ParseError, Result};
+use crate::models::{OrderInfo, OrderTrack, ProdPlatform};
+use crate::services::parser::{parse_order_track, parse_orders};
+use crate::services::endpoints::endpoints;
+use crate::services::transport::HttpClient;
+use chrono::{Datelike, NaiveDate};
 use log::{debug, error, info, warn};
 use scraper::{Html, Selector};
 use std::collections::{BTreeMap};
@@ -21,34 +25,138 @@ impl Default for QueryCondition {
     }
 }
 
+// 订单列表的时间段: 1 是最近三个月, 2 是今年内, 其他年份直接用年份
+const RECENT_PERIOD: &'static str = "1";
+const THIS_YEAR_PERIOD: &'static str = "2";
+// 订单列表能按年份查到的最早年份, 不限制开始日期时查到这一年
+const EARLIEST_ORDER_YEAR: i32 = 2014;
+// 翻页的上限, 防止页面结构变了一直翻下去
+const MAX_ORDER_PAGES: u32 = 50;
+
+/// 覆盖 [from, today] 需要查询的时间段, from 在三个月内时只查最近三个月, 不限制 from 时查所有年份
+pub fn order_periods(from: Option<NaiveDate>, today: NaiveDate) -> Vec<String> {
+    let from = from.unwrap_or_else(|| NaiveDate::from_ymd(EARLIEST_ORDER_YEAR, 1, 1));
+    if from >= today - chrono::Duration::days(90) {
+        return vec![RECENT_PERIOD.to_string()];
+    }
+    (from.year()..=today.year())
+        .rev()
+        .map(|y| {
+            if y == today.year() {
+                THIS_YEAR_PERIOD.to_string()
+            } else {
+                y.to_string()
+            }
+        })
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct OrderService;
 
 impl OrderService {
+    /// 最近三个月的第一页订单
     pub async fn get_orders_by_user(
         account: String,
         cookie_str: Arc<String>,
         cond: QueryCondition,
     ) -> Result<BTreeMap<String, OrderInfo>> {
-        let url = match cond.clone() {
-            QueryCondition::KeyWord(kw) => {
-                format!("https://order.jd.com/center/search.action?keyword={}", kw)
+        Self::get_orders_by_page(account, cookie_str, cond, RECENT_PERIOD, 1).await
+    }
+
+    /// 按时间段逐页查询订单, 某一页没有新订单时停止
+    pub async fn get_all_orders_by_user(
+        account: String,
+        cookie_str: Arc<String>,
+        cond: QueryCondition,
+        periods: Vec<String>,
+    ) -> Result<BTreeMap<String, OrderInfo>> {
+        let mut ret = BTreeMap::new();
+        for period in periods.iter() {
+            for page in 1..=MAX_ORDER_PAGES {
+                let orders = Self::get_orders_by_page(
+                    account.clone(),
+                    cookie_str.clone(),
+                    cond.clone(),
+                    period.as_str(),
+                    page,
+                )
+                .await?;
+                let cnt = ret.len();
+                for (order_no, order) in orders.into_iter() {
+                    ret.entry(order_no).or_insert(order);
+                }
+                if ret.len() == cnt {
+                    break;
+                }
+                debug!("{} 第{}页订单, 时间段:{}", account, page, period);
             }
+        }
+        Ok(ret)
+    }
+
+    /// 逐页查询最近三个月的订单, 订单按下单时间倒序, 翻到 date 之前的订单或者某一页没有新订单时停止
+    pub async fn get_orders_since(
+        account: String,
+        cookie_str: Arc<String>,
+        cond: QueryCondition,
+        date: String,
+    ) -> Result<BTreeMap<String, OrderInfo>> {
+        let mut ret = BTreeMap::new();
+        for page in 1..=MAX_ORDER_PAGES {
+            let orders = Self::get_orders_by_page(
+                account.clone(),
+                cookie_str.clone(),
+                cond.clone(),
+                RECENT_PERIOD,
+                page,
+            )
+            .await?;
+            let passed = orders
+                .values()
+                .any(|o| !o.create_dt.is_empty() && o.create_dt.as_str() < date.as_str());
+            let cnt = ret.len();
+            for (order_no, order) in orders.into_iter() {
+                ret.entry(order_no).or_insert(order);
+            }
+            if passed || ret.len() == cnt {
+                break;
+            }
+            debug!("{} 第{}页订单, {}之后", account, page, date);
+        }
+        Ok(ret)
+    }
+
+    pub async fn get_orders_by_page(
+        account: String,
+        cookie_str: Arc<String>,
+        cond: QueryCondition,
+        period: &str,
+        page: u32,
+    ) -> Result<BTreeMap<String, OrderInfo>> {
+        let ep = endpoints().await;
+        let mut url = match cond.clone() {
+            QueryCondition::KeyWord(kw) => ep.order_search(kw.as_str()),
             _ => {
                 format!(
-                    "https://order.jd.com/center/list.action?s={}&d=1",
+                    "{}?s={}&d={}",
+                    ep.order_list(),
                     match cond {
                         QueryCondition::Unpaid => "1",
                         QueryCondition::WaitForReceiving => "128",
                         QueryCondition::Finish => "1024",
                         QueryCondition::All => "4096",
                         _ => unreachable!(),
-                    }
+                    },
+                    period
                 )
             }
         };
+        if page > 1 {
+            url = format!("{}&page={}", url, page);
+        }
 
-        let resp = reqwest::Client::new()
+        let resp = HttpClient::new()
             .get(url)
             .header("cookie", cookie_str.as_str())
             .header("authority", "order.jd.com")
@@ -68,59 +176,56 @@ impl OrderService {
             .send()
             .await?;
         let html_body = resp.text().await?;
-        if html_body.contains("我的订单") {
-            let document = Html::parse_document(html_body.as_str());
-            let one_order_row = Selector::parse("tr.tr-bd").unwrap(); // 找到商品行
-            let prod_name = Selector::parse("div.p-name a").unwrap(); // 找到商品名称
-            let goods_num = Selector::parse("div.goods-number").unwrap(); // 找到商品数量
-            let money_amount = Selector::parse("div.amount span").unwrap(); // 找到商品总价
-            let receiver = Selector::parse("div.consignee span").unwrap(); // 找到商品收货人
-            let order_status = Selector::parse("span.order-status").unwrap(); // 找到商品状态
-            let mut ret = BTreeMap::new();
-            for element in document.select(&one_order_row) {
-                if let Some(order_no) = element.value().attr("id") {
-                    // attr("id")格式->track163821896625
-                    let (_, _order_no) = order_no.split_at(5);
-                    if ret.get(_order_no).is_some(){
-                        // 同一个订单里有多个商品时，只显示第一个商品;
-                        // todo: 以后再兼容 一个订单多个商品的情况
-                        continue;
-                    }
-                    let mut order = OrderInfo::default();
-                    order.order_no = _order_no.to_string();
-                    // 订单生成时间
-                    let dt_selector =
-                        Selector::parse(format!("input#datasubmit-{}", _order_no).as_str())
-                            .unwrap();
-                    if let Some(e) = document.select(&dt_selector).next() {
-                        order.create_dt = e.value().attr("value").unwrap_or("").trim().to_string();
-                    }
-                    let inner = Html::parse_fragment(element.inner_html().as_str());
-                    if let Some(e) = inner.select(&prod_name).next() {
-                        order.name = e.text().next().unwrap_or("").trim().to_string();
-                    }
-                    if let Some(e) = inner.select(&goods_num).next() {
-                        order.purchase_num = e.text().next().unwrap_or("").trim().to_string();
-                    }
-                    if let Some(e) = inner.select(&money_amount).next() {
-                        order.total_price = e.text().next().unwrap_or("").trim().to_string();
-                    }
-                    if let Some(e) = inner.select(&receiver).next() {
-                        order.receiver = e.text().next().unwrap_or("").trim().to_string();
-                    }
-                    if let Some(e) = inner.select(&order_status).next() {
-                        order.status = e.text().next().unwrap_or("").trim().to_string();
-                    }
+        match parse_orders(html_body.as_str()) {
+            Ok(orders) => {
+                let mut ret = BTreeMap::new();
+                for mut order in orders.into_iter() {
                     order.account = account.clone();
-                    // info!("account:{},order:{}", account, serde_json::to_string(&order).unwrap_or_default());
                     order.cookie = cookie_str.clone();
                     ret.insert(order.order_no.clone(), order);
                 }
+                Ok(ret)
             }
-            Ok(ret)
-        } else {
             // 用户cookie 过期了
-            Err(OpError::CookieExpired(account).into())
+            Err(ParseError::LoginRequired(_)) => Err(OpError::CookieExpired(account).into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 订单的物流轨迹
+    pub async fn get_order_track(
+        account: String,
+        cookie_str: Arc<String>,
+        order_no: String,
+    ) -> Result<OrderTrack> {
+        let ep = endpoints().await;
+        let resp = HttpClient::new()
+            .get(ep.order_track(order_no.as_str()))
+            .header("cookie", cookie_str.as_str())
+            .header("user-agent", crate::utils::get_useragent())
+            .header("accept", "application/json, text/javascript, */*; q=0.01")
+            .header("x-requested-with", "XMLHttpRequest")
+            .header("referer", format!("{}/normal/item.action?orderid={}", ep.details, order_no))
+            .header("accept-language", "zh-CN,zh;q=0.9")
+            .send()
+            .await?;
+        // cookie 失效时跳转到登录页
+        let to_login = resp
+            .headers()
+            .get("location")
+            .map(|u| u.to_str().unwrap_or_default().contains("login"))
+            .unwrap_or(false);
+        if to_login {
+            return Err(OpError::CookieExpired(account).into());
+        }
+        let body = resp.text().await?;
+        match parse_order_track(body.as_str(), order_no.as_str()) {
+            Ok(track) => Ok(track),
+            Err(ParseError::LoginRequired(_)) => Err(OpError::CookieExpired(account).into()),
+            Err(e) => Err(e.into()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests;
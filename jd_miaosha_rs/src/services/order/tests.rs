@@ -0,0 +1,32 @@
+use crate::services::order::{OrderService, order_periods};
+use crate::services::transport::JdScenario;
+use crate::testing::{cookie, install};
+
+#[tokio::test]
+async fn test_get_order_track() {
+    let _g = install(JdScenario::Success).await;
+    let track = OrderService::get_order_track("mock".to_string(), cookie(), "20210601001".to_string())
+        .await
+        .unwrap();
+    assert_eq!(track.order_no, "20210601001");
+    assert_eq!(track.packages.len(), 1);
+    assert_eq!(track.packages[0].tracking_no, "JDVA00012345678");
+    assert_eq!(track.packages[0].events[0].content, "您的订单已出库");
+    assert!(!track.delivered());
+    assert!(track.is_stale(crate::utils::parse_datetime("2021-06-05 20:00:01").unwrap(), 3));
+}
+
+#[test]
+fn test_order_periods() {
+    let date = |s: &str| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+    let today = date("2021-06-10");
+    // 不限制开始日期时查所有年份
+    assert_eq!(
+        order_periods(None, today),
+        vec!["2", "2020", "2019", "2018", "2017", "2016", "2015", "2014"]
+    );
+    // 三个月内只查最近三个月
+    assert_eq!(order_periods(Some(date("2021-03-20")), today), vec!["1"]);
+    // 更早的按年查, 今年用 2
+    assert_eq!(order_periods(Some(date("2019-12-01")), today), vec!["2", "2020", "2019"]);
+}
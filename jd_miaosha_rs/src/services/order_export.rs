@@ -0,0 +1,268 @@
+//! 导出所有账号的订单, 给财务对账用; 每个账号的订单后面跟一行合计
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use futures::future::join_all;
+use log::{error, info};
+use serde_json::{json, Value};
+
+use crate::error::{OpError, Result};
+use crate::models::{OrderInfo, UserState};
+use crate::services::order::{order_periods, QueryCondition};
+use crate::services::retailer::retailer;
+use crate::utils::{now, parse_datetime};
+use crate::HOME;
+
+const DATE_FMT: &'static str = "%Y-%m-%d";
+const HEADERS: [&'static str; 8] = ["账号", "订单号", "商品名称", "数量", "总金额", "状态", "收货人", "下单时间"];
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    /// 按文件后缀判断, 不是 .json 的都当作 csv
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => ExportFormat::Json,
+            _ => ExportFormat::Csv,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+impl Default for ExportFormat {
+    fn default() -> Self {
+        ExportFormat::Csv
+    }
+}
+
+/// 下单日期的范围, 两头都包含, 不填表示不限制
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub struct DateRange {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+fn parse_date(name: &str, s: &str) -> Result<Option<NaiveDate>> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(None);
+    }
+    NaiveDate::parse_from_str(s, DATE_FMT)
+        .map(Some)
+        .map_err(|_| OpError::InvalidArgs(format!("{}的格式要是 2021-06-01: {}", name, s)).into())
+}
+
+impl DateRange {
+    pub fn parse(from: &str, to: &str) -> Result<Self> {
+        let range = DateRange {
+            from: parse_date("开始日期", from)?,
+            to: parse_date("结束日期", to)?,
+        };
+        if let (Some(from), Some(to)) = (range.from, range.to) {
+            if from > to {
+                return Err(OpError::InvalidArgs(format!("开始日期不能晚于结束日期: {} > {}", from, to)).into());
+            }
+        }
+        Ok(range)
+    }
+
+    pub fn is_unbounded(&self) -> bool {
+        self.from.is_none() && self.to.is_none()
+    }
+
+    /// 下单时间解析不出来的订单只在不限制日期时导出
+    pub fn contains(&self, create_dt: &str) -> bool {
+        if self.is_unbounded() {
+            return true;
+        }
+        match parse_datetime(create_dt) {
+            Some(dt) => {
+                let date = dt.naive_local().date();
+                self.from.map(|f| date >= f).unwrap_or(true) && self.to.map(|t| date <= t).unwrap_or(true)
+            }
+            None => false,
+        }
+    }
+}
+
+/// 一个账号的合计
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct AccountTotal {
+    pub account: String,
+    pub orders: usize,
+    pub num: u32,
+    pub amount: f64,
+}
+
+/// 按账号合计订单数、商品数量和金额, 按账号排序
+pub fn summarize(orders: &[OrderInfo]) -> Vec<AccountTotal> {
+    let mut totals: BTreeMap<&str, AccountTotal> = BTreeMap::new();
+    for o in orders.iter() {
+        let t = totals.entry(o.account.as_str()).or_insert_with(|| AccountTotal {
+            account: o.account.clone(),
+            ..Default::default()
+        });
+        t.orders += 1;
+        t.num += o.num();
+        t.amount += o.amount();
+    }
+    totals.into_iter().map(|(_, t)| t).collect()
+}
+
+fn order_row(o: &OrderInfo) -> Vec<String> {
+    vec![
+        o.account.clone(),
+        o.order_no.clone(),
+        o.name.clone(),
+        o.purchase_num.clone(),
+        o.total_price.clone(),
+        o.status.clone(),
+        o.receiver.clone(),
+        o.create_dt.clone(),
+    ]
+}
+
+fn total_row(t: &AccountTotal) -> Vec<String> {
+    vec![
+        t.account.clone(),
+        "合计".to_string(),
+        format!("{}个订单", t.orders),
+        t.num.to_string(),
+        format!("{:.2}", t.amount),
+        String::new(),
+        String::new(),
+        String::new(),
+    ]
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn csv_line(row: &[String]) -> String {
+    row.iter().map(|s| csv_field(s)).collect::<Vec<_>>().join(",")
+}
+
+fn orders_of<'a>(orders: &'a [OrderInfo], account: &'a str) -> impl Iterator<Item = &'a OrderInfo> {
+    orders.iter().filter(move |o| o.account == account)
+}
+
+/// 带 BOM, excel 打开中文不会乱码
+pub fn to_csv(orders: &[OrderInfo], totals: &[AccountTotal]) -> String {
+    let mut lines = vec![HEADERS.join(",")];
+    for t in totals.iter() {
+        lines.extend(orders_of(orders, t.account.as_str()).map(|o| csv_line(&order_row(o))));
+        lines.push(csv_line(&total_row(t)));
+    }
+    format!("\u{feff}{}\r\n", lines.join("\r\n"))
+}
+
+pub fn to_json(orders: &[OrderInfo], totals: &[AccountTotal], range: &DateRange) -> String {
+    let fmt = |d: Option<NaiveDate>| d.map(|d| d.format(DATE_FMT).to_string());
+    let accounts: Vec<Value> = totals
+        .iter()
+        .map(|t| {
+            let orders: Vec<Value> = orders_of(orders, t.account.as_str())
+                .map(|o| {
+                    json!({
+                        "order_no": o.order_no,
+                        "name": o.name,
+                        "purchase_num": o.purchase_num,
+                        "total_price": o.total_price,
+                        "status": o.status,
+                        "receiver": o.receiver,
+                        "create_dt": o.create_dt,
+                    })
+                })
+                .collect();
+            json!({
+                "account": t.account,
+                "orders": orders,
+                "total": {"orders": t.orders, "num": t.num, "amount": format!("{:.2}", t.amount)},
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&json!({
+        "from": fmt(range.from),
+        "to": fmt(range.to),
+        "accounts": accounts,
+    }))
+    .unwrap_or_default()
+}
+
+/// 默认导出到 $HOME/export/orders_时间.csv
+pub fn default_path(format: ExportFormat) -> PathBuf {
+    PathBuf::from(env::var(HOME).unwrap_or_default())
+        .join("export")
+        .join(format!("orders_{}.{}", now().format("%Y%m%d%H%M%S"), format.extension()))
+}
+
+/// 导出的结果, failed 是查询订单失败的账号
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct ExportReport {
+    pub path: PathBuf,
+    pub totals: Vec<AccountTotal>,
+    pub failed: Vec<String>,
+}
+
+/// 同时查询所有账号所有页的订单, 按日期过滤后写到 path
+pub async fn export_orders(
+    users: Vec<UserState>,
+    cond: QueryCondition,
+    range: DateRange,
+    path: PathBuf,
+) -> Result<ExportReport> {
+    let periods = order_periods(range.from, now().naive_local().date());
+    let results = join_all(users.iter().map(|u| {
+        let (cond, periods) = (cond.clone(), periods.clone());
+        async move {
+            retailer(u.platform)
+                .get_all_orders(u.account.clone(), u.cookie.clone(), cond, periods)
+                .await
+        }
+    }))
+    .await;
+    let mut orders = vec![];
+    let mut failed = vec![];
+    for (u, r) in users.iter().zip(results.into_iter()) {
+        match r {
+            Ok(o) => orders.extend(o.into_iter().map(|(_, o)| o).filter(|o| range.contains(o.create_dt.as_str()))),
+            Err(e) => {
+                error!("导出订单时查询失败:{}, {:?}", u.account, e);
+                failed.push(u.account.clone());
+            }
+        }
+    }
+    // 同一个账号里新的订单在前
+    orders.sort_by(|a, b| a.account.cmp(&b.account).then_with(|| b.create_dt.cmp(&a.create_dt)));
+    let totals = summarize(&orders);
+    let content = match ExportFormat::from_path(&path) {
+        ExportFormat::Csv => to_csv(&orders, &totals),
+        ExportFormat::Json => to_json(&orders, &totals, &range),
+    };
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    tokio::fs::write(&path, content).await?;
+    info!("导出了{}个订单到{}", orders.len(), path.display());
+    Ok(ExportReport { path, totals, failed })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,104 @@
+use crate::models::OrderInfo;
+use crate::services::order::QueryCondition;
+use crate::services::order_export::{self, AccountTotal, DateRange, ExportFormat};
+use crate::services::transport::{HttpResponse, JdScenario};
+use crate::testing::{cookie, install, order_list_page, temp_path};
+
+#[test]
+fn test_order_export_format() {
+    let range = DateRange::parse("2021-05-25", "").unwrap();
+    assert!(range.contains("2021-05-25 00:00:00"));
+    assert!(range.contains("2021-06-01 10:08:12"));
+    assert!(!range.contains("2021-05-24 23:59:59"));
+    assert!(!range.contains(""));
+    assert!(DateRange::default().contains(""));
+    assert!(DateRange::parse("2021/05/25", "").is_err());
+    assert!(DateRange::parse("2021-06-02", "2021-06-01").is_err());
+    assert_eq!(ExportFormat::from_path(std::path::Path::new("a.JSON")), ExportFormat::Json);
+    assert_eq!(ExportFormat::from_path(std::path::Path::new("a.txt")), ExportFormat::Csv);
+
+    let order = |account: &str, order_no: &str, num: &str, price: &str| OrderInfo {
+        account: account.to_string(),
+        order_no: order_no.to_string(),
+        name: "商品, \"特价\"".to_string(),
+        purchase_num: num.to_string(),
+        total_price: price.to_string(),
+        create_dt: "2021-06-01 10:08:12".to_string(),
+        ..Default::default()
+    };
+    let orders = vec![
+        order("a", "1", "x1", "¥10.50"),
+        order("b", "3", "x3", "¥30.00"),
+        order("a", "2", "x2", "¥20.00"),
+    ];
+    let totals = order_export::summarize(&orders);
+    assert_eq!(
+        totals,
+        vec![
+            AccountTotal { account: "a".to_string(), orders: 2, num: 3, amount: 30.5 },
+            AccountTotal { account: "b".to_string(), orders: 1, num: 3, amount: 30.0 },
+        ]
+    );
+    let csv = order_export::to_csv(&orders, &totals);
+    let lines: Vec<&str> = csv.trim_start_matches('\u{feff}').lines().collect();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[0], "账号,订单号,商品名称,数量,总金额,状态,收货人,下单时间");
+    // 含逗号和引号的字段要转义
+    assert_eq!(lines[1], "a,1,\"商品, \"\"特价\"\"\",x1,¥10.50,,,2021-06-01 10:08:12");
+    assert_eq!(lines[3], "a,合计,2个订单,3,30.50,,,");
+    assert_eq!(lines[5], "b,合计,1个订单,3,30.00,,,");
+
+    let v: serde_json::Value = serde_json::from_str(&order_export::to_json(&orders, &totals, &range)).unwrap();
+    assert_eq!(v["from"], "2021-05-25");
+    assert_eq!(v["to"], serde_json::Value::Null);
+    assert_eq!(v["accounts"][0]["orders"].as_array().unwrap().len(), 2);
+    assert_eq!(v["accounts"][0]["total"]["amount"], "30.50");
+    assert_eq!(v["accounts"][1]["total"]["orders"], 1);
+}
+
+#[tokio::test]
+async fn test_export_orders() {
+    let g = install(JdScenario::Success).await;
+    g.mock.replace(
+        "order.jd.com/center/list.action",
+        HttpResponse::ok(order_list_page()),
+    );
+    let user = |account: &str| crate::models::UserState {
+        account: account.to_string(),
+        cookie: cookie(),
+        ..Default::default()
+    };
+    let path = temp_path("orders.csv");
+    let range = DateRange::parse("2021-05-25", "2021-06-30").unwrap();
+    let report = order_export::export_orders(vec![user("a"), user("b")], QueryCondition::All, range, path.clone())
+        .await
+        .unwrap();
+    // 第二页没有新订单就不再翻页, 5 月 20 号的订单被过滤掉
+    assert!(g.mock.hits("page=2") > 0);
+    assert!(g.mock.hits("page=3") == 0);
+    assert_eq!(report.totals.len(), 2);
+    assert_eq!(report.totals[0], AccountTotal { account: "a".to_string(), orders: 1, num: 1, amount: 5999.0 });
+    assert!(report.failed.is_empty());
+    let csv = std::fs::read_to_string(&path).unwrap();
+    assert!(csv.contains("b,163821896625,"));
+    assert!(!csv.contains("163900112233"));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_export_orders_expired_cookie() {
+    let _g = install(JdScenario::ExpiredCookie).await;
+    let user = crate::models::UserState {
+        account: "a".to_string(),
+        cookie: cookie(),
+        ..Default::default()
+    };
+    let path = temp_path("orders.json");
+    let report = order_export::export_orders(vec![user], QueryCondition::All, DateRange::default(), path.clone())
+        .await
+        .unwrap();
+    assert_eq!(report.failed, vec!["a"]);
+    let v: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert!(v["accounts"].as_array().unwrap().is_empty());
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,503 @@
+//! 解析电商平台返回的页面和 json, 都是不发请求的纯函数, 方便用根目录 fixtures 里的页面(和 fake_retailer 共用)做测试;
+//! 页面结构变了解析不出来时返回 ParseError, 不再悄悄返回默认值
+use std::collections::HashMap;
+
+use log::{debug, info};
+use scraper::{ElementRef, Html, Selector};
+use serde_json::{from_str, Value};
+
+use crate::error::ParseError;
+use crate::models::{
+    AddressInfo, OrderInfo, OrderTrack, SearchItem, SearchPage, StockStatus, TrackEvent, TrackPackage, YuyueInfo,
+};
+use crate::utils::parse_datetime;
+use crate::*;
+
+type ParseResult<T> = std::result::Result<T, ParseError>;
+
+/// 商品详情页里解析出来的信息
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ItemPage {
+    pub name: String,
+    // 详情页里的分类, 逗号分隔
+    pub category_hint: String,
+    pub purchase_type: String,
+    pub purchase_url: String,
+}
+
+fn selector(s: &str) -> Selector {
+    Selector::parse(s).unwrap()
+}
+
+fn first_text(element: &ElementRef, sel: &Selector) -> Option<String> {
+    element
+        .select(sel)
+        .next()
+        .map(|e| e.text().next().unwrap_or("").trim().to_string())
+}
+
+/// cookie 失效时京东会跳转到登录页
+fn is_login_page(html: &str) -> bool {
+    html.contains("passport.jd.com/new/login") || html.contains("欢迎登录")
+}
+
+/// 从商品详情页的购买按钮判断购买方式和购买链接;
+/// 页面上没有能看见的购买按钮, 或者按钮上没有链接、预约按钮的字认不出来时返回错误
+pub fn parse_purchase_info(html: &Html) -> ParseResult<(String, String)> {
+    let mut purchase_type = NORMAL.to_string();
+    let mut cart_link = String::default();
+    let mut found = false;
+    'selectors: for s in &["a#choose-btn-ko", "a#btn-reservation", "a#InitCartUrl"] {
+        let purchase_selector = selector(s);
+        'finder: for element in html.select(&purchase_selector) {
+            // 隐藏的按钮不算, 否则普通商品页里隐藏的"立即预约"按钮会被当成预约商品
+            let hidden = element
+                .value()
+                .attr("style")
+                .map(|s| s.replace(' ', "").contains("display:none"))
+                .unwrap_or(false);
+            if hidden {
+                continue 'finder;
+            }
+            found = true;
+            let link = element
+                .value()
+                .attr("href")
+                .ok_or_else(|| ParseError::MissingField("item", format!("href of {}", s)))?;
+            if let Some(_id) = element.value().attr("id") {
+                // 秒杀的商品
+                if _id.contains("choose-btn-ko") {
+                    purchase_type = SECOND_KILL.to_string();
+                } else if _id.contains("btn-reservation") {
+                    let txt = element.inner_html();
+                    let _txt = txt.trim();
+                    if _txt.contains("立即预约") {
+                        purchase_type = YUYUE.to_string();
+                    } else if _txt.contains("支付定金") || _txt.contains("立即购买") {
+                        // https://item.jd.com/100016777664.html  支付定金的预售商品
+                        // https://item.jd.com/31335300862.html  立即购买的预售商品
+                        purchase_type = PRESALE.to_string();
+                    } else if _txt.contains("抢购") {
+                        purchase_type = SECOND_KILL.to_string();
+                    } else {
+                        return Err(ParseError::MissingField(
+                            "item",
+                            format!("purchase type of {}", _txt),
+                        ));
+                    }
+                }
+            }
+            let link = link.trim();
+            if link.contains("none") {
+                continue 'finder;
+            }
+            if link.starts_with("//") {
+                cart_link = format!("https:{}", link);
+            } else {
+                cart_link = link.to_string();
+            }
+            info!("找到购买链接:{}", &cart_link);
+            break 'selectors;
+        }
+    }
+    if !found {
+        return Err(ParseError::MissingElement("item", "purchase button".to_string()));
+    }
+    Ok((purchase_type, cart_link))
+}
+
+/// 解析商品详情页: 名称、分类、购买方式和购买链接
+pub fn parse_item_page(html: &str) -> ParseResult<ItemPage> {
+    let mut page = ItemPage::default();
+    let pname = "name:";
+    if let Some(idx) = html.find(pname) {
+        let txt = html[idx + pname.len()..].trim_start();
+        if let Some(end_idx) = txt.find("',") {
+            let x: &[_] = &['\'', '"', ' '];
+            page.name = txt[..end_idx].trim_matches(x).to_string();
+        }
+    }
+    // 详情页里的分类, 如: catName: ["手机通讯","手机","手机"],
+    let pcat = "catName:";
+    if let Some(idx) = html.find(pcat) {
+        let txt = html[idx + pcat.len()..].trim_start();
+        if let Some(end_idx) = txt.find(']') {
+            let x: &[_] = &['\'', '"', ' ', '[', ']'];
+            page.category_hint = txt[..end_idx]
+                .split(',')
+                .map(|c| c.trim_matches(x))
+                .filter(|c| !c.is_empty())
+                .collect::<Vec<_>>()
+                .join(",");
+        }
+    }
+    let document = Html::parse_document(html);
+    if page.name.is_empty() {
+        if let Some(e) = document.select(&selector("div#name h1")).next() {
+            page.name = e.inner_html().trim().to_string();
+        }
+    }
+    if page.name.is_empty() {
+        if let Some(e) = document.select(&selector("div.sku-name")).next() {
+            let mut name = e.inner_html().trim().to_string();
+            if let Some(idx) = name.find('>') {
+                name = name.split_off(idx + 1);
+            }
+            page.name = name.trim().to_string();
+        }
+    }
+    if page.name.is_empty() {
+        return Err(ParseError::MissingField("item", "name".to_string()));
+    }
+    let (purchase_type, purchase_url) = parse_purchase_info(&document)?;
+    page.purchase_type = purchase_type;
+    page.purchase_url = purchase_url;
+    Ok(page)
+}
+
+/// 解析商品搜索结果页, 价格是页面上显示的价格, 库存要另外查
+pub fn parse_search_page(html: &str) -> ParseResult<SearchPage> {
+    let document = Html::parse_document(html);
+    let mut page = SearchPage {
+        page: 1,
+        ..Default::default()
+    };
+    if document.select(&selector("div#J_goodsList")).next().is_none() {
+        // 没有搜到商品时没有商品列表
+        if html.contains("没有找到") {
+            page.total_pages = 0;
+            return Ok(page);
+        }
+        return Err(ParseError::MissingElement("search", "div#J_goodsList".to_string()));
+    }
+    let item_selector = selector("div#J_goodsList li.gl-item");
+    let name_selector = selector("div.p-name em");
+    let price_selector = selector("div.p-price i");
+    let shop_selector = selector("div.p-shop a");
+    for element in document.select(&item_selector) {
+        let sku = element
+            .value()
+            .attr("data-sku")
+            .filter(|sku| !sku.is_empty())
+            .ok_or_else(|| ParseError::MissingField("search", "data-sku".to_string()))?;
+        // 名称里搜索的关键字是高亮的, 在单独的标签里
+        let name = element
+            .select(&name_selector)
+            .next()
+            .map(|e| e.text().collect::<String>().trim().to_string())
+            .ok_or_else(|| ParseError::MissingElement("search", format!("div.p-name em of {}", sku)))?;
+        page.items.push(SearchItem {
+            sku: sku.to_string(),
+            name,
+            price: first_text(&element, &price_selector).unwrap_or_default(),
+            shop: first_text(&element, &shop_selector).unwrap_or_default(),
+            status: StockStatus::Unknown,
+        });
+    }
+    // 分页: <span class="fp-text"><b>1</b><em>/</em><i>100</i></span>
+    let page_text = document.select(&selector("span.fp-text")).next();
+    let number = |sel: &str| {
+        page_text
+            .and_then(|e| first_text(&e, &selector(sel)))
+            .and_then(|n| n.parse::<u32>().ok())
+    };
+    page.page = number("b").unwrap_or(1).max(1);
+    page.total_pages = number("i").unwrap_or(page.page).max(page.page);
+    Ok(page)
+}
+
+/// 从输入的 sku 或商品链接里取出 sku, 如 https://item.jd.com/100012043978.html、
+/// item.m.jd.com/product/100012043978.html、https://...?sku=100012043978; 不是时返回 None, 按关键字搜索
+pub fn parse_sku_input(input: &str) -> Option<String> {
+    let is_sku = |s: &str| !s.is_empty() && s.len() <= 20 && s.chars().all(|c| c.is_ascii_digit());
+    let input = input.trim();
+    if is_sku(input) {
+        return Some(input.to_string());
+    }
+    let link = if input.starts_with("//") {
+        format!("https:{}", input)
+    } else if input.contains("://") {
+        input.to_string()
+    } else {
+        format!("https://{}", input)
+    };
+    let link = url::Url::parse(link.as_str()).ok()?;
+    if !link.host_str()?.contains('.') {
+        return None;
+    }
+    if let Some((_, sku)) = link
+        .query_pairs()
+        .find(|(k, v)| ["sku", "skuId", "wareId"].contains(&k.as_ref()) && is_sku(v))
+    {
+        return Some(sku.into_owned());
+    }
+    let last = link.path_segments()?.last()?;
+    let sku = last.strip_suffix(".html").unwrap_or(last);
+    if is_sku(sku) {
+        Some(sku.to_string())
+    } else {
+        None
+    }
+}
+
+/// 解析订单列表页, 同一个订单里有多个商品时只取第一个商品
+pub fn parse_orders(html: &str) -> ParseResult<Vec<OrderInfo>> {
+    if !html.contains("我的订单") {
+        return Err(ParseError::LoginRequired("order_list"));
+    }
+    let document = Html::parse_document(html);
+    let one_order_row = selector("tr.tr-bd"); // 找到商品行
+    let prod_name = selector("div.p-name a"); // 找到商品名称
+    let goods_num = selector("div.goods-number"); // 找到商品数量
+    let money_amount = selector("div.amount span"); // 找到商品总价
+    let receiver = selector("div.consignee span"); // 找到商品收货人
+    let order_status = selector("span.order-status"); // 找到商品状态
+    let pay_link = selector("a.btn-pay"); // 待付款订单的付款按钮
+    let mut ret: Vec<OrderInfo> = vec![];
+    for element in document.select(&one_order_row) {
+        // attr("id")格式->track163821896625
+        let order_no = match element.value().attr("id") {
+            Some(id) => match id.strip_prefix("track") {
+                Some(no) if !no.is_empty() => no,
+                _ => return Err(ParseError::MissingField("order_list", format!("order_no in {}", id))),
+            },
+            None => continue,
+        };
+        if ret.iter().any(|o| o.order_no == order_no) {
+            // todo: 以后再兼容 一个订单多个商品的情况
+            continue;
+        }
+        let mut order = OrderInfo::default();
+        order.order_no = order_no.to_string();
+        // 订单生成时间
+        let dt_selector = selector(format!("input#datasubmit-{}", order_no).as_str());
+        if let Some(e) = document.select(&dt_selector).next() {
+            order.create_dt = e.value().attr("value").unwrap_or("").trim().to_string();
+        }
+        // 付款截止时间
+        let deadline_selector = selector(format!("input#payLimitTime-{}", order_no).as_str());
+        if let Some(e) = document.select(&deadline_selector).next() {
+            order.pay_deadline = e.value().attr("value").unwrap_or("").trim().to_string();
+        }
+        if let Some(href) = element.select(&pay_link).next().and_then(|e| e.value().attr("href")) {
+            order.pay_url = if href.starts_with("//") {
+                format!("https:{}", href)
+            } else {
+                href.to_string()
+            };
+        }
+        order.name = first_text(&element, &prod_name)
+            .ok_or_else(|| ParseError::MissingElement("order_list", "div.p-name a".to_string()))?;
+        order.purchase_num = first_text(&element, &goods_num).unwrap_or_default();
+        order.total_price = first_text(&element, &money_amount).unwrap_or_default();
+        order.receiver = first_text(&element, &receiver).unwrap_or_default();
+        order.status = first_text(&element, &order_status).unwrap_or_default();
+        ret.push(order);
+    }
+    Ok(ret)
+}
+
+/// 解析收货地址列表页
+pub fn parse_addresses(html: &str) -> ParseResult<Vec<AddressInfo>> {
+    if is_login_page(html) {
+        return Err(ParseError::LoginRequired("address_list"));
+    }
+    let document = Html::parse_document(html);
+    if document.select(&selector("div#addressList")).next().is_none() {
+        return Err(ParseError::MissingElement("address_list", "div#addressList".to_string()));
+    }
+    let addr_selector = selector("div#addressList div.easebuy-m");
+    let left_col_selector = selector("div.item-lcol");
+    let item_selector = selector("div.item");
+    let label_selector = selector("span.label");
+    let fr_selector = selector("div.fl");
+    let mut ret = vec![];
+    for element in document.select(&addr_selector) {
+        let mut addr = AddressInfo::default();
+        // id 的格式 addresssDiv-3759910743
+        addr.addr_id = element
+            .value()
+            .attr("id")
+            .and_then(|id| id.split('-').nth(1))
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| ParseError::MissingField("address_list", "addr_id".to_string()))?
+            .to_string();
+        if let Some(left_div) = element.select(&left_col_selector).next() {
+            for item in left_div.select(&item_selector) {
+                let label = match first_text(&item, &label_selector) {
+                    Some(label) => label,
+                    None => continue,
+                };
+                let value = first_text(&item, &fr_selector).ok_or_else(|| {
+                    ParseError::MissingElement("address_list", format!("div.fl of {}", label))
+                })?;
+                match label.as_str() {
+                    "收货人：" => addr.receiver = value,
+                    "所在地区：" => addr.area_zone = value,
+                    "地址：" => addr.address = value,
+                    "手机：" => addr.mobile_phone = value,
+                    "固定电话：" => addr.fixed_line_phone = value,
+                    "电子邮箱：" => addr.email = value,
+                    _ => debug!("Unknown address label:{}", label),
+                }
+            }
+        }
+        ret.push(addr);
+    }
+    Ok(ret)
+}
+
+/// 解析新增、修改、删除收货地址接口的结果:
+/// {"success":true,"addressId":3759910999} 或 {"success":false,"message":"收货地址最多20个"}
+/// 成功时返回地址 id (删除时可能为空), 失败时返回平台的提示
+pub fn parse_address_result(txt: &str, op: &'static str) -> ParseResult<std::result::Result<String, String>> {
+    if is_login_page(txt) {
+        return Err(ParseError::LoginRequired(op));
+    }
+    let v: Value = from_str(txt).map_err(|e| ParseError::InvalidJson(op, e.to_string()))?;
+    let success = v["success"]
+        .as_bool()
+        .ok_or_else(|| ParseError::MissingField(op, "success".to_string()))?;
+    if !success {
+        return Ok(Err(v["message"].as_str().unwrap_or("未知错误").to_string()));
+    }
+    // addressId 有时是数字有时是字符串
+    let addr_id = match &v["addressId"] {
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        _ => String::new(),
+    };
+    Ok(Ok(addr_id))
+}
+
+/// 从商品移动端详情页里截取 "yuyue":{...} 得到预约时间和预约链接
+pub fn parse_yuyue_info(html: &str) -> ParseResult<YuyueInfo> {
+    let yuyue = r##""yuyue":"##;
+    let start_idx = html
+        .find(yuyue)
+        .ok_or_else(|| ParseError::MissingField("yuyue", "yuyue".to_string()))?
+        + yuyue.len();
+    let txt = &html[start_idx..];
+    // "yuyue":{"type":"1","num":6948,"d":218782,"category":"4","stime":"2021-05-19 00:21:12","etime":"2021-05-23 09:54:59","state":2,"sku":100010104457,"isJ":0,"url":"//yushou.jd.com/toYuyue.action?sku=100010104457&key=d1df2da18dc20f13c30d6ce237137bb8","info":"预约进行中",...}
+    let end_idx = txt
+        .find('}')
+        .ok_or_else(|| ParseError::InvalidJson("yuyue", "missing }".to_string()))?;
+    let jsonv: Value = from_str(&txt[..=end_idx])
+        .map_err(|e| ParseError::InvalidJson("yuyue", e.to_string()))?;
+    let mut stime = jsonv["yueStime"].as_str().unwrap_or_default();
+    let mut etime = jsonv["yueEtime"].as_str().unwrap_or_default();
+    if stime.is_empty() {
+        stime = jsonv["stime"].as_str().unwrap_or_default();
+    }
+    if etime.is_empty() {
+        etime = jsonv["etime"].as_str().unwrap_or_default();
+    }
+    let mut yuyue_url = jsonv["url"]
+        .as_str()
+        .filter(|u| !u.is_empty())
+        .ok_or_else(|| ParseError::MissingField("yuyue", "url".to_string()))?
+        .to_string();
+    if yuyue_url.starts_with("//") {
+        yuyue_url = format!("https:{}", yuyue_url)
+    }
+    Ok(YuyueInfo {
+        yuyue_start_dt: parse_datetime(stime),
+        yuyue_end_dt: parse_datetime(etime),
+        qiang_start_dt: parse_datetime(jsonv["qiangStime"].as_str().unwrap_or_default()),
+        yuyue_url,
+        ..Default::default()
+    })
+}
+
+// StockState 可能是数字也可能是字符串
+fn stock_state(d: &Value) -> Option<StockStatus> {
+    match &d["StockState"] {
+        Value::Number(n) => Some(StockStatus::from(n.to_string().as_str())),
+        Value::String(s) => Some(StockStatus::from(s.as_str())),
+        _ => None,
+    }
+}
+
+/// 解析一次查询多个商品的库存接口, 返回里没有 StockState 的商品跳过
+pub fn parse_stocks(txt: &str) -> ParseResult<HashMap<String, StockStatus>> {
+    let v: Value = from_str(txt).map_err(|e| ParseError::InvalidJson("stock", e.to_string()))?;
+    let items = v
+        .as_object()
+        .ok_or_else(|| ParseError::InvalidJson("stock", "not an object".to_string()))?;
+    Ok(items
+        .iter()
+        .filter_map(|(sku, d)| stock_state(d).map(|s| (sku.clone(), s)))
+        .collect())
+}
+
+/// 解析一次查询多个商品的价格接口: [{"id":"J_100012043978","p":"99.00","op":"129.00"},...],
+/// key 是去掉 J_ 的 sku, 没有现价的商品跳过
+pub fn parse_prices(txt: &str) -> ParseResult<HashMap<String, (String, String)>> {
+    let v: Value = from_str(txt).map_err(|e| ParseError::InvalidJson("price", e.to_string()))?;
+    let items = v
+        .as_array()
+        .ok_or_else(|| ParseError::InvalidJson("price", "not an array".to_string()))?;
+    Ok(items
+        .iter()
+        .filter_map(|d| {
+            let sku = d["id"].as_str()?.trim_start_matches("J_");
+            let price = d["p"].as_str()?;
+            Some((
+                sku.to_string(),
+                (price.to_string(), d["op"].as_str().unwrap_or_default().to_string()),
+            ))
+        })
+        .collect())
+}
+
+/// 签收后物流轨迹里会出现的字样
+const DELIVERED_MARKS: [&'static str; 3] = ["已签收", "妥投", "已完成"];
+
+/// 解析订单物流接口:
+/// {"multiPackageTrackList":[{"carrier":"京东快递","waybillCode":"JDV...","isSign":false,
+///   "trackList":[{"msgTime":"2021-12-21 10:00:00","content":"..."}]}]}
+pub fn parse_order_track(txt: &str, order_no: &str) -> ParseResult<OrderTrack> {
+    if is_login_page(txt) {
+        return Err(ParseError::LoginRequired("order_track"));
+    }
+    let v: Value = from_str(txt).map_err(|e| ParseError::InvalidJson("order_track", e.to_string()))?;
+    let list = v["multiPackageTrackList"]
+        .as_array()
+        .ok_or_else(|| ParseError::MissingField("order_track", "multiPackageTrackList".to_string()))?;
+    let packages = list
+        .iter()
+        .map(|p| {
+            let mut events: Vec<TrackEvent> = p["trackList"]
+                .as_array()
+                .map(|l| {
+                    l.iter()
+                        .map(|e| TrackEvent {
+                            dt: e["msgTime"].as_str().and_then(parse_datetime),
+                            content: e["content"].as_str().unwrap_or_default().trim().to_string(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            // 接口返回的顺序不固定, 统一按时间从新到旧排
+            events.sort_by(|a, b| b.dt.cmp(&a.dt));
+            let delivered = p["isSign"].as_bool().unwrap_or(false)
+                || events
+                    .first()
+                    .map(|e| DELIVERED_MARKS.iter().any(|m| e.content.contains(m)))
+                    .unwrap_or(false);
+            TrackPackage {
+                carrier: p["carrier"].as_str().unwrap_or_default().to_string(),
+                tracking_no: p["waybillCode"].as_str().unwrap_or_default().to_string(),
+                events,
+                delivered,
+            }
+        })
+        .collect();
+    Ok(OrderTrack {
+        order_no: order_no.to_string(),
+        packages,
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,375 @@
+use crate::error::ParseError;
+use crate::models::StockStatus;
+use crate::utils::parse_datetime;
+use crate::*;
+
+use crate::testing::{address_list_page, fixture, item_page, order_list_page, render_fixture, search_page};
+
+use super::*;
+
+// 预约商品的移动端详情页, 和 fake_retailer 一样用 item_m.html
+fn item_m_page() -> String {
+    render_fixture(
+        "item_m.html",
+        &[
+            ("sku", "100010104457"),
+            ("name", "华为 HUAWEI Mate 40 Pro 5G 麒麟9000 8GB+256GB 亮黑色"),
+            ("base", "https://yushou.jd.com"),
+            ("stock", "33"),
+            ("yuyue_start", "2021-05-19 00:21:12"),
+            ("yuyue_end", "2021-05-23 09:54:59"),
+            ("qiang_start", "2021-05-23 10:08:00"),
+        ],
+    )
+}
+
+#[test]
+fn test_parse_item_page_normal() {
+    let page = parse_item_page(&item_page(
+        NORMAL,
+        "100009077475",
+        "Apple iPhone 12 (A2404) 128GB 蓝色 支持移动联通电信5G 双卡双待手机",
+        "手机通讯,手机,手机",
+        "https://cart.jd.com",
+    ))
+    .unwrap();
+    assert_eq!(
+        page,
+        ItemPage {
+            name: "Apple iPhone 12 (A2404) 128GB 蓝色 支持移动联通电信5G 双卡双待手机".to_string(),
+            category_hint: "手机通讯,手机,手机".to_string(),
+            purchase_type: NORMAL.to_string(),
+            purchase_url: "https://cart.jd.com/gate.action?pid=100009077475&pcount=1&ptype=1"
+                .to_string(),
+        }
+    );
+}
+
+#[test]
+fn test_parse_item_page_seckill() {
+    let page = parse_item_page(&item_page(
+        SECOND_KILL,
+        "100012043978",
+        "贵州茅台 飞天53度 500ml 酱香型白酒",
+        "酒类,白酒,白酒",
+        "https://cart.jd.com",
+    ))
+    .unwrap();
+    assert_eq!(page.name, "贵州茅台 飞天53度 500ml 酱香型白酒");
+    assert_eq!(page.category_hint, "酒类,白酒,白酒");
+    assert_eq!(page.purchase_type, SECOND_KILL);
+    // 秒杀链接在抢购时再获取
+    assert_eq!(page.purchase_url, "");
+}
+
+#[test]
+fn test_parse_item_page_yuyue() {
+    let page = parse_item_page(&item_page(
+        YUYUE,
+        "100010104457",
+        "华为 HUAWEI Mate 40 Pro 5G 麒麟9000 8GB+256GB 亮黑色",
+        "手机通讯,手机,手机",
+        "https://yushou.jd.com",
+    ))
+    .unwrap();
+    assert_eq!(page.purchase_type, YUYUE);
+    assert_eq!(page.purchase_url, "https://yushou.jd.com/toYuyue.action?sku=100010104457&key=fake");
+}
+
+#[test]
+fn test_parse_item_page_presale() {
+    let html = item_page(
+        PRESALE,
+        "100016777664",
+        "小米 Redmi K40 游戏增强版 天玑1200 12GB+256GB 暗影",
+        "手机通讯,手机,手机",
+        "https://cart.jd.com",
+    );
+    // 去掉 pageConfig 里的 name 和 catName
+    let html: Vec<&str> = html
+        .lines()
+        .filter(|l| !l.trim_start().starts_with("name:") && !l.trim_start().starts_with("catName:"))
+        .collect();
+    let page = parse_item_page(html.join("\n").as_str()).unwrap();
+    // 没有 name: 时从 div.sku-name 取商品名称
+    assert_eq!(page.name, "小米 Redmi K40 游戏增强版 天玑1200 12GB+256GB 暗影");
+    assert_eq!(page.category_hint, "");
+    assert_eq!(page.purchase_type, PRESALE);
+    assert_eq!(
+        page.purchase_url,
+        "https://cart.jd.com/cart/dynamic/gateForSubFlow.action?wids=100016777664&nums=1&subType=32"
+    );
+}
+
+#[test]
+fn test_parse_item_page_without_name() {
+    assert_eq!(
+        parse_item_page(fixture("login.html").as_str()),
+        Err(ParseError::MissingField("item", "name".to_string()))
+    );
+}
+
+#[test]
+fn test_parse_item_page_without_purchase_info() {
+    let html = item_page(NORMAL, "100009077475", "Apple iPhone 12", "手机通讯,手机,手机", "https://cart.jd.com");
+    // 购物车按钮也隐藏了
+    let hidden = html.replace(r#"id="InitCartUrl""#, r#"id="InitCartUrl" style="display: none""#);
+    assert_eq!(
+        parse_item_page(hidden.as_str()),
+        Err(ParseError::MissingElement("item", "purchase button".to_string()))
+    );
+    let no_href = html.replace(r#"<a href="https://cart.jd.com/gate.action"#, r#"<a data-href="https://cart.jd.com/gate.action"#);
+    assert_eq!(
+        parse_item_page(no_href.as_str()),
+        Err(ParseError::MissingField("item", "href of a#InitCartUrl".to_string()))
+    );
+    let html = item_page(YUYUE, "100010104457", "华为 Mate 40 Pro", "手机通讯,手机,手机", "https://yushou.jd.com");
+    assert_eq!(
+        parse_item_page(html.replace("立即预约", "敬请期待").as_str()),
+        Err(ParseError::MissingField("item", "purchase type of 敬请期待".to_string()))
+    );
+}
+
+#[test]
+fn test_parse_orders() {
+    let orders = parse_orders(order_list_page().as_str()).unwrap();
+    // 同一个订单的第二个商品被忽略
+    assert_eq!(orders.len(), 2);
+    let o = &orders[0];
+    assert_eq!(o.order_no, "163821896625");
+    assert_eq!(o.name, "Apple iPhone 12 (A2404) 128GB 蓝色");
+    assert_eq!(o.purchase_num, "x1");
+    assert_eq!(o.total_price, "¥5999.00");
+    assert_eq!(o.receiver, "张三");
+    assert_eq!(o.status, "等待付款");
+    assert_eq!(o.create_dt, "2021-06-01 10:08:12");
+    assert_eq!(o.pay_deadline, "2021-06-02 10:08:12");
+    assert_eq!(o.pay_url, "https://pcashier.jd.com/cashier/index.action?orderId=163821896625");
+    let o = &orders[1];
+    assert_eq!(o.order_no, "163900112233");
+    assert_eq!(o.name, "华为 HUAWEI Mate 40 Pro 5G 麒麟9000 8GB+256GB 亮黑色");
+    assert_eq!(o.total_price, "¥6999.00");
+    assert_eq!(o.receiver, "李四");
+    assert_eq!(o.status, "已完成");
+    assert_eq!(o.create_dt, "2021-05-20 21:30:45");
+    // 已完成的订单没有付款信息
+    assert_eq!(o.pay_deadline, "");
+    assert_eq!(o.pay_url, "");
+}
+
+#[test]
+fn test_parse_orders_errors() {
+    assert_eq!(
+        parse_orders(fixture("login.html").as_str()).unwrap_err(),
+        ParseError::LoginRequired("order_list")
+    );
+    assert_eq!(
+        parse_orders(fixture("order_list_changed.html").as_str()).unwrap_err(),
+        ParseError::MissingElement("order_list", "div.p-name a".to_string())
+    );
+}
+
+#[test]
+fn test_parse_addresses() {
+    let addrs = parse_addresses(address_list_page().as_str()).unwrap();
+    assert_eq!(addrs.len(), 2);
+    let a = &addrs[0];
+    assert_eq!(a.addr_id, "3759910743");
+    assert_eq!(a.receiver, "张三");
+    assert_eq!(a.area_zone, "北京朝阳区三环以内");
+    assert_eq!(a.address, "建国路88号SOHO现代城A座1001");
+    assert_eq!(a.mobile_phone, "138****0000");
+    assert_eq!(a.fixed_line_phone, "");
+    assert_eq!(a.email, "zhangsan@example.com");
+    let a = &addrs[1];
+    assert_eq!(a.addr_id, "3759910888");
+    assert_eq!(a.receiver, "李四");
+    assert_eq!(a.address, "世纪大道100号");
+    assert_eq!(a.email, "");
+}
+
+#[test]
+fn test_parse_addresses_errors() {
+    assert_eq!(
+        parse_addresses(fixture("login.html").as_str()).unwrap_err(),
+        ParseError::LoginRequired("address_list")
+    );
+    assert_eq!(
+        parse_addresses(order_list_page().as_str()).unwrap_err(),
+        ParseError::MissingElement("address_list", "div#addressList".to_string())
+    );
+}
+
+#[test]
+fn test_parse_address_result() {
+    assert_eq!(
+        parse_address_result(r#"{"success":true,"addressId":3759910999}"#, "address_add").unwrap(),
+        Ok("3759910999".to_string())
+    );
+    assert_eq!(
+        parse_address_result(r#"{"success":true,"addressId":"3759910743"}"#, "address_update").unwrap(),
+        Ok("3759910743".to_string())
+    );
+    assert_eq!(parse_address_result(r#"{"success":true}"#, "address_delete").unwrap(), Ok(String::new()));
+    assert_eq!(
+        parse_address_result(r#"{"success":false,"message":"收货地址最多20个"}"#, "address_add").unwrap(),
+        Err("收货地址最多20个".to_string())
+    );
+    assert_eq!(
+        parse_address_result(fixture("login.html").as_str(), "address_add").unwrap_err(),
+        ParseError::LoginRequired("address_add")
+    );
+    assert_eq!(
+        parse_address_result("{}", "address_add").unwrap_err(),
+        ParseError::MissingField("address_add", "success".to_string())
+    );
+}
+
+#[test]
+fn test_parse_yuyue_info() {
+    let info = parse_yuyue_info(item_m_page().as_str()).unwrap();
+    assert_eq!(info.yuyue_url, "https://yushou.jd.com/toYuyue.action?sku=100010104457&key=fake");
+    assert_eq!(info.yuyue_start_dt, parse_datetime("2021-05-19 00:21:12"));
+    assert_eq!(info.yuyue_end_dt, parse_datetime("2021-05-23 09:54:59"));
+    assert_eq!(info.qiang_start_dt, parse_datetime("2021-05-23 10:08:00"));
+    assert!(info.qiang_start_dt.is_some());
+}
+
+#[test]
+fn test_parse_yuyue_info_missing() {
+    assert_eq!(
+        parse_yuyue_info(item_m_page().replace(r#""yuyue":"#, r#""noYuyue":"#).as_str()).unwrap_err(),
+        ParseError::MissingField("yuyue", "yuyue".to_string())
+    );
+}
+
+#[test]
+fn test_stock_status_from() {
+    assert_eq!(StockStatus::from("33"), StockStatus::OnSale);
+    assert_eq!(StockStatus::from("34"), StockStatus::OutOfStock);
+    assert_eq!(StockStatus::from("0"), StockStatus::OutOfStock);
+    assert_eq!(StockStatus::from("36"), StockStatus::PreSell);
+    assert_eq!(StockStatus::from("39"), StockStatus::Distribution);
+    assert_eq!(StockStatus::from("40"), StockStatus::Distribution);
+    assert!(matches!(StockStatus::from("99"), StockStatus::Unknown));
+}
+
+#[test]
+fn test_parse_stocks() {
+    let stocks = parse_stocks(fixture("stocks.json").as_str()).unwrap();
+    assert_eq!(stocks.len(), 4);
+    assert_eq!(stocks["100009077475"], StockStatus::OnSale);
+    assert_eq!(stocks["100012043978"], StockStatus::OutOfStock);
+    // StockState 也可能是字符串
+    assert_eq!(stocks["100016777664"], StockStatus::PreSell);
+    assert_eq!(stocks["100010104457"], StockStatus::Distribution);
+    // 没有 StockState 的商品跳过
+    assert!(parse_stocks(r#"{"1":{"skuState":1}}"#).unwrap().is_empty());
+    assert!(matches!(parse_stocks("<html></html>"), Err(ParseError::InvalidJson("stock", _))));
+    assert!(matches!(parse_stocks("[]"), Err(ParseError::InvalidJson("stock", _))));
+}
+
+#[test]
+fn test_parse_prices() {
+    let prices = parse_prices(fixture("prices.json").as_str()).unwrap();
+    assert_eq!(prices.len(), 2);
+    assert_eq!(prices["100009077475"], ("5999.00".to_string(), "6299.00".to_string()));
+    assert_eq!(prices["100012043978"], ("99.00".to_string(), "129.00".to_string()));
+    // 没有现价的商品跳过
+    assert!(!prices.contains_key("100016777664"));
+    assert!(parse_prices(fixture("price_missing.json").as_str()).unwrap().is_empty());
+    assert!(parse_prices("[]").unwrap().is_empty());
+    assert!(matches!(parse_prices("{}"), Err(ParseError::InvalidJson("price", _))));
+}
+
+#[test]
+fn test_parse_order_track() {
+    let track = parse_order_track(fixture("order_track.json").as_str(), "228899001122").unwrap();
+    assert_eq!(track.order_no, "228899001122");
+    assert_eq!(track.packages.len(), 2);
+    let p = &track.packages[0];
+    assert_eq!(p.carrier, "京东快递");
+    assert_eq!(p.tracking_no, "JDVA00012345678");
+    // 按时间从新到旧
+    assert_eq!(p.events.len(), 3);
+    assert_eq!(p.events[0].content, "您的订单已到达【北京通州分拣中心】");
+    assert_eq!(p.events[2].dt, parse_datetime("2021-12-20 09:30:00"));
+    assert!(!p.delivered);
+    // 最新的记录是签收
+    assert!(track.packages[1].delivered);
+    assert!(!track.delivered());
+    assert_eq!(track.last_update(), parse_datetime("2021-12-22 10:00:00"));
+
+    assert_eq!(
+        parse_order_track("{}", "1").unwrap_err(),
+        ParseError::MissingField("order_track", "multiPackageTrackList".to_string())
+    );
+    assert_eq!(
+        parse_order_track(fixture("login.html").as_str(), "1").unwrap_err(),
+        ParseError::LoginRequired("order_track")
+    );
+}
+
+#[test]
+fn test_order_track_stale() {
+    let mut track = parse_order_track(fixture("order_track.json").as_str(), "228899001122").unwrap();
+    // 只看第一个包裹, 最后一次更新是 2021-12-21 18:05:12
+    track.packages.truncate(1);
+    let now = parse_datetime("2021-12-24 18:00:00").unwrap();
+    assert!(!track.is_stale(now, 3));
+    assert!(track.is_stale(now, 2));
+    // 签收了就不算停滞
+    track.packages[0].delivered = true;
+    assert!(!track.is_stale(now, 1));
+    // 没有物流记录时不提示
+    assert!(!OrderTrack::default().is_stale(now, 1));
+}
+
+#[test]
+fn test_parse_search_page() {
+    let page = parse_search_page(search_page().as_str()).unwrap();
+    assert_eq!((page.page, page.total_pages), (2, 5));
+    assert!(page.has_prev() && page.has_next());
+    let skus: Vec<&str> = page.items.iter().map(|i| i.sku.as_str()).collect();
+    assert_eq!(skus, vec!["100009077475", "100016777664", "10025346234651"]);
+    // 高亮的关键字也在名称里
+    assert_eq!(
+        page.items[0].name,
+        "Apple iPhone 12 (A2404) 128GB 蓝色 支持移动联通电信5G 双卡双待手机"
+    );
+    assert_eq!(page.items[0].price, "5999.00");
+    assert_eq!(page.items[0].shop, "Apple产品京东自营旗舰店");
+    assert_eq!(page.items[2].name, "iPhone 12 手机壳 透明防摔");
+    // 库存要另外查
+    assert_eq!(page.items[0].status.to_string(), StockStatus::Unknown.to_string());
+
+    let empty = parse_search_page(render_fixture("search_empty.html", &[("keyword", "不存在的商品")]).as_str()).unwrap();
+    assert!(empty.items.is_empty());
+    assert!(!empty.has_next());
+    assert_eq!(
+        parse_search_page(order_list_page().as_str()).unwrap_err(),
+        ParseError::MissingElement("search", "div#J_goodsList".to_string())
+    );
+}
+
+#[test]
+fn test_parse_sku_input() {
+    let sku = |s: &str| parse_sku_input(s);
+    assert_eq!(sku(" 100012043978 "), Some("100012043978".to_string()));
+    assert_eq!(sku("https://item.jd.com/100012043978.html"), Some("100012043978".to_string()));
+    assert_eq!(sku("item.jd.com/100012043978.html#crumb-wrap"), Some("100012043978".to_string()));
+    assert_eq!(sku("//item.jd.com/100012043978.html"), Some("100012043978".to_string()));
+    assert_eq!(
+        sku("https://item.m.jd.com/product/100012043978.html?_fd=jdm&sid=abc"),
+        Some("100012043978".to_string())
+    );
+    assert_eq!(
+        sku("https://yushou.jd.com/toYuyue.action?sku=100010104457&key=abc"),
+        Some("100010104457".to_string())
+    );
+    // 关键字按名称搜索
+    assert_eq!(sku("手机"), None);
+    assert_eq!(sku("iphone 12"), None);
+    assert_eq!(sku("https://search.jd.com/Search?keyword=abc"), None);
+    assert_eq!(sku(""), None);
+}
@@ -0,0 +1,203 @@
+//! 商品价格和库存的历史: 每次查询价格、库存都记到本地数据库, 用来判断"促销价"是不是真的便宜
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use log::{error, info};
+
+use crate::error::Result;
+use crate::models::StockStatus;
+use crate::services::store;
+use crate::utils::{datetime_fmt, now, parse_amount, parse_datetime};
+use crate::PKDateTime;
+
+// 相同的价格和库存在这段时间内只记一次, 监控库存时每秒都会查询
+const SAME_VALUE_MINUTES: i64 = 10;
+// 价格历史保留的天数, 界面上最多看 90 天
+const KEEP_DAYS: i64 = 180;
+
+/// 一次观测, 只查了价格时 status 为 None, 只查了库存时 price 为 None
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct Observation {
+    pub observe_dt: String,
+    pub sku: String,
+    // 查询时知道商品名称才有
+    pub name: String,
+    // 查询库存时的地区, 只查价格时为空
+    pub area: String,
+    pub price: Option<f64>,
+    pub status: Option<StockStatus>,
+}
+
+impl Observation {
+    pub fn new(sku: &str, name: &str, area: &str, price: Option<&str>, status: Option<StockStatus>) -> Self {
+        Self {
+            observe_dt: datetime_fmt(now()),
+            sku: sku.to_string(),
+            name: name.to_string(),
+            area: area.to_string(),
+            price: price.and_then(parse_amount),
+            status,
+        }
+    }
+
+    pub fn time(&self) -> Option<PKDateTime> {
+        parse_datetime(self.observe_dt.as_str())
+    }
+}
+
+/// 有价格历史的商品
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct WatchedSku {
+    pub sku: String,
+    pub name: String,
+    pub count: usize,
+    pub last_dt: String,
+}
+
+/// 一段时间内的价格统计
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct PriceStats {
+    pub min: f64,
+    pub max: f64,
+    // 按观测次数平均
+    pub avg: f64,
+    pub last: f64,
+    pub count: usize,
+    // 查库存时有货的比例, 没查过库存时为 None
+    pub in_stock_ratio: Option<f64>,
+}
+
+impl PriceStats {
+    /// 最新价格比平均价格低多少, 如 0.2 表示低 20%
+    pub fn discount(&self) -> f64 {
+        if self.avg > 0.0 {
+            (self.avg - self.last) / self.avg
+        } else {
+            0.0
+        }
+    }
+}
+
+/// 统计价格, 没有价格记录时返回 None
+pub fn stats(history: &[Observation]) -> Option<PriceStats> {
+    let prices: Vec<f64> = history.iter().filter_map(|o| o.price).collect();
+    let last = *prices.last()?;
+    let stocks: Vec<bool> = history
+        .iter()
+        .filter_map(|o| o.status)
+        .filter(|s| s.code() != StockStatus::Unknown.code())
+        .map(|s| s.is_stock())
+        .collect();
+    let in_stock_ratio = if stocks.is_empty() {
+        None
+    } else {
+        Some(stocks.iter().filter(|s| **s).count() as f64 / stocks.len() as f64)
+    };
+    Some(PriceStats {
+        min: prices.iter().cloned().fold(f64::MAX, f64::min),
+        max: prices.iter().cloned().fold(f64::MIN, f64::max),
+        avg: prices.iter().sum::<f64>() / prices.len() as f64,
+        last,
+        count: prices.len(),
+        in_stock_ratio,
+    })
+}
+
+// 最近一次记下的值, 和记下的时间
+type LastValues<K, V> = Mutex<HashMap<K, (V, Option<PKDateTime>)>>;
+
+lazy_static! {
+    // 价格和库存分开比较: 只查价格时没有地区, 和只查库存的交替出现时也不会重复记录
+    // 每个 sku 最近一次记下的价格
+    static ref LAST_PRICE: LastValues<String, f64> = Default::default();
+    // 每个 (sku, 地区) 最近一次记下的库存, 库存状态的 PartialEq 认为 Unknown 和自己不相等, 比较状态码
+    static ref LAST_STOCK: LastValues<(String, String), &'static str> = Default::default();
+}
+
+static PRUNED: AtomicBool = AtomicBool::new(false);
+
+// 和上次记下的不一样或者过了一段时间
+fn changed<K: Eq + Hash, V: PartialEq>(
+    last: &HashMap<K, (V, Option<PKDateTime>)>,
+    key: &K,
+    value: &V,
+    dt: Option<PKDateTime>,
+) -> bool {
+    match last.get(key) {
+        Some((prev, Some(prev_dt))) if prev == value => match dt {
+            Some(dt) => dt.signed_duration_since(*prev_dt) >= chrono::Duration::minutes(SAME_VALUE_MINUTES),
+            None => true,
+        },
+        _ => true,
+    }
+}
+
+/// 价格和库存都和上次记下的一样并且没过多久时不用再记
+fn should_record(obs: &Observation) -> bool {
+    let dt = obs.time();
+    let price = obs.price.map(|p| (obs.sku.clone(), p));
+    let stock = obs.status.map(|s| ((obs.sku.clone(), obs.area.clone()), s.code()));
+    let mut last_price = LAST_PRICE.lock().unwrap();
+    let mut last_stock = LAST_STOCK.lock().unwrap();
+    let price_changed = price.as_ref().map(|(k, v)| changed(&last_price, k, v, dt)).unwrap_or(false);
+    let stock_changed = stock.as_ref().map(|(k, v)| changed(&last_stock, k, v, dt)).unwrap_or(false);
+    if !price_changed && !stock_changed {
+        return false;
+    }
+    // 整条记下来了, 价格和库存都算新记的
+    if let Some((k, v)) = price {
+        last_price.insert(k, (v, dt));
+    }
+    if let Some((k, v)) = stock {
+        last_stock.insert(k, (v, dt));
+    }
+    true
+}
+
+/// 删掉保留天数之前的价格历史, 每次启动只删一次
+async fn prune() {
+    if PRUNED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let before = datetime_fmt(now() - chrono::Duration::days(KEEP_DAYS));
+    match store::run(move |s| s.prune_price_history(before.as_str())).await {
+        Ok(n) if n > 0 => info!("删除了 {} 条过期的价格历史", n),
+        Ok(_) => {}
+        Err(e) => error!("删除过期的价格历史失败:{:?}", e),
+    }
+}
+
+/// 记一次观测, 返回是否写入了; 失败只记日志, 不影响查询
+pub async fn record(obs: Observation) -> bool {
+    if obs.sku.is_empty() || (obs.price.is_none() && obs.status.is_none()) || !should_record(&obs) {
+        return false;
+    }
+    prune().await;
+    match store::run(move |s| s.append_observation(&obs)).await {
+        Ok(()) => true,
+        Err(e) => {
+            error!("保存价格历史失败:{:?}", e);
+            false
+        }
+    }
+}
+
+/// 在后台记录, 不耽误抢购时的查询
+pub fn record_in_background(obs: Observation) {
+    tokio::spawn(record(obs));
+}
+
+/// 最近 days 天的价格和库存
+pub async fn history(sku: String, days: u32) -> Result<Vec<Observation>> {
+    let since = datetime_fmt(now() - chrono::Duration::days(days as i64));
+    store::run(move |s| s.price_history(sku.as_str(), since.as_str())).await
+}
+
+pub async fn watched() -> Result<Vec<WatchedSku>> {
+    store::run(|s| s.watched_skus()).await
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,51 @@
+use crate::models::StockStatus;
+use crate::services::price_history::{self, Observation, stats};
+use crate::testing::{AREA, SKU, observation};
+
+#[test]
+fn test_price_stats() {
+    assert_eq!(stats(&[]), None);
+    assert_eq!(stats(&[observation("2021-12-21 09:00:00", SKU, None, Some(StockStatus::OnSale))]), None);
+    let history = vec![
+        observation("2021-12-20 09:00:00", SKU, Some(100.0), Some(StockStatus::OnSale)),
+        observation("2021-12-20 10:00:00", SKU, None, Some(StockStatus::OutOfStock)),
+        // 未知的库存不算
+        observation("2021-12-20 11:00:00", SKU, None, Some(StockStatus::Unknown)),
+        observation("2021-12-21 09:00:00", SKU, Some(80.0), None),
+        observation("2021-12-22 09:00:00", SKU, Some(60.0), None),
+    ];
+    let s = stats(&history).unwrap();
+    assert_eq!((s.min, s.max, s.avg, s.last, s.count), (60.0, 100.0, 80.0, 60.0, 3));
+    assert_eq!(s.in_stock_ratio, Some(0.5));
+    assert!((s.discount() - 0.25).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_record_price_history() {
+    let sku = "price-history-record";
+    let obs = Observation::new(sku, "手机", AREA, Some("¥99.00"), Some(StockStatus::OnSale));
+    assert_eq!(obs.price, Some(99.0));
+    assert!(price_history::record(obs.clone()).await);
+    // 一样的价格和库存不重复记录
+    assert!(!price_history::record(obs.clone()).await);
+    // 库存或价格变了马上记录
+    assert!(price_history::record(Observation { status: Some(StockStatus::Distribution), ..obs.clone() }).await);
+    assert!(price_history::record(Observation { price: Some(98.0), ..obs.clone() }).await);
+    // 只查价格和只查库存的交替出现时分开比较, 也不重复记录
+    let price_only = Observation { area: String::new(), price: Some(98.0), status: None, ..obs.clone() };
+    let stock_only = Observation { price: None, ..obs.clone() };
+    for _ in 0..2 {
+        assert!(!price_history::record(price_only.clone()).await);
+        assert!(!price_history::record(stock_only.clone()).await);
+    }
+    // 过了一段时间再记一次
+    let later = crate::utils::datetime_fmt(crate::utils::now() + chrono::Duration::minutes(11));
+    assert!(price_history::record(Observation { observe_dt: later, price: Some(98.0), ..obs.clone() }).await);
+    // 没有价格和库存的不记
+    assert!(!price_history::record(Observation::new(sku, "", "", None, None)).await);
+
+    let history = price_history::history(sku.to_string(), 1).await.unwrap();
+    assert_eq!(history.len(), 4);
+    assert_eq!(history[0].name, "手机");
+    assert!(price_history::watched().await.unwrap().iter().any(|w| w.sku == sku && w.count == 4));
+}
@@ -0,0 +1,572 @@
+use std::collections::{BTreeMap, HashMap, LinkedList};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::{error, info, warn};
+
+use crate::*;
+use crate::error::{ParseError, Result};
+use crate::models::*;
+use crate::services::budget::{self, BLOCKED};
+use crate::services::delivery_address::DeliveryAddressService;
+use crate::services::dry_run;
+use crate::services::goods::GoodsService;
+use crate::services::inventory;
+use crate::services::order::{OrderService, QueryCondition};
+use crate::services::price_history::{self, Observation};
+use crate::services::shopping_cart::ShoppingCartService;
+use crate::services::store::{self, JournalEntry};
+use crate::utils::{datetime_fmt, greater_than_now, now, parse_amount, workers};
+
+// 最新价格超过了最高价格, 不购买
+pub const PRICE_TOO_HIGH: &'static str = "price-high";
+
+/// 提交订单时的购买方式，不同平台按各自的流程下单
+#[derive(Debug, Clone)]
+pub enum SubmitKind {
+    // 普通商品: 加入购物车后结算
+    Normal { area: String, workers: usize },
+    // 预约商品: in_yuyue 为 true 时只预约, 否则直接购买
+    Yuyue { in_yuyue: bool, area: String },
+    // 预售商品, 定金模式
+    Presale { eid: String, fp: String },
+    // 秒杀商品
+    Seckill { eid: String, fp: String },
+}
+
+/// 提交订单所需的参数
+#[derive(Debug, Clone)]
+pub struct SubmitOrderReq {
+    pub account_id: IDType,
+    pub account: String,
+    pub cookie: Arc<String>,
+    pub cart_goods_id: IDType,
+    pub sku: String,
+    // 提交时的商品单价, 用来检查预算
+    pub price: String,
+    pub num: u32,
+    pub kind: SubmitKind,
+    // 试运行: 只记录要提交的订单, 不真正提交
+    pub dry_run: bool,
+}
+
+/// 电商平台的抽象, 界面只通过这个 trait 访问平台, 新增平台时只需要新增一个实现
+#[async_trait]
+pub trait Retailer: Send + Sync {
+    fn platform(&self) -> ProdPlatform;
+
+    // 商品信息(名称、价格、库存、购买方式)
+    async fn get_prod_info(&self, sku: &str, area_id: &str) -> Result<GoodsState>;
+
+    // 按关键字搜索商品, 结果带上在选中地区的库存
+    async fn search_goods(&self, keyword: String, page: u32, area_id: String, shop: String) -> Result<SearchPage>;
+
+    // 商品的 (现价, 原价)
+    async fn get_prod_price(&self, sku: String) -> Result<(String, String)>;
+
+    // 商品在某个地区的库存
+    async fn get_prod_stock(
+        &self,
+        cart_goods_id: IDType,
+        sku: String,
+        area_id: String,
+    ) -> Result<(IDType, StockStatus)>;
+
+    // 商品的 (现价, 原价), 下单前核对价格时用, 不用缓存也不受退避影响
+    async fn get_prod_price_now(&self, sku: String) -> Result<(String, String)>;
+
+    // 一次查询多个商品的价格, 查不到价格的商品不在结果里
+    async fn get_prod_prices(&self, skus: Vec<String>) -> Result<HashMap<String, (String, String)>>;
+
+    // 一次查询多个商品在某个地区的库存, 接口没有返回的商品不在结果里
+    async fn get_prod_stocks(&self, skus: Vec<String>, area_id: String) -> Result<HashMap<String, StockStatus>>;
+
+    // 预售商品的信息: (购买链接, 预售时间, 购买方式)
+    async fn get_presale_info(&self, sku: String) -> Result<(String, Option<PKDateTime>, String)>;
+
+    // 把商品加入各个账号在平台上的购物车
+    async fn add_to_cart(
+        &self,
+        num: u32,
+        sku: Option<String>,
+        account_cookies: Vec<(IDType, Arc<String>)>,
+    ) -> Result<()>;
+
+    // 提交订单, 返回 (购物车商品id, 数量, 状态)
+    async fn submit_order(&self, req: SubmitOrderReq) -> Result<(IDType, u32, &'static str)>;
+
+    // 账号的订单
+    async fn get_orders(
+        &self,
+        account: String,
+        cookie: Arc<String>,
+        cond: QueryCondition,
+    ) -> Result<BTreeMap<String, OrderInfo>>;
+
+    // 账号从 date 那天开始的订单, 会逐页查询
+    async fn get_orders_since(
+        &self,
+        account: String,
+        cookie: Arc<String>,
+        cond: QueryCondition,
+        date: String,
+    ) -> Result<BTreeMap<String, OrderInfo>>;
+
+    // 账号在这些时间段里的所有订单, 会逐页查询
+    async fn get_all_orders(
+        &self,
+        account: String,
+        cookie: Arc<String>,
+        cond: QueryCondition,
+        periods: Vec<String>,
+    ) -> Result<BTreeMap<String, OrderInfo>>;
+
+    // 订单的物流轨迹
+    async fn get_order_track(
+        &self,
+        account: String,
+        cookie: Arc<String>,
+        order_no: String,
+    ) -> Result<OrderTrack>;
+
+    // 账号的收货地址
+    async fn get_addresses(
+        &self,
+        account: String,
+        cookie: Arc<String>,
+    ) -> Result<LinkedList<AddressInfo>>;
+
+    // 设置结算时的收货地址
+    async fn set_order_address(
+        &self,
+        addr_id: String,
+        account: String,
+        cookie: Arc<String>,
+    ) -> Result<Option<(String, String)>>;
+
+    // 新增收货地址, 返回新地址的 id
+    async fn add_address(&self, account: String, cookie: Arc<String>, form: AddressForm) -> Result<String>;
+
+    // 修改收货地址
+    async fn update_address(
+        &self,
+        account: String,
+        cookie: Arc<String>,
+        addr_id: String,
+        form: AddressForm,
+    ) -> Result<String>;
+
+    // 删除收货地址
+    async fn delete_address(&self, account: String, cookie: Arc<String>, addr_id: String) -> Result<()>;
+}
+
+/// 京东
+pub struct JdRetailer;
+
+#[async_trait]
+impl Retailer for JdRetailer {
+    fn platform(&self) -> ProdPlatform {
+        ProdPlatform::JD
+    }
+
+    async fn get_prod_info(&self, sku: &str, area_id: &str) -> Result<GoodsState> {
+        let goods = GoodsService::get_prod_info(sku, area_id).await?;
+        price_history::record_in_background(Observation::new(
+            sku,
+            goods.name.as_str(),
+            area_id,
+            Some(goods.cur_price.as_str()),
+            Some(goods.status),
+        ));
+        Ok(goods)
+    }
+
+    async fn search_goods(&self, keyword: String, page: u32, area_id: String, shop: String) -> Result<SearchPage> {
+        let page = GoodsService::search_goods(keyword.as_str(), page, area_id.as_str(), shop.as_str()).await?;
+        for item in page.items.iter() {
+            price_history::record_in_background(Observation::new(
+                item.sku.as_str(),
+                item.name.as_str(),
+                area_id.as_str(),
+                Some(item.price.as_str()),
+                Some(item.status),
+            ));
+        }
+        Ok(page)
+    }
+
+    async fn get_prod_price(&self, sku: String) -> Result<(String, String)> {
+        let mut prices = self.get_prod_prices(vec![sku.clone()]).await?;
+        Ok(prices
+            .remove(&sku)
+            .ok_or_else(|| ParseError::MissingField("price", "p".to_string()))?)
+    }
+
+    async fn get_prod_stock(
+        &self,
+        cart_goods_id: IDType,
+        sku: String,
+        area_id: String,
+    ) -> Result<(IDType, StockStatus)> {
+        let stocks = self.get_prod_stocks(vec![sku.clone()], area_id).await?;
+        Ok((cart_goods_id, stocks.get(&sku).cloned().unwrap_or(StockStatus::Unknown)))
+    }
+
+    async fn get_prod_price_now(&self, sku: String) -> Result<(String, String)> {
+        let mut prices = inventory::prices_now(vec![sku.clone()]).await?;
+        let price = prices
+            .remove(&sku)
+            .ok_or_else(|| ParseError::MissingField("price", "p".to_string()))?;
+        price_history::record_in_background(Observation::new(sku.as_str(), "", "", Some(price.0.as_str()), None));
+        Ok(price)
+    }
+
+    async fn get_prod_prices(&self, skus: Vec<String>) -> Result<HashMap<String, (String, String)>> {
+        let prices = inventory::prices(skus).await?;
+        for (sku, (price, _)) in prices.iter() {
+            price_history::record_in_background(Observation::new(sku.as_str(), "", "", Some(price.as_str()), None));
+        }
+        Ok(prices)
+    }
+
+    async fn get_prod_stocks(&self, skus: Vec<String>, area_id: String) -> Result<HashMap<String, StockStatus>> {
+        let stocks = inventory::stocks(skus, area_id.clone()).await?;
+        for (sku, status) in stocks.iter() {
+            price_history::record_in_background(Observation::new(
+                sku.as_str(),
+                "",
+                area_id.as_str(),
+                None,
+                Some(*status),
+            ));
+        }
+        Ok(stocks)
+    }
+
+    async fn get_presale_info(&self, sku: String) -> Result<(String, Option<PKDateTime>, String)> {
+        GoodsService::get_presale_info(sku).await
+    }
+
+    async fn add_to_cart(
+        &self,
+        num: u32,
+        sku: Option<String>,
+        account_cookies: Vec<(IDType, Arc<String>)>,
+    ) -> Result<()> {
+        ShoppingCartService::add_to_cat_and_get_sku_uuid_from_account_cart(num, sku, account_cookies)
+            .await
+    }
+
+    async fn submit_order(&self, req: SubmitOrderReq) -> Result<(IDType, u32, &'static str)> {
+        let SubmitOrderReq {
+            account_id,
+            cookie,
+            cart_goods_id,
+            sku,
+            num,
+            kind,
+            dry_run,
+            ..
+        } = req;
+        let dry_run = dry_run || dry_run::is_global_dry_run().await;
+        match kind {
+            SubmitKind::Normal { area, workers } => {
+                ShoppingCartService::submit_order_wrapper(
+                    account_id,
+                    cookie,
+                    cart_goods_id,
+                    sku,
+                    num,
+                    area,
+                    false,
+                    workers,
+                    dry_run,
+                )
+                .await
+            }
+            SubmitKind::Yuyue { in_yuyue, area } => {
+                ShoppingCartService::submit_yuyue_order(
+                    account_id,
+                    cookie,
+                    cart_goods_id,
+                    sku,
+                    num,
+                    in_yuyue,
+                    area,
+                    dry_run,
+                )
+                .await
+            }
+            SubmitKind::Presale { eid, fp } => {
+                ShoppingCartService::submit_presale_order(
+                    account_id,
+                    cookie,
+                    cart_goods_id,
+                    sku,
+                    eid,
+                    fp,
+                    num,
+                    dry_run,
+                )
+                .await
+            }
+            SubmitKind::Seckill { eid, fp } => {
+                ShoppingCartService::submit_seckill_order(
+                    account_id,
+                    cookie,
+                    cart_goods_id,
+                    sku,
+                    eid,
+                    fp,
+                    dry_run,
+                )
+                .await
+            }
+        }
+    }
+
+    async fn get_orders(
+        &self,
+        account: String,
+        cookie: Arc<String>,
+        cond: QueryCondition,
+    ) -> Result<BTreeMap<String, OrderInfo>> {
+        OrderService::get_orders_by_user(account, cookie, cond).await
+    }
+
+    async fn get_orders_since(
+        &self,
+        account: String,
+        cookie: Arc<String>,
+        cond: QueryCondition,
+        date: String,
+    ) -> Result<BTreeMap<String, OrderInfo>> {
+        OrderService::get_orders_since(account, cookie, cond, date).await
+    }
+
+    async fn get_all_orders(
+        &self,
+        account: String,
+        cookie: Arc<String>,
+        cond: QueryCondition,
+        periods: Vec<String>,
+    ) -> Result<BTreeMap<String, OrderInfo>> {
+        OrderService::get_all_orders_by_user(account, cookie, cond, periods).await
+    }
+
+    async fn get_order_track(
+        &self,
+        account: String,
+        cookie: Arc<String>,
+        order_no: String,
+    ) -> Result<OrderTrack> {
+        OrderService::get_order_track(account, cookie, order_no).await
+    }
+
+    async fn get_addresses(
+        &self,
+        account: String,
+        cookie: Arc<String>,
+    ) -> Result<LinkedList<AddressInfo>> {
+        let mut addrs = DeliveryAddressService::get_all_address_by_user(account, cookie).await?;
+        for addr in addrs.iter_mut() {
+            addr.platform = self.platform();
+        }
+        Ok(addrs)
+    }
+
+    async fn set_order_address(
+        &self,
+        addr_id: String,
+        account: String,
+        cookie: Arc<String>,
+    ) -> Result<Option<(String, String)>> {
+        DeliveryAddressService::set_order_express_address(addr_id, account, cookie).await
+    }
+
+    async fn add_address(&self, account: String, cookie: Arc<String>, form: AddressForm) -> Result<String> {
+        DeliveryAddressService::add_address(account, cookie, form).await
+    }
+
+    async fn update_address(
+        &self,
+        account: String,
+        cookie: Arc<String>,
+        addr_id: String,
+        form: AddressForm,
+    ) -> Result<String> {
+        DeliveryAddressService::update_address(account, cookie, addr_id, form).await
+    }
+
+    async fn delete_address(&self, account: String, cookie: Arc<String>, addr_id: String) -> Result<()> {
+        DeliveryAddressService::delete_address(account, cookie, addr_id).await
+    }
+}
+
+/// 根据平台获取对应的实现
+pub fn retailer(platform: ProdPlatform) -> Arc<dyn Retailer> {
+    match platform {
+        ProdPlatform::JD => Arc::new(JdRetailer),
+    }
+}
+
+/// 设置了最高价格的商品, 提交订单前重新查询价格; 返回 (最新价格, 是否可以购买), 查不到价格时不购买
+pub async fn check_max_price(platform: ProdPlatform, sku: String, max_price: f64) -> Result<(String, bool)> {
+    let (cur_price, _) = retailer(platform).get_prod_price_now(sku.clone()).await?;
+    let ok = parse_amount(cur_price.as_str())
+        .map(|p| p <= max_price)
+        .unwrap_or(false);
+    if !ok {
+        warn!("{}:最新价格{}超过了最高价格{:.2}, 不购买", sku, cur_price, max_price);
+    }
+    Ok((cur_price, ok))
+}
+
+/// 提交订单前先检查预算, 超出预算的不提交, 返回的状态是 blocked; 界面和命令行共用
+pub async fn submit_within_budget(
+    platform: ProdPlatform,
+    req: SubmitOrderReq,
+) -> Result<(IDType, u32, &'static str)> {
+    let account = req.account.clone();
+    let cart_goods_id = req.cart_goods_id;
+    let num = req.num;
+    let mut entry = JournalEntry {
+        create_dt: datetime_fmt(now()),
+        account: account.clone(),
+        cart_goods_id,
+        sku: req.sku.clone(),
+        num,
+        price: req.price.clone(),
+        ..Default::default()
+    };
+    if !budget::reserve(account.as_str(), cart_goods_id, req.sku.as_str(), req.price.as_str(), num).await {
+        entry.status = BLOCKED.to_string();
+        append_journal(entry).await;
+        return Ok((cart_goods_id, num, BLOCKED));
+    }
+    let r = retailer(platform).submit_order(req).await;
+    let status = r.as_ref().map(|t| t.2).unwrap_or("fail");
+    budget::settle(account.as_str(), cart_goods_id, status).await;
+    entry.status = status.to_string();
+    append_journal(entry).await;
+    r
+}
+
+// 每次下单的结果都记到本地数据库的流水里, 记录失败不影响下单
+async fn append_journal(entry: JournalEntry) {
+    if let Err(e) = store::run(move |s| s.append_journal(&entry)).await {
+        error!("记录下单流水失败:{:?}", e);
+    }
+}
+
+/// 根据购物车商品的购买方式和状态, 生成各个账号需要提交的订单; 界面和命令行共用
+pub fn plan_submit_orders(
+    cart_item: &CartProdState,
+    accounts: &[UserState],
+    area: &str,
+) -> Vec<SubmitOrderReq> {
+    let id = cart_item.id;
+    let sku = cart_item.sku.clone();
+    let num = cart_item.purchase_num;
+    accounts
+        .iter()
+        .filter(|item| item.platform == cart_item.platform)
+        .map(|item| {
+            let account_id = item.id;
+            let req = |kind: SubmitKind| SubmitOrderReq {
+                account_id,
+                account: item.account.clone(),
+                cookie: item.cookie.clone(),
+                cart_goods_id: id,
+                sku: sku.clone(),
+                price: cart_item.cur_price.clone(),
+                num,
+                kind,
+                dry_run: cart_item.is_dry_run(),
+            };
+            if cart_item.purchase_type.eq(SECOND_KILL) {
+                (1..=workers())
+                    .map(|_| {
+                        req(SubmitKind::Seckill {
+                            eid: item.eid.clone(),
+                            fp: item.fp.clone(),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            } else if cart_item.purchase_type.eq(PRESALE) {
+                if let Some(ref yuyue_dt) = cart_item.yuyue_dt {
+                    if greater_than_now(yuyue_dt) {
+                        // 还不到设定的预约时间， 不能购买
+                        warn!(
+                            "预售商品还不到设定的预约时间,不能购买: {}",
+                            serde_json::to_string(&cart_item).unwrap_or_default()
+                        );
+                        return vec![];
+                    }
+                }
+                (1..=workers())
+                    .map(|_| {
+                        req(SubmitKind::Presale {
+                            eid: item.eid.clone(),
+                            fp: item.fp.clone(),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            } else if cart_item.purchase_type.eq(YUYUE) {
+                if cart_item.purchase_status.eq("ready") {
+                    let is_yuyue = if let Some(ref end_dt) = cart_item.yuyue_end_dt {
+                        greater_than_now(end_dt)
+                    } else {
+                        false
+                    };
+                    if is_yuyue {
+                        info!("{}-{}:预约商品...", account_id, sku);
+                    } else {
+                        info!("{}-{}:跳过预约，直接购买预约商品...", account_id, sku);
+                    }
+                    vec![req(SubmitKind::Yuyue {
+                        in_yuyue: is_yuyue,
+                        area: area.to_string(),
+                    })]
+                } else if cart_item.purchase_status.eq("yuyueing")
+                    && cart_item.yuyue_dt.as_ref().map(|dt| !greater_than_now(dt)).unwrap_or(false)
+                {
+                    // 预约时间到了就去真正购买
+                    info!("{}-{}:购买已经预约过的商品", account_id, sku);
+                    // 把并发预约放到 submit_yuyue_order 里， 因为可能导致预约的商品在购物车的数量不对
+                    vec![req(SubmitKind::Yuyue {
+                        in_yuyue: false,
+                        area: area.to_string(),
+                    })]
+                } else {
+                    warn!(
+                        "{}-{}:预约商品的状态不对,不能购买: {}!",
+                        account_id, sku, cart_item.purchase_status
+                    );
+                    vec![]
+                }
+            } else {
+                let mut _workers = 1;
+                if let Some(ref yuyue_dt) = cart_item.yuyue_dt {
+                    if greater_than_now(yuyue_dt) {
+                        // 还不到设定的预约时间， 不能购买
+                        warn!(
+                            "普通商品预约时间不到,不能购买: {}",
+                            serde_json::to_string(&cart_item).unwrap_or_default()
+                        );
+                        return vec![];
+                    }
+                    _workers = workers();
+                }
+                vec![req(SubmitKind::Normal {
+                    area: area.to_string(),
+                    workers: _workers,
+                })]
+            }
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,15 @@
+use crate::models::ProdPlatform;
+use crate::services::retailer::check_max_price;
+use crate::services::transport::JdScenario;
+use crate::testing::{SKU, install};
+
+#[tokio::test]
+async fn test_check_max_price() {
+    let g = install(JdScenario::Success).await;
+    let check = |max_price| check_max_price(ProdPlatform::JD, SKU.to_string(), max_price);
+    assert_eq!(check(100.0).await.unwrap(), ("99.00".to_string(), true));
+    assert_eq!(check(99.0).await.unwrap(), ("99.00".to_string(), true));
+    assert_eq!(check(98.0).await.unwrap(), ("99.00".to_string(), false));
+    // 下单前核对价格每次都重新查
+    assert_eq!(g.mock.hits("p.3.cn/prices/mgets"), 3);
+}
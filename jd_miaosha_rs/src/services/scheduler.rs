@@ -0,0 +1,313 @@
+//! 定时购买的任务: 预约时间到了之后提交订单。任务保存在本地数据库的 scheduled_jobs 表里,
+//! 程序重启或者崩溃之后, 启动时按 conf.toml 里 [scheduler] 配置的策略处理错过的任务
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, RwLock};
+
+use crate::error::Result;
+use crate::services::store::{self, Store};
+use crate::utils::now;
+use crate::{IDType, CONFIG};
+
+/// 启动时错过了触发时间的任务怎么处理
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum MissedPolicy {
+    // 不再执行, 标记为错过
+    Skip,
+    // 马上执行
+    RunNow,
+}
+
+impl Default for MissedPolicy {
+    fn default() -> Self {
+        MissedPolicy::Skip
+    }
+}
+
+/// 定时任务的配置, 在 conf.toml 的 [scheduler] 里配置
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    pub missed: MissedPolicy,
+    // run_now 时最多晚多少秒还执行, 0 表示不限制
+    pub max_delay_secs: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    // 等待触发
+    Scheduled,
+    // 已经触发, 正在下单
+    Running,
+    // 启动时发现已经错过了触发时间
+    Missed,
+    // 手动取消
+    Cancelled,
+}
+
+impl Default for JobStatus {
+    fn default() -> Self {
+        JobStatus::Scheduled
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct Job {
+    pub cart_goods_id: IDType,
+    pub sku: String,
+    pub name: String,
+    // 预约时间
+    pub yuyue_dt: String,
+    pub yuyue_at: i64,
+    // 触发时间, 比预约时间提前一点
+    pub fire_dt: String,
+    pub fire_at: i64,
+    pub status: JobStatus,
+    // 每次修改加 1, 旧的等待就不再触发
+    pub rev: u64,
+}
+
+impl Job {
+    /// yuyue_at 和 fire_at 都是 utils::now() 同一个时区的毫秒时间戳
+    pub fn new(cart_goods_id: IDType, sku: &str, name: &str, yuyue_at: i64, fire_at: i64) -> Self {
+        Self {
+            cart_goods_id,
+            sku: sku.to_string(),
+            name: name.to_string(),
+            yuyue_dt: millis_fmt(yuyue_at),
+            yuyue_at,
+            fire_dt: millis_fmt(fire_at),
+            fire_at,
+            status: JobStatus::Scheduled,
+            rev: 0,
+        }
+    }
+}
+
+fn millis_fmt(millis: i64) -> String {
+    let dt = chrono::NaiveDateTime::from_timestamp(millis.div_euclid(1000), (millis.rem_euclid(1000) * 1_000_000) as u32);
+    dt.format("%Y-%m-%d %H:%M:%S%.3f").to_string()
+}
+
+fn now_millis() -> i64 {
+    now().timestamp_millis()
+}
+
+// 取消和错过的任务留一天在列表里看, 之后删掉
+const KEEP_FINISHED_MILLIS: i64 = 24 * 3600 * 1000;
+
+fn prune(jobs: &mut BTreeMap<IDType, Job>, now: i64) {
+    jobs.retain(|_, j| match j.status {
+        JobStatus::Cancelled | JobStatus::Missed => j.yuyue_at + KEEP_FINISHED_MILLIS > now,
+        _ => true,
+    });
+}
+
+/// 启动时恢复的任务
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct Recovery {
+    // 还没到时间的, 接着等
+    pub pending: Vec<Job>,
+    // 错过了但要马上执行的
+    pub due: Vec<Job>,
+    // 错过了不再执行的
+    pub missed: Vec<Job>,
+}
+
+pub struct Scheduler {
+    store: Arc<Store>,
+    jobs: RwLock<BTreeMap<IDType, Job>>,
+    changed: Notify,
+}
+
+impl Scheduler {
+    /// 从数据库读出任务, 读不出来时从空的开始
+    pub fn open(store: Arc<Store>) -> Self {
+        let jobs = store.load_jobs().unwrap_or_else(|e| {
+            error!("读取定时任务失败:{:?}", e);
+            vec![]
+        });
+        Self {
+            store,
+            jobs: RwLock::new(jobs.into_iter().map(|j| (j.cart_goods_id, j)).collect()),
+            changed: Notify::new(),
+        }
+    }
+
+    // 整个替换, 在一个事务里写, 写一半崩溃不会把任务弄坏
+    async fn save(&self, jobs: &BTreeMap<IDType, Job>) -> Result<()> {
+        let store = self.store.clone();
+        let jobs: Vec<Job> = jobs.values().cloned().collect();
+        tokio::task::spawn_blocking(move || store.store_jobs(jobs.as_slice()))
+            .await
+            .map_err(anyhow::Error::from)?
+    }
+
+    async fn modify<T, F: FnOnce(&mut BTreeMap<IDType, Job>) -> T>(&self, f: F) -> T {
+        let mut guard = self.jobs.write().await;
+        let ret = f(&mut guard);
+        prune(&mut guard, now_millis());
+        if let Err(e) = self.save(&guard).await {
+            error!("保存定时任务失败:{:?}", e);
+        }
+        drop(guard);
+        self.changed.notify_waiters();
+        ret
+    }
+
+    /// 新建或更新任务, 预约时间没变的不动(包括已经取消的); 返回需要开始等待的版本
+    pub async fn schedule(&self, job: Job) -> Option<u64> {
+        {
+            let guard = self.jobs.read().await;
+            if let Some(old) = guard.get(&job.cart_goods_id) {
+                if old.yuyue_at == job.yuyue_at {
+                    return None;
+                }
+            }
+        }
+        self.modify(|jobs| {
+            let rev = jobs.get(&job.cart_goods_id).map(|j| j.rev + 1).unwrap_or(0);
+            jobs.insert(job.cart_goods_id, Job { rev, ..job });
+            Some(rev)
+        })
+        .await
+    }
+
+    /// 修改触发时间, 预约时间也跟着改
+    pub async fn reschedule(&self, id: IDType, yuyue_at: i64, fire_at: i64) -> Option<u64> {
+        self.modify(|jobs| {
+            jobs.get_mut(&id).map(|j| {
+                j.yuyue_at = yuyue_at;
+                j.yuyue_dt = millis_fmt(yuyue_at);
+                j.fire_at = fire_at;
+                j.fire_dt = millis_fmt(fire_at);
+                j.status = JobStatus::Scheduled;
+                j.rev += 1;
+                j.rev
+            })
+        })
+        .await
+    }
+
+    /// 取消任务, 正在等待的不会再触发
+    pub async fn cancel(&self, id: IDType) -> bool {
+        self.modify(|jobs| {
+            jobs.get_mut(&id)
+                .map(|j| {
+                    j.status = JobStatus::Cancelled;
+                    j.rev += 1;
+                })
+                .is_some()
+        })
+        .await
+    }
+
+    /// 下单结束, 删掉任务
+    pub async fn complete(&self, id: IDType) {
+        if self.jobs.read().await.contains_key(&id) {
+            self.modify(|jobs| jobs.remove(&id)).await;
+        }
+    }
+
+    pub async fn jobs(&self) -> Vec<Job> {
+        let mut jobs: Vec<Job> = self.jobs.read().await.values().cloned().collect();
+        jobs.sort_by_key(|j| j.fire_at);
+        jobs
+    }
+
+    /// 等到任务的触发时间, 触发了返回 true; 任务被取消、改了时间或者删掉了返回 false
+    pub async fn wait(&self, id: IDType, rev: u64) -> bool {
+        loop {
+            // 先注册通知再读任务, 中间的修改不会漏掉
+            let changed = self.changed.notified();
+            let fire_at = match self.jobs.read().await.get(&id) {
+                Some(j) if j.rev == rev && j.status == JobStatus::Scheduled => j.fire_at,
+                _ => return false,
+            };
+            let remaining = fire_at - now_millis();
+            if remaining <= 0 {
+                return self
+                    .modify(|jobs| match jobs.get_mut(&id) {
+                        Some(j) if j.rev == rev && j.status == JobStatus::Scheduled => {
+                            j.status = JobStatus::Running;
+                            true
+                        }
+                        _ => false,
+                    })
+                    .await;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(remaining as u64)) => {}
+                _ = changed => {}
+            }
+        }
+    }
+
+    /// 启动时处理上次留下的任务: 没到时间的接着等, 错过的按策略马上执行或者标记为错过;
+    /// 上次触发了但没结束的直接标记为错过
+    pub async fn recover(&self, config: &SchedulerConfig) -> Recovery {
+        let now = now_millis();
+        self.modify(|jobs| {
+            let mut ret = Recovery::default();
+            for j in jobs.values_mut() {
+                match j.status {
+                    JobStatus::Scheduled | JobStatus::Running => {}
+                    _ => continue,
+                }
+                if j.status == JobStatus::Scheduled && j.fire_at > now {
+                    ret.pending.push(j.clone());
+                    continue;
+                }
+                let delay = (now - j.fire_at).max(0) as u64;
+                let in_time = config.max_delay_secs == 0 || delay <= config.max_delay_secs * 1000;
+                j.rev += 1;
+                // 上次已经触发的可能已经下了单, 不再执行
+                if j.status == JobStatus::Scheduled && config.missed == MissedPolicy::RunNow && in_time {
+                    info!("马上执行错过的定时任务:{}-{}, 晚了{}ms", j.cart_goods_id, j.sku, delay);
+                    j.status = JobStatus::Running;
+                    ret.due.push(j.clone());
+                } else {
+                    warn!("错过了定时任务:{}-{}, {}", j.cart_goods_id, j.sku, j.fire_dt);
+                    j.status = JobStatus::Missed;
+                    ret.missed.push(j.clone());
+                }
+            }
+            ret
+        })
+        .await
+    }
+}
+
+lazy_static! {
+    static ref SCHEDULER: Scheduler = Scheduler::open(store::shared());
+}
+
+pub fn scheduler() -> &'static Scheduler {
+    &SCHEDULER
+}
+
+pub async fn config() -> SchedulerConfig {
+    CONFIG.read().await.scheduler()
+}
+
+/// 启动时用 conf.toml 里的配置恢复任务
+pub async fn recover() -> Recovery {
+    let config = config().await;
+    let recovery = scheduler().recover(&config).await;
+    info!(
+        "恢复定时任务: 等待{}个, 马上执行{}个, 错过{}个",
+        recovery.pending.len(),
+        recovery.due.len(),
+        recovery.missed.len()
+    );
+    recovery
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use crate::services::scheduler::{Job, JobStatus, MissedPolicy, Scheduler, SchedulerConfig};
+use crate::services::store::Store;
+use crate::testing::SKU;
+
+fn new_store() -> Arc<Store> {
+    Arc::new(Store::open_in_memory().unwrap())
+}
+
+#[tokio::test]
+async fn test_scheduler_persist() {
+    let store = new_store();
+    let at = crate::utils::now().timestamp_millis() + 3_600_000;
+    let s = Scheduler::open(store.clone());
+    assert_eq!(s.schedule(Job::new(1, SKU, "mock", at, at - 1200)).await, Some(0));
+    // 预约时间没变的不重复等待
+    assert_eq!(s.schedule(Job::new(1, SKU, "mock", at, at - 1200)).await, None);
+    assert_eq!(s.schedule(Job::new(1, SKU, "mock", at + 1000, at - 200)).await, Some(1));
+    assert_eq!(s.schedule(Job::new(2, SKU, "mock2", at, at - 1200)).await, Some(0));
+    assert!(s.cancel(2).await);
+    assert!(!s.cancel(3).await);
+
+    // 重新打开后任务还在
+    let jobs = Scheduler::open(store.clone()).jobs().await;
+    assert_eq!(jobs, s.jobs().await);
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(jobs[0].cart_goods_id, 2);
+    assert_eq!(jobs[0].status, JobStatus::Cancelled);
+    assert_eq!((jobs[1].fire_at, jobs[1].rev), (at - 200, 1));
+    s.complete(1).await;
+    assert_eq!(Scheduler::open(store.clone()).jobs().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_scheduler_wait() {
+    let store = new_store();
+    let s = Scheduler::open(store.clone());
+    let now = crate::utils::now().timestamp_millis();
+    let rev = s.schedule(Job::new(1, SKU, "mock", now + 1200, now + 50)).await.unwrap();
+    assert!(s.wait(1, rev).await);
+    assert_eq!(s.jobs().await[0].status, JobStatus::Running);
+
+    // 取消或者改了时间, 正在等待的不再触发
+    let rev = s.schedule(Job::new(2, SKU, "mock", now + 60_000, now + 60_000)).await.unwrap();
+    let (fired, _) = tokio::join!(s.wait(2, rev), async {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        s.cancel(2).await
+    });
+    assert!(!fired);
+    let new_rev = s.reschedule(2, now + 1200, now + 100).await.unwrap();
+    assert!(!s.wait(2, rev).await);
+    assert!(s.wait(2, new_rev).await);
+}
+
+#[tokio::test]
+async fn test_scheduler_recover() {
+    let store = new_store();
+    let now = crate::utils::now().timestamp_millis();
+    {
+        let s = Scheduler::open(store.clone());
+        s.schedule(Job::new(1, SKU, "future", now + 60_000, now + 60_000)).await;
+        s.schedule(Job::new(2, SKU, "late", now - 5_000, now - 5_000)).await;
+        s.schedule(Job::new(3, SKU, "too late", now - 600_000, now - 600_000)).await;
+        s.schedule(Job::new(4, SKU, "cancelled", now - 5_000, now - 5_000)).await;
+        s.cancel(4).await;
+    }
+    let ids = |jobs: &[Job]| jobs.iter().map(|j| j.cart_goods_id).collect::<Vec<_>>();
+    let run_now = SchedulerConfig {
+        missed: MissedPolicy::RunNow,
+        max_delay_secs: 60,
+    };
+    let s = Scheduler::open(store.clone());
+    let r = s.recover(&run_now).await;
+    assert_eq!(ids(&r.pending), vec![1]);
+    assert_eq!(ids(&r.due), vec![2]);
+    assert_eq!(ids(&r.missed), vec![3]);
+    // 已经处理过的不会再执行
+    let r = Scheduler::open(store.clone()).recover(&run_now).await;
+    assert_eq!((ids(&r.pending), r.due.len(), r.missed.len()), (vec![1], 0, 1));
+
+    // 默认不执行错过的任务
+    let store = new_store();
+    let s = Scheduler::open(store.clone());
+    s.schedule(Job::new(5, SKU, "late", now - 5_000, now - 5_000)).await;
+    let r = s.recover(&SchedulerConfig::default()).await;
+    assert_eq!((r.due.len(), ids(&r.missed)), (0, vec![5]));
+    assert_eq!(s.jobs().await[0].status, JobStatus::Missed);
+}
+
+#[tokio::test]
+async fn test_scheduler_prune() {
+    let store = new_store();
+    let day = 24 * 3600 * 1000;
+    let now = crate::utils::now().timestamp_millis();
+    let s = Scheduler::open(store.clone());
+    s.schedule(Job::new(1, SKU, "old cancelled", now - day - 1000, now - day - 2000)).await;
+    s.schedule(Job::new(2, SKU, "cancelled", now - 5_000, now - 5_000)).await;
+    s.schedule(Job::new(3, SKU, "old missed", now - day - 1000, now - day - 2000)).await;
+    s.schedule(Job::new(4, SKU, "future", now + 60_000, now + 60_000)).await;
+    assert!(s.cancel(1).await);
+    assert!(s.cancel(2).await);
+    // 预约时间过了 1 天的取消和错过的任务删掉, 其他的留着
+    let r = s.recover(&SchedulerConfig::default()).await;
+    assert_eq!(r.missed.len(), 1);
+    let ids: Vec<_> = s.jobs().await.iter().map(|j| j.cart_goods_id).collect();
+    assert_eq!(ids, vec![2, 4]);
+    assert_eq!(Scheduler::open(store.clone()).jobs().await.len(), 2);
+}
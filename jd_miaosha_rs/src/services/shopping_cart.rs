@@ -3,10 +3,9 @@ use std::env;
 use std::sync::Arc;
 
 use futures::{future::join_all, FutureExt, StreamExt, TryFutureExt};
-use http::{HeaderMap, StatusCode};
+use http::{HeaderMap, Method, StatusCode};
 use log::{debug, error, info, warn};
 use rand::prelude::*;
-use reqwest::Client;
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
 use serde_json::{from_slice, from_str, json, to_string, Value};
@@ -18,8 +17,13 @@ use url::form_urlencoded;
 use crate::*;
 use crate::error::{JdMiaoshaError, OpError, Result};
 use crate::models::*;
+use crate::services::api::call_api;
 use crate::services::driver::*;
+use crate::services::dry_run::{self, DRY_RUN};
+use crate::services::endpoints::endpoints;
 use crate::services::goods::*;
+use crate::services::parser::parse_yuyue_info;
+use crate::services::transport::HttpClient;
 use crate::utils::*;
 
 const READY: u8 = 1;
@@ -32,7 +36,13 @@ lazy_static! {
     static ref ACCOUNT_CART_LOCKS: Arc<Mutex<HashMap<IDType, AccountCartLockType>>> = Default::default();
     static ref ACCOUNT_CART_SKU_UUIDS: Arc<RwLock<HashMap<IDType, HashMap<String, String>>>> =
         Default::default();
+    // 本客户端的租约标识: mac地址-进程号
+    static ref CLAIM_OWNER: String = format!("{}-{}", UserInfo::get_user_mac_address(), std::process::id());
+    // 本客户端持有租约的商品: 购物车商品id -> 激活码
+    static ref CLAIMED_CART_GOODS: Arc<RwLock<HashMap<IDType, String>>> = Default::default();
 }
+// 抢购租约时长(秒), 续约间隔要比这个短
+pub const CLAIM_LEASE_SECONDS: u32 = 120;
 type AccountCartLockType = Arc<Mutex<IDType>>;
 type AccountCartBuyResultLockType = Arc<Mutex<bool>>;
 
@@ -48,28 +58,25 @@ async fn get_account_cart_lock(account_id: IDType) -> AccountCartLockType {
 pub struct ShoppingCartService;
 
 impl ShoppingCartService {
-    // pub const ADD_TO_CART_URL: &'static str = "https://cart.jd.com/gate.action?pid=100016514918&pcount=6&ptype=1";
-    pub const ADD_TO_CART_URL: &'static str = "https://cart.jd.com/gate.action?ptype=1";
-    pub const UNCHECK_ALL_CART_GOODS_URL: &'static str = "https://api.m.jd.com/api?functionId=pcCart_jc_cartUnCheckAll&appid=JDC_mall_cart&loginType=3";
     pub const COUNTS: usize = 2;
 
     pub async fn list_cart_goods(
         code: String,
         key_word: String,
         page_no: i64,
+        category: Option<u8>,
     ) -> Result<Option<ShoppingCartPageState>> {
         let addr_prefix = CONFIG.read().await.server_addr();
-        let url = format!(
+        let mut url = format!(
             "{}/api/shopping_cart?page_no={}&key_word={}",
             addr_prefix.as_str(),
             page_no,
             key_word
         );
-        let resp = reqwest::Client::new()
-            .get(url)
-            .header("token", code.as_str())
-            .send()
-            .await?;
+        if let Some(category) = category {
+            url.push_str(format!("&category={}", category).as_str());
+        }
+        let resp = call_api(Method::GET, url, code.as_str(), None).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code, resp.status().as_str());
             return Ok(None);
@@ -117,6 +124,21 @@ impl ShoppingCartService {
         Ok(Some(records))
     }
 
+    /// 按 sku 搜索购物车, 找到 id 对应的商品
+    pub async fn find_cart_goods(code: String, id: IDType, sku: String) -> Result<Option<CartProdState>> {
+        let mut page_no = 1;
+        while let Some(page) = Self::list_cart_goods(code.clone(), sku.clone(), page_no, None).await? {
+            if let Some(item) = page.records.into_iter().find(|item| item.id == id) {
+                return Ok(Some(item));
+            }
+            if page.page_no >= page.pages {
+                break;
+            }
+            page_no += 1;
+        }
+        Ok(None)
+    }
+
     pub async fn delete_cart_goods(code: String, ids: Option<Vec<IDType>>) -> Result<()> {
         let addr_prefix = CONFIG.read().await.server_addr();
         let url = format!("{}/api/shopping_cart", addr_prefix.as_str());
@@ -127,12 +149,7 @@ impl ShoppingCartService {
             // 删除全部
             body = json!({});
         }
-        let resp = reqwest::Client::new()
-            .delete(url)
-            .header("token", code.as_str())
-            .body(body.to_string())
-            .send()
-            .await?;
+        let resp = call_api(Method::DELETE, url, code.as_str(), Some(body.to_string())).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code, resp.status().as_str());
         }
@@ -143,12 +160,7 @@ impl ShoppingCartService {
         let addr_prefix = CONFIG.read().await.server_addr();
         info!("{}", body);
         let url = format!("{}/api/shopping_cart", addr_prefix.as_str());
-        let resp = reqwest::Client::new()
-            .post(url)
-            .header("token", code.as_str())
-            .body(body)
-            .send()
-            .await?;
+        let resp = call_api(Method::POST, url, code.as_str(), Some(body)).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code, resp.status().as_str());
         }
@@ -158,12 +170,7 @@ impl ShoppingCartService {
     pub async fn update_cart_goods(code: String, id: IDType, body: String) -> Result<IDType> {
         let addr_prefix = CONFIG.read().await.server_addr();
         let url = format!("{}/api/shopping_cart/{}", addr_prefix.as_str(), id);
-        let resp = reqwest::Client::new()
-            .put(url)
-            .header("token", code.as_str())
-            .body(body)
-            .send()
-            .await?;
+        let resp = call_api(Method::PUT, url, code.as_str(), Some(body)).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code, resp.status().as_str());
         }
@@ -172,12 +179,7 @@ impl ShoppingCartService {
     pub async fn update_yuyue_cart_goods(code: String, id: IDType, body: String) -> Result<IDType> {
         let addr_prefix = CONFIG.read().await.server_addr();
         let url = format!("{}/api/shopping_cart/yuyue/{}", addr_prefix.as_str(), id);
-        let resp = reqwest::Client::new()
-            .put(url)
-            .header("token", code.as_str())
-            .body(body)
-            .send()
-            .await?;
+        let resp = call_api(Method::PUT, url, code.as_str(), Some(body)).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code, resp.status().as_str());
         }
@@ -187,35 +189,83 @@ impl ShoppingCartService {
     pub async fn update_cart_goods_purchase_link(code: String, body: String) -> Result<()> {
         let addr_prefix = CONFIG.read().await.server_addr();
         let url = format!("{}/api/shopping_cart", addr_prefix.as_str());
-        let resp = reqwest::Client::new()
-            .put(url)
-            .header("token", code.as_str())
-            .body(body)
-            .send()
-            .await?;
+        let resp = call_api(Method::PUT, url, code.as_str(), Some(body)).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code, resp.status().as_str());
         }
         Ok(())
     }
 
+    pub async fn claim_cart_goods(code: &str, id: IDType) -> Result<()> {
+        // 抢购前先拿到租约, 避免多个客户端同时抢购同一个商品; 同一个客户端可以重复获取。
+        // 由调用方在分发给各个账号之前获取一次, 所有账号都结束后再释放
+        let addr_prefix = CONFIG.read().await.server_addr();
+        let url = format!("{}/api/shopping_cart/claim/{}", addr_prefix.as_str(), id);
+        let body = json!({
+            "owner": CLAIM_OWNER.as_str(),
+            "lease_seconds": CLAIM_LEASE_SECONDS,
+        });
+        let resp = call_api(Method::POST, url, code, Some(body.to_string())).await?;
+        if resp.status() != StatusCode::OK {
+            warn!("获取抢购租约失败:{}-{}", id, resp.status().as_str());
+            return Err(OpError::CartGoodsClaimed(id).into());
+        }
+        CLAIMED_CART_GOODS.write().await.insert(id, code.to_string());
+        Ok(())
+    }
+
+    pub async fn renew_cart_goods_claims() -> Result<()> {
+        let claimed = CLAIMED_CART_GOODS.read().await.clone();
+        if claimed.is_empty() {
+            return Ok(());
+        }
+        let addr_prefix = CONFIG.read().await.server_addr();
+        let body = json!({
+            "owner": CLAIM_OWNER.as_str(),
+            "lease_seconds": CLAIM_LEASE_SECONDS,
+        })
+        .to_string();
+        for (id, code) in claimed.into_iter() {
+            let url = format!("{}/api/shopping_cart/claim/{}", addr_prefix.as_str(), id);
+            let resp = call_api(Method::PUT, url, code.as_str(), Some(body.clone())).await?;
+            if resp.status() == StatusCode::CONFLICT {
+                // 租约已经丢了, 就不再续约了
+                warn!("抢购租约已失效:{}", id);
+                CLAIMED_CART_GOODS.write().await.remove(&id);
+            } else if resp.status() != StatusCode::OK {
+                error!("续约失败:{}-{}", id, resp.status().as_str());
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn release_cart_goods_claim(code: String, id: IDType) -> Result<()> {
+        if CLAIMED_CART_GOODS.write().await.remove(&id).is_none() {
+            return Ok(());
+        }
+        let addr_prefix = CONFIG.read().await.server_addr();
+        let url = format!("{}/api/shopping_cart/claim/{}", addr_prefix.as_str(), id);
+        let body = json!({ "owner": CLAIM_OWNER.as_str() });
+        let resp = call_api(Method::DELETE, url, code.as_str(), Some(body.to_string())).await?;
+        if resp.status() != StatusCode::OK {
+            error!("释放抢购租约失败:{}-{}", id, resp.status().as_str());
+        }
+        Ok(())
+    }
+
     pub async fn delete_one_cart_goods(code: String, id: IDType) -> Result<()> {
         let addr_prefix = CONFIG.read().await.server_addr();
         let url = format!("{}/api/shopping_cart/{}", addr_prefix.as_str(), id);
-        let resp = reqwest::Client::new()
-            .delete(url)
-            .header("token", code.as_str())
-            .send()
-            .await?;
+        let resp = call_api(Method::DELETE, url, code.as_str(), None).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code, resp.status().as_str());
         }
         Ok(())
     }
 
-    async fn uncheck_all_cart_goods(account_id: IDType, client: reqwest::Client) -> Result<()> {
+    async fn uncheck_all_cart_goods(account_id: IDType, client: HttpClient) -> Result<()> {
         let resp = client
-            .post(Self::UNCHECK_ALL_CART_GOODS_URL)
+            .post(endpoints().await.cart_function("pcCart_jc_cartUnCheckAll"))
             .header("authority", "api.m.jd.com")
             .header("sec-ch-ua", r##"" Not A;Brand";v="99", "Chromium";v="90", "Google Chrome";v="90""##)
             .header("accept", "application/json, text/plain, */*")
@@ -248,7 +298,7 @@ impl ShoppingCartService {
 
     async fn select_cart_good(
         account_id: IDType,
-        client: reqwest::Client,
+        client: HttpClient,
         sku: String,
         sku_uuid: String,
         area: String,
@@ -289,7 +339,7 @@ impl ShoppingCartService {
             )
             .finish();
         let resp = client
-            .post("https://api.m.jd.com/api")
+            .post(endpoints().await.cart_api())
             .header("authority", "api.m.jd.com")
             .header("accept", "application/json, text/plain, */*")
             .header("origin", "https://cart.jd.com")
@@ -312,16 +362,11 @@ impl ShoppingCartService {
 
     async fn inner_add_cart_goods(
         account_id: IDType,
-        client: reqwest::Client,
+        client: HttpClient,
         num: u32,
         sku: String,
     ) -> Result<()> {
-        // // pub const ADD_TO_CART_URL: &'static str = "https://cart.jd.com/gate.action?pid=100016514918&pcount=6&ptype=1";
-        // let url = format!("{}&pid={}&pcount={}", Self::ADD_TO_CART_URL, sku, num);
-        let purchase_url = format!(
-            "https://cart.jd.com/gate.action?pid={}&pcount={}&ptype=1",
-            &sku, num
-        );
+        let purchase_url = endpoints().await.add_to_cart(&sku, num);
         // 此接口会302的状态码，此时不需要跟踪这个重定向的url
         let resp = client
             .get(purchase_url.as_str())
@@ -351,12 +396,12 @@ impl ShoppingCartService {
     }
 
     async fn remove_cart_goods(
-        client: reqwest::Client,
+        client: HttpClient,
         sku: String,
         sku_uuid: String,
     ) -> Result<bool> {
         // 移除购物车里的商品
-        let url = "https://api.m.jd.com/api";
+        let url = endpoints().await.cart_api();
         let encoded = form_urlencoded::Serializer::new(String::new())
             .append_pair("functionId", "pcCart_jc_cartRemove")
             .append_pair("appid", "JDC_mall_cart")
@@ -409,9 +454,9 @@ impl ShoppingCartService {
         }
     }
 
-    async fn get_skuuuid_from_cart(client: reqwest::Client, sku: String) -> Result<String> {
+    async fn get_skuuuid_from_cart(client: HttpClient, sku: String) -> Result<String> {
         // 主要是拿 skuUuid ， 然后用其去从购物车删除 商品
-        let url = "https://api.m.jd.com/api?functionId=pcCart_jc_getCurrentCart&appid=JDC_mall_cart&loginType=3";
+        let url = endpoints().await.cart_function("pcCart_jc_getCurrentCart");
         let resp = client
             .post(url)
             .header("authority", "api.m.jd.com")
@@ -506,9 +551,9 @@ impl ShoppingCartService {
     ) -> Result<()> {
         // 主要是拿 skuUuid ， 然后用其去从购物车删除 商品
 
-        let url = "https://api.m.jd.com/api?functionId=pcCart_jc_getCurrentCart&appid=JDC_mall_cart&loginType=3";
+        let url = endpoints().await.cart_function("pcCart_jc_getCurrentCart");
         for (account_id, cookie) in account_cookies {
-            let client = default_client(cookie.as_str()).build()?;
+            let client = HttpClient::build(default_client(cookie.as_str()))?;
             if let Some(sku) = &sku {
                 if let Err(_) =
                     Self::inner_add_cart_goods(account_id, client.clone(), num, sku.clone()).await
@@ -517,7 +562,7 @@ impl ShoppingCartService {
                 }
             }
             let resp = client
-                .post(url)
+                .post(&url)
                 .header("authority", "api.m.jd.com")
                 .header("user-agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/90.0.4430.93 Safari/537.36")
                 .header("origin", "https://cart.jd.com")
@@ -612,11 +657,11 @@ impl ShoppingCartService {
     }
 
     async fn get_goods_info(
-        client: reqwest::Client,
+        client: HttpClient,
         sku: String,
         account_id: IDType,
     ) -> Result<Option<PInfo>> {
-        let prod_info_url = format!("https://item.m.jd.com/product/{}.html", sku.as_str());
+        let prod_info_url = endpoints().await.item_m_page(sku.as_str());
         let resp = client.get(prod_info_url).send().await?;
         let status = resp.status();
         if status == StatusCode::OK {
@@ -643,17 +688,19 @@ impl ShoppingCartService {
         account_id: IDType,
     ) -> Result<(IDType, String)> {
         // 领取优惠券
-        let client = default_client(cookie.as_str())
-            .redirect(reqwest::redirect::Policy::none())
-            .build()?;
+        let client = HttpClient::build(
+            default_client(cookie.as_str()).redirect(reqwest::redirect::Policy::none()),
+        )?;
         let info = Self::get_goods_info(client.clone(), sku.clone(), account_id).await?;
         if info.is_none() {
             warn!("不能获取商品优惠券信息:{}-{}", account_id, sku);
             return Ok((account_id, sku));
         }
         let info = info.unwrap();
+        let ep = endpoints().await;
         let list_coupons = format!(
-            "https://cd.jd.com/coupon/service?skuId={}&cat={}&venderId={}",
+            "{}?skuId={}&cat={}&venderId={}",
+            ep.coupon_service(),
             sku.as_str(),
             info.category.join(","),
             info.venderID.as_str()
@@ -661,10 +708,7 @@ impl ShoppingCartService {
         // 查找该商品的优惠券
         let resp = client
             .get(list_coupons)
-            .header(
-                "referer",
-                format!("https://item.jd.com/{}.html", sku.as_str()),
-            )
+            .header("referer", ep.item_page(sku.as_str()))
             .header("accept-language", "zh-CN,zh;q=0.9")
             .send()
             .await?;
@@ -705,7 +749,7 @@ impl ShoppingCartService {
         let mut to_be_get_coupons = vec![];
         for (name, ids) in coupons.into_iter() {
             // 在领取中心搜索 name 的 并在其中找到 batch_id 对应的商品优惠券
-            let search_url = format!("https://a.jd.com/search.html?searchText={}", &name);
+            let search_url = ep.coupon_search(&name);
             let resp = client
                 .get(&search_url)
                 .header("Referer", "https://a.jd.com/")
@@ -742,7 +786,7 @@ impl ShoppingCartService {
                             batchId.clone(),
                             name.clone(),
                             search_url.clone(),
-                            format!("https://a.jd.com/ajax/freeGetCoupon.html?key={}", key),
+                            ep.coupon_get(key),
                         ));
                     }
                 }
@@ -784,14 +828,17 @@ impl ShoppingCartService {
     }
 
     async fn summit_cart_goods(
-        client: reqwest::Client,
+        client: HttpClient,
         sku: String,
         account_id: IDType,
+        cart_goods_id: IDType,
+        dry_run: bool,
     ) -> Result<(bool, String)> {
         // 提交订单前需要生成订单信息
-        let order_url = "https://trade.jd.com/shopping/order/getOrderInfo.action";
+        let ep = endpoints().await;
+        let order_url = ep.order_info();
         let resp = client
-            .get(order_url)
+            .get(&order_url)
             .header("sec-ch-ua", r##"" Not A;Brand";v="99", "Chromium";v="90", "Google Chrome";v="90""##)
             .header("sec-ch-ua-mobile", "?0")
             .header("upgrade-insecure-requests", "1")
@@ -811,7 +858,7 @@ impl ShoppingCartService {
             error!("get order.info error, order_url:{}, {}", order_url, status);
             return Err(OpError::CreateOrderInfo.into());
         }
-        let url = "https://trade.jd.com/shopping/order/submitOrder.action?&presaleStockSign=1";
+        let url = format!("{}?&presaleStockSign=1", ep.submit_order());
         let encoded = form_urlencoded::Serializer::new(String::new())
             .append_pair("overseaPurchaseCookies", "")
             .append_pair("submitOrderParam.btSupport", "1")
@@ -835,12 +882,13 @@ impl ShoppingCartService {
             .header("user-agent", get_useragent())
             .header("Content-Type", "application/x-www-form-urlencoded")
             .header("accept", "application/json, text/javascript, */*; q=0.01")
-            .header(
-                "referer",
-                "https://trade.jd.com/shopping/order/getOrderInfo.action",
-            )
+            .header("referer", order_url.as_str())
             .header("X-Requested-With", "XMLHttpRequest")
             .body(encoded);
+        if dry_run {
+            dry_run::record(account_id, cart_goods_id, &sku, req_builder.as_request()).await;
+            return Ok((true, DRY_RUN.to_string()));
+        }
         let resp = req_builder.send().await?;
         if resp.status() != StatusCode::OK {
             error!("url:{}, {}", url, resp.status().as_str());
@@ -882,6 +930,7 @@ impl ShoppingCartService {
         area: String,
         is_add_to_cart: bool,
         _workers: usize,
+        dry_run: bool,
     ) -> Result<(IDType, u32, &'static str)> {
         let lock = get_account_cart_lock(account_id).await;
         let buy_success = Arc::new(Mutex::new(false));
@@ -903,26 +952,36 @@ impl ShoppingCartService {
                     is_add_to_cart,
                     _lock,
                     _buy_success,
+                    dry_run,
                 )
                     .await
                 {
-                    Ok((_, _, s)) => s == "success",
+                    Ok((_, _, s)) => s,
                     Err(e) => {
                         error!(
                             "购买商品失败:{}-{}-{}:{:?}",
                             account_id, cart_goods_id, _sku, e
                         );
-                        false
+                        "fail"
                     }
                 }
             });
         }
-        let success = futures::future::join_all(handles).map(|r| r.contains(&true));
-        Ok((
-            cart_goods_id,
-            num,
-            if success.await { "success" } else { "fail" },
-        ))
+        let status = futures::future::join_all(handles).await;
+        Ok((cart_goods_id, num, Self::merge_status(status.as_slice())))
+    }
+
+    /// 多个并发下单的结果: 有一个成功就是成功, 试运行的就是试运行, 都没成功才是失败
+    pub fn merge_status(status: &[&'static str]) -> &'static str {
+        if status.contains(&"success") {
+            "success"
+        } else if status.contains(&DRY_RUN) {
+            DRY_RUN
+        } else if status.contains(&"yuyueing") {
+            "yuyueing"
+        } else {
+            "fail"
+        }
     }
 
     async fn submit_order(
@@ -935,6 +994,7 @@ impl ShoppingCartService {
         is_add_to_cart: bool,
         user_cart_lock: AccountCartLockType,
         buy_success: AccountCartBuyResultLockType,
+        dry_run: bool,
     ) -> Result<(IDType, u32, &'static str)> {
         // 用户购物车排它锁
         let mut _g = user_cart_lock.lock().await;
@@ -944,15 +1004,16 @@ impl ShoppingCartService {
                 "已经成功购买商品，不用重复购买了:{}-{}-{}-{}!",
                 account_id, cart_goods_id, sku, num
             );
-            return Ok((cart_goods_id, num, "success"));
+            return Ok((cart_goods_id, num, if dry_run { DRY_RUN } else { "success" }));
         }
         info!("开始购买商品:{}-{}-{}", account_id, cart_goods_id, sku);
         let mut success = false;
 
-        let client = proxy_client_builder(account_id, &sku, cookie.clone())
-            .await
-            .redirect(reqwest::redirect::Policy::none())
-            .build()?;
+        let client = HttpClient::build(
+            proxy_client_builder(account_id, &sku, cookie.clone())
+                .await
+                .redirect(reqwest::redirect::Policy::none()),
+        )?;
         // 避免重复查询 skuUuid
         let mut skuUuid = Self::get_prod_sku_uuid(account_id, &sku).await;
         let mut cond = false;
@@ -991,7 +1052,7 @@ impl ShoppingCartService {
                 }
                 cond = true;
             }
-            match Self::summit_cart_goods(client.clone(), sku.clone(), account_id).await {
+            match Self::summit_cart_goods(client.clone(), sku.clone(), account_id, cart_goods_id, dry_run).await {
                 Ok((suc, msg)) => {
                     info!(
                         "summit_cart_goods_result:{}-{}-{}-{}",
@@ -1030,7 +1091,9 @@ impl ShoppingCartService {
             }
         }
         *guard = success;
-        if success {
+        if success && dry_run {
+            Ok((cart_goods_id, num, DRY_RUN))
+        } else if success {
             Ok((cart_goods_id, num, "success"))
         } else {
             Ok((cart_goods_id, num, "fail"))
@@ -1039,14 +1102,14 @@ impl ShoppingCartService {
 
     async fn gen_seckill_order_data(
         account_id: IDType,
-        client: reqwest::Client,
+        client: HttpClient,
         sku: String,
         eid: String,
         fp: String,
         num: u32,
     ) -> Result<Value> {
         let resp = client
-            .post("https://marathon.jd.com/seckillnew/orderService/pc/init.action")
+            .post(endpoints().await.seckill_init())
             .header("Host", "marathon.jd.com")
             .form(&json!({
                 "sku":sku.as_str(),
@@ -1114,13 +1177,13 @@ impl ShoppingCartService {
     }
     async fn request_seckill_url(
         account_id: IDType,
-        client: reqwest::Client,
+        client: HttpClient,
         purchase_url: String,
         sku: String,
     ) -> Result<Option<()>> {
         let resp = client
             .get(purchase_url.as_str())
-            .header("Referer", format!("https://item.jd.com/{}.html", sku))
+            .header("Referer", endpoints().await.item_page(&sku))
             .send()
             .await?;
         let status = resp.status();
@@ -1150,6 +1213,7 @@ impl ShoppingCartService {
         sku: String,
         eid: String,
         fp: String,
+        dry_run: bool,
         // purchase_url: String,
     ) -> Result<(IDType, u32, &'static str)> {
         // 抢购商品的下单流程与普通商品不同，不支持加入购物车，可能需要提前预约，主要执行流程如下：
@@ -1163,10 +1227,11 @@ impl ShoppingCartService {
                 "账号[id-{}],第{}/{}次请求抢购:{}",
                 account_id, i, total, sku
             );
-            let client = proxy_client_builder(account_id, &sku, cookie.clone())
-                .await
-                .redirect(reqwest::redirect::Policy::none())
-                .build()?;
+            let client = HttpClient::build(
+                proxy_client_builder(account_id, &sku, cookie.clone())
+                    .await
+                    .redirect(reqwest::redirect::Policy::none()),
+            )?;
             let purchase_url =
                 GoodsService::get_goods_seckill_link2(sku.clone(), client.clone()).await?;
             if let Ok(Some(())) =
@@ -1182,18 +1247,19 @@ impl ShoppingCartService {
                     1,
                 )
                 .await?;
-                let url = format!(
-                "https://marathon.jd.com/seckillnew/orderService/pc/submitOrder.action?skuId={}",
-                sku.as_str()
-            );
+                let ep = endpoints().await;
+                let url = ep.seckill_submit(sku.as_str());
 
-                match client.post(url.clone())
+                let req_builder = client.post(url.clone())
                     .header("Host", "marathon.jd.com")
-                    .header("Referer", format!("https://marathon.jd.com/seckill/seckill.action?skuId={0}&num={1}&rid={2}",
-                                               sku.as_str(), 1, PKLocal::now().timestamp()))
-                    .form(&body)
-                    .send()
-                    .await{
+                    .header("Referer", format!("{0}?skuId={1}&num={2}&rid={3}",
+                                               ep.seckill_page(), sku.as_str(), 1, PKLocal::now().timestamp()))
+                    .form(&body);
+                if dry_run {
+                    dry_run::record(account_id, cart_goods_id, &sku, req_builder.as_request()).await;
+                    return Ok((cart_goods_id, 1, DRY_RUN));
+                }
+                match req_builder.send().await{
                     Ok(resp) =>{
                         let status = resp.status();
                         let txt = resp.text().await.unwrap_or_default();
@@ -1241,60 +1307,25 @@ impl ShoppingCartService {
     pub async fn get_yuyue_goods_infos(
         cart_goods_id: IDType,
         sku: String,
-        client: Option<Client>,
+        client: Option<HttpClient>,
     ) -> Result<YuyueInfo> {
         // 获取预约商品相关的时间: 预约开始时间， 预约结束时间
-        let url = format!("https://item.m.jd.com/product/{}.html", sku.as_str());
+        let url = endpoints().await.item_m_page(sku.as_str());
         let _client = if let Some(client_) = client {
             client_
         } else {
-            default_client("").build()?
+            HttpClient::build(default_client(""))?
         };
         let resp = _client.get(url.as_str()).send().await?;
         let status = resp.status();
         let txt = resp.text().await?;
-        let mut info = YuyueInfo::default();
-        info.cart_goods_id = cart_goods_id;
-        if status == StatusCode::OK {
-            let yuyue = r##""yuyue":"##;
-            if let Some(idx) = txt.find(yuyue) {
-                let start_idx = idx + yuyue.len();
-                let txt = &txt.as_str()[start_idx..];
-                if let Some(end_idx) = txt.find('}') {
-                    // 在商品信息中找以下到的数据结构
-                    // "yuyue":{"type":"1","num":6948,"d":218782,"category":"4","stime":"2021-05-19 00:21:12","etime":"2021-05-23 09:54:59","state":2,"sku":100010104457,"isJ":0,"url":"//yushou.jd.com/toYuyue.action?sku=100010104457&key=d1df2da18dc20f13c30d6ce237137bb8","info":"预约进行中","flag":false,"insertTime":1621516116,"yueStime":"2021-05-19 00:21:12","yueEtime":"2021-05-23 09:54:59","qiangStime":"","qiangEtime":"","plusStime":"","plusEtime":"","plusType":0,"plusD":218782,"isBefore":0,"riskCheck":"","hasAddress":false,"address":"","hidePrice":0,"showPromoPrice":"0","sellWhilePresell":"0"},
-                    let sku_json_content = &txt[..=end_idx];
-                    info!("{},<{}-{}>, {}", sku, start_idx, end_idx, sku_json_content);
-                    let jsonv: Value = from_str(sku_json_content)?;
-                    let mut stime = jsonv["yueStime"].as_str().unwrap_or_default();
-                    let mut etime = jsonv["yueEtime"].as_str().unwrap_or_default();
-                    if stime.is_empty() {
-                        stime = jsonv["stime"].as_str().unwrap_or_default();
-                    }
-                    if etime.is_empty() {
-                        etime = jsonv["etime"].as_str().unwrap_or_default();
-                    }
-                    let yuyue_start_dt = parse_datetime(stime);
-                    let yuyue_end_dt = parse_datetime(etime);
-                    let mut yuyue_url = jsonv["url"].as_str().unwrap_or_default().to_string();
-                    if yuyue_url.starts_with("//") {
-                        yuyue_url = format!("https:{}", yuyue_url)
-                    }
-                    let qiang_start_dt =
-                        parse_datetime(jsonv["qiangStime"].as_str().unwrap_or_default());
-                    info.yuyue_url = yuyue_url;
-                    info.yuyue_start_dt = yuyue_start_dt;
-                    info.yuyue_end_dt = yuyue_end_dt;
-                    info.qiang_start_dt = qiang_start_dt;
-                    return Ok(info);
-                } else {
-                    warn!("{}: Not found }}", sku);
-                }
-            }
-            warn!("{}:Not found {}", sku, yuyue,);
+        if status != StatusCode::OK {
+            warn!("获取预约商品[{}]信息失败:{}, {}", sku, status, txt);
         }
-        warn!("获取预约商品[{}]信息失败:{}, {}", sku, status, txt);
-        return Ok(info);
+        let mut info = parse_yuyue_info(txt.as_str())?;
+        info!("{}, yuyue:{}", sku, to_string(&info).unwrap_or_default());
+        info.cart_goods_id = cart_goods_id;
+        Ok(info)
     }
 
     pub async fn submit_yuyue_order(
@@ -1305,12 +1336,14 @@ impl ShoppingCartService {
         num: u32,
         in_yuyue: bool,
         area: String,
+        dry_run: bool,
     ) -> Result<(IDType, u32, &'static str)> {
         // 预约订单流程： 先预约， 然后再购买（或者抢购）
+        // 预约不花钱, 试运行时也照常预约, 只在最后购买时不提交订单
         if in_yuyue {
-            let client = default_client(cookie.as_str())
-                .redirect(reqwest::redirect::Policy::none())
-                .build()?;
+            let client = HttpClient::build(
+                default_client(cookie.as_str()).redirect(reqwest::redirect::Policy::none()),
+            )?;
             let info =
                 Self::get_yuyue_goods_infos(cart_goods_id, sku.clone(), Some(client.clone()))
                     .await?;
@@ -1347,28 +1380,21 @@ impl ShoppingCartService {
                         _sku,
                         "".to_string(),
                         "".to_string(),
+                        dry_run,
                     )
                     .await
                     {
-                        Ok((id, num, status)) => status == "success",
+                        Ok((_, _, status)) => status,
                         Err(e) => {
                             error!("{}-{}:{:?}", account_id, cart_goods_id, e);
-                            false
+                            "fail"
                         }
                     }
                 });
             }
-            let success = join_all(handles)
-                .map(|r| {
-                    // 有一个成功那就是购买成功了
-                    r.into_iter().filter(|i| *i).count() > 0
-                })
-                .await;
-            if success {
-                Ok((cart_goods_id, num, "success"))
-            } else {
-                Ok((cart_goods_id, num, "fail"))
-            }
+            // 有一个成功那就是购买成功了
+            let status = join_all(handles).await;
+            Ok((cart_goods_id, num, Self::merge_status(status.as_slice())))
         } else if purchase_type.eq(PRESALE) {
             info!(
                 "预约预售商品{}-{}-{}-{}:{}",
@@ -1387,25 +1413,20 @@ impl ShoppingCartService {
                         "".to_string(),
                         "".to_string(),
                         num,
+                        dry_run,
                     )
                     .await
                     {
-                        Ok((id, num, status)) => status == "success",
+                        Ok((_, _, status)) => status,
                         Err(e) => {
                             error!("{}-{}:{:?}", account_id, cart_goods_id, e);
-                            false
+                            "fail"
                         }
                     }
                 });
             }
-            let success = join_all(handles)
-                .map(|r| r.into_iter().filter(|i| *i).count() > 0)
-                .await;
-            if success {
-                Ok((cart_goods_id, num, "success"))
-            } else {
-                Ok((cart_goods_id, num, "fail"))
-            }
+            let status = join_all(handles).await;
+            Ok((cart_goods_id, num, Self::merge_status(status.as_slice())))
         } else {
             info!(
                 "预约普通商品的购买:{}-{}-{}-{}:{}",
@@ -1421,6 +1442,7 @@ impl ShoppingCartService {
                 area,
                 false,
                 workers(),
+                dry_run,
             )
                 .await
         }
@@ -1432,6 +1454,7 @@ impl ShoppingCartService {
         cart_goods_id: IDType,
         sku: String,
         purchase_url: String,
+        dry_run: bool,
     ) -> Result<Option<(IDType, u32, &'static str)>> {
         let mut i = 0;
         info!("driver initing....");
@@ -1446,8 +1469,13 @@ impl ShoppingCartService {
             while i < 10 {
                 match driver.find_element(By::Css("button.checkout-submit")).await {
                     Ok(submit) => {
-                        submit.click().await?;
-                        info!("下单成功:{}-{}", account_id, sku.as_str());
+                        if dry_run {
+                            let page_url = driver.current_url().await.unwrap_or_default();
+                            dry_run::record_click(account_id, cart_goods_id, &sku, page_url, "button.checkout-submit").await;
+                        } else {
+                            submit.click().await?;
+                            info!("下单成功:{}-{}", account_id, sku.as_str());
+                        }
                         success = true;
                         break;
                     }
@@ -1473,7 +1501,9 @@ impl ShoppingCartService {
             }
             driver.quit().await?;
         }
-        if success {
+        if success && dry_run {
+            Ok(Some((cart_goods_id, 1, DRY_RUN)))
+        } else if success {
             Ok(Some((cart_goods_id, 1, "success")))
         } else {
             Ok(Some((cart_goods_id, 1, "fail")))
@@ -1488,17 +1518,15 @@ impl ShoppingCartService {
         eid: String,
         fp: String,
         num: u32,
+        dry_run: bool,
     ) -> Result<(IDType, u32, &'static str)> {
         let mut success = false;
-        let url = format!(
-            "https://cart.jd.com/cart/dynamic/gateForSubFlow.action?wids={}&nums={}&subType=32",
-            sku.as_str(),
-            num,
-        );
-        let client = proxy_client_builder(account_id, &sku, cookie.clone())
-            .await
-            .redirect(reqwest::redirect::Policy::none())
-            .build()?;
+        let url = endpoints().await.presale_gate(sku.as_str(), num);
+        let client = HttpClient::build(
+            proxy_client_builder(account_id, &sku, cookie.clone())
+                .await
+                .redirect(reqwest::redirect::Policy::none()),
+        )?;
         let total = Self::COUNTS;
         for i in 1..=total {
             info!("第{}/{}次购买预售商品:{}-{}", i, total, account_id, sku);
@@ -1510,7 +1538,7 @@ impl ShoppingCartService {
                     let resp = client.get(order_info_url).send().await?;
                     let status = resp.status();
                     if status == StatusCode::FOUND || status == StatusCode::OK {
-                        let url = "https://trade.jd.com/shopping/order/submitOrder.action?";
+                        let url = format!("{}?", endpoints().await.submit_order());
                         let encoded = form_urlencoded::Serializer::new(String::new())
                             .append_pair("overseaPurchaseCookies", "")
                             .append_pair("vendorRemarks", "[]")
@@ -1527,7 +1555,7 @@ impl ShoppingCartService {
                             .append_pair("submitOrderParam.fp", fp.as_str())
                             .finish();
                         let req_builder = client
-                            .post(url)
+                            .post(&url)
                             .header(
                                 "sec-ch-ua",
                                 r#""Google Chrome";v="89", "Chromium";v="89", ";secNot A Brand";v="99""#,
@@ -1541,6 +1569,11 @@ impl ShoppingCartService {
                             .header("referer", order_info_url)
                             .header("X-Requested-With", "XMLHttpRequest")
                             .body(encoded);
+                        if dry_run {
+                            dry_run::record(account_id, cart_goods_id, &sku, req_builder.as_request()).await;
+                            success = true;
+                            break;
+                        }
                         let resp = req_builder.send().await?;
                         if resp.status() != StatusCode::OK {
                             error!("url:{}, {}", url, resp.status().as_str());
@@ -1583,7 +1616,9 @@ impl ShoppingCartService {
             }
             sleep(500).await;
         }
-        if success {
+        if success && dry_run {
+            Ok((cart_goods_id, num, DRY_RUN))
+        } else if success {
             Ok((cart_goods_id, num, "success"))
         } else {
             Ok((cart_goods_id, num, "fail"))
@@ -1598,9 +1633,10 @@ impl ShoppingCartService {
         fp: String,
         sku: String,
         num: u32,
+        dry_run: bool,
     ) -> Result<(IDType, u32, &'static str)> {
         let mut driver = init_miaosha_driver(false).await?;
-        let url = format!("https://item.jd.com/{}.html", sku.as_str());
+        let url = endpoints().await.item_page(sku.as_str());
         let url = build_driver_url(url, cookie.as_str());
         info!("111:{}", url);
         driver.get(url.as_str()).await?;
@@ -1625,7 +1661,12 @@ impl ShoppingCartService {
                                     .await
                                 {
                                     Ok(submit_button) => {
-                                        submit_button.click().await?;
+                                        if dry_run {
+                                            let page_url = driver.current_url().await.unwrap_or_default();
+                                            dry_run::record_click(account_id, cart_goods_id, &sku, page_url, "#enterPriseUserPaymentSubmit").await;
+                                        } else {
+                                            submit_button.click().await?;
+                                        }
                                         success = true;
                                         break;
                                     }
@@ -1669,10 +1710,15 @@ impl ShoppingCartService {
             }
         }
         driver.quit().await?;
-        if success {
+        if success && dry_run {
+            Ok((cart_goods_id, num, DRY_RUN))
+        } else if success {
             Ok((cart_goods_id, num, "success"))
         } else {
             Ok((cart_goods_id, num, "fail"))
         }
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,216 @@
+use http::StatusCode;
+
+use crate::IDType;
+use crate::error::{JdMiaoshaError, OpError};
+use crate::services::dry_run::DRY_RUN;
+use crate::services::shopping_cart::ShoppingCartService;
+use crate::services::transport::{HttpResponse, JdScenario};
+use crate::testing::{AREA, SKU, cookie, install};
+
+async fn submit_order(account_id: IDType, cart_goods_id: IDType) -> (IDType, u32, &'static str) {
+    ShoppingCartService::submit_order_wrapper(
+        account_id,
+        cookie(),
+        cart_goods_id,
+        SKU.to_string(),
+        1,
+        AREA.to_string(),
+        true,
+        1,
+        false,
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_submit_order_success() {
+    let g = install(JdScenario::Success).await;
+    assert_eq!(submit_order(1001, 1).await, (1, 1, "success"));
+    // 抢购租约由调用方在分发之前获取
+    assert_eq!(g.mock.hits("/api/shopping_cart/claim/1"), 0);
+    assert_eq!(g.mock.hits("cart.jd.com/gate.action"), 1);
+    assert_eq!(g.mock.hits("getOrderInfo.action"), 1);
+    assert_eq!(g.mock.hits("submitOrder.action"), 1);
+}
+
+#[tokio::test]
+async fn test_submit_order_out_of_stock() {
+    let g = install(JdScenario::OutOfStock).await;
+    assert_eq!(submit_order(1002, 2).await, (2, 1, "fail"));
+    // 无货不用重试
+    assert_eq!(g.mock.hits("submitOrder.action"), 1);
+}
+
+#[tokio::test]
+async fn test_submit_order_failure_retries() {
+    let g = install(JdScenario::SubmitFailure).await;
+    assert_eq!(submit_order(1003, 3).await, (3, 1, "fail"));
+    assert_eq!(
+        g.mock.hits("submitOrder.action"),
+        ShoppingCartService::COUNTS
+    );
+}
+
+#[tokio::test]
+async fn test_submit_order_expired_cookie() {
+    let g = install(JdScenario::ExpiredCookie).await;
+    assert_eq!(submit_order(1004, 4).await, (4, 1, "fail"));
+    assert_eq!(
+        g.mock.hits("pcCart_jc_cartUnCheckAll"),
+        ShoppingCartService::COUNTS
+    );
+    assert_eq!(g.mock.hits("submitOrder.action"), 0);
+}
+
+#[tokio::test]
+async fn test_submit_order_claimed_by_other_client() {
+    let g = install(JdScenario::Success).await;
+    g.mock.replace(
+        "/api/shopping_cart/claim/",
+        HttpResponse::new(StatusCode::CONFLICT, ""),
+    );
+    let r = ShoppingCartService::claim_cart_goods("code", 5).await;
+    assert!(matches!(
+        r,
+        Err(JdMiaoshaError::OpError(OpError::CartGoodsClaimed(5)))
+    ));
+    assert_eq!(g.mock.hits("/api/shopping_cart/claim/5"), 1);
+    // 没拿到租约的不用续约, 也不用释放
+    ShoppingCartService::renew_cart_goods_claims().await.unwrap();
+    ShoppingCartService::release_cart_goods_claim("code".to_string(), 5).await.unwrap();
+    assert_eq!(g.mock.hits("/api/shopping_cart/claim/5"), 1);
+}
+
+#[tokio::test]
+async fn test_submit_yuyue_order() {
+    let g = install(JdScenario::Success).await;
+    let r = ShoppingCartService::submit_yuyue_order(
+        1006,
+        cookie(),
+        6,
+        SKU.to_string(),
+        1,
+        true,
+        AREA.to_string(),
+        false,
+    )
+    .await
+    .unwrap();
+    assert_eq!(r, (6, 1, "yuyueing"));
+    assert_eq!(g.mock.hits("yushou.jd.com/toYuyue.action?sku=100012043978"), 1);
+
+    // 预约结束后, 普通商品按加入购物车再下单的流程购买
+    let r = ShoppingCartService::submit_yuyue_order(
+        1006,
+        cookie(),
+        6,
+        SKU.to_string(),
+        1,
+        false,
+        AREA.to_string(),
+        false,
+    )
+    .await
+    .unwrap();
+    assert_eq!(r, (6, 1, "success"));
+    assert_eq!(g.mock.hits("submitOrder.action"), 1);
+}
+
+#[tokio::test]
+async fn test_submit_yuyue_order_expired_cookie() {
+    let _g = install(JdScenario::ExpiredCookie).await;
+    let r = ShoppingCartService::submit_yuyue_order(
+        1007,
+        cookie(),
+        7,
+        SKU.to_string(),
+        1,
+        true,
+        AREA.to_string(),
+        false,
+    )
+    .await
+    .unwrap();
+    assert_eq!(r, (7, 1, "fail"));
+}
+
+#[tokio::test]
+async fn test_submit_presale_order() {
+    let g = install(JdScenario::Success).await;
+    let r = ShoppingCartService::submit_presale_order(
+        1008,
+        cookie(),
+        8,
+        SKU.to_string(),
+        "".to_string(),
+        "".to_string(),
+        2,
+        false,
+    )
+    .await
+    .unwrap();
+    assert_eq!(r, (8, 2, "success"));
+    let submit = g
+        .mock
+        .requests()
+        .into_iter()
+        .find(|r| r.url.contains("submitOrder.action"))
+        .unwrap();
+    assert!(submit.body.unwrap_or_default().contains("flowType=15"));
+}
+
+#[tokio::test]
+async fn test_submit_presale_order_failure() {
+    let g = install(JdScenario::SubmitFailure).await;
+    let r = ShoppingCartService::submit_presale_order(
+        1009,
+        cookie(),
+        9,
+        SKU.to_string(),
+        "".to_string(),
+        "".to_string(),
+        1,
+        false,
+    )
+    .await
+    .unwrap();
+    assert_eq!(r, (9, 1, "fail"));
+    assert_eq!(
+        g.mock.hits("submitOrder.action"),
+        ShoppingCartService::COUNTS
+    );
+}
+
+#[tokio::test]
+async fn test_submit_seckill_order() {
+    let g = install(JdScenario::Success).await;
+    let r = ShoppingCartService::submit_seckill_order(
+        1010,
+        cookie(),
+        10,
+        SKU.to_string(),
+        "".to_string(),
+        "".to_string(),
+        false,
+    )
+    .await
+    .unwrap();
+    assert_eq!(r, (10, 1, "success"));
+    assert_eq!(g.mock.hits("marathon.jd.com/captcha.html"), 1);
+    let submit = g
+        .mock
+        .requests()
+        .into_iter()
+        .find(|r| r.url.contains("seckillnew/orderService/pc/submitOrder.action"))
+        .unwrap();
+    assert!(submit.body.unwrap_or_default().contains("skuId=100012043978"));
+}
+
+#[test]
+fn test_merge_status() {
+    assert_eq!(ShoppingCartService::merge_status(&["fail", "success", DRY_RUN]), "success");
+    assert_eq!(ShoppingCartService::merge_status(&["fail", DRY_RUN]), DRY_RUN);
+    assert_eq!(ShoppingCartService::merge_status(&["fail", "fail"]), "fail");
+    assert_eq!(ShoppingCartService::merge_status(&[]), "fail");
+}
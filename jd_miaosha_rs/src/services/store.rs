@@ -0,0 +1,745 @@
+//! 本地数据库 db/jd_miaosha.db(sqlite): 账号缓存、激活信息、地区、抢购流水、价格历史、到货提醒、预算、定时任务和设置页的设置。
+//! 每次写都在一个事务里, 写一半崩溃不会把数据弄坏; 第一次打开时导入以前的 db/*.json 等文件
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use log::{error, info, warn};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::models::{Area, AreaTree, ProdPlatform, StockStatus, UserInfo, UserState};
+use crate::services::budget::{BlockedSubmit, DaySpend};
+use crate::services::price_history::{Observation, WatchedSku};
+use crate::services::scheduler::Job;
+use crate::services::watch::WatchItem;
+use crate::settings::SettingsLayer;
+use crate::utils::{datetime_fmt, now};
+use crate::{IDType, HOME};
+
+// 按顺序执行, 执行到第几个记在 PRAGMA user_version 里; 只能往后加, 不能改已有的
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE accounts (
+        account TEXT PRIMARY KEY,
+        data TEXT NOT NULL,
+        update_dt TEXT NOT NULL
+    );
+    CREATE TABLE activation (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        data TEXT NOT NULL,
+        update_dt TEXT NOT NULL
+    );
+    CREATE TABLE areas (
+        id INTEGER NOT NULL,
+        parent_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        update_dt TEXT NOT NULL,
+        PRIMARY KEY (parent_id, id)
+    );
+    CREATE TABLE purchase_journal (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        create_dt TEXT NOT NULL,
+        account TEXT NOT NULL,
+        cart_goods_id INTEGER NOT NULL,
+        sku TEXT NOT NULL,
+        num INTEGER NOT NULL,
+        price TEXT NOT NULL,
+        status TEXT NOT NULL
+    );
+    CREATE INDEX purchase_journal_account ON purchase_journal (account, create_dt);
+    CREATE TABLE settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL,
+        update_dt TEXT NOT NULL
+    );",
+    // 价格和库存的历史, 只查了价格或者只查了库存时另一个是 NULL
+    "CREATE TABLE price_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        observe_dt TEXT NOT NULL,
+        sku TEXT NOT NULL,
+        name TEXT NOT NULL,
+        area TEXT NOT NULL,
+        price REAL,
+        stock TEXT
+    );
+    CREATE INDEX price_history_sku ON price_history (sku, observe_dt);",
+    "CREATE TABLE watch_items (
+        sku TEXT NOT NULL,
+        area TEXT NOT NULL,
+        area_name TEXT NOT NULL,
+        name TEXT NOT NULL,
+        target_price REAL,
+        create_dt TEXT NOT NULL,
+        PRIMARY KEY (sku, area)
+    );",
+    "ALTER TABLE watch_items ADD COLUMN platform INTEGER NOT NULL DEFAULT 0;",
+    // 预算统计的每个账号每天的花费
+    "CREATE TABLE budget_spend (
+        date TEXT NOT NULL,
+        account TEXT NOT NULL,
+        data TEXT NOT NULL,
+        update_dt TEXT NOT NULL,
+        PRIMARY KEY (date, account)
+    );",
+    // 定时购买的任务, data 是 Job 的 json
+    "CREATE TABLE scheduled_jobs (
+        cart_goods_id INTEGER PRIMARY KEY,
+        data TEXT NOT NULL,
+        update_dt TEXT NOT NULL
+    );",
+    // 被预算拦下的下单, data 是 BlockedSubmit 的 json
+    "CREATE TABLE budget_blocked (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        create_dt TEXT NOT NULL,
+        data TEXT NOT NULL
+    );",
+];
+
+// settings 表里的 key
+pub const AREA_KEY: &'static str = "area";
+// 地区树最近一次完整刷新的时间
+pub const AREA_VERSION_KEY: &'static str = "area_version";
+pub const SETTINGS_KEY: &'static str = "settings";
+const IMPORTED_KEY: &'static str = "legacy_imported";
+
+/// 一次下单的结果
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct JournalEntry {
+    pub id: i64,
+    pub create_dt: String,
+    pub account: String,
+    pub cart_goods_id: IDType,
+    pub sku: String,
+    pub num: u32,
+    pub price: String,
+    pub status: String,
+}
+
+/// 导入了哪些旧文件
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Imported {
+    pub accounts: usize,
+    pub activation: bool,
+    pub area: bool,
+    pub settings: bool,
+}
+
+fn now_str() -> String {
+    datetime_fmt(now())
+}
+
+fn set_setting_tx(tx: &Transaction, key: &str, value: &str) -> Result<()> {
+    tx.execute(
+        "INSERT INTO settings (key, value, update_dt) VALUES (?1, ?2, ?3)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, update_dt = excluded.update_dt",
+        params![key, value, now_str()],
+    )?;
+    Ok(())
+}
+
+fn store_accounts_tx(tx: &Transaction, accounts: &[UserState]) -> Result<()> {
+    tx.execute("DELETE FROM accounts", params![])?;
+    let update_dt = now_str();
+    for a in accounts {
+        tx.execute(
+            "INSERT OR REPLACE INTO accounts (account, data, update_dt) VALUES (?1, ?2, ?3)",
+            params![a.account, serde_json::to_string(a)?, update_dt],
+        )?;
+    }
+    Ok(())
+}
+
+fn store_jobs_tx(tx: &Transaction, jobs: &[Job]) -> Result<()> {
+    tx.execute("DELETE FROM scheduled_jobs", params![])?;
+    let update_dt = now_str();
+    for j in jobs {
+        tx.execute(
+            "INSERT OR REPLACE INTO scheduled_jobs (cart_goods_id, data, update_dt) VALUES (?1, ?2, ?3)",
+            params![j.cart_goods_id, serde_json::to_string(j)?, update_dt],
+        )?;
+    }
+    Ok(())
+}
+
+fn store_activation_tx(tx: &Transaction, data: &str) -> Result<()> {
+    tx.execute(
+        "INSERT OR REPLACE INTO activation (id, data, update_dt) VALUES (1, ?1, ?2)",
+        params![data, now_str()],
+    )?;
+    Ok(())
+}
+
+// 读旧文件, 不存在或者是空的返回 None
+fn read_legacy(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok().filter(|s| !s.trim().is_empty())
+}
+
+pub struct Store {
+    conn: Mutex<Connection>,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+        Self::init(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        let store = Self {
+            conn: Mutex::new(conn),
+        };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    fn with_tx<T, F: FnOnce(&Transaction) -> Result<T>>(&self, f: F) -> Result<T> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let ret = f(&tx)?;
+        tx.commit()?;
+        Ok(ret)
+    }
+
+    fn with_conn<T, F: FnOnce(&Connection) -> Result<T>>(&self, f: F) -> Result<T> {
+        f(&self.conn.lock().unwrap())
+    }
+
+    /// 已经执行到第几个 migration
+    pub fn version(&self) -> Result<usize> {
+        self.with_conn(|conn| {
+            let v: i64 = conn.query_row("PRAGMA user_version", params![], |row| row.get(0))?;
+            Ok(v as usize)
+        })
+    }
+
+    // 每个 migration 和版本号在同一个事务里, 要么都成功要么都不变
+    fn migrate(&self) -> Result<()> {
+        let from = self.version()?;
+        for (idx, sql) in MIGRATIONS.iter().enumerate().skip(from) {
+            self.with_tx(|tx| {
+                tx.execute_batch(sql)?;
+                tx.execute_batch(&format!("PRAGMA user_version = {}", idx + 1))?;
+                Ok(())
+            })?;
+            info!("数据库升级到版本:{}", idx + 1);
+        }
+        Ok(())
+    }
+
+    /// 用最新的账号列表整个替换缓存
+    pub fn store_accounts(&self, accounts: &[UserState]) -> Result<()> {
+        self.with_tx(|tx| store_accounts_tx(tx, accounts))
+    }
+
+    pub fn load_accounts(&self) -> Result<Vec<UserState>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT data FROM accounts ORDER BY account")?;
+            let rows = stmt.query_map(params![], |row| row.get::<_, String>(0))?;
+            let mut ret = vec![];
+            for data in rows {
+                ret.push(serde_json::from_str(data?.as_str())?);
+            }
+            Ok(ret)
+        })
+    }
+
+    /// 激活信息, 是 UserInfo 的 json
+    pub fn store_activation(&self, data: &str) -> Result<()> {
+        self.with_tx(|tx| store_activation_tx(tx, data))
+    }
+
+    pub fn load_activation(&self) -> Result<Option<String>> {
+        self.with_conn(|conn| {
+            Ok(conn
+                .query_row("SELECT data FROM activation WHERE id = 1", params![], |row| row.get(0))
+                .optional()?)
+        })
+    }
+
+    /// 某个地区下面的子地区, 整个替换
+    pub fn store_areas(&self, parent_id: i64, areas: &Area) -> Result<()> {
+        self.with_tx(|tx| {
+            tx.execute("DELETE FROM areas WHERE parent_id = ?1", params![parent_id])?;
+            let update_dt = now_str();
+            for (id, name) in areas {
+                tx.execute(
+                    "INSERT INTO areas (id, parent_id, name, update_dt) VALUES (?1, ?2, ?3, ?4)",
+                    params![id, parent_id, name, update_dt],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn load_areas(&self, parent_id: i64) -> Result<Area> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT id, name FROM areas WHERE parent_id = ?1")?;
+            let rows = stmt.query_map(params![parent_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            let mut ret = Area::new();
+            for r in rows {
+                let (id, name) = r?;
+                ret.insert(id, name);
+            }
+            Ok(ret)
+        })
+    }
+
+    /// 整个地区树替换掉, 和版本一起在一个事务里写
+    pub fn store_area_tree(&self, tree: &AreaTree) -> Result<()> {
+        self.with_tx(|tx| {
+            tx.execute("DELETE FROM areas", params![])?;
+            let update_dt = now_str();
+            for (parent_id, areas) in tree.all() {
+                for (id, name) in areas {
+                    tx.execute(
+                        "INSERT INTO areas (id, parent_id, name, update_dt) VALUES (?1, ?2, ?3, ?4)",
+                        params![id, parent_id, name, update_dt],
+                    )?;
+                }
+            }
+            set_setting_tx(tx, AREA_VERSION_KEY, tree.version.as_str())
+        })
+    }
+
+    /// 本地的地区树, 包括选地区时一级一级存下来的
+    pub fn load_area_tree(&self) -> Result<AreaTree> {
+        let version = self.get_setting(AREA_VERSION_KEY)?.unwrap_or_default();
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT parent_id, id, name FROM areas")?;
+            let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            let mut children = BTreeMap::new();
+            for r in rows {
+                let (parent_id, id, name): (i64, i64, String) = r?;
+                children.entry(parent_id).or_insert_with(Area::new).insert(id, name);
+            }
+            Ok(AreaTree::new(children, version))
+        })
+    }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        self.with_conn(|conn| {
+            Ok(conn
+                .query_row("SELECT value FROM settings WHERE key = ?1", params![key], |row| row.get(0))
+                .optional()?)
+        })
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.with_tx(|tx| set_setting_tx(tx, key, value))
+    }
+
+    pub fn remove_setting(&self, key: &str) -> Result<()> {
+        self.with_tx(|tx| {
+            tx.execute("DELETE FROM settings WHERE key = ?1", params![key])?;
+            Ok(())
+        })
+    }
+
+    /// 记一条下单流水, 返回流水 id
+    pub fn append_journal(&self, entry: &JournalEntry) -> Result<i64> {
+        self.with_tx(|tx| {
+            tx.execute(
+                "INSERT INTO purchase_journal (create_dt, account, cart_goods_id, sku, num, price, status)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    entry.create_dt,
+                    entry.account,
+                    entry.cart_goods_id,
+                    entry.sku,
+                    entry.num,
+                    entry.price,
+                    entry.status
+                ],
+            )?;
+            Ok(tx.last_insert_rowid())
+        })
+    }
+
+    /// 最近的下单流水, 新的在前
+    pub fn journal(&self, limit: usize) -> Result<Vec<JournalEntry>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, create_dt, account, cart_goods_id, sku, num, price, status
+                 FROM purchase_journal ORDER BY id DESC LIMIT ?1",
+            )?;
+            let rows = stmt.query_map(params![limit as i64], |row| {
+                Ok(JournalEntry {
+                    id: row.get(0)?,
+                    create_dt: row.get(1)?,
+                    account: row.get(2)?,
+                    cart_goods_id: row.get(3)?,
+                    sku: row.get(4)?,
+                    num: row.get(5)?,
+                    price: row.get(6)?,
+                    status: row.get(7)?,
+                })
+            })?;
+            let mut ret = vec![];
+            for r in rows {
+                ret.push(r?);
+            }
+            Ok(ret)
+        })
+    }
+
+    pub fn append_observation(&self, obs: &Observation) -> Result<()> {
+        self.with_tx(|tx| {
+            tx.execute(
+                "INSERT INTO price_history (observe_dt, sku, name, area, price, stock)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    obs.observe_dt,
+                    obs.sku,
+                    obs.name,
+                    obs.area,
+                    obs.price,
+                    obs.status.map(|s| s.code())
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// 删掉 before 之前的价格历史, 返回删除的条数
+    pub fn prune_price_history(&self, before: &str) -> Result<usize> {
+        self.with_tx(|tx| Ok(tx.execute("DELETE FROM price_history WHERE observe_dt < ?1", params![before])?))
+    }
+
+    /// 某个商品从 since 开始的价格和库存, 按时间排序
+    pub fn price_history(&self, sku: &str, since: &str) -> Result<Vec<Observation>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT observe_dt, sku, name, area, price, stock FROM price_history
+                 WHERE sku = ?1 AND observe_dt >= ?2 ORDER BY observe_dt, id",
+            )?;
+            let rows = stmt.query_map(params![sku, since], |row| {
+                let stock: Option<String> = row.get(5)?;
+                Ok(Observation {
+                    observe_dt: row.get(0)?,
+                    sku: row.get(1)?,
+                    name: row.get(2)?,
+                    area: row.get(3)?,
+                    price: row.get(4)?,
+                    status: stock.map(|s| StockStatus::from(s.as_str())),
+                })
+            })?;
+            let mut ret = vec![];
+            for r in rows {
+                ret.push(r?);
+            }
+            Ok(ret)
+        })
+    }
+
+    /// 有价格历史的商品, 最近观测的在前
+    pub fn watched_skus(&self) -> Result<Vec<WatchedSku>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT sku, MAX(name), COUNT(*), MAX(observe_dt) FROM price_history
+                 GROUP BY sku ORDER BY MAX(observe_dt) DESC",
+            )?;
+            let rows = stmt.query_map(params![], |row| {
+                let count: i64 = row.get(2)?;
+                Ok(WatchedSku {
+                    sku: row.get(0)?,
+                    name: row.get(1)?,
+                    count: count as usize,
+                    last_dt: row.get(3)?,
+                })
+            })?;
+            let mut ret = vec![];
+            for r in rows {
+                ret.push(r?);
+            }
+            Ok(ret)
+        })
+    }
+
+    /// 保存账号一天的花费, 顺便删掉以前的
+    pub fn save_budget_spend(&self, date: &str, account: &str, spend: &DaySpend) -> Result<()> {
+        self.with_tx(|tx| {
+            tx.execute("DELETE FROM budget_spend WHERE date < ?1", params![date])?;
+            tx.execute(
+                "INSERT OR REPLACE INTO budget_spend (date, account, data, update_dt) VALUES (?1, ?2, ?3, ?4)",
+                params![date, account, serde_json::to_string(spend)?, now_str()],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// 各个账号某天的花费
+    pub fn budget_spend(&self, date: &str) -> Result<HashMap<String, DaySpend>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT account, data FROM budget_spend WHERE date = ?1")?;
+            let rows = stmt.query_map(params![date], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            let mut ret = HashMap::new();
+            for r in rows {
+                let (account, data) = r?;
+                ret.insert(account, serde_json::from_str(data.as_str())?);
+            }
+            Ok(ret)
+        })
+    }
+
+    /// 记一条被预算拦下的下单, 只保留最近的 keep 条
+    pub fn append_budget_blocked(&self, blocked: &BlockedSubmit, keep: usize) -> Result<()> {
+        self.with_tx(|tx| {
+            tx.execute(
+                "INSERT INTO budget_blocked (create_dt, data) VALUES (?1, ?2)",
+                params![blocked.create_dt, serde_json::to_string(blocked)?],
+            )?;
+            tx.execute(
+                "DELETE FROM budget_blocked WHERE id NOT IN (SELECT id FROM budget_blocked ORDER BY id DESC LIMIT ?1)",
+                params![keep as i64],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// 最近被预算拦下的下单, 新的在前
+    pub fn budget_blocked(&self, limit: usize) -> Result<Vec<BlockedSubmit>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT data FROM budget_blocked ORDER BY id DESC LIMIT ?1")?;
+            let rows = stmt.query_map(params![limit as i64], |row| row.get::<_, String>(0))?;
+            let mut ret = vec![];
+            for r in rows {
+                ret.push(serde_json::from_str(r?.as_str())?);
+            }
+            Ok(ret)
+        })
+    }
+
+    /// 加入或修改到货提醒的商品
+    pub fn save_watch_item(&self, item: &WatchItem) -> Result<()> {
+        self.with_tx(|tx| {
+            tx.execute(
+                "INSERT OR REPLACE INTO watch_items (sku, area, area_name, name, target_price, create_dt, platform)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    item.sku,
+                    item.area,
+                    item.area_name,
+                    item.name,
+                    item.target_price,
+                    item.create_dt,
+                    u8::from(item.platform)
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn remove_watch_item(&self, sku: &str, area: &str) -> Result<()> {
+        self.with_tx(|tx| {
+            tx.execute("DELETE FROM watch_items WHERE sku = ?1 AND area = ?2", params![sku, area])?;
+            Ok(())
+        })
+    }
+
+    /// 到货提醒的商品, 按加入时间排序
+    pub fn watch_items(&self) -> Result<Vec<WatchItem>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT sku, area, area_name, name, target_price, create_dt, platform FROM watch_items
+                 ORDER BY create_dt, sku",
+            )?;
+            let rows = stmt.query_map(params![], |row| {
+                Ok(WatchItem {
+                    sku: row.get(0)?,
+                    area: row.get(1)?,
+                    area_name: row.get(2)?,
+                    name: row.get(3)?,
+                    target_price: row.get(4)?,
+                    create_dt: row.get(5)?,
+                    platform: ProdPlatform::try_from(row.get::<_, u8>(6)?).unwrap_or_default(),
+                    ..Default::default()
+                })
+            })?;
+            let mut ret = vec![];
+            for r in rows {
+                ret.push(r?);
+            }
+            Ok(ret)
+        })
+    }
+
+    /// 用最新的定时任务整个替换
+    pub fn store_jobs(&self, jobs: &[Job]) -> Result<()> {
+        self.with_tx(|tx| store_jobs_tx(tx, jobs))
+    }
+
+    pub fn load_jobs(&self) -> Result<Vec<Job>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT data FROM scheduled_jobs ORDER BY cart_goods_id")?;
+            let rows = stmt.query_map(params![], |row| row.get::<_, String>(0))?;
+            let mut ret = vec![];
+            for data in rows {
+                ret.push(serde_json::from_str(data?.as_str())?);
+            }
+            Ok(ret)
+        })
+    }
+
+    /// 导入以前的 config/jobs.json, 数据库里已经有任务时不导入; 导入后文件改名为 *.imported
+    pub fn import_legacy_jobs(&self, home: &Path) -> Result<usize> {
+        let file = home.join("config/jobs.json");
+        let jobs: Vec<Job> = match read_legacy(&file) {
+            Some(s) => match serde_json::from_str(s.as_str()) {
+                Ok(jobs) => jobs,
+                Err(e) => {
+                    warn!("任务文件格式不对, 不导入:{:?}", e);
+                    return Ok(0);
+                }
+            },
+            None => return Ok(0),
+        };
+        let imported = self.with_tx(|tx| {
+            let cnt: i64 = tx.query_row("SELECT COUNT(*) FROM scheduled_jobs", params![], |row| row.get(0))?;
+            if cnt > 0 {
+                return Ok(0);
+            }
+            store_jobs_tx(tx, jobs.as_slice())?;
+            Ok(jobs.len())
+        })?;
+        let to = PathBuf::from(format!("{}.imported", file.display()));
+        if let Err(e) = std::fs::rename(&file, &to) {
+            error!("旧文件改名失败:{}, {:?}", file.display(), e);
+        }
+        info!("导入旧的定时任务:{}个", imported);
+        Ok(imported)
+    }
+
+    /// 导入以前的 db/user.json、db/code.json、db/area.txt 和 config/settings.json, 只导入一次;
+    /// 格式不对的文件跳过, 导入成功的文件改名为 *.imported
+    pub fn import_legacy(&self, home: &Path) -> Result<Imported> {
+        if self.get_setting(IMPORTED_KEY)?.is_some() {
+            return Ok(Imported::default());
+        }
+        let user_file = home.join("db/user.json");
+        let code_file = home.join("db/code.json");
+        let area_file = home.join("db/area.txt");
+        let settings_file = home.join("config/settings.json");
+        let accounts: Vec<UserState> = read_legacy(&user_file)
+            .and_then(|s| {
+                serde_json::from_str(s.as_str())
+                    .map_err(|e| warn!("账号文件格式不对, 不导入:{:?}", e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        let activation = read_legacy(&code_file).filter(|s| {
+            serde_json::from_str::<UserInfo>(s.as_str())
+                .map_err(|e| warn!("激活信息文件格式不对, 不导入:{:?}", e))
+                .is_ok()
+        });
+        let area = read_legacy(&area_file).map(|s| s.trim().to_string());
+        let settings = read_legacy(&settings_file).filter(|s| {
+            serde_json::from_str::<SettingsLayer>(s.as_str())
+                .map_err(|e| warn!("设置文件格式不对, 不导入:{:?}", e))
+                .is_ok()
+        });
+        let imported = Imported {
+            accounts: accounts.len(),
+            activation: activation.is_some(),
+            area: area.is_some(),
+            settings: settings.is_some(),
+        };
+        self.with_tx(|tx| {
+            if !accounts.is_empty() {
+                store_accounts_tx(tx, accounts.as_slice())?;
+            }
+            if let Some(data) = activation.as_ref() {
+                store_activation_tx(tx, data)?;
+            }
+            if let Some(area) = area.as_ref() {
+                set_setting_tx(tx, AREA_KEY, area)?;
+            }
+            if let Some(data) = settings.as_ref() {
+                set_setting_tx(tx, SETTINGS_KEY, data)?;
+            }
+            set_setting_tx(tx, IMPORTED_KEY, now_str().as_str())
+        })?;
+        for (file, ok) in [
+            (user_file, imported.accounts > 0),
+            (code_file, imported.activation),
+            (area_file, imported.area),
+            (settings_file, imported.settings),
+        ]
+        .iter()
+        {
+            if *ok {
+                let to = PathBuf::from(format!("{}.imported", file.display()));
+                if let Err(e) = std::fs::rename(file, &to) {
+                    error!("旧文件改名失败:{}, {:?}", file.display(), e);
+                }
+            }
+        }
+        info!("导入旧的数据文件:{:?}", imported);
+        Ok(imported)
+    }
+}
+
+#[cfg(not(test))]
+fn open_default() -> Store {
+    let home = PathBuf::from(env::var(HOME).unwrap_or_default());
+    let path = home.join("db/jd_miaosha.db");
+    let store = Store::open(&path)
+        .or_else(|e| {
+            // 打不开时用内存数据库, 至少不影响抢购, 只是重启后数据没了
+            error!("打开数据库失败, 使用内存数据库:{}, {:?}", path.display(), e);
+            Store::open_in_memory()
+        })
+        .expect("open sqlite store");
+    if let Err(e) = store.import_legacy(&home) {
+        error!("导入旧的数据文件失败:{:?}", e);
+    }
+    if let Err(e) = store.import_legacy_jobs(&home) {
+        error!("导入旧的定时任务失败:{:?}", e);
+    }
+    store
+}
+
+// 测试不读写程序目录下的数据库
+#[cfg(test)]
+fn open_default() -> Store {
+    Store::open_in_memory().expect("open sqlite store")
+}
+
+lazy_static! {
+    static ref STORE: Arc<Store> = Arc::new(open_default());
+}
+
+pub fn store() -> &'static Store {
+    &STORE
+}
+
+/// 需要自己持有数据库的地方(定时任务)用这个
+pub fn shared() -> Arc<Store> {
+    STORE.clone()
+}
+
+/// sqlite 的读写是同步的, 放到阻塞线程里执行
+pub async fn run<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Store) -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || f(store()))
+        .await
+        .map_err(anyhow::Error::from)?
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use crate::models::{AreaTree, StockStatus};
+use crate::services::budget::{self, Spend};
+use crate::services::price_history::Observation;
+use crate::services::scheduler::Job;
+use crate::services::store::{JournalEntry, Store, AREA_KEY, SETTINGS_KEY};
+use crate::services::watch::WatchItem;
+use crate::settings;
+use crate::testing::{AREA, SKU, area_children, cookie, observation, temp_path, watch_item};
+
+#[test]
+fn test_store_migrate() {
+    let path = temp_path("store.db");
+    let s = Store::open(&path).unwrap();
+    assert_eq!(s.version().unwrap(), 7);
+    s.set_setting(AREA_KEY, "1_72_2819_0").unwrap();
+    drop(s);
+    // 重新打开不会重复执行 migration, 数据还在
+    let s = Store::open(&path).unwrap();
+    assert_eq!(s.version().unwrap(), 7);
+    assert_eq!(s.get_setting(AREA_KEY).unwrap(), Some("1_72_2819_0".to_string()));
+    s.remove_setting(AREA_KEY).unwrap();
+    assert_eq!(s.get_setting(AREA_KEY).unwrap(), None);
+    drop(s);
+    for ext in &["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), ext));
+    }
+}
+
+#[test]
+fn test_store_records() {
+    let s = Store::open_in_memory().unwrap();
+    let user = |account: &str| crate::models::UserState {
+        id: 1,
+        account: account.to_string(),
+        cookie: Arc::new("pt_key=1".to_string()),
+        ..Default::default()
+    };
+    s.store_accounts(&[user("b"), user("a")]).unwrap();
+    let accounts: Vec<String> = s.load_accounts().unwrap().into_iter().map(|u| u.account).collect();
+    assert_eq!(accounts, vec!["a", "b"]);
+    // 整个替换
+    s.store_accounts(&[user("c")]).unwrap();
+    assert_eq!(s.load_accounts().unwrap().len(), 1);
+    assert_eq!(s.load_accounts().unwrap()[0].cookie.as_str(), "pt_key=1");
+
+    assert_eq!(s.load_activation().unwrap(), None);
+    s.store_activation("{}").unwrap();
+    s.store_activation("{\"activate_code\":\"x\"}").unwrap();
+    assert_eq!(s.load_activation().unwrap(), Some("{\"activate_code\":\"x\"}".to_string()));
+
+    let mut area = crate::models::Area::new();
+    area.insert(72, "朝阳区".to_string());
+    s.store_areas(1, &area).unwrap();
+    assert_eq!(s.load_areas(1).unwrap(), area);
+    assert!(s.load_areas(2).unwrap().is_empty());
+
+    let entry = |status: &str| JournalEntry {
+        create_dt: "2021-12-21 09:00:00".to_string(),
+        account: "a".to_string(),
+        cart_goods_id: 21,
+        sku: SKU.to_string(),
+        num: 1,
+        price: "99.00".to_string(),
+        status: status.to_string(),
+        ..Default::default()
+    };
+    let first = s.append_journal(&entry("fail")).unwrap();
+    let second = s.append_journal(&entry("success")).unwrap();
+    assert!(second > first);
+    let journal = s.journal(10).unwrap();
+    assert_eq!(journal.len(), 2);
+    assert_eq!(journal[0], JournalEntry { id: second, ..entry("success") });
+    assert_eq!(s.journal(1).unwrap().len(), 1);
+}
+
+#[test]
+fn test_store_import_legacy() {
+    let home = temp_path("legacy");
+    std::fs::create_dir_all(home.join("db")).unwrap();
+    std::fs::create_dir_all(home.join("config")).unwrap();
+    let users = serde_json::to_string(&vec![crate::models::UserState {
+        account: "a".to_string(),
+        ..Default::default()
+    }])
+    .unwrap();
+    std::fs::write(home.join("db/user.json"), users).unwrap();
+    // 格式不对的跳过, 文件留着
+    std::fs::write(home.join("db/code.json"), "not json").unwrap();
+    std::fs::write(home.join("db/area.txt"), "1_72_2819_0\n").unwrap();
+    std::fs::write(home.join("config/settings.json"), "{\"workers\": 20}").unwrap();
+
+    let s = Store::open_in_memory().unwrap();
+    let imported = s.import_legacy(&home).unwrap();
+    assert_eq!((imported.accounts, imported.activation, imported.area, imported.settings), (1, false, true, true));
+    assert_eq!(s.load_accounts().unwrap()[0].account, "a");
+    assert_eq!(s.load_activation().unwrap(), None);
+    assert_eq!(s.get_setting(AREA_KEY).unwrap(), Some("1_72_2819_0".to_string()));
+    assert_eq!(settings::read_layer(&s).workers, Some(20));
+    assert!(s.get_setting(SETTINGS_KEY).unwrap().is_some());
+    assert!(home.join("db/user.json.imported").exists());
+    assert!(!home.join("db/user.json").exists());
+    assert!(home.join("db/code.json").exists());
+
+    // 只导入一次
+    std::fs::write(home.join("db/area.txt"), "2_2_2_0").unwrap();
+    assert_eq!(s.import_legacy(&home).unwrap(), Default::default());
+    assert_eq!(s.get_setting(AREA_KEY).unwrap(), Some("1_72_2819_0".to_string()));
+    let _ = std::fs::remove_dir_all(&home);
+}
+
+#[test]
+fn test_store_area_tree() {
+    let s = Store::open_in_memory().unwrap();
+    assert!(s.load_area_tree().unwrap().is_empty());
+    let tree = AreaTree::new(area_children(), "2021-12-21 09:00:00".to_string());
+    s.store_area_tree(&tree).unwrap();
+    // 没有子地区的 2800 不会存, 读出来一样是最后一级
+    let loaded = s.load_area_tree().unwrap();
+    assert_eq!(loaded.version, tree.version);
+    assert_eq!(loaded.len(), tree.len());
+    assert_eq!(loaded.search("朝阳", 10), tree.search("朝阳", 10));
+    assert_eq!(s.load_areas(72).unwrap().len(), 2);
+
+    // 整个替换
+    let mut children = area_children();
+    children.remove(&72);
+    s.store_area_tree(&AreaTree::new(children, "2021-12-22 09:00:00".to_string())).unwrap();
+    let loaded = s.load_area_tree().unwrap();
+    assert_eq!(loaded.version, "2021-12-22 09:00:00");
+    assert!(s.load_areas(72).unwrap().is_empty());
+}
+
+#[test]
+fn test_store_price_history() {
+    let s = Store::open_in_memory().unwrap();
+    assert!(s.watched_skus().unwrap().is_empty());
+    s.append_observation(&observation("2021-12-20 09:00:00", SKU, Some(99.0), None)).unwrap();
+    s.append_observation(&Observation {
+        name: "手机".to_string(),
+        ..observation("2021-12-21 09:00:00", SKU, Some(89.0), Some(StockStatus::OutOfStock))
+    })
+    .unwrap();
+    s.append_observation(&observation("2021-12-21 10:00:00", SKU, None, Some(StockStatus::Unknown))).unwrap();
+    s.append_observation(&observation("2021-12-22 09:00:00", "100016777664", Some(10.0), None)).unwrap();
+
+    let history = s.price_history(SKU, "2021-12-21 00:00:00").unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].price, Some(89.0));
+    assert_eq!(history[0].status.map(|s| s.to_string()), Some("无货".to_string()));
+    assert_eq!(history[1].price, None);
+    assert_eq!(history[1].status.map(|s| s.to_string()), Some("未知".to_string()));
+    assert_eq!(s.price_history(SKU, "2021-12-01 00:00:00").unwrap()[0].status, None);
+
+    // 最近观测的在前, 名称取有值的
+    let watched = s.watched_skus().unwrap();
+    assert_eq!(watched.len(), 2);
+    assert_eq!(watched[0].sku, "100016777664");
+    assert_eq!((watched[1].name.as_str(), watched[1].count), ("手机", 3));
+    assert_eq!(watched[1].last_dt, "2021-12-21 10:00:00");
+
+    // 过期的删掉
+    assert_eq!(s.prune_price_history("2021-12-21 00:00:00").unwrap(), 1);
+    assert_eq!(s.price_history(SKU, "2021-12-01 00:00:00").unwrap().len(), 2);
+    assert_eq!(s.prune_price_history("2021-12-21 00:00:00").unwrap(), 0);
+}
+
+#[test]
+fn test_store_watch_items() {
+    let s = Store::open_in_memory().unwrap();
+    assert!(s.watch_items().unwrap().is_empty());
+    s.save_watch_item(&watch_item(Some(90.0))).unwrap();
+    s.save_watch_item(&WatchItem {
+        area: "2_2813_51976_0".to_string(),
+        create_dt: "2021-12-21 09:00:00".to_string(),
+        ..watch_item(None)
+    })
+    .unwrap();
+    // 同一个商品、地区再加入是修改
+    s.save_watch_item(&WatchItem {
+        price: Some(99.0),
+        ..watch_item(Some(80.0))
+    })
+    .unwrap();
+    let items = s.watch_items().unwrap();
+    assert_eq!(items.len(), 2);
+    // 查询结果不保存
+    assert_eq!(items[0], watch_item(Some(80.0)));
+    assert_eq!((items[1].area.as_str(), items[1].target_price), ("2_2813_51976_0", None));
+    s.remove_watch_item(SKU, AREA).unwrap();
+    assert_eq!(s.watch_items().unwrap().len(), 1);
+}
+
+#[test]
+fn test_store_budget_spend() {
+    let s = Store::open_in_memory().unwrap();
+    let mut spend = budget::DaySpend::default();
+    spend.orders.insert("1".to_string(), Spend::new(188.0, 2));
+    spend.submitted.push(budget::Submitted {
+        spend: Spend::new(99.0, 1),
+        at: crate::PKLocal::now(),
+    });
+    s.save_budget_spend("2021-12-20", "a", &spend).unwrap();
+    assert_eq!(s.budget_spend("2021-12-20").unwrap()["a"], spend);
+    // 换天后以前的删掉
+    s.save_budget_spend("2021-12-21", "b", &Default::default()).unwrap();
+    assert!(s.budget_spend("2021-12-20").unwrap().is_empty());
+    assert_eq!(s.budget_spend("2021-12-21").unwrap().len(), 1);
+}
+
+#[test]
+fn test_store_budget_blocked() {
+    let s = Store::open_in_memory().unwrap();
+    let blocked = |id| budget::BlockedSubmit {
+        account: "a".to_string(),
+        cart_goods_id: id,
+        sku: SKU.to_string(),
+        spend: Spend::new(99.0, 1),
+        reason: "单笔金额99.00超过上限50.00".to_string(),
+        create_dt: "2021-12-20 10:00:00".to_string(),
+    };
+    for id in 1..=3 {
+        s.append_budget_blocked(&blocked(id), 2).unwrap();
+    }
+    // 只保留最近的两条, 新的在前
+    assert_eq!(s.budget_blocked(10).unwrap(), vec![blocked(3), blocked(2)]);
+    assert_eq!(s.budget_blocked(1).unwrap(), vec![blocked(3)]);
+}
+
+#[test]
+fn test_store_jobs() {
+    let s = Store::open_in_memory().unwrap();
+    assert!(s.load_jobs().unwrap().is_empty());
+    let jobs = vec![Job::new(2, SKU, "b", 2000, 1000), Job::new(1, SKU, "a", 2000, 1000)];
+    s.store_jobs(&jobs).unwrap();
+    assert_eq!(s.load_jobs().unwrap(), vec![jobs[1].clone(), jobs[0].clone()]);
+    s.store_jobs(&jobs[..1]).unwrap();
+    assert_eq!(s.load_jobs().unwrap(), vec![jobs[0].clone()]);
+
+    // 以前的 config/jobs.json 导入一次, 数据库里已经有任务时不导入
+    let home = temp_path("legacy_jobs");
+    std::fs::create_dir_all(home.join("config")).unwrap();
+    std::fs::write(home.join("config/jobs.json"), serde_json::to_string(&jobs).unwrap()).unwrap();
+    assert_eq!(s.import_legacy_jobs(&home).unwrap(), 0);
+    let s = Store::open_in_memory().unwrap();
+    assert_eq!(s.import_legacy_jobs(&home).unwrap(), 0);
+    std::fs::write(home.join("config/jobs.json"), serde_json::to_string(&jobs).unwrap()).unwrap();
+    assert_eq!(s.import_legacy_jobs(&home).unwrap(), 2);
+    assert_eq!(s.load_jobs().unwrap().len(), 2);
+    assert!(home.join("config/jobs.json.imported").exists());
+    assert!(!home.join("config/jobs.json").exists());
+    let _ = std::fs::remove_dir_all(&home);
+}
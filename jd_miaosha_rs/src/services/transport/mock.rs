@@ -0,0 +1,232 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use http::StatusCode;
+use log::warn;
+use serde_json::json;
+
+use crate::error::Result;
+
+use super::{HttpRequest, HttpResponse, HttpTransport};
+
+struct MockRoute {
+    pattern: String,
+    responses: VecDeque<HttpResponse>,
+}
+
+/// 内存里的 transport: 按 "方法 url 请求体" 做子串匹配, 多个规则都匹配时取最长的那个;
+/// 同一个规则可以编排多个响应, 按顺序返回, 最后一个会一直重复. 没有匹配的请求返回 404
+#[derive(Default)]
+pub struct MockTransport {
+    routes: Mutex<Vec<MockRoute>>,
+    requests: Mutex<Vec<HttpRequest>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on(&self, pattern: &str, resp: HttpResponse) -> &Self {
+        let mut routes = self.routes.lock().unwrap();
+        match routes.iter_mut().find(|r| r.pattern == pattern) {
+            Some(route) => route.responses.push_back(resp),
+            None => routes.push(MockRoute {
+                pattern: pattern.to_string(),
+                responses: vec![resp].into(),
+            }),
+        }
+        self
+    }
+
+    /// 清掉 pattern 已经编排的响应, 重新编排
+    pub fn replace(&self, pattern: &str, resp: HttpResponse) -> &Self {
+        self.routes.lock().unwrap().retain(|r| r.pattern != pattern);
+        self.on(pattern, resp)
+    }
+
+    /// 收到的所有请求, 按时间顺序
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// 命中 pattern 的请求数
+    pub fn hits(&self, pattern: &str) -> usize {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| haystack(r).contains(pattern))
+            .count()
+    }
+
+    fn respond(&self, req: &HttpRequest) -> HttpResponse {
+        let key = haystack(req);
+        let mut routes = self.routes.lock().unwrap();
+        let route = routes
+            .iter_mut()
+            .filter(|r| key.contains(r.pattern.as_str()))
+            .max_by_key(|r| r.pattern.len());
+        match route {
+            Some(route) => {
+                if route.responses.len() > 1 {
+                    route.responses.pop_front().unwrap()
+                } else {
+                    route.responses[0].clone()
+                }
+            }
+            None => {
+                warn!("mock transport 没有匹配的响应:{}", key);
+                HttpResponse::new(StatusCode::NOT_FOUND, "")
+            }
+        }
+    }
+
+    /// 按场景编排好京东下单流程用到的接口: 服务端租约、购物车、库存、价格、结算和提交订单
+    pub fn jd(scenario: JdScenario, sku: &str) -> Self {
+        let mock = Self::new();
+        let login_url = "https://passport.jd.com/new/login.aspx";
+        let order_info_url = "https://trade.jd.com/shopping/order/getOrderInfo.action";
+        let cart_ok = json!({"success": true, "message": ""});
+        let cart_info = json!({
+            "success": true,
+            "resultData": {"cartInfo": {"vendors": [{"sorted": [{"item": {
+                "Id": sku,
+                "skuUuid": format!("uuid-{}", sku),
+                "items": []
+            }}]}]}}
+        });
+        // 服务端接口
+        mock.on("/api/shopping_cart/claim/", HttpResponse::json_ok(json!({})));
+        // 商品详情、价格、库存
+        mock.on(
+            format!("item.jd.com/{}.html", sku).as_str(),
+            HttpResponse::ok(format!(
+                r#"<html>name: '模拟商品{0}',<div id="choose-btns"><a id="InitCartUrl" href="//cart.jd.com/gate.action?pid={0}&pcount=1&ptype=1">加入购物车</a></div></html>"#,
+                sku
+            )),
+        );
+        mock.on(
+            format!("item.m.jd.com/product/{}.html", sku).as_str(),
+            HttpResponse::ok(format!(
+                r#"window._itemOnly = ({{"item": {{"skuId":"{0}"}}, "yuyue":{{"type":"1","stime":"2021-05-19 00:00:00","etime":"2021-05-23 09:00:00","qiangStime":"2021-05-23 10:00:00","url":"//yushou.jd.com/toYuyue.action?sku={0}&key=mock"}}}});"#,
+                sku
+            )),
+        );
+        mock.on(
+            "p.3.cn/prices/mgets",
+            HttpResponse::json_ok(json!([{"id": format!("J_{}", sku), "p": "99.00", "op": "129.00"}])),
+        );
+        let stock_state = if scenario == JdScenario::OutOfStock { 34 } else { 33 };
+        let mut stock = json!({});
+        stock[sku] = json!({"StockState": stock_state});
+        mock.on("c0.3.cn/stocks", HttpResponse::json_ok(stock));
+        if scenario == JdScenario::ExpiredCookie {
+            // cookie 失效时: 购物车接口返回未登录, 页面都跳转到登录页
+            let not_login = json!({"success": false, "message": "未登录"});
+            mock.on("api.m.jd.com/api", HttpResponse::json_ok(not_login));
+            mock.on("order.jd.com/center/list.action", HttpResponse::found(login_url));
+            mock.on("getOrderTrackInfoMultiPackage.action", HttpResponse::found(login_url));
+            mock.on("easybuy.jd.com/address", HttpResponse::found(login_url));
+            mock.on("cart.jd.com/gate.action", HttpResponse::found(login_url));
+            mock.on("trade.jd.com", HttpResponse::found(login_url));
+            mock.on("yushou.jd.com", HttpResponse::found(login_url));
+            mock.on("gateForSubFlow.action", HttpResponse::found(login_url));
+            return mock;
+        }
+        mock.on("order.jd.com/center/list.action", HttpResponse::ok("<html>我的订单</html>"));
+        mock.on(
+            "details.jd.com/lazy/getOrderTrackInfoMultiPackage.action",
+            HttpResponse::json_ok(json!({"multiPackageTrackList": [{
+                "carrier": "京东快递",
+                "waybillCode": "JDVA00012345678",
+                "isSign": false,
+                "trackList": [
+                    {"msgTime": "2021-06-01 10:00:00", "content": "您提交了订单，请等待系统确认"},
+                    {"msgTime": "2021-06-01 20:00:00", "content": "您的订单已出库"}
+                ]
+            }]})),
+        );
+        // 收货地址
+        mock.on(
+            "easybuy.jd.com/address/addAddress.action",
+            HttpResponse::json_ok(json!({"success": true, "addressId": 3759910999u64})),
+        );
+        mock.on("easybuy.jd.com/address/updateAddress.action", HttpResponse::json_ok(json!({"success": true})));
+        mock.on("easybuy.jd.com/address/deleteAddress.action", HttpResponse::json_ok(json!({"success": true})));
+        // 购物车
+        mock.on("cart.jd.com/gate.action", HttpResponse::found("https://cart.jd.com/addToCart.html"));
+        mock.on("pcCart_jc_cartUnCheckAll", HttpResponse::json_ok(cart_ok.clone()));
+        mock.on("pcCart_jc_cartCheckSingle", HttpResponse::json_ok(cart_ok.clone()));
+        mock.on("pcCart_jc_cartRemove", HttpResponse::json_ok(cart_ok));
+        mock.on("pcCart_jc_getCurrentCart", HttpResponse::json_ok(cart_info));
+        // 预约
+        mock.on("yushou.jd.com/toYuyue.action", HttpResponse::ok("预约成功"));
+        // 结算、提交订单
+        mock.on("gateForSubFlow.action", HttpResponse::found(order_info_url));
+        mock.on("getOrderInfo.action", HttpResponse::ok("<html>订单结算页</html>"));
+        let submit_resp = match scenario {
+            JdScenario::Success => json!({"success": true, "orderId": 20210601001u64, "message": ""}),
+            JdScenario::OutOfStock => json!({"success": false, "resultCode": 600158, "message": "抱歉，您购买的商品无货"}),
+            _ => json!({"success": false, "resultCode": 0, "message": "系统繁忙，请稍后再试"}),
+        };
+        mock.on("submitOrder.action", HttpResponse::json_ok(submit_resp));
+        // 秒杀
+        mock.on(
+            "itemko.jd.com/itemShowBtn",
+            HttpResponse::ok(format!(
+                r#"jQuery1234567({{"type":"3","url":"//divide.jd.com/user_routing?skuId={}"}})"#,
+                sku
+            )),
+        );
+        mock.on("marathon.jd.com/captcha.html", HttpResponse::found("https://marathon.jd.com/seckill/seckill.action"));
+        mock.on(
+            "seckillnew/orderService/pc/init.action",
+            HttpResponse::json_ok(json!({
+                "addressList": [{"id": 1, "name": "张三", "provinceId": 1, "cityId": 72, "countyId": 2819, "townId": 0, "addressDetail": "模拟地址", "mobile": "138****0000", "mobileKey": "mock"}],
+                "invoiceInfo": {},
+                "seckillSkuVO": {"extMap": {"YuShou": "0"}},
+                "token": "mock"
+            })),
+        );
+        let seckill_resp = if scenario == JdScenario::Success {
+            json!({"success": true, "orderId": 20210601002u64, "totalMoney": "99.00", "pcUrl": "//pay.jd.com/mock"})
+        } else {
+            json!({"success": false, "errorMessage": "很遗憾没有抢到，再接再厉哦"})
+        };
+        mock.on("seckillnew/orderService/pc/submitOrder.action", HttpResponse::json_ok(seckill_resp));
+        mock
+    }
+}
+
+fn haystack(req: &HttpRequest) -> String {
+    format!(
+        "{} {} {}",
+        req.method,
+        req.url,
+        req.body.as_deref().unwrap_or_default()
+    )
+}
+
+#[async_trait]
+impl HttpTransport for MockTransport {
+    async fn execute(&self, req: HttpRequest) -> Result<HttpResponse> {
+        let resp = self.respond(&req);
+        self.requests.lock().unwrap().push(req);
+        Ok(resp)
+    }
+}
+
+/// 模拟京东下单的几种结果
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum JdScenario {
+    // 有货, 下单成功
+    Success,
+    // 无货, 库存接口返回 34, 提交订单返回无货
+    OutOfStock,
+    // cookie 失效, 购物车接口返回未登录, 页面跳转到登录页
+    ExpiredCookie,
+    // 有货, 但是提交订单一直失败
+    SubmitFailure,
+}
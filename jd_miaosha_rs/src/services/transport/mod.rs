@@ -0,0 +1,251 @@
+//! 访问电商平台(以及服务端)的 http 请求都通过 HttpClient 发送,
+//! 默认用 reqwest 发请求; 测试或者离线调试时可以注入 MockTransport, 按 url 返回预先编排好的响应
+pub mod mock;
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use http::{HeaderMap, Method, StatusCode};
+use http::header::{HeaderName, HeaderValue, LOCATION};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{from_str, Value};
+use url::form_urlencoded;
+
+use crate::error::Result;
+
+pub use mock::{JdScenario, MockTransport};
+
+lazy_static! {
+    // 注入的 transport, 为空时用 reqwest
+    static ref TRANSPORT: RwLock<Option<Arc<dyn HttpTransport>>> = RwLock::new(None);
+}
+
+/// 替换所有 HttpClient 使用的 transport, 之后新建的 client 都会走这个 transport
+pub fn set_transport(transport: Arc<dyn HttpTransport>) {
+    *TRANSPORT.write().unwrap() = Some(transport);
+}
+
+/// 恢复成 reqwest 发请求
+pub fn reset_transport() {
+    *TRANSPORT.write().unwrap() = None;
+}
+
+fn injected_transport() -> Option<Arc<dyn HttpTransport>> {
+    TRANSPORT.read().unwrap().clone()
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+    pub timeout: Option<Duration>,
+}
+
+impl HttpRequest {
+    /// 请求头不区分大小写
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: String,
+}
+
+impl HttpResponse {
+    pub fn new(status: StatusCode, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn ok(body: impl Into<String>) -> Self {
+        Self::new(StatusCode::OK, body)
+    }
+
+    pub fn json_ok(body: Value) -> Self {
+        Self::ok(body.to_string()).with_header("content-type", "application/json")
+    }
+
+    /// 302 跳转, 京东的 cookie 失效时会跳转到登录页
+    pub fn found(location: &str) -> Self {
+        Self::new(StatusCode::FOUND, "").with_header(LOCATION.as_str(), location)
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        if let (Ok(k), Ok(v)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            self.headers.insert(k, v);
+        }
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub async fn text(self) -> Result<String> {
+        Ok(self.body)
+    }
+
+    pub async fn json<T: DeserializeOwned>(self) -> Result<T> {
+        Ok(from_str(self.body.as_str())?)
+    }
+}
+
+#[async_trait]
+pub trait HttpTransport: Send + Sync {
+    async fn execute(&self, req: HttpRequest) -> Result<HttpResponse>;
+}
+
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl HttpTransport for ReqwestTransport {
+    async fn execute(&self, req: HttpRequest) -> Result<HttpResponse> {
+        let mut builder = self.client.request(req.method, req.url.as_str());
+        for (k, v) in req.headers.iter() {
+            builder = builder.header(k.as_str(), v.as_str());
+        }
+        if let Some(timeout) = req.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(body) = req.body {
+            builder = builder.body(body);
+        }
+        let resp = builder.send().await?;
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let body = resp.text().await?;
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct HttpClient {
+    transport: Arc<dyn HttpTransport>,
+}
+
+impl HttpClient {
+    /// 用 reqwest 的 builder 构建(cookie、代理、重定向策略等都在 builder 里设置);
+    /// 注入了 transport 时忽略 builder 的设置
+    pub fn build(builder: reqwest::ClientBuilder) -> Result<Self> {
+        if let Some(transport) = injected_transport() {
+            return Ok(Self { transport });
+        }
+        Ok(Self {
+            transport: Arc::new(ReqwestTransport {
+                client: builder.build()?,
+            }),
+        })
+    }
+
+    pub fn new() -> Self {
+        let transport = injected_transport().unwrap_or_else(|| {
+            Arc::new(ReqwestTransport {
+                client: reqwest::Client::new(),
+            })
+        });
+        Self { transport }
+    }
+
+    pub fn request<U: AsRef<str>>(&self, method: Method, url: U) -> RequestBuilder {
+        RequestBuilder {
+            transport: self.transport.clone(),
+            req: HttpRequest {
+                method,
+                url: url.as_ref().to_string(),
+                headers: vec![],
+                body: None,
+                timeout: None,
+            },
+        }
+    }
+
+    pub fn get<U: AsRef<str>>(&self, url: U) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    pub fn post<U: AsRef<str>>(&self, url: U) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct RequestBuilder {
+    transport: Arc<dyn HttpTransport>,
+    req: HttpRequest,
+}
+
+impl RequestBuilder {
+    pub fn header<K: AsRef<str>, V: AsRef<str>>(mut self, name: K, value: V) -> Self {
+        self.req
+            .headers
+            .push((name.as_ref().to_string(), value.as_ref().to_string()));
+        self
+    }
+
+    pub fn body<B: Into<String>>(mut self, body: B) -> Self {
+        self.req.body = Some(body.into());
+        self
+    }
+
+    /// 按 application/x-www-form-urlencoded 编码, 只支持一层的对象
+    pub fn form<T: Serialize + ?Sized>(self, form: &T) -> Self {
+        let mut serializer = form_urlencoded::Serializer::new(String::new());
+        if let Ok(Value::Object(m)) = serde_json::to_value(form) {
+            for (k, v) in m.iter() {
+                match v {
+                    Value::String(s) => serializer.append_pair(k, s),
+                    Value::Null => serializer.append_pair(k, ""),
+                    _ => serializer.append_pair(k, v.to_string().as_str()),
+                };
+            }
+        }
+        self.header("Content-Type", "application/x-www-form-urlencoded")
+            .body(serializer.finish())
+    }
+
+    /// 还没发出的请求, 试运行时用来记录要提交的内容
+    pub fn as_request(&self) -> &HttpRequest {
+        &self.req
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.req.timeout = Some(timeout);
+        self
+    }
+
+    pub async fn send(self) -> Result<HttpResponse> {
+        self.transport.execute(self.req).await
+    }
+}
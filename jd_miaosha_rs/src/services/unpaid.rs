@@ -0,0 +1,168 @@
+//! 待付款订单: 抢到的订单不付款, 到了付款截止时间就被平台取消了。
+//! 这里记下所有账号的待付款订单, 按截止时间倒计时, 快到时逐级提醒
+use std::collections::BTreeMap;
+
+use crate::error::Result;
+use crate::models::OrderInfo;
+use crate::services::endpoints::endpoints;
+use crate::services::notify::{Event, EventKind};
+use crate::utils::{datetime_fmt, open_in_browser, parse_datetime};
+use crate::PKDateTime;
+
+// 剩余多少分钟时提醒, 从大到小; 最后一级用 PaymentUrgent 提醒
+pub const REMIND_MINUTES: [i64; 4] = [60, 30, 10, 5];
+// 剩余不到多少分钟时倒计时标红
+pub const URGENT_MINUTES: i64 = 10;
+// 定时刷新待付款订单的间隔
+pub const REFRESH_SECS: u64 = 300;
+
+pub fn is_unpaid(order: &OrderInfo) -> bool {
+    order.status.contains("等待付款")
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct UnpaidOrder {
+    pub account: String,
+    pub order_no: String,
+    pub name: String,
+    pub total_price: String,
+    pub deadline: PKDateTime,
+    // 页面上没有截止时间, 按下单时间估算的
+    pub estimated: bool,
+    pub pay_url: String,
+    // 已经提醒过的级数
+    reminded: usize,
+}
+
+impl UnpaidOrder {
+    /// 截止时间优先用页面上的, 没有时按 下单时间 + window_minutes 估算
+    pub fn from_order(order: &OrderInfo, window_minutes: i64) -> Option<Self> {
+        let (deadline, estimated) = match parse_datetime(order.pay_deadline.as_str()) {
+            Some(dt) => (dt, false),
+            None => (
+                parse_datetime(order.create_dt.as_str())? + chrono::Duration::minutes(window_minutes),
+                true,
+            ),
+        };
+        Some(UnpaidOrder {
+            account: order.account.clone(),
+            order_no: order.order_no.clone(),
+            name: order.name.clone(),
+            total_price: order.total_price.clone(),
+            deadline,
+            estimated,
+            pay_url: order.pay_url.clone(),
+            reminded: 0,
+        })
+    }
+
+    pub fn remaining_secs(&self, now: PKDateTime) -> i64 {
+        self.deadline.signed_duration_since(now).num_seconds()
+    }
+
+    pub fn is_urgent(&self, now: PKDateTime) -> bool {
+        self.remaining_secs(now) <= URGENT_MINUTES * 60
+    }
+
+    /// 倒计时, 估算的前面加"约"
+    pub fn countdown(&self, now: PKDateTime) -> String {
+        let secs = self.remaining_secs(now);
+        if secs <= 0 {
+            return "已超时".to_string();
+        }
+        format!(
+            "{}{:02}:{:02}:{:02}",
+            if self.estimated { "约" } else { "" },
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60
+        )
+    }
+
+    /// 剩余时间到了的最紧急的一级, 还没提醒过就返回提醒
+    fn remind(&mut self, now: PKDateTime) -> Option<Event> {
+        let secs = self.remaining_secs(now);
+        if secs <= 0 {
+            return None;
+        }
+        let level = REMIND_MINUTES.iter().rposition(|m| secs <= m * 60)?;
+        if level < self.reminded {
+            return None;
+        }
+        self.reminded = level + 1;
+        let kind = if level + 1 == REMIND_MINUTES.len() {
+            EventKind::PaymentUrgent
+        } else {
+            EventKind::PaymentDue
+        };
+        Some(Event::new(
+            kind,
+            format!("订单{}还有{}分钟付款截止", self.order_no, (secs + 59) / 60),
+            format!(
+                "账号:{}, 商品:{}, 金额:{}, 截止时间:{}",
+                self.account,
+                self.name,
+                self.total_price,
+                datetime_fmt(&self.deadline)
+            ),
+        ))
+    }
+}
+
+/// 所有账号的待付款订单, 订单号 -> 订单
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Watchlist {
+    orders: BTreeMap<String, UnpaidOrder>,
+}
+
+impl Watchlist {
+    /// 加入待付款的订单, 已经在列表里的保留提醒进度
+    pub fn merge(&mut self, orders: &BTreeMap<String, OrderInfo>, window_minutes: i64) {
+        for o in orders.values().filter(|o| is_unpaid(o)) {
+            if let Some(mut order) = UnpaidOrder::from_order(o, window_minutes) {
+                if let Some(old) = self.orders.get(&order.order_no) {
+                    order.reminded = old.reminded;
+                }
+                self.orders.insert(order.order_no.clone(), order);
+            }
+        }
+    }
+
+    /// 用账号最新的待付款订单替换, 已经付款或者被取消的去掉
+    pub fn sync(&mut self, account: &str, orders: &BTreeMap<String, OrderInfo>, window_minutes: i64) {
+        self.orders
+            .retain(|order_no, o| o.account != account || orders.get(order_no).map(is_unpaid).unwrap_or(false));
+        self.merge(orders, window_minutes);
+    }
+
+    pub fn get(&self, order_no: &str) -> Option<&UnpaidOrder> {
+        self.orders.get(order_no)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    /// 到了提醒时间的订单, 每一级只提醒一次
+    pub fn remind(&mut self, now: PKDateTime) -> Vec<Event> {
+        self.orders.values_mut().filter_map(|o| o.remind(now)).collect()
+    }
+}
+
+/// 在系统浏览器里打开订单的付款页
+pub async fn open_pay_page(order_no: String, pay_url: String) -> Result<()> {
+    let url = if pay_url.is_empty() {
+        endpoints().await.pay_page(order_no.as_str())
+    } else {
+        pay_url
+    };
+    open_in_browser(url.as_str())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
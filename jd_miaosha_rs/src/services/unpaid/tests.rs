@@ -0,0 +1,83 @@
+use crate::models::OrderInfo;
+use crate::services::notify::EventKind;
+use crate::services::order::{OrderService, QueryCondition};
+use crate::services::transport::{HttpResponse, JdScenario};
+use crate::services::unpaid::{UnpaidOrder, Watchlist};
+use crate::testing::{cookie, dt, install, order_list_page};
+
+#[test]
+fn test_unpaid_watchlist() {
+    let order = |order_no: &str, status: &str, pay_deadline: &str| OrderInfo {
+        account: "a".to_string(),
+        order_no: order_no.to_string(),
+        status: status.to_string(),
+        create_dt: "2021-06-01 10:00:00".to_string(),
+        pay_deadline: pay_deadline.to_string(),
+        ..Default::default()
+    };
+    let orders: std::collections::BTreeMap<_, _> = vec![
+        order("1", "等待付款", "2021-06-01 11:00:00"),
+        order("2", "等待付款", ""),
+        order("3", "已完成", ""),
+    ]
+    .into_iter()
+    .map(|o| (o.order_no.clone(), o))
+    .collect();
+    let mut list = Watchlist::default();
+    list.sync("a", &orders, 30);
+    assert_eq!(list.len(), 2);
+    let dt = |s: &str| crate::utils::parse_datetime(s).unwrap();
+    // 页面上没有截止时间时按下单时间估算
+    let estimated: &UnpaidOrder = list.get("2").unwrap();
+    assert!(estimated.estimated);
+    assert_eq!(estimated.deadline, dt("2021-06-01 10:30:00"));
+    assert!(!list.get("1").unwrap().estimated);
+    assert_eq!(list.get("1").unwrap().countdown(dt("2021-06-01 10:58:55")), "00:01:05");
+    assert_eq!(estimated.countdown(dt("2021-06-01 10:00:00")), "约00:30:00");
+    assert_eq!(estimated.countdown(dt("2021-06-01 10:30:00")), "已超时");
+    assert!(estimated.is_urgent(dt("2021-06-01 10:20:00")));
+
+    // 都还剩一个多小时, 不提醒
+    assert!(list.remind(dt("2021-06-01 09:20:00")).is_empty());
+    // 1 号订单到了 60 分钟那一级, 2 号订单直接到了 30 分钟那一级, 同一级只提醒一次
+    let events = list.remind(dt("2021-06-01 10:01:00"));
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|e| e.kind == EventKind::PaymentDue));
+    assert!(list.remind(dt("2021-06-01 10:02:00")).is_empty());
+    // 跳过的级别不补发, 最后一级是紧急提醒
+    let events = list.remind(dt("2021-06-01 10:26:00"));
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, EventKind::PaymentUrgent);
+    assert!(events[0].title.contains("订单2"));
+    // 超时的订单不再提醒
+    let events = list.remind(dt("2021-06-01 10:31:00"));
+    assert_eq!(events.len(), 1);
+    assert!(events[0].title.contains("订单1"));
+
+    // 重新同步时保留提醒进度, 付款了的订单移出列表
+    let mut orders = orders;
+    orders.get_mut("1").unwrap().status = "等待收货".to_string();
+    list.sync("a", &orders, 30);
+    assert_eq!(list.len(), 1);
+    assert!(list.remind(dt("2021-06-01 10:27:00")).is_empty());
+    // 其他账号的订单不受影响
+    list.sync("b", &std::collections::BTreeMap::new(), 30);
+    assert_eq!(list.len(), 1);
+}
+
+#[tokio::test]
+async fn test_parse_unpaid_orders() {
+    let g = install(JdScenario::Success).await;
+    g.mock.replace(
+        "order.jd.com/center/list.action",
+        HttpResponse::ok(order_list_page()),
+    );
+    let orders = OrderService::get_orders_by_user("a".to_string(), cookie(), QueryCondition::Unpaid)
+        .await
+        .unwrap();
+    let mut list = Watchlist::default();
+    list.sync("a", &orders, 30);
+    let order = list.get("163821896625").unwrap();
+    assert!(!order.estimated);
+    assert_eq!(order.pay_url, "https://pcashier.jd.com/cashier/index.action?orderId=163821896625");
+}
@@ -1,6 +1,10 @@
 use crate::error::{JdMiaoshaError, OpError, Result};
 use crate::models::*;
+use crate::services::api::call_api;
 use crate::services::driver::*;
+use crate::services::endpoints::endpoints;
+use crate::services::store;
+use crate::services::transport::HttpClient;
 use crate::ui::UserCookieInfo;
 use crate::utils::sleep;
 use crate::*;
@@ -14,7 +18,7 @@ use reqwest::{
 };
 use scraper::{Html, Selector};
 use serde::Deserialize;
-use serde_json::{from_slice, from_str, json, to_string, Value};
+use serde_json::{from_slice, from_str, json, Value};
 use std::env;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -23,8 +27,6 @@ use thirtyfour::error::WebDriverError;
 use thirtyfour::prelude::*;
 use thirtyfour::GenericWebDriver;
 use thirtyfour::{By, WebDriverCommands};
-use tokio::fs::{create_dir_all, File, OpenOptions};
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
 use tokio::sync::RwLock;
 
 lazy_static! {
@@ -45,19 +47,21 @@ struct UserData {
 pub struct UserService;
 
 impl UserService {
-    const LOGIN_URL: &'static str = "https://passport.jd.com/new/login.aspx";
-    const CHECK_COOKIE_URL: &'static str =
-        "https://order.jd.com/center/list.action?s=1024&search=0&d=1";
-
-    pub async fn init_web_driver() -> Result<()> {
+    // 读取 config/conf.toml
+    pub async fn load_config() -> Result<()> {
         let exe_dir = PathBuf::from(env::var(HOME).unwrap());
         let conf_file = exe_dir.join("config/conf.toml");
-        let config: Config =
+        let mut config: Config =
             toml::from_slice(tokio::fs::read(conf_file).await.unwrap().as_slice()).unwrap();
-        {
-            let mut guard = CONFIG.write().await;
-            *guard = config;
-        }
+        // 合并环境变量和设置页保存的设置
+        settings::load(&mut config)?;
+        let mut guard = CONFIG.write().await;
+        *guard = config;
+        Ok(())
+    }
+
+    pub async fn init_web_driver() -> Result<()> {
+        Self::load_config().await?;
         // 初始化 chromedriver
         // 需要把 DriverManager 用全局变量保存起来，不然会被 drop，而后 kill 对应的 chromedriver进程
         let dm = DriverManager::init_servers().await?;
@@ -93,7 +97,7 @@ impl UserService {
         // }
         // let driver = CHROME_DRIVER.read().await;
         let mut driver = init_driver(false).await?;
-        driver.get(Self::LOGIN_URL).await?;
+        driver.get(endpoints().await.login()).await?;
         let mut final_cookies_str = String::with_capacity(1200);
         let mut eid = String::with_capacity(100);
         let mut fp_str = String::with_capacity(100);
@@ -184,7 +188,7 @@ impl UserService {
                 }
             }
         }
-        driver.get("https://order.jd.com/center/list.action").await?;
+        driver.get(endpoints().await.order_list()).await?;
         let cookies = driver.session.get_cookies().await?;
         final_cookies_str = cookies
             .iter()
@@ -210,10 +214,11 @@ impl UserService {
 
     pub async fn is_valid_cookie(account: String, cookie: Arc<String>) -> Result<(String, bool)> {
         // 禁止重定向
-        let resp = reqwest::Client::builder()
-            .redirect(reqwest::redirect::Policy::none())
-            .build()?
-            .get(Self::CHECK_COOKIE_URL)
+        let client = HttpClient::build(
+            reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()),
+        )?;
+        let resp = client
+            .get(format!("{}?s=1024&search=0&d=1", endpoints().await.order_list()))
             .header("cookie", cookie.as_str())
             .header("authority", "order.jd.com")
             .header("cache-control", "max-age=0")
@@ -249,11 +254,7 @@ impl UserService {
             page_no,
             key_word
         );
-        let resp = reqwest::Client::new()
-            .get(url)
-            .header("token", code.as_str())
-            .send()
-            .await?;
+        let resp = call_api(Method::GET, url, code.as_str(), None).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code, resp.status().as_str());
             return Ok(None);
@@ -262,81 +263,35 @@ impl UserService {
         Ok(Some(records))
     }
 
-    fn user_data_file_path() -> PathBuf {
-        let mut home = PathBuf::from(env::var(crate::HOME).unwrap());
-        let db_file_path = home.join("db/user.json");
-        info!("db_file_path: {:?}", db_file_path);
-        db_file_path
+    // 账号列表缓存在本地数据库里
+    pub async fn store_user_data(data: Vec<UserState>) -> Result<()> {
+        store::run(move |s| s.store_accounts(data.as_slice())).await
     }
 
-    fn user_code_file_path() -> PathBuf {
-        let mut home = PathBuf::from(env::var(crate::HOME).unwrap());
-        let db_file_path = home.join("db/code.json");
-        info!("{:?}", db_file_path);
-        db_file_path
-    }
-
-    pub async fn store_user_data(data: Vec<UserState>) -> Result<()> {
-        let db_file_path = Self::user_data_file_path();
-        let mut db_file = OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .append(false)
-            .open(db_file_path)
-            .await?;
-        let d = to_string(&data)?;
-        db_file.write_all(d.as_bytes()).await?;
-        Ok(())
+    pub async fn load_user_data() -> Result<Vec<UserState>> {
+        store::run(|s| s.load_accounts()).await
     }
 
     pub async fn load_user_activate_info() -> Result<Option<UserInfo>> {
-        let db_file_path = Self::user_code_file_path();
-        match File::open(&db_file_path).await {
-            Ok(db_file) => {
-                let mut reader = BufReader::new(db_file);
-                let mut fd = vec![];
-                let _ = reader.read_to_end(&mut fd).await?;
-                Ok(serde_json::from_slice(fd.as_slice())?)
-            }
-            Err(e) => {
-                use std::io::ErrorKind::NotFound;
-                if e.kind() == NotFound {
-                    let parent = db_file_path.parent().unwrap();
-                    create_dir_all(parent).await?;
-                    File::create(db_file_path.as_path()).await?;
-                    return Ok(None);
-                }
-                return Err(e.into());
-            }
+        match store::run(|s| s.load_activation()).await? {
+            Some(data) => Ok(serde_json::from_str(data.as_str())?),
+            None => Ok(None),
         }
     }
 
     pub async fn store_user_activate_info(user_info: String) -> Result<()> {
-        let db_file_path = Self::user_code_file_path();
-        let mut db_file = OpenOptions::new()
-            .write(true)
-            .append(false)
-            .truncate(true)
-            .open(db_file_path)
-            .await?;
-        db_file.write_all(user_info.as_bytes()).await?;
-        Ok(())
+        store::run(move |s| s.store_activation(user_info.as_str())).await
     }
 
     pub async fn activate(code: String) -> Result<Option<UserInfo>> {
         let addr_prefix = CONFIG.read().await.server_addr();
         let url = format!("{}/api/user/activate", addr_prefix.as_str());
-        let resp = reqwest::Client::new()
-            .post(url)
-            .body(
-                json!({
-                    "mac":UserInfo::get_user_mac_address(),
-                    "activate_code":code.as_str()
-                })
-                .to_string(),
-            )
-            .send()
-            .await?;
+        let body = json!({
+            "mac":UserInfo::get_user_mac_address(),
+            "activate_code":code.as_str()
+        })
+        .to_string();
+        let resp = call_api(Method::POST, url, code.as_str(), Some(body)).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code.as_str(), resp.status().as_str());
             return Ok(None);
@@ -348,11 +303,7 @@ impl UserService {
     pub async fn user_info(code: String) -> Result<Option<UserInfo>> {
         let addr_prefix = CONFIG.read().await.server_addr();
         let url = format!("{}/api/user", addr_prefix.as_str());
-        let resp = reqwest::Client::new()
-            .get(url)
-            .header("token", code.as_str())
-            .send()
-            .await?;
+        let resp = call_api(Method::GET, url, code.as_str(), None).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code, resp.status().as_str());
             return Ok(None);
@@ -372,12 +323,7 @@ impl UserService {
             // 删除全部账号
             body = json!({});
         }
-        let resp = reqwest::Client::new()
-            .delete(url)
-            .header("token", code.as_str())
-            .body(body.to_string())
-            .send()
-            .await?;
+        let resp = call_api(Method::DELETE, url, code.as_str(), Some(body.to_string())).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code, resp.status().as_str());
         }
@@ -387,12 +333,7 @@ impl UserService {
     pub async fn create_account(code: String, body: String) -> Result<()> {
         let addr_prefix = CONFIG.read().await.server_addr();
         let url = format!("{}/api/account", addr_prefix.as_str());
-        let resp = reqwest::Client::new()
-            .post(url)
-            .header("token", code.as_str())
-            .body(body)
-            .send()
-            .await?;
+        let resp = call_api(Method::POST, url, code.as_str(), Some(body)).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code, resp.status().as_str());
         }
@@ -402,12 +343,7 @@ impl UserService {
     pub async fn update_account(code: String, id: IDType, body: String) -> Result<()> {
         let addr_prefix = CONFIG.read().await.server_addr();
         let url = format!("{}/api/account/{}", addr_prefix.as_str(), id);
-        let resp = reqwest::Client::new()
-            .put(url)
-            .header("token", code.as_str())
-            .body(body)
-            .send()
-            .await?;
+        let resp = call_api(Method::PUT, url, code.as_str(), Some(body)).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code, resp.status().as_str());
         }
@@ -417,11 +353,7 @@ impl UserService {
     pub async fn delete_account(code: String, id: IDType) -> Result<()> {
         let addr_prefix = CONFIG.read().await.server_addr();
         let url = format!("{}/api/account/{}", addr_prefix.as_str(), id);
-        let resp = reqwest::Client::new()
-            .delete(url)
-            .header("token", code.as_str())
-            .send()
-            .await?;
+        let resp = call_api(Method::DELETE, url, code.as_str(), None).await?;
         if resp.status() != StatusCode::OK {
             error!("code:{}, {}", code, resp.status().as_str());
         }
@@ -431,12 +363,10 @@ impl UserService {
     pub async fn heartbeat(code: String, body:String) -> Result<bool>{
         let addr_prefix = CONFIG.read().await.server_addr();
         let url = format!("{}/api/user/heartbeat", &addr_prefix);
-        let resp = reqwest::Client::new()
-            .post(url)
-            .header("token", &code)
-            .body(body)
-            .send()
-            .await?;
+        let resp = call_api(Method::POST, url, code.as_str(), Some(body)).await?;
         Ok(resp.status() == StatusCode::OK)
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,26 @@
+use crate::error::{JdMiaoshaError, OpError};
+use crate::services::order::{OrderService, QueryCondition};
+use crate::services::transport::JdScenario;
+use crate::services::user::UserService;
+use crate::testing::{cookie, install};
+
+#[tokio::test]
+async fn test_expired_cookie() {
+    let _g = install(JdScenario::ExpiredCookie).await;
+    let (_, valid) = UserService::is_valid_cookie("mock".to_string(), cookie())
+        .await
+        .unwrap();
+    assert!(!valid);
+    let r = OrderService::get_orders_by_user("mock".to_string(), cookie(), QueryCondition::Unpaid)
+        .await;
+    assert!(matches!(
+        r,
+        Err(JdMiaoshaError::OpError(OpError::CookieExpired(_)))
+    ));
+    let r = OrderService::get_order_track("mock".to_string(), cookie(), "20210601001".to_string())
+        .await;
+    assert!(matches!(
+        r,
+        Err(JdMiaoshaError::OpError(OpError::CookieExpired(_)))
+    ));
+}
@@ -0,0 +1,322 @@
+//! 到货和降价提醒: 和购物车分开的监控列表, 只发通知, 不会自动下单。
+//! 商品在选定地区从无货变成有货、价格低于设定的价格时提醒;
+//! 同一个提醒在去重时间内只发一次, 免打扰时段里的提醒等时段结束后再发
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::NaiveTime;
+use futures::future::join_all;
+use log::error;
+
+use crate::error::Result;
+use crate::models::{ProdPlatform, StockStatus};
+use crate::services::notify::{Event, EventKind};
+use crate::services::price_history::Observation;
+use crate::services::retailer::retailer;
+use crate::services::store;
+use crate::settings;
+use crate::utils::datetime_fmt;
+use crate::PKDateTime;
+
+/// 监控的商品, 同一个商品可以监控多个地区
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct WatchItem {
+    pub sku: String,
+    pub platform: ProdPlatform,
+    pub name: String,
+    // 地区 id 路径, 如 1_72_2819_0
+    pub area: String,
+    pub area_name: String,
+    // 价格低于它时提醒, 为 None 时只提醒到货
+    pub target_price: Option<f64>,
+    pub create_dt: String,
+    // 最近一次查询到的价格和库存, 不保存
+    pub price: Option<f64>,
+    pub status: Option<StockStatus>,
+    pub check_dt: String,
+}
+
+impl WatchItem {
+    pub fn key(&self) -> (String, String) {
+        (self.sku.clone(), self.area.clone())
+    }
+
+    fn event(&self, kind: EventKind) -> Event {
+        let price = self.price.map(|p| format!("{:.2}", p)).unwrap_or_default();
+        match kind {
+            EventKind::PriceDrop => Event::new(
+                kind,
+                format!("降价了: {}", self.name),
+                format!(
+                    "商品:{}, sku:{}, 价格:{} 低于 {:.2}",
+                    self.name,
+                    self.sku,
+                    price,
+                    self.target_price.unwrap_or_default()
+                ),
+            ),
+            _ => Event::new(
+                kind,
+                format!("有货了: {}", self.name),
+                format!("商品:{}, sku:{}, 地区:{}, 价格:{}", self.name, self.sku, self.area_name, price),
+            ),
+        }
+    }
+
+    // 按最近一次查询结果, 这个提醒是不是还成立
+    fn still_alerting(&self, kind: EventKind) -> bool {
+        match kind {
+            EventKind::PriceDrop => match (self.price, self.target_price) {
+                (Some(price), Some(target)) => price < target,
+                _ => false,
+            },
+            _ => self.status.map(|s| s.is_stock()).unwrap_or(false),
+        }
+    }
+}
+
+/// 免打扰时段, 如 23:00-07:00, 可以跨过零点
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// 空字符串表示不设置免打扰, 格式不对时返回错误信息
+    pub fn parse(txt: &str) -> std::result::Result<Option<Self>, String> {
+        let txt = txt.trim();
+        if txt.is_empty() {
+            return Ok(None);
+        }
+        let invalid = || format!("免打扰时段的格式是 23:00-07:00: {}", txt);
+        let mut parts = txt.splitn(2, '-');
+        let mut time = || {
+            parts
+                .next()
+                .and_then(|t| NaiveTime::parse_from_str(t.trim(), "%H:%M").ok())
+                .ok_or_else(invalid)
+        };
+        let (start, end) = (time()?, time()?);
+        if start == end {
+            return Err(invalid());
+        }
+        Ok(Some(QuietHours { start, end }))
+    }
+
+    pub fn contains(&self, t: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= t && t < self.end
+        } else {
+            t >= self.start || t < self.end
+        }
+    }
+}
+
+/// 提醒规则, 来自设置
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WatchRules {
+    // 同一个商品、地区的同一种提醒在这段时间内只发一次, 0 表示不去重
+    pub dedup_minutes: i64,
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl WatchRules {
+    pub fn current() -> Self {
+        let s = settings::get();
+        WatchRules {
+            dedup_minutes: s.watch_dedup_minutes,
+            quiet_hours: QuietHours::parse(s.watch_quiet_hours.as_str()).unwrap_or_default(),
+        }
+    }
+
+    pub fn is_quiet(&self, now: PKDateTime) -> bool {
+        self.quiet_hours.map(|q| q.contains(now.time())).unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RestockWatchlist {
+    items: BTreeMap<(String, String), WatchItem>,
+    // 每个商品、地区、提醒类型最近一次提醒的时间
+    last_alert: HashMap<(String, String, EventKind), PKDateTime>,
+    // 免打扰时段里攒下的提醒, 每个商品、地区、提醒类型只留一个, 发的时候按最新的查询结果生成
+    pending: Vec<(String, String, EventKind)>,
+}
+
+impl RestockWatchlist {
+    pub fn new(items: Vec<WatchItem>) -> Self {
+        let mut list = RestockWatchlist::default();
+        for item in items {
+            list.insert(item);
+        }
+        list
+    }
+
+    /// 已经在监控的只更新名称和提醒价格, 保留查询结果
+    pub fn insert(&mut self, item: WatchItem) {
+        match self.items.get_mut(&item.key()) {
+            Some(old) => {
+                old.name = item.name;
+                old.target_price = item.target_price;
+            }
+            None => {
+                self.items.insert(item.key(), item);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, sku: &str, area: &str) -> Option<WatchItem> {
+        self.items.remove(&(sku.to_string(), area.to_string()))
+    }
+
+    pub fn get(&self, sku: &str, area: &str) -> Option<&WatchItem> {
+        self.items.get(&(sku.to_string(), area.to_string()))
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &WatchItem> {
+        self.items.values()
+    }
+
+    /// 要查询的 (平台, sku, 地区)
+    pub fn targets(&self) -> Vec<(ProdPlatform, String, String)> {
+        self.items
+            .values()
+            .map(|w| (w.platform, w.sku.clone(), w.area.clone()))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// 更新查询结果, 返回现在要发的提醒
+    pub fn observe(&mut self, obs: &Observation, now: PKDateTime, rules: &WatchRules) -> Vec<Event> {
+        let item = match self.items.get_mut(&(obs.sku.clone(), obs.area.clone())) {
+            Some(item) => item,
+            None => return vec![],
+        };
+        let mut kinds = vec![];
+        // 查不到库存时不算变化
+        if let Some(status) = obs.status.filter(|s| s.code() != StockStatus::Unknown.code()) {
+            let was_out = item
+                .status
+                .map(|s| s.code() == StockStatus::OutOfStock.code())
+                .unwrap_or(false);
+            if was_out && status.is_stock() {
+                kinds.push(EventKind::StockAvailable);
+            }
+            item.status = Some(status);
+        }
+        if let Some(price) = obs.price {
+            if let Some(target) = item.target_price {
+                // 从高于提醒价格降下来时才提醒, 刚加入时已经低于也提醒
+                let was_above = item.price.map(|p| p >= target).unwrap_or(true);
+                if price < target && was_above {
+                    kinds.push(EventKind::PriceDrop);
+                }
+            }
+            item.price = Some(price);
+        }
+        item.check_dt = datetime_fmt(now);
+
+        let quiet = rules.is_quiet(now);
+        let mut events = vec![];
+        for kind in kinds {
+            let key = (item.sku.clone(), item.area.clone(), kind);
+            let duplicated = self
+                .last_alert
+                .get(&key)
+                .map(|dt| now.signed_duration_since(*dt) < chrono::Duration::minutes(rules.dedup_minutes))
+                .unwrap_or(false);
+            if duplicated {
+                continue;
+            }
+            self.last_alert.insert(key.clone(), now);
+            if !quiet {
+                events.push(item.event(kind));
+            } else if !self.pending.contains(&key) {
+                self.pending.push(key);
+            }
+        }
+        events
+    }
+
+    /// 免打扰时段结束后, 把攒下的提醒发出去; 商品已经不监控了, 或者又没货了、价格又涨回去了的不发
+    pub fn flush(&mut self, now: PKDateTime, rules: &WatchRules) -> Vec<Event> {
+        if rules.is_quiet(now) {
+            return vec![];
+        }
+        let items = &self.items;
+        std::mem::take(&mut self.pending)
+            .into_iter()
+            .filter_map(|(sku, area, kind)| {
+                items
+                    .get(&(sku, area))
+                    .filter(|item| item.still_alerting(kind))
+                    .map(|item| item.event(kind))
+            })
+            .collect()
+    }
+}
+
+pub async fn load() -> Result<Vec<WatchItem>> {
+    store::run(|s| s.watch_items()).await
+}
+
+pub async fn add(item: WatchItem) -> Result<WatchItem> {
+    store::run(move |s| s.save_watch_item(&item).map(|_| item)).await
+}
+
+pub async fn remove(sku: String, area: String) -> Result<()> {
+    store::run(move |s| s.remove_watch_item(sku.as_str(), area.as_str())).await
+}
+
+/// 查询监控商品的库存和价格, 同一平台、地区的商品一起查库存, 同一平台的商品一起查价格;
+/// 查询失败的对应字段为 None
+pub async fn check(targets: Vec<(ProdPlatform, String, String)>) -> Vec<Observation> {
+    let mut stock_groups: HashMap<(ProdPlatform, String), Vec<String>> = HashMap::new();
+    let mut price_groups: HashMap<ProdPlatform, Vec<String>> = HashMap::new();
+    for (platform, sku, area) in targets.iter() {
+        stock_groups.entry((*platform, area.clone())).or_default().push(sku.clone());
+        price_groups.entry(*platform).or_default().push(sku.clone());
+    }
+    let (stocks, prices) = futures::join!(
+        join_all(stock_groups.into_iter().map(|((platform, area), skus)| async move {
+            let stocks = retailer(platform)
+                .get_prod_stocks(skus, area.clone())
+                .await
+                .map_err(|e| error!("查询监控商品库存失败:{} {}, {:?}", platform, area, e))
+                .unwrap_or_default();
+            ((platform, area), stocks)
+        })),
+        join_all(price_groups.into_iter().map(|(platform, skus)| async move {
+            let prices = retailer(platform)
+                .get_prod_prices(skus)
+                .await
+                .map_err(|e| error!("查询监控商品价格失败:{}, {:?}", platform, e))
+                .unwrap_or_default();
+            (platform, prices)
+        }))
+    );
+    let stocks: HashMap<(ProdPlatform, String), HashMap<String, StockStatus>> = stocks.into_iter().collect();
+    let prices: HashMap<ProdPlatform, HashMap<String, (String, String)>> = prices.into_iter().collect();
+    targets
+        .into_iter()
+        .map(|(platform, sku, area)| {
+            let status = stocks.get(&(platform, area.clone())).and_then(|s| s.get(&sku)).cloned();
+            let price = prices.get(&platform).and_then(|p| p.get(&sku)).map(|(p, _)| p.as_str());
+            Observation::new(sku.as_str(), "", area.as_str(), price, status)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,146 @@
+use crate::models::{ProdPlatform, StockStatus};
+use crate::services::inventory;
+use crate::services::notify::EventKind;
+use crate::services::transport::{HttpResponse, JdScenario};
+use crate::services::watch::{self, QuietHours, WatchRules};
+use crate::testing::{AREA, SKU, dt, install, observation, watch_item};
+
+#[test]
+fn test_quiet_hours() {
+    assert_eq!(QuietHours::parse(" ").unwrap(), None);
+    assert!(QuietHours::parse("23:00").is_err());
+    assert!(QuietHours::parse("25:00-07:00").is_err());
+    assert!(QuietHours::parse("08:00-08:00").is_err());
+    let t = |s: &str| chrono::NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+    // 跨过零点
+    let night = QuietHours::parse("23:00-07:00").unwrap().unwrap();
+    assert!(night.contains(t("23:00")) && night.contains(t("03:00")));
+    assert!(!night.contains(t("07:00")) && !night.contains(t("12:00")));
+    let noon = QuietHours::parse("12:00 - 13:30").unwrap().unwrap();
+    assert!(noon.contains(t("13:00")) && !noon.contains(t("13:30")) && !noon.contains(t("11:59")));
+}
+
+#[test]
+fn test_watchlist_restock() {
+    let rules = WatchRules { dedup_minutes: 60, quiet_hours: None };
+    let mut list = watch::RestockWatchlist::new(vec![watch_item(None)]);
+    let obs = |status| observation("", SKU, None, Some(status));
+    // 第一次查询只是基准
+    assert!(list.observe(&obs(StockStatus::OnSale), dt("2021-12-21 09:00:00"), &rules).is_empty());
+    assert!(list.observe(&obs(StockStatus::OutOfStock), dt("2021-12-21 09:01:00"), &rules).is_empty());
+    // 查不到库存不算变化
+    assert!(list.observe(&obs(StockStatus::Unknown), dt("2021-12-21 09:02:00"), &rules).is_empty());
+    let events = list.observe(&obs(StockStatus::Distribution), dt("2021-12-21 09:03:00"), &rules);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, EventKind::StockAvailable);
+    assert!(events[0].body.contains("北京 朝阳区"));
+    let item = list.get(SKU, AREA).unwrap();
+    assert_eq!(item.check_dt, "2021-12-21 09:03:00");
+    assert_eq!(item.status.map(|s| s.to_string()), Some("可配货".to_string()));
+
+    // 去重时间内再次到货不提醒, 过了之后提醒
+    list.observe(&obs(StockStatus::OutOfStock), dt("2021-12-21 09:10:00"), &rules);
+    assert!(list.observe(&obs(StockStatus::OnSale), dt("2021-12-21 09:20:00"), &rules).is_empty());
+    list.observe(&obs(StockStatus::OutOfStock), dt("2021-12-21 10:00:00"), &rules);
+    assert_eq!(list.observe(&obs(StockStatus::OnSale), dt("2021-12-21 10:04:00"), &rules).len(), 1);
+
+    // 不去重时每次都提醒
+    let rules = WatchRules { dedup_minutes: 0, ..rules };
+    list.observe(&obs(StockStatus::OutOfStock), dt("2021-12-21 10:05:00"), &rules);
+    assert_eq!(list.observe(&obs(StockStatus::OnSale), dt("2021-12-21 10:06:00"), &rules).len(), 1);
+    // 不在列表里的商品
+    let other = observation("", "100016777664", None, Some(StockStatus::OnSale));
+    assert!(list.observe(&other, dt("2021-12-21 10:07:00"), &rules).is_empty());
+}
+
+#[test]
+fn test_watchlist_price_drop() {
+    let rules = WatchRules { dedup_minutes: 60, quiet_hours: None };
+    let mut list = watch::RestockWatchlist::new(vec![watch_item(Some(90.0))]);
+    let obs = |price| observation("", SKU, Some(price), None);
+    assert!(list.observe(&obs(99.0), dt("2021-12-21 09:00:00"), &rules).is_empty());
+    let events = list.observe(&obs(89.0), dt("2021-12-21 09:01:00"), &rules);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, EventKind::PriceDrop);
+    assert!(events[0].body.contains("89.00 低于 90.00"));
+    // 一直低于提醒价格时不重复提醒
+    assert!(list.observe(&obs(85.0), dt("2021-12-21 11:00:00"), &rules).is_empty());
+    assert!(list.observe(&obs(95.0), dt("2021-12-21 11:01:00"), &rules).is_empty());
+    assert_eq!(list.observe(&obs(80.0), dt("2021-12-21 11:02:00"), &rules).len(), 1);
+
+    // 修改提醒价格保留查询结果; 刚加入时已经低于提醒价格的也提醒
+    list.insert(watch_item(Some(70.0)));
+    assert_eq!(list.get(SKU, AREA).unwrap().price, Some(80.0));
+    let mut fresh = watch::RestockWatchlist::new(vec![watch_item(Some(90.0))]);
+    assert_eq!(fresh.observe(&obs(80.0), dt("2021-12-21 09:00:00"), &rules).len(), 1);
+    assert_eq!(fresh.remove(SKU, AREA).map(|w| w.sku), Some(SKU.to_string()));
+    assert!(fresh.is_empty());
+}
+
+#[test]
+fn test_watchlist_quiet_hours() {
+    let rules = WatchRules {
+        dedup_minutes: 60,
+        quiet_hours: QuietHours::parse("23:00-07:00").unwrap(),
+    };
+    let mut list = watch::RestockWatchlist::new(vec![watch_item(Some(90.0))]);
+    list.observe(&observation("", SKU, Some(99.0), Some(StockStatus::OutOfStock)), dt("2021-12-21 22:00:00"), &rules);
+    // 免打扰时段里攒着, 结束后一起发
+    let obs = observation("", SKU, Some(80.0), Some(StockStatus::OnSale));
+    assert!(list.observe(&obs, dt("2021-12-21 23:30:00"), &rules).is_empty());
+    assert_eq!(list.pending_len(), 2);
+    assert!(list.flush(dt("2021-12-22 06:59:00"), &rules).is_empty());
+    let events = list.flush(dt("2021-12-22 07:00:00"), &rules);
+    let kinds: Vec<EventKind> = events.iter().map(|e| e.kind).collect();
+    assert_eq!(kinds, vec![EventKind::StockAvailable, EventKind::PriceDrop]);
+    assert_eq!(list.pending_len(), 0);
+    assert!(list.flush(dt("2021-12-22 07:01:00"), &rules).is_empty());
+}
+
+#[test]
+fn test_watchlist_quiet_hours_latest_only() {
+    let rules = WatchRules {
+        dedup_minutes: 0,
+        quiet_hours: QuietHours::parse("23:00-07:00").unwrap(),
+    };
+    let mut list = watch::RestockWatchlist::new(vec![watch_item(Some(90.0))]);
+    let obs = |price, status| observation("", SKU, Some(price), Some(status));
+    list.observe(&obs(99.0, StockStatus::OutOfStock), dt("2021-12-21 22:00:00"), &rules);
+    // 整晚反复到货、降价, 每种提醒只攒一个
+    for h in 0..5 {
+        let t = format!("2021-12-22 0{}:00:00", h);
+        list.observe(&obs(95.0, StockStatus::OutOfStock), dt(t.as_str()), &rules);
+        list.observe(&obs(80.0 - h as f64, StockStatus::OnSale), dt(t.replace(":00:00", ":30:00").as_str()), &rules);
+    }
+    assert_eq!(list.pending_len(), 2);
+    // 又没货了, 到货提醒过时了不发; 降价提醒用最新的价格
+    list.observe(&obs(70.0, StockStatus::OutOfStock), dt("2021-12-22 06:00:00"), &rules);
+    let events = list.flush(dt("2021-12-22 07:00:00"), &rules);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, EventKind::PriceDrop);
+    assert!(events[0].body.contains("70.00 低于 90.00"));
+
+    // 不再监控的商品不发
+    list.observe(&obs(95.0, StockStatus::OnSale), dt("2021-12-22 23:00:00"), &rules);
+    list.observe(&obs(85.0, StockStatus::OnSale), dt("2021-12-22 23:30:00"), &rules);
+    assert_eq!(list.pending_len(), 2);
+    list.remove(SKU, AREA);
+    assert!(list.flush(dt("2021-12-23 07:00:00"), &rules).is_empty());
+    assert_eq!(list.pending_len(), 0);
+}
+
+#[tokio::test]
+async fn test_watch_check() {
+    let g = install(JdScenario::Success).await;
+    let obs = watch::check(vec![(ProdPlatform::JD, SKU.to_string(), AREA.to_string())]).await;
+    assert_eq!(obs.len(), 1);
+    assert_eq!((obs[0].sku.as_str(), obs[0].area.as_str(), obs[0].price), (SKU, AREA, Some(99.0)));
+    assert_eq!(obs[0].status.map(|s| s.to_string()), Some("有货".to_string()));
+
+    // 价格查不到时只更新库存
+    inventory::clear();
+    g.mock.replace("p.3.cn/prices/mgets", HttpResponse::ok("not json"));
+    let obs = watch::check(vec![(ProdPlatform::JD, SKU.to_string(), AREA.to_string())]).await;
+    assert_eq!(obs[0].price, None);
+    assert!(obs[0].status.is_some());
+}
@@ -0,0 +1,411 @@
+//! 客户端设置, 按层覆盖: 默认值 < conf.toml < 环境变量 < 设置页保存在本地数据库里的设置。
+//! 设置页修改后马上生效, 不需要重启
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+
+use log::{error, info, warn, LevelFilter};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{OpError, Result};
+use crate::services::notify::NotifyConfig;
+use crate::services::shopping_cart::CLAIM_LEASE_SECONDS;
+use crate::services::store::{self, Store, SETTINGS_KEY};
+use crate::services::watch::QuietHours;
+use crate::{Config, CONFIG};
+
+const ENV_PREFIX: &'static str = "JD_MIAOSHA_";
+
+/// 一层设置, 没有设置的值用下面一层的
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SettingsLayer {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub addr: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_ip_pool_url: Option<String>,
+    // 并发抢购的数量
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workers: Option<usize>,
+    // 定时抢购提前多少毫秒开始
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ahead_millis: Option<i64>,
+    // 检查库存、cookie、激活状态、续约抢购租约的间隔
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stock_check_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cookie_check_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heartbeat_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim_renew_secs: Option<u64>,
+    // 刷新未签收订单物流的间隔, 物流多少天没更新算停滞
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_refresh_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_stale_days: Option<u32>,
+    // 订单列表里没有付款截止时间时, 按下单后多少分钟内付款估算
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pay_window_minutes: Option<i64>,
+    // 到货提醒: 检查间隔, 相同提醒的间隔分钟数, 免打扰时段如 23:00-07:00
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch_check_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch_dedup_minutes: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch_quiet_hours: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify: Option<NotifyConfig>,
+}
+
+fn parse_env<T: FromStr, F: Fn(&str) -> Option<String>>(get: &F, key: &str) -> Option<T> {
+    let value = get(key)?;
+    match value.trim().parse::<T>() {
+        Ok(v) => Some(v),
+        Err(_) => {
+            warn!("环境变量的值不对, 忽略:{}={}", key, value);
+            None
+        }
+    }
+}
+
+impl SettingsLayer {
+    /// upper 里设置了的值覆盖自己的
+    pub fn merge(self, upper: SettingsLayer) -> SettingsLayer {
+        SettingsLayer {
+            addr: upper.addr.or(self.addr),
+            proxy_ip_pool_url: upper.proxy_ip_pool_url.or(self.proxy_ip_pool_url),
+            workers: upper.workers.or(self.workers),
+            ahead_millis: upper.ahead_millis.or(self.ahead_millis),
+            stock_check_secs: upper.stock_check_secs.or(self.stock_check_secs),
+            cookie_check_secs: upper.cookie_check_secs.or(self.cookie_check_secs),
+            heartbeat_secs: upper.heartbeat_secs.or(self.heartbeat_secs),
+            claim_renew_secs: upper.claim_renew_secs.or(self.claim_renew_secs),
+            track_refresh_secs: upper.track_refresh_secs.or(self.track_refresh_secs),
+            track_stale_days: upper.track_stale_days.or(self.track_stale_days),
+            pay_window_minutes: upper.pay_window_minutes.or(self.pay_window_minutes),
+            watch_check_secs: upper.watch_check_secs.or(self.watch_check_secs),
+            watch_dedup_minutes: upper.watch_dedup_minutes.or(self.watch_dedup_minutes),
+            watch_quiet_hours: upper.watch_quiet_hours.or(self.watch_quiet_hours),
+            log_level: upper.log_level.or(self.log_level),
+            notify: upper.notify.or(self.notify),
+        }
+    }
+
+    /// conf.toml 里的 addr、proxy_ip_pool_url、[notify] 和 [settings]
+    pub fn from_config(config: &Config) -> Self {
+        SettingsLayer {
+            addr: Some(config.addr.clone()).filter(|a| !a.is_empty()),
+            proxy_ip_pool_url: config.proxy_ip_pool_url.clone(),
+            notify: Some(config.notify.clone()),
+            ..config.settings.clone()
+        }
+    }
+
+    /// JD_MIAOSHA_ 开头的环境变量, 兼容以前的 workers 和 ahead
+    pub fn from_env<F: Fn(&str) -> Option<String>>(get: F) -> Self {
+        let key = |name: &str| format!("{}{}", ENV_PREFIX, name);
+        SettingsLayer {
+            addr: get(&key("ADDR")),
+            proxy_ip_pool_url: get(&key("PROXY_IP_POOL_URL")),
+            workers: parse_env(&get, &key("WORKERS"))
+                .or_else(|| parse_env::<usize, _>(&get, "workers").map(|w| w.clamp(5, 100))),
+            ahead_millis: parse_env(&get, &key("AHEAD_MILLIS"))
+                .or_else(|| parse_env::<i64, _>(&get, "ahead").map(|a| a.clamp(500, 10000))),
+            stock_check_secs: parse_env(&get, &key("STOCK_CHECK_SECS")),
+            cookie_check_secs: parse_env(&get, &key("COOKIE_CHECK_SECS")),
+            heartbeat_secs: parse_env(&get, &key("HEARTBEAT_SECS")),
+            claim_renew_secs: parse_env(&get, &key("CLAIM_RENEW_SECS")),
+            track_refresh_secs: parse_env(&get, &key("TRACK_REFRESH_SECS")),
+            track_stale_days: parse_env(&get, &key("TRACK_STALE_DAYS")),
+            pay_window_minutes: parse_env(&get, &key("PAY_WINDOW_MINUTES")),
+            watch_check_secs: parse_env(&get, &key("WATCH_CHECK_SECS")),
+            watch_dedup_minutes: parse_env(&get, &key("WATCH_DEDUP_MINUTES")),
+            watch_quiet_hours: get(&key("WATCH_QUIET_HOURS")),
+            log_level: get(&key("LOG_LEVEL")),
+            notify: None,
+        }
+    }
+}
+
+/// 合并之后的设置
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub addr: String,
+    pub proxy_ip_pool_url: Option<String>,
+    pub workers: usize,
+    pub ahead_millis: i64,
+    pub stock_check_secs: u64,
+    pub cookie_check_secs: u64,
+    pub heartbeat_secs: u64,
+    pub claim_renew_secs: u64,
+    pub track_refresh_secs: u64,
+    pub track_stale_days: u32,
+    pub pay_window_minutes: i64,
+    pub watch_check_secs: u64,
+    pub watch_dedup_minutes: i64,
+    // 空字符串表示不设置免打扰
+    pub watch_quiet_hours: String,
+    pub log_level: String,
+    pub notify: NotifyConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            addr: String::new(),
+            proxy_ip_pool_url: None,
+            workers: 15,
+            ahead_millis: 1200,
+            stock_check_secs: 3,
+            cookie_check_secs: 180,
+            heartbeat_secs: 300,
+            claim_renew_secs: 30,
+            track_refresh_secs: 1800,
+            track_stale_days: 3,
+            pay_window_minutes: 30,
+            watch_check_secs: 60,
+            watch_dedup_minutes: 60,
+            watch_quiet_hours: String::new(),
+            log_level: "info".to_string(),
+            notify: NotifyConfig::default(),
+        }
+    }
+}
+
+fn check_range<T: PartialOrd + std::fmt::Display>(name: &str, v: T, min: T, max: T) -> Result<()> {
+    if v < min || v > max {
+        return Err(OpError::InvalidSettings(format!("{}要在{}到{}之间: {}", name, min, max, v)).into());
+    }
+    Ok(())
+}
+
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+impl Settings {
+    /// 没有设置的用默认值
+    pub fn resolve(layer: SettingsLayer) -> Settings {
+        let d = Settings::default();
+        Settings {
+            addr: layer.addr.unwrap_or(d.addr),
+            proxy_ip_pool_url: layer.proxy_ip_pool_url.filter(|u| !u.trim().is_empty()),
+            workers: layer.workers.unwrap_or(d.workers),
+            ahead_millis: layer.ahead_millis.unwrap_or(d.ahead_millis),
+            stock_check_secs: layer.stock_check_secs.unwrap_or(d.stock_check_secs),
+            cookie_check_secs: layer.cookie_check_secs.unwrap_or(d.cookie_check_secs),
+            heartbeat_secs: layer.heartbeat_secs.unwrap_or(d.heartbeat_secs),
+            claim_renew_secs: layer.claim_renew_secs.unwrap_or(d.claim_renew_secs),
+            track_refresh_secs: layer.track_refresh_secs.unwrap_or(d.track_refresh_secs),
+            track_stale_days: layer.track_stale_days.unwrap_or(d.track_stale_days),
+            pay_window_minutes: layer.pay_window_minutes.unwrap_or(d.pay_window_minutes),
+            watch_check_secs: layer.watch_check_secs.unwrap_or(d.watch_check_secs),
+            watch_dedup_minutes: layer.watch_dedup_minutes.unwrap_or(d.watch_dedup_minutes),
+            watch_quiet_hours: layer.watch_quiet_hours.unwrap_or(d.watch_quiet_hours),
+            log_level: layer.log_level.unwrap_or(d.log_level),
+            notify: layer.notify.unwrap_or(d.notify),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !is_http_url(self.addr.as_str()) {
+            return Err(OpError::InvalidSettings(format!("服务端地址要以 http:// 或 https:// 开头: {}", self.addr)).into());
+        }
+        if let Some(url) = self.proxy_ip_pool_url.as_ref() {
+            if !is_http_url(url.as_str()) {
+                return Err(OpError::InvalidSettings(format!("代理ip池地址要以 http:// 或 https:// 开头: {}", url)).into());
+            }
+        }
+        check_range("并发数", self.workers, 5, 100)?;
+        check_range("提前抢购毫秒数", self.ahead_millis, 500, 10000)?;
+        check_range("库存检查间隔秒数", self.stock_check_secs, 1, 600)?;
+        check_range("cookie检查间隔秒数", self.cookie_check_secs, 30, 3600)?;
+        check_range("激活检查间隔秒数", self.heartbeat_secs, 30, 3600)?;
+        // 续约间隔要小于租约时长的一半, 不然网络慢一点租约就过期了
+        check_range("租约续约间隔秒数", self.claim_renew_secs, 5, CLAIM_LEASE_SECONDS as u64 / 2)?;
+        check_range("物流刷新间隔秒数", self.track_refresh_secs, 60, 86400)?;
+        check_range("物流停滞天数", self.track_stale_days, 1, 30)?;
+        check_range("付款时限分钟数", self.pay_window_minutes, 5, 1440)?;
+        check_range("到货提醒检查间隔秒数", self.watch_check_secs, 10, 3600)?;
+        check_range("相同提醒间隔分钟数", self.watch_dedup_minutes, 0, 1440)?;
+        QuietHours::parse(self.watch_quiet_hours.as_str()).map_err(OpError::InvalidSettings)?;
+        if LevelFilter::from_str(self.log_level.as_str()).is_err() {
+            return Err(OpError::InvalidSettings(format!(
+                "日志级别只能是 off/error/warn/info/debug/trace: {}",
+                self.log_level
+            ))
+            .into());
+        }
+        if let Some(webhook) = self.notify.webhook.as_ref() {
+            if !is_http_url(webhook.url.as_str()) {
+                return Err(OpError::InvalidSettings(format!("webhook 地址要以 http:// 或 https:// 开头: {}", webhook.url)).into());
+            }
+        }
+        if let Some(email) = self.notify.email.as_ref() {
+            if email.host.is_empty() || email.to.is_empty() {
+                return Err(OpError::InvalidSettings("邮件通知要配置 host 和 to".to_string()).into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn log_level_filter(&self) -> LevelFilter {
+        LevelFilter::from_str(self.log_level.as_str()).unwrap_or(LevelFilter::Info)
+    }
+
+    /// 和 base 不一样的值, 设置页只保存改过的, 没改的还是跟着 conf.toml 和环境变量
+    pub fn diff(&self, base: &Settings) -> SettingsLayer {
+        fn changed<T: PartialEq + Clone>(v: &T, base: &T) -> Option<T> {
+            if v != base {
+                Some(v.clone())
+            } else {
+                None
+            }
+        }
+        SettingsLayer {
+            addr: changed(&self.addr, &base.addr),
+            // 清空代理地址时保存空字符串, 才能覆盖下面一层的
+            proxy_ip_pool_url: changed(&self.proxy_ip_pool_url, &base.proxy_ip_pool_url)
+                .map(|u| u.unwrap_or_default()),
+            workers: changed(&self.workers, &base.workers),
+            ahead_millis: changed(&self.ahead_millis, &base.ahead_millis),
+            stock_check_secs: changed(&self.stock_check_secs, &base.stock_check_secs),
+            cookie_check_secs: changed(&self.cookie_check_secs, &base.cookie_check_secs),
+            heartbeat_secs: changed(&self.heartbeat_secs, &base.heartbeat_secs),
+            claim_renew_secs: changed(&self.claim_renew_secs, &base.claim_renew_secs),
+            track_refresh_secs: changed(&self.track_refresh_secs, &base.track_refresh_secs),
+            track_stale_days: changed(&self.track_stale_days, &base.track_stale_days),
+            pay_window_minutes: changed(&self.pay_window_minutes, &base.pay_window_minutes),
+            watch_check_secs: changed(&self.watch_check_secs, &base.watch_check_secs),
+            watch_dedup_minutes: changed(&self.watch_dedup_minutes, &base.watch_dedup_minutes),
+            watch_quiet_hours: changed(&self.watch_quiet_hours, &base.watch_quiet_hours),
+            log_level: changed(&self.log_level, &base.log_level),
+            notify: changed(&self.notify, &base.notify),
+        }
+    }
+}
+
+struct State {
+    // conf.toml 和环境变量合并后的设置
+    base: SettingsLayer,
+    current: Settings,
+}
+
+lazy_static! {
+    static ref STATE: RwLock<State> = RwLock::new(State {
+        base: SettingsLayer::default(),
+        current: Settings::default(),
+    });
+    // 日志配置文件和 log4rs 的句柄, 修改日志级别时重新加载
+    static ref LOGGER: Mutex<Option<(PathBuf, log4rs::Handle)>> = Mutex::new(None);
+}
+
+/// 当前生效的设置
+pub fn get() -> Settings {
+    STATE.read().unwrap().current.clone()
+}
+
+pub fn workers() -> usize {
+    STATE.read().unwrap().current.workers
+}
+
+pub fn ahead_millis() -> i64 {
+    STATE.read().unwrap().current.ahead_millis
+}
+
+/// 读设置页保存的设置, 没有或者格式不对时返回空的
+pub fn read_layer(store: &Store) -> SettingsLayer {
+    match store.get_setting(SETTINGS_KEY) {
+        Ok(Some(data)) => serde_json::from_str(data.as_str()).unwrap_or_else(|e| {
+            error!("保存的设置格式不对:{:?}", e);
+            SettingsLayer::default()
+        }),
+        Ok(None) => SettingsLayer::default(),
+        Err(e) => {
+            error!("读取保存的设置失败:{:?}", e);
+            SettingsLayer::default()
+        }
+    }
+}
+
+pub fn write_layer(store: &Store, layer: &SettingsLayer) -> Result<()> {
+    store.set_setting(SETTINGS_KEY, serde_json::to_string_pretty(layer)?.as_str())
+}
+
+/// 读完 conf.toml 之后合并各层设置并生效, 设置页保存的不合法时忽略掉
+pub fn load(config: &mut Config) -> Result<Settings> {
+    let base = SettingsLayer::from_config(config).merge(SettingsLayer::from_env(|k| env::var(k).ok()));
+    Settings::resolve(base.clone()).validate()?;
+    let mut current = Settings::resolve(base.clone().merge(read_layer(store::store())));
+    if let Err(e) = current.validate() {
+        error!("设置页保存的设置不合法, 不使用:{:?}", e);
+        current = Settings::resolve(base.clone());
+    }
+    apply_to(config, &current);
+    STATE.write().unwrap().base = base;
+    set_current(current.clone());
+    Ok(current)
+}
+
+fn apply_to(config: &mut Config, settings: &Settings) {
+    config.addr = settings.addr.clone();
+    config.proxy_ip_pool_url = settings.proxy_ip_pool_url.clone();
+    config.notify = settings.notify.clone();
+}
+
+fn set_current(settings: Settings) {
+    let level = settings.log_level_filter();
+    STATE.write().unwrap().current = settings;
+    apply_log_level(level);
+}
+
+async fn apply(settings: Settings) {
+    apply_to(&mut CONFIG.write().await, &settings);
+    info!("设置已生效:{:?}", settings);
+    set_current(settings);
+}
+
+/// 设置页保存: 只保存和 conf.toml、环境变量不一样的值
+pub async fn save(settings: Settings) -> Result<Settings> {
+    settings.validate()?;
+    let base = Settings::resolve(STATE.read().unwrap().base.clone());
+    let layer = settings.diff(&base);
+    store::run(move |s| write_layer(s, &layer)).await?;
+    apply(settings.clone()).await;
+    Ok(settings)
+}
+
+/// 删掉设置页保存的设置, 恢复成 conf.toml 和环境变量的
+pub async fn reset() -> Result<Settings> {
+    store::run(|s| s.remove_setting(SETTINGS_KEY)).await?;
+    let settings = Settings::resolve(STATE.read().unwrap().base.clone());
+    apply(settings.clone()).await;
+    Ok(settings)
+}
+
+/// 初始化日志, 之后可以按设置修改根日志级别
+pub fn init_logger(log_yaml: PathBuf) -> anyhow::Result<()> {
+    let config = log4rs::config::load_config_file(&log_yaml, Default::default())?;
+    let handle = log4rs::init_config(config)?;
+    *LOGGER.lock().unwrap() = Some((log_yaml, handle));
+    Ok(())
+}
+
+fn apply_log_level(level: LevelFilter) {
+    if let Some((path, handle)) = LOGGER.lock().unwrap().as_ref() {
+        match log4rs::config::load_config_file(path, Default::default()) {
+            Ok(mut config) => {
+                config.root_mut().set_level(level);
+                handle.set_config(config);
+            }
+            Err(e) => error!("重新加载日志配置失败:{}, {:?}", path.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,111 @@
+use crate::error::{JdMiaoshaError, OpError};
+use crate::services::notify::{self, EventKind, SinkKind};
+use crate::services::store::Store;
+use crate::settings::{self, Settings, SettingsLayer};
+use crate::testing::env_of;
+
+#[test]
+fn test_settings_layers() {
+    let config: crate::Config = toml::from_str(
+        r#"
+addr = "http://127.0.0.1:48180"
+[settings]
+workers = 20
+stock_check_secs = 5
+log_level = "debug"
+[notify.routes]
+purchase_success = ["sound"]
+"#,
+    )
+    .unwrap();
+    let file = SettingsLayer::from_config(&config);
+    let env = SettingsLayer::from_env(env_of(&[
+        ("JD_MIAOSHA_WORKERS", "30"),
+        ("JD_MIAOSHA_HEARTBEAT_SECS", "not a number"),
+        // 以前的环境变量还能用, 超出范围的截断
+        ("ahead", "20000"),
+    ]));
+    assert_eq!(env.heartbeat_secs, None);
+    let ui = SettingsLayer {
+        stock_check_secs: Some(10),
+        proxy_ip_pool_url: Some("".to_string()),
+        ..Default::default()
+    };
+    let s = Settings::resolve(file.merge(env).merge(ui));
+    assert_eq!(s.addr, "http://127.0.0.1:48180");
+    assert_eq!((s.workers, s.ahead_millis), (30, 10000));
+    assert_eq!((s.stock_check_secs, s.cookie_check_secs, s.heartbeat_secs), (10, 180, 300));
+    assert_eq!(s.log_level, "debug");
+    assert_eq!(s.log_level_filter(), log::LevelFilter::Debug);
+    assert_eq!(s.proxy_ip_pool_url, None);
+    assert_eq!(s.notify.routes[&EventKind::PurchaseSuccess], vec![SinkKind::Sound]);
+    assert!(s.validate().is_ok());
+}
+
+#[test]
+fn test_settings_validate() {
+    let ok = Settings {
+        addr: "http://127.0.0.1:48180".to_string(),
+        ..Default::default()
+    };
+    assert!(ok.validate().is_ok());
+    let invalid = |s: Settings| match s.validate() {
+        Err(JdMiaoshaError::OpError(OpError::InvalidSettings(_))) => true,
+        _ => false,
+    };
+    assert!(invalid(Settings::default()));
+    assert!(invalid(Settings { addr: "127.0.0.1:48180".to_string(), ..ok.clone() }));
+    assert!(invalid(Settings { workers: 4, ..ok.clone() }));
+    assert!(invalid(Settings { workers: 101, ..ok.clone() }));
+    assert!(invalid(Settings { ahead_millis: 499, ..ok.clone() }));
+    assert!(invalid(Settings { stock_check_secs: 0, ..ok.clone() }));
+    assert!(invalid(Settings { cookie_check_secs: 10, ..ok.clone() }));
+    assert!(invalid(Settings { heartbeat_secs: 3601, ..ok.clone() }));
+    // 续约间隔不能超过租约时长的一半
+    assert!(invalid(Settings { claim_renew_secs: 61, ..ok.clone() }));
+    assert!(!invalid(Settings { claim_renew_secs: 60, ..ok.clone() }));
+    assert!(invalid(Settings { track_refresh_secs: 59, ..ok.clone() }));
+    assert!(invalid(Settings { track_stale_days: 0, ..ok.clone() }));
+    assert!(invalid(Settings { pay_window_minutes: 1441, ..ok.clone() }));
+    assert!(invalid(Settings { watch_check_secs: 9, ..ok.clone() }));
+    assert!(invalid(Settings { watch_dedup_minutes: -1, ..ok.clone() }));
+    assert!(invalid(Settings { watch_quiet_hours: "23:00".to_string(), ..ok.clone() }));
+    assert!(!invalid(Settings { watch_quiet_hours: "23:00-07:00".to_string(), ..ok.clone() }));
+    assert!(invalid(Settings { log_level: "verbose".to_string(), ..ok.clone() }));
+    let mut webhook = ok.clone();
+    webhook.notify.webhook = Some(notify::WebhookConfig {
+        url: "localhost/hook".to_string(),
+        ..Default::default()
+    });
+    assert!(invalid(webhook));
+}
+
+#[test]
+fn test_settings_persist() {
+    let base = Settings {
+        addr: "http://127.0.0.1:48180".to_string(),
+        proxy_ip_pool_url: Some("http://proxy".to_string()),
+        ..Default::default()
+    };
+    let mut edited = base.clone();
+    edited.heartbeat_secs = 60;
+    edited.proxy_ip_pool_url = None;
+    edited.notify.routes.insert(EventKind::CookieExpired, vec![SinkKind::Desktop]);
+    // 只保存改过的值
+    let layer = edited.diff(&base);
+    assert_eq!(layer.heartbeat_secs, Some(60));
+    assert_eq!(layer.proxy_ip_pool_url, Some("".to_string()));
+    assert_eq!((layer.addr.as_ref(), layer.workers), (None, None));
+
+    let s = Store::open_in_memory().unwrap();
+    assert_eq!(settings::read_layer(&s), SettingsLayer::default());
+    settings::write_layer(&s, &layer).unwrap();
+    let saved = settings::read_layer(&s);
+    assert_eq!(saved, layer);
+    let base_layer = SettingsLayer {
+        addr: Some(base.addr.clone()),
+        proxy_ip_pool_url: base.proxy_ip_pool_url.clone(),
+        ..Default::default()
+    };
+    assert_eq!(Settings::resolve(base_layer.merge(saved)), edited);
+}
@@ -0,0 +1,236 @@
+//! 测试共用的 mock 和数据
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::models::{AddressForm, Area, StockStatus};
+use crate::services::inventory;
+use crate::services::price_history::Observation;
+use crate::services::transport::{self, JdScenario, MockTransport};
+use crate::services::watch::WatchItem;
+
+pub(crate) const SKU: &str = "100012043978";
+pub(crate) const AREA: &str = "1_72_2819_0";
+
+lazy_static! {
+    // transport 是全局的, 用到 mock 的测试要串行执行
+    static ref MOCK_LOCK: Mutex<()> = Mutex::new(());
+}
+
+pub(crate) struct MockGuard {
+    pub(crate) mock: Arc<MockTransport>,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl Drop for MockGuard {
+    fn drop(&mut self) {
+        transport::reset_transport();
+    }
+}
+
+/// 用到全局 transport 或 endpoints 的测试拿着它串行执行
+pub(crate) async fn serial() -> MutexGuard<'static, ()> {
+    MOCK_LOCK.lock().await
+}
+
+pub(crate) async fn install(scenario: JdScenario) -> MockGuard {
+    let guard = serial().await;
+    let mock = Arc::new(MockTransport::jd(scenario, SKU));
+    transport::set_transport(mock.clone());
+    inventory::clear();
+    MockGuard {
+        mock,
+        _guard: guard,
+    }
+}
+
+pub(crate) fn cookie() -> Arc<String> {
+    Arc::new("pt_key=mock; pt_pin=mock".to_string())
+}
+
+/// 临时目录下按进程区分的路径, 已有的先删掉
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("jd_miaosha_{}_{}", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_dir_all(&path);
+    path
+}
+
+pub(crate) fn env_of(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+    move |key| vars.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+}
+
+pub(crate) fn address_form() -> AddressForm {
+    AddressForm {
+        receiver: "张三".to_string(),
+        area_ids: [1, 72, 2819, 0],
+        area_zone: "北京朝阳区三环以内".to_string(),
+        address: "建国路88号SOHO现代城A座1001".to_string(),
+        mobile_phone: "13800000000".to_string(),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn area_children() -> BTreeMap<i64, Area> {
+    let area = |items: &[(i64, &str)]| items.iter().map(|(id, name)| (*id, name.to_string())).collect::<Area>();
+    vec![
+        (0, area(&[(1, "北京"), (2, "上海")])),
+        (1, area(&[(72, "朝阳区"), (2800, "海淀区")])),
+        (2, area(&[(2813, "徐汇区")])),
+        (72, area(&[(2819, "三环以内"), (2839, "三环到四环之间")])),
+        (2800, Area::new()),
+    ]
+    .into_iter()
+    .collect()
+}
+
+pub(crate) fn observation(dt: &str, sku: &str, price: Option<f64>, status: Option<StockStatus>) -> Observation {
+    Observation {
+        observe_dt: dt.to_string(),
+        sku: sku.to_string(),
+        name: String::new(),
+        area: AREA.to_string(),
+        price,
+        status,
+    }
+}
+
+pub(crate) fn dt(s: &str) -> crate::PKDateTime {
+    crate::utils::parse_datetime(s).unwrap()
+}
+
+pub(crate) fn watch_item(target_price: Option<f64>) -> WatchItem {
+    WatchItem {
+        sku: SKU.to_string(),
+        name: "手机".to_string(),
+        area: AREA.to_string(),
+        area_name: "北京 朝阳区".to_string(),
+        target_price,
+        create_dt: "2021-12-20 09:00:00".to_string(),
+        ..Default::default()
+    }
+}
+
+/// workspace 根目录的 fixtures, fake_retailer 也用这一份
+pub(crate) fn fixtures_dir() -> PathBuf {
+    PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../fixtures"))
+}
+
+pub(crate) fn fixture(name: &str) -> String {
+    std::fs::read_to_string(fixtures_dir().join(name)).unwrap()
+}
+
+/// 和 fake_retailer 一样替换模板里的 {{key}}
+pub(crate) fn render_fixture(name: &str, vars: &[(&str, &str)]) -> String {
+    fake_retailer::render(fixture(name), vars)
+}
+
+/// 商品详情页, category 是逗号分隔的分类, base 是购买按钮链接的地址
+pub(crate) fn item_page(purchase_type: &str, sku: &str, name: &str, category: &str, base: &str) -> String {
+    let category: Vec<String> = category.split(',').map(|c| format!("\"{}\"", c)).collect();
+    render_fixture(
+        format!("item_{}.html", purchase_type.to_lowercase()).as_str(),
+        &[("sku", sku), ("name", name), ("category", category.join(",").as_str()), ("base", base)],
+    )
+}
+
+/// 订单列表页: 一个待付款的订单, 一个有两个商品的已完成订单
+pub(crate) fn order_list_page() -> String {
+    let row = |id: &str, dt: &str, name: &str, receiver: &str, total: &str, status: &str, deadline: &str, pay_url: &str| {
+        render_fixture(
+            "order_row.html",
+            &[
+                ("order_id", id),
+                ("create_dt", dt),
+                ("sku", "100009077475"),
+                ("name", name),
+                ("num", "1"),
+                ("receiver", receiver),
+                ("total", total),
+                ("status", status),
+                ("pay_deadline", deadline),
+                ("pay_url", pay_url),
+            ],
+        )
+    };
+    let rows = vec![
+        row(
+            "163821896625",
+            "2021-06-01 10:08:12",
+            "Apple iPhone 12 (A2404) 128GB 蓝色",
+            "张三",
+            "5999.00",
+            "等待付款",
+            "2021-06-02 10:08:12",
+            "//pcashier.jd.com/cashier/index.action?orderId=163821896625",
+        ),
+        row(
+            "163900112233",
+            "2021-05-20 21:30:45",
+            "华为 HUAWEI Mate 40 Pro 5G 麒麟9000 8GB+256GB 亮黑色",
+            "李四",
+            "6999.00",
+            "已完成",
+            "",
+            "",
+        ),
+        row("163900112233", "2021-05-20 21:30:45", "华为 40W 超级快充充电器", "李四", "6999.00", "已完成", "", ""),
+    ];
+    render_fixture("order_list.html", &[("orders", rows.join("\n").as_str())])
+}
+
+/// 收货地址列表页, 和 fake_retailer 第一次查询时的两个默认地址一样
+pub(crate) fn address_list_page() -> String {
+    let row = |id: &str, receiver: &str, area: &str, address: &str, mobile: &str, email: &str| {
+        render_fixture(
+            "address_row.html",
+            &[
+                ("id", id),
+                ("receiver", receiver),
+                ("area", area),
+                ("address", address),
+                ("mobile", mobile),
+                ("phone", ""),
+                ("email", email),
+            ],
+        )
+    };
+    let rows = vec![
+        row("3759910743", "张三", "北京朝阳区三环以内", "建国路88号SOHO现代城A座1001", "138****0000", "zhangsan@example.com"),
+        row("3759910888", "李四", "上海浦东新区外环以内", "世纪大道100号", "139****1111", ""),
+    ];
+    render_fixture("address_list.html", &[("addresses", rows.join("\n").as_str())])
+}
+
+/// 搜索 iPhone 的第 2 页, 共 5 页; 最后一个商品不是自营店铺
+pub(crate) fn search_page() -> String {
+    let highlight = r#"<font class="skcolor_ljg">iPhone</font>"#;
+    let item = |sku: &str, name: &str, price: &str, shop: &str| {
+        render_fixture(
+            "search_item.html",
+            &[
+                ("sku", sku),
+                ("name", name),
+                ("name_html", name.replace("iPhone", highlight).as_str()),
+                ("price", price),
+                ("shop", shop),
+            ],
+        )
+    };
+    let items = vec![
+        item(
+            "100009077475",
+            "Apple iPhone 12 (A2404) 128GB 蓝色 支持移动联通电信5G 双卡双待手机",
+            "5999.00",
+            "Apple产品京东自营旗舰店",
+        ),
+        item("100016777664", "小米 Redmi K40 游戏增强版 天玑1200 12GB+256GB 暗影", "1999.00", "小米京东自营旗舰店"),
+        item("10025346234651", "iPhone 12 手机壳 透明防摔", "19.90", "数码配件专营店"),
+    ];
+    render_fixture(
+        "search.html",
+        &[("keyword", "iPhone"), ("items", items.join("\n").as_str()), ("page", "2"), ("pages", "5")],
+    )
+}
@@ -1,6 +1,8 @@
+use std::convert::TryFrom;
 use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use serde::de::Error as DeError;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Hash)]
 #[repr(u8)]
 pub enum ProdPlatform {
     JD = 0
@@ -25,15 +27,23 @@ impl ProdPlatform {
             D: Deserializer<'de>,
     {
         let s = u8::deserialize(deserializer)?;
-        Ok(s.into())
+        ProdPlatform::try_from(s).map_err(DeError::custom)
+    }
+
+    // 目前支持的所有平台
+    pub fn all() -> &'static [ProdPlatform] {
+        &[ProdPlatform::JD]
     }
 }
 
-impl From<u8> for ProdPlatform {
-    fn from(level: u8) -> Self {
-        match level {
-            0 => ProdPlatform::JD,
-            _ => unreachable!()
+impl TryFrom<u8> for ProdPlatform {
+    type Error = String;
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(ProdPlatform::JD),
+            // 数据损坏或者服务端新增的平台: 返回错误而不是 panic, 但含有这一行的整页数据仍然会反序列化失败
+            _ => Err(format!("unknown platform: {}", v))
         }
     }
 }
@@ -174,59 +184,3 @@ impl From<VipLevel> for u8 {
         level.into_u8()
     }
 }
-
-
-
-
-#[derive(Copy, Clone, Debug)]
-#[repr(u8)]
-pub enum Category {
-    Computer = 0
-}
-
-
-impl Category {
-    pub fn into_u8(self) -> u8 {
-        match self {
-            Category::Computer => 0,
-        }
-    }
-
-    pub fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-        where
-            S: Serializer,
-    {
-        serializer.serialize_u8(self.into_u8())
-    }
-
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Category, D::Error>
-        where
-            D: Deserializer<'de>,
-    {
-        let s = u8::deserialize(deserializer)?;
-        Ok(s.into())
-    }
-}
-
-impl From<u8> for Category {
-    fn from(level: u8) -> Self {
-        match level {
-            0 => Category::Computer,
-            _ => unreachable!()
-        }
-    }
-}
-
-impl From<Category> for u8 {
-    fn from(pt: Category) -> u8 {
-        match pt {
-            Category::Computer => 0,
-        }
-    }
-}
-
-impl Default for Category {
-    fn default() -> Self {
-        Category::Computer
-    }
-}
\ No newline at end of file
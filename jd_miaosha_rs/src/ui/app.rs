@@ -11,20 +11,32 @@ use tokio::sync::Mutex;
 use tokio::time::Duration;
 use toml::to_string;
 
-use crate::{IDType, PKLocal, PRESALE, SECOND_KILL, YUYUE};
-use crate::models::{UserInfo, UserState};
+use crate::IDType;
+use crate::models::{ProdPlatform, UserInfo, UserState};
 use crate::services::area::AreaService;
-use crate::services::delivery_address::DeliveryAddressService;
-use crate::services::order::{OrderService, QueryCondition};
+use crate::services::budget;
+use crate::services::delivery_address;
+use crate::services::notify::{self, Event, EventKind};
+use crate::services::order::QueryCondition;
+use crate::services::order_export;
+use crate::services::scheduler::{self, scheduler};
+use crate::services::retailer::{check_max_price, plan_submit_orders, retailer, submit_within_budget, SubmitOrderReq};
 use crate::services::shopping_cart::ShoppingCartService;
+use crate::services::unpaid;
 use crate::services::user::UserService;
+use crate::settings;
+use crate::ui::components::BudgetComponent;
+use crate::ui::components::SchedulerComponent;
+use crate::ui::components::SettingsComponent;
 use crate::ui::components::DeliveryAddressComponent;
 use crate::ui::components::GoodsComponent;
 use crate::ui::components::OrderComponent;
+use crate::ui::components::PriceHistoryComponent;
 use crate::ui::components::ShoppingCartComponent;
 use crate::ui::components::UserComponent;
+use crate::ui::components::WatchComponent;
 use crate::ui::UserMessage::Select;
-use crate::utils::{greater_than_now, workers};
+use crate::utils::{ahead_purchase_millis, datetime_fmt, now, workers};
 
 use super::{flags, message::*, style};
 use super::components;
@@ -39,12 +51,24 @@ pub struct JdMiaoshaApp {
     order_button: button::State,
     delivery_address_button: button::State,
     personal_center_button: button::State,
+    budget_button: button::State,
+    scheduler_button: button::State,
+    settings_button: button::State,
+    price_history_button: button::State,
+    watch_button: button::State,
     // 商品表
     goods_table: Option<Box<GoodsComponent>>,
     user_table: Option<Box<UserComponent>>,
     address_table: Option<Box<DeliveryAddressComponent>>,
     shopping_cart_table: Option<Box<ShoppingCartComponent>>,
     order_table: Option<Box<OrderComponent>>,
+    budget_table: Option<Box<BudgetComponent>>,
+    scheduler_table: Option<Box<SchedulerComponent>>,
+    settings_table: Option<Box<SettingsComponent>>,
+    price_history_table: Option<Box<PriceHistoryComponent>>,
+    watch_table: Option<Box<WatchComponent>>,
+    // 到时间了但还没激活的定时任务, 激活后再执行
+    due_jobs: Vec<(IDType, String)>,
 }
 
 lazy_static! {
@@ -121,132 +145,143 @@ impl JdMiaoshaApp {
         })
     }
 
-    fn submit_order(
-        account_id: IDType,
-        cookie: Arc<String>,
-        card_goods_id: IDType,
-        sku: String,
-        num: u32,
-        area: String,
-        _workers: usize,
-    ) -> Vec<Command<JdMiaoshaAppMessage>> {
-        return vec![Command::perform(
-            ShoppingCartService::submit_order_wrapper(
-                account_id,
-                cookie.clone(),
-                card_goods_id,
-                sku.clone(),
-                num,
-                area.clone(),
-                false,
-                _workers,
-            ),
-            |r| match r {
-                Ok((id, num, status)) => {
-                    ShoppingCartMessage::SubmitOrderFinish(id, status).into()
-                }
-                Err(e) => {
-                    error!("提交订单失败！{:?}", e);
-                    ShoppingCartMessage::Noop.into()
-                }
-            },
-        )];
-    }
-
-    fn submit_yuyue_order(
-        account_id: IDType,
-        cookie: Arc<String>,
-        card_goods_id: IDType,
-        sku: String,
-        num: u32,
-        in_yuyue: bool,
-        area: String,
-    ) -> Command<JdMiaoshaAppMessage> {
+    fn submit_order(platform: ProdPlatform, req: SubmitOrderReq) -> Command<JdMiaoshaAppMessage> {
+        // 下单流程由各个平台自己实现, 提交前先检查预算
+        let kind = format!("{:?}", req.kind);
+        let cart_goods_id = req.cart_goods_id;
         Command::perform(
-            async move {
-                ShoppingCartService::submit_yuyue_order(
-                    account_id,
-                    cookie,
-                    card_goods_id,
-                    sku,
-                    num,
-                    in_yuyue,
-                    area,
-                )
-                    .await
-            },
-            |r| match r {
-                Ok((id, num, status)) => ShoppingCartMessage::SubmitOrderFinish(id, status).into(),
+            async move { submit_within_budget(platform, req).await },
+            move |r| match r {
+                Ok((id, _num, status)) => ShoppingCartMessage::SubmitOrderFinish(id, status).into(),
                 Err(e) => {
-                    error!("提交预约订单失败！{:?}", e);
-                    ShoppingCartMessage::Noop.into()
+                    // 出错也算这个账号结束了, 所有账号都结束后才会释放抢购租约
+                    error!("提交订单失败！{}: {:?}", kind, e);
+                    ShoppingCartMessage::SubmitOrderFinish(cart_goods_id, "fail").into()
                 }
             },
         )
     }
 
-    fn submit_presale_order(
-        account_id: IDType,
-        cookie: Arc<String>,
-        card_goods_id: IDType,
-        sku: String,
-        eid: String,
-        fp: String,
-        num: u32,
-    ) -> Command<JdMiaoshaAppMessage> {
+    // 等到定时任务的触发时间
+    fn wait_job(id: IDType, sku: String, rev: u64) -> Command<JdMiaoshaAppMessage> {
+        Command::perform(scheduler().wait(id, rev), move |fired| {
+            if fired {
+                SchedulerMessage::Fire(id, sku).into()
+            } else {
+                JdMiaoshaAppMessage::GlobalNoop
+            }
+        })
+    }
+
+    fn refresh_jobs() -> Command<JdMiaoshaAppMessage> {
+        Command::perform(async {}, |_| SchedulerMessage::Refresh.into())
+    }
+
+    // 没有下单的定时任务也要结束, 不然一直是执行中
+    fn finish_job(id: IDType) -> Command<JdMiaoshaAppMessage> {
+        Command::perform(scheduler().complete(id), |_| SchedulerMessage::Refresh.into())
+    }
+
+    fn fire_due_jobs(&mut self) -> Vec<Command<JdMiaoshaAppMessage>> {
+        self.due_jobs
+            .drain(..)
+            .map(|(id, sku)| Command::perform(async {}, move |_| SchedulerMessage::Fire(id, sku).into()))
+            .collect()
+    }
+
+    // 先拿到抢购租约, 拿到后再分发给各个账号
+    fn submit_cart_item(&mut self, id: IDType) -> Command<JdMiaoshaAppMessage> {
+        let code = self.user_table.as_ref().unwrap().user_info.activate_code.clone();
+        let area = self.goods_table.as_ref().unwrap().get_addr_str();
+        let accounts = &self.user_table.as_ref().unwrap().user_info.users.records;
+        let cart_item = match self
+            .shopping_cart_table
+            .as_mut()
+            .unwrap()
+            .prods
+            .records
+            .iter_mut()
+            .find(|item| item.id == id)
+        {
+            Some(item) => item,
+            None => return Command::none(),
+        };
+        if plan_submit_orders(cart_item, accounts, area.as_str()).is_empty() {
+            warn!("没有可以下单的账号, 不购买:{}-{}", id, &cart_item.sku);
+            return Self::finish_job(id);
+        }
+        cart_item.purchase_status = "purchasing".to_string();
         Command::perform(
-            async move {
-                ShoppingCartService::submit_presale_order(
-                    account_id,
-                    cookie,
-                    card_goods_id,
-                    sku,
-                    eid,
-                    fp,
-                    num,
-                )
-                .await
-            },
-            |r| match r {
-                Ok((id, num, status)) => ShoppingCartMessage::SubmitOrderFinish(id, status).into(),
-                Err(e) => {
-                    error!("提交预约订单失败！{:?}", e);
-                    ShoppingCartMessage::Noop.into()
+            async move { ShoppingCartService::claim_cart_goods(code.as_str(), id).await },
+            move |r| {
+                if let Err(e) = &r {
+                    warn!("获取抢购租约失败, 不购买:{}, {:?}", id, e);
                 }
+                ShoppingCartMessage::ClaimFinish(id, r.is_ok()).into()
             },
         )
     }
 
-    fn submit_seckill_order(
-        account_id: IDType,
-        cookie: Arc<String>,
-        card_goods_id: IDType,
-        sku: String,
-        eid: String,
-        fp: String,
-    ) -> Command<JdMiaoshaAppMessage> {
-        Command::perform(
-            async move {
-                ShoppingCartService::submit_seckill_order(
-                    account_id,
-                    cookie,
-                    card_goods_id,
-                    sku,
-                    eid,
-                    fp,
-                )
-                .await
-            },
-            |r| match r {
-                Ok((id, num, status)) => {
-                    return ShoppingCartMessage::SubmitOrderFinish(id, status).into();
-                }
-                Err(e) => {
-                    error!("提交秒杀抢购订单失败！{:?}", e);
-                    ShoppingCartMessage::Noop.into()
-                }
+    // 按账号生成订单并提交
+    fn submit_claimed_item(&mut self, id: IDType) -> Command<JdMiaoshaAppMessage> {
+        let cart_item = match self
+            .shopping_cart_table
+            .as_mut()
+            .unwrap()
+            .prods
+            .records
+            .iter_mut()
+            .find(|item| item.id == id)
+        {
+            Some(item) => item,
+            None => return Command::none(),
+        };
+        info!("SubmitOrder buying: {}-{}", id, &cart_item.sku);
+        let platform = cart_item.platform;
+        let sku = cart_item.sku.clone();
+        let area = self.goods_table.as_ref().unwrap().get_addr_str();
+        let purchase_url = cart_item.purchase_url.clone();
+        let accounts = &self.user_table.as_ref().unwrap().user_info.users.records;
+        let valid_users: Vec<String> = accounts
+            .iter()
+            .filter(|item| item.platform == platform)
+            .map(|item| item.account.clone())
+            .collect();
+        let cmds: Vec<Command<JdMiaoshaAppMessage>> =
+            plan_submit_orders(cart_item, accounts, area.as_str())
+                .into_iter()
+                .map(|req| Self::submit_order(platform, req))
+                .collect();
+        if cmds.is_empty() {
+            // 拿到租约后账号没了, 直接释放租约并结束定时任务
+            warn!("没有可以下单的账号, 不购买:{}-{}", id, sku);
+            return Command::batch(vec![
+                Self::finish_job(id),
+                Command::perform(async {}, move |_| {
+                    ShoppingCartMessage::MaybeRemoveShoppingCartLock(id).into()
+                }),
+            ]);
+        }
+        Self::set_check_times(cart_item.id, cmds.len());
+        // 重新开始计数, 所有账号都结束后才释放抢购租约
+        cart_item.cur_check = 0;
+        cart_item.purchase_status = "purchasing".to_string();
+        let m = std::cmp::min(5, valid_users.len());
+        info!(
+            "购买商品:{}-{},参与用户:{}{}{}人,总次数: {}, [May be concurrent workers:{}]!",
+            cart_item.id,
+            sku,
+            valid_users.join(","),
+            if valid_users.len() > m{
+                "...等"
+            }else{
+                ",共"
             },
-        )
+            valid_users.len(),
+            cmds.len(),
+            workers()
+        );
+        Command::batch(cmds)
     }
 }
 
@@ -264,8 +299,15 @@ impl Application for JdMiaoshaApp {
         obj.address_table = Some(Box::new(DeliveryAddressComponent::default()));
         obj.order_table = Some(Box::new(OrderComponent::default()));
         obj.shopping_cart_table = Some(Box::new(ShoppingCartComponent::new()));
+        obj.budget_table = Some(Box::new(BudgetComponent::default()));
+        obj.scheduler_table = Some(Box::new(SchedulerComponent::default()));
+        obj.settings_table = Some(Box::new(SettingsComponent::default()));
+        obj.price_history_table = Some(Box::new(PriceHistoryComponent::default()));
+        obj.watch_table = Some(Box::new(WatchComponent::default()));
         obj.app_settings = flags;
-        (obj, Self::init_driver())
+        // 启动时就恢复上次留下的定时任务, 不用等打开购物车
+        let recover = Command::perform(scheduler::recover(), |r| SchedulerMessage::Recovered(r).into());
+        (obj, Command::batch(vec![Self::init_driver(), recover]))
     }
 
     fn title(&self) -> String {
@@ -303,16 +345,15 @@ impl Application for JdMiaoshaApp {
                             .records
                             .iter()
                             .map(|u| {
-                                let account = u.account.clone();
-                                let cookie = u.cookie.clone();
+                                let user = u.clone();
                                 Command::perform(
-                                    async {
-                                        OrderService::get_orders_by_user(
-                                            account,
-                                            cookie,
-                                            QueryCondition::Unpaid,
-                                        )
-                                        .await
+                                    async move {
+                                        let r = retailer(user.platform)
+                                            .get_orders(user.account.clone(), user.cookie.clone(), QueryCondition::Unpaid)
+                                            .await;
+                                        // 顺便更新今天的花费, 已经付款的订单不在待付款里, 要查全部订单
+                                        budget::sync_accounts(vec![user]).await;
+                                        r
                                     },
                                     |r| match r {
                                         Ok(info) => OrderMessage::LoadFinish(info).into(),
@@ -329,6 +370,17 @@ impl Application for JdMiaoshaApp {
             GlobalDeliveryAddressPressed => {
                 self.cur_tab = GlobalDeliveryAddressPressed;
                 if self.is_activate_and_have_users() {
+                    // 批量设置地址时从这些账号里选
+                    self.address_table.as_mut().unwrap().accounts = self
+                        .user_table
+                        .as_ref()
+                        .unwrap()
+                        .user_info
+                        .users
+                        .records
+                        .iter()
+                        .map(|u| u.account.clone())
+                        .collect();
                     return Command::batch(
                         self.user_table
                             .as_ref()
@@ -340,12 +392,10 @@ impl Application for JdMiaoshaApp {
                             .map(|u| {
                                 let account = u.account.clone();
                                 let cookie = u.cookie.clone();
+                                let platform = u.platform;
                                 Command::perform(
-                                    async {
-                                        DeliveryAddressService::get_all_address_by_user(
-                                            account, cookie,
-                                        )
-                                        .await
+                                    async move {
+                                        retailer(platform).get_addresses(account, cookie).await
                                     },
                                     |r| match r {
                                         Ok(info) => DeliveryAddressMessage::LoadFinish(info).into(),
@@ -363,6 +413,182 @@ impl Application for JdMiaoshaApp {
             GlobalPersonalCenterPressed => {
                 self.cur_tab = GlobalPersonalCenterPressed;
             }
+            GlobalBudgetPressed => {
+                self.cur_tab = GlobalBudgetPressed;
+                return Command::perform(async {}, |_| BudgetMessage::Refresh.into());
+            }
+            Budget(BudgetMessage::Refresh) => {
+                let accounts = if self.is_activate_and_have_users() {
+                    self.user_table.as_ref().unwrap().user_info.users.records.clone()
+                } else {
+                    vec![]
+                };
+                return Command::perform(
+                    async move {
+                        budget::sync_accounts(accounts).await;
+                        budget::summary().await
+                    },
+                    |summary| BudgetMessage::LoadFinish(summary).into(),
+                );
+            }
+            Budget(bmsg) => {
+                return self.budget_table.as_mut().unwrap().update(bmsg);
+            }
+            GlobalPriceHistoryPressed => {
+                self.cur_tab = GlobalPriceHistoryPressed;
+                return Command::perform(async {}, |_| PriceHistoryMessage::Refresh.into());
+            }
+            PriceHistory(pmsg) => {
+                return self.price_history_table.as_mut().unwrap().update(pmsg);
+            }
+            GlobalWatchPressed => {
+                self.cur_tab = GlobalWatchPressed;
+            }
+            Watch(WatchMessage::Add) => {
+                let area = self.goods_table.as_ref().unwrap().get_addr_str();
+                return self.watch_table.as_mut().unwrap().add(area);
+            }
+            Watch(wmsg) => {
+                return self.watch_table.as_mut().unwrap().update(wmsg);
+            }
+            GlobalSchedulerPressed => {
+                self.cur_tab = GlobalSchedulerPressed;
+                return Self::refresh_jobs();
+            }
+            Scheduler(SchedulerMessage::Refresh) => {
+                return Command::perform(scheduler().jobs(), |jobs| {
+                    SchedulerMessage::LoadFinish(jobs).into()
+                });
+            }
+            Scheduler(SchedulerMessage::Recovered(recovery)) => {
+                let mut cmds: Vec<Command<JdMiaoshaAppMessage>> = recovery
+                    .pending
+                    .into_iter()
+                    .map(|job| Self::wait_job(job.cart_goods_id, job.sku, job.rev))
+                    .collect();
+                cmds.extend(recovery.due.into_iter().map(|job| {
+                    let (id, sku) = (job.cart_goods_id, job.sku);
+                    Command::perform(async {}, move |_| SchedulerMessage::Fire(id, sku).into())
+                }));
+                cmds.push(Self::refresh_jobs());
+                return Command::batch(cmds);
+            }
+            Scheduler(SchedulerMessage::Fire(id, sku)) => {
+                if !self.is_activate_and_have_users() {
+                    warn!("还没激活或者还没登录账号, 登录后再执行定时任务:{}-{}", id, sku);
+                    self.due_jobs.push((id, sku));
+                    return Command::none();
+                }
+                let in_page = self
+                    .shopping_cart_table
+                    .as_ref()
+                    .unwrap()
+                    .prods
+                    .records
+                    .iter()
+                    .any(|item| item.id == id);
+                if in_page {
+                    return Command::perform(async {}, move |_| {
+                        ShoppingCartMessage::SubmitOrder(id).into()
+                    });
+                }
+                // 不在购物车当前页的, 先从服务端查出来
+                let code = self.user_table.as_ref().unwrap().user_info.activate_code.clone();
+                return Command::perform(
+                    ShoppingCartService::find_cart_goods(code, id, sku),
+                    move |r| {
+                        let item = r.unwrap_or_else(|e| {
+                            error!("查询购物车商品失败:{}, {:?}", id, e);
+                            None
+                        });
+                        SchedulerMessage::FireLoaded(id, item).into()
+                    },
+                );
+            }
+            Scheduler(SchedulerMessage::FireLoaded(id, item)) => {
+                match item {
+                    Some(item) => {
+                        let records = &mut self.shopping_cart_table.as_mut().unwrap().prods.records;
+                        if !records.iter().any(|r| r.id == id) {
+                            records.push(item);
+                        }
+                        return Command::perform(async {}, move |_| {
+                            ShoppingCartMessage::SubmitOrder(id).into()
+                        });
+                    }
+                    None => {
+                        warn!("定时任务的商品已经不在购物车里了:{}", id);
+                        return Self::finish_job(id);
+                    }
+                }
+            }
+            Scheduler(SchedulerMessage::Cancel(id)) => {
+                return Command::perform(scheduler().cancel(id), |_| SchedulerMessage::Refresh.into());
+            }
+            Scheduler(SchedulerMessage::Remove(id)) => {
+                return Command::perform(scheduler().complete(id), |_| SchedulerMessage::Refresh.into());
+            }
+            Scheduler(SchedulerMessage::EditFinish(id)) => {
+                let table = self.scheduler_table.as_mut().unwrap();
+                let dt = table.edit_dt(id);
+                table.update(SchedulerMessage::EditFinish(id));
+                if let (Some(dt), true) = (dt, self.is_activate()) {
+                    let code = self.user_table.as_ref().unwrap().user_info.activate_code.clone();
+                    let yuyue_at = dt.timestamp_millis();
+                    let fire_at = std::cmp::max(now().timestamp_millis() + 1, yuyue_at - ahead_purchase_millis());
+                    return Command::perform(
+                        async move {
+                            let rev = scheduler().reschedule(id, yuyue_at, fire_at).await;
+                            // 购物车里的预约时间也跟着改, 避免重新加载购物车时又按原来的时间定时
+                            let body = serde_json::json!({
+                                "op":4,
+                                "yuyue_dt":datetime_fmt(&dt),
+                            })
+                            .to_string();
+                            if let Err(e) = ShoppingCartService::update_cart_goods(code, id, body).await {
+                                error!("修改预约时间失败:{}, {:?}", id, e);
+                            }
+                            rev
+                        },
+                        move |rev| SchedulerMessage::Rescheduled(id, rev).into(),
+                    );
+                }
+            }
+            Scheduler(SchedulerMessage::Rescheduled(id, rev)) => {
+                let mut cmds = vec![Self::refresh_jobs()];
+                if let Some(rev) = rev {
+                    let sku = self.scheduler_table.as_ref().unwrap().sku(id);
+                    cmds.push(Self::wait_job(id, sku, rev));
+                }
+                if self.shopping_cart_table.as_ref().unwrap().prods.records.iter().any(|r| r.id == id) {
+                    cmds.push(Command::perform(async {}, |_| ShoppingCartMessage::Loading.into()));
+                }
+                return Command::batch(cmds);
+            }
+            Scheduler(smsg) => {
+                return self.scheduler_table.as_mut().unwrap().update(smsg);
+            }
+            GlobalSettingsPressed => {
+                self.cur_tab = GlobalSettingsPressed;
+                self.settings_table.as_mut().unwrap().load(settings::get());
+            }
+            Settings(SettingsMessage::Save) => {
+                let table = self.settings_table.as_mut().unwrap();
+                return match table.edited() {
+                    Ok(edited) => Command::perform(settings::save(edited), |r| {
+                        SettingsMessage::SaveFinish(r.err().map(|e| e.to_string())).into()
+                    }),
+                    Err(tip) => table.update(SettingsMessage::Invalid(tip)),
+                };
+            }
+            Settings(SettingsMessage::Reset) => {
+                return Command::perform(settings::reset(), |r| {
+                    SettingsMessage::ResetFinish(r.err().map(|e| e.to_string())).into()
+                });
+            }
+            Settings(smsg) => {
+                return self.settings_table.as_mut().unwrap().update(smsg);
+            }
             Goods(GoodsMessage::MaybeUpdatePurchaseLink(sku, url)) => {
                 if self.is_activate() {
                     let code = self
@@ -385,6 +611,15 @@ impl Application for JdMiaoshaApp {
                     );
                 }
             }
+            Goods(GoodsMessage::MonitorStockFinish(stocks)) => {
+                let cart = self.shopping_cart_table.as_mut().unwrap();
+                return Command::batch(
+                    stocks
+                        .into_iter()
+                        .map(|(id, status)| cart.update(ShoppingCartMessage::UpdateStock(id, status)))
+                        .collect::<Vec<_>>(),
+                );
+            }
             Goods(gs) => {
                 return self.goods_table.as_mut().unwrap().update(gs);
             }
@@ -396,12 +631,22 @@ impl Application for JdMiaoshaApp {
                 return Command::batch(vec![
                     Command::perform(async {}, |_| UserMessage::Loading.into()),
                     Command::perform(async {}, |_| InitDriverClient),
+                    Command::perform(async {}, |_| WatchMessage::Load.into()),
                     Command::perform(async { AreaService::load_area().await }, |t| {
                         if t.is_err() {
                             error!("{:?}", t);
                         }
                         AreaMessage::AreaLoad(t.unwrap_or_default()).into()
                     }),
+                    // 本地的地区树太旧或者没有时在后台刷新
+                    Command::perform(async { AreaService::init_tree().await }, |r| match r {
+                        Ok(true) => AreaMessage::TreeRefresh.into(),
+                        Ok(false) => GlobalNoop,
+                        Err(e) => {
+                            error!("读取地区树失败:{:?}", e);
+                            AreaMessage::TreeRefresh.into()
+                        }
+                    }),
                 ]);
             }
             InitDriverClient => {
@@ -416,6 +661,16 @@ impl Application for JdMiaoshaApp {
                 error!("InitDriverManagerFail");
             }
 
+            // 后台刷新地区树, 不切换页面
+            Area(AreaMessage::TreeRefresh) => {
+                return Command::perform(async { AreaService::refresh_tree().await }, |r| {
+                    AreaMessage::TreeRefreshed(r.map_err(|e| e.to_string())).into()
+                });
+            }
+            Area(AreaMessage::TreeRefreshed(r)) => match r {
+                Ok(n) => info!("地区树刷新完成, 共 {} 个地区", n),
+                Err(e) => warn!("地区树刷新失败, 继续使用本地的:{}", e),
+            },
             Area(as_) => {
                 self.cur_tab = GlobalGoodsPressed;
                 return self.goods_table.as_mut().unwrap().update_area(as_);
@@ -451,6 +706,16 @@ impl Application for JdMiaoshaApp {
                     info!("HeartBeatFinish:{}", b);
                     if table.heartbeat_error_times > 1 {
                         info!("激活码已被使用过, 请获取新的激活码");
+                        let notify_cmd = if table.is_activate {
+                            let event = Event::new(
+                                EventKind::LicenseExpired,
+                                "激活码失效",
+                                "激活码已失效或在其他设备上使用, 请获取新的激活码",
+                            );
+                            Command::perform(notify::notify(event), |_| GlobalNoop)
+                        } else {
+                            Command::none()
+                        };
                         // 连续两次检测失败就把激活置为false， 然后整个程序，涉及到激活的操作就不能再使用了
                         table.is_activate = false;
                         if let Some(table) = &mut self.goods_table {
@@ -459,6 +724,7 @@ impl Application for JdMiaoshaApp {
                         if let Some(table) = &mut self.shopping_cart_table {
                             table.activate_code.clear();
                         }
+                        return notify_cmd;
                     }
                 }
             }
@@ -472,18 +738,67 @@ impl Application for JdMiaoshaApp {
                         goods_table.activate_code = code.clone();
                     }
                 }
-                return self
+                let cmd = self
                     .user_table
                     .as_mut()
                     .unwrap()
                     .update(umsg, self.app_settings.driver_inited.clone());
+                if self.is_activate_and_have_users() && !self.due_jobs.is_empty() {
+                    let mut cmds = vec![cmd];
+                    cmds.extend(self.fire_due_jobs());
+                    return Command::batch(cmds);
+                }
+                return cmd;
+            }
+            DeliveryAddress(DeliveryAddressMessage::Apply) => {
+                if self.is_activate_and_have_users() {
+                    let table = self.address_table.as_mut().unwrap();
+                    let (form, selected) = match table.apply_target() {
+                        Some(target) => target,
+                        None => return Command::none(),
+                    };
+                    if let Err(e) = form.validate() {
+                        return table.update(DeliveryAddressMessage::Invalid(e));
+                    }
+                    let users: Vec<UserState> = self
+                        .user_table
+                        .as_ref()
+                        .unwrap()
+                        .user_info
+                        .users
+                        .records
+                        .iter()
+                        .filter(|u| selected.contains(&u.account))
+                        .cloned()
+                        .collect();
+                    if users.is_empty() {
+                        return table.update(DeliveryAddressMessage::Invalid("请选择要设置地址的账号".to_string()));
+                    }
+                    let _ = table.update(DeliveryAddressMessage::Apply);
+                    return Command::perform(delivery_address::apply_address(users, form), |r| {
+                        DeliveryAddressMessage::ApplyFinish(r).into()
+                    });
+                }
             }
             DeliveryAddress(dmsg) => {
                 return self.address_table.as_mut().unwrap().update(dmsg);
             }
 
             ShoppingCart(ShoppingCartMessage::MaybeRemoveShoppingCartLock(id)) => {
-                return Command::none();
+                if !self.is_activate() {
+                    return Command::none();
+                }
+                // 抢购结束了, 释放抢购租约
+                let code = self.user_table.as_ref().unwrap().user_info.activate_code.clone();
+                return Command::perform(
+                    ShoppingCartService::release_cart_goods_claim(code, id),
+                    |r| {
+                        if let Err(e) = r {
+                            error!("{:?}", e);
+                        }
+                        ShoppingCartMessage::Noop.into()
+                    },
+                );
             }
 
             ShoppingCart(ShoppingCartMessage::SubmitOrder(id)) => {
@@ -491,6 +806,8 @@ impl Application for JdMiaoshaApp {
                     // 没激活， 跳过
                     return Command::none();
                 }
+                let mut check_price = None;
+                let mut submit = false;
                 if let Some(cart_item) = self
                     .shopping_cart_table
                     .as_mut()
@@ -500,162 +817,64 @@ impl Application for JdMiaoshaApp {
                     .iter_mut()
                     .find(|item| item.id == id && item.purchase_status != "purchasing")
                 {
-                    info!("SubmitOrder buying: {}-{}", id, &cart_item.sku);
-                    let sku = cart_item.sku.clone();
-                    let num = cart_item.purchase_num;
-                    let area = self.goods_table.as_ref().unwrap().get_addr_str();
-                    let purchase_url = cart_item.purchase_url.clone();
-                    let mut valid_users = vec![];
-                    let cmds: Vec<Command<JdMiaoshaAppMessage>> = self
-                        .user_table
-                        .as_ref()
-                        .unwrap()
-                        .user_info
-                        .users
-                        .records
-                        .iter()
-                        .map(|item| {
-                            valid_users.push(item.account.clone());
-                            let account_id = item.id;
-                            let cookie = item.cookie.clone();
-                            let _sku = sku.clone();
-                            let num = num.clone();
-                            if cart_item.purchase_type.eq(SECOND_KILL) {
-                                (1..=workers())
-                                    .into_iter()
-                                    .map(|_| {
-                                        Self::submit_seckill_order(
-                                            account_id,
-                                            cookie.clone(),
-                                            id,
-                                            sku.clone(),
-                                            item.eid.clone(),
-                                            item.fp.clone(),
-                                        )
-                                    })
-                                    .collect::<Vec<_>>()
-                            } else if cart_item.purchase_type.eq(PRESALE) {
-                                if let Some(ref yuyue_dt) = cart_item.yuyue_dt {
-                                    if greater_than_now(yuyue_dt) {
-                                        // 还不到设定的预约时间， 不能购买
-                                        warn!(
-                                            "预售商品还不到设定的预约时间,不能购买: {}",
-                                            serde_json::to_string(&cart_item).unwrap_or_default()
-                                        );
-                                        return vec![];
-                                    }
-                                }
-                                (1..=workers())
-                                    .into_iter()
-                                    .map(|_| {
-                                        Self::submit_presale_order(
-                                            account_id,
-                                            cookie.clone(),
-                                            id,
-                                            sku.clone(),
-                                            item.eid.clone(),
-                                            item.fp.clone(),
-                                            num,
-                                        )
-                                    })
-                                    .collect::<Vec<_>>()
-                            } else if cart_item.purchase_type.eq(YUYUE) {
-                                if cart_item.purchase_status.eq("ready") {
-                                    let is_yuyue = if let Some(ref end_dt) = cart_item.yuyue_end_dt
-                                    {
-                                        greater_than_now(end_dt)
-                                    } else {
-                                        false
-                                    };
-                                    if is_yuyue {
-                                        info!("{}-{}:预约商品...", account_id, sku);
-                                    } else {
-                                        info!(
-                                            "{}-{}:跳过预约，直接购买预约商品...",
-                                            account_id, sku
-                                        );
-                                    }
-                                    vec![Self::submit_yuyue_order(
-                                        account_id,
-                                        cookie.clone(),
-                                        id,
-                                        sku.clone(),
-                                        num,
-                                        is_yuyue,
-                                        area.clone(),
-                                    )]
-                                } else if cart_item.purchase_status.eq("yuyueing")
-                                    && !greater_than_now(cart_item.yuyue_dt.as_ref().unwrap())
-                                {
-                                    // 预约时间到了就去真正购买
-                                    info!("{}-{}:购买已经预约过的商品", account_id, sku);
-                                    // 把并发预约放到 submit_yuyue_order 里， 因为可能导致预约的商品在购物车的数量不对
-                                    vec![Self::submit_yuyue_order(
-                                        account_id,
-                                        cookie.clone(),
-                                        id,
-                                        sku.clone(),
-                                        num,
-                                        false,
-                                        area.clone(),
-                                    )]
-                                } else {
-                                    warn!(
-                                        "{}-{}:预约商品的状态不对,不能购买: {}!",
-                                        account_id, sku, cart_item.purchase_status
-                                    );
-                                    // warn!("Ignored yuyue: {}", serde_json::to_string(&cart_item).unwrap_or_default());
-                                    vec![]
-                                }
-                            } else {
-                                let mut _workers = 1;
-                                if let Some(ref yuyue_dt) = cart_item.yuyue_dt {
-                                    if greater_than_now(yuyue_dt) {
-                                        // 还不到设定的预约时间， 不能购买
-                                        warn!(
-                                            "普通商品预约时间不到,不能购买: {}",
-                                            serde_json::to_string(&cart_item).unwrap_or_default()
-                                        );
-                                        return vec![];
-                                    }
-                                    _workers = workers();
-                                }
-                                Self::submit_order(
-                                    account_id,
-                                    cookie.clone(),
-                                    id,
-                                    _sku,
-                                    num,
-                                    area.clone(),
-                                    _workers,
-                                )
-                            }
-                        })
-                        .flatten()
-                        .collect();
-                    Self::set_check_times(cart_item.id, cmds.len());
-                    if cmds.len() > 0 {
+                    if let Some(max_price) = cart_item.max_price_value() {
+                        // 设置了最高价格, 先查询最新价格再决定是否购买
                         cart_item.purchase_status = "purchasing".to_string();
+                        check_price = Some((cart_item.platform, cart_item.sku.clone(), max_price));
+                    } else {
+                        submit = true;
                     }
-                    let m = std::cmp::min(5, valid_users.len());
-                    info!(
-                        "购买商品:{}-{},参与用户:{}{}{}人,总次数: {}, [May be concurrent workers:{}]!",
-                        cart_item.id,
-                        sku,
-                        valid_users.join(","),
-                        if valid_users.len() > m{
-                            "...等"
-                        }else{
-                            ",共"
+                }
+                if submit {
+                    return self.submit_cart_item(id);
+                }
+                if let Some((platform, sku, max_price)) = check_price {
+                    return Command::perform(
+                        check_max_price(platform, sku, max_price),
+                        move |r| match r {
+                            Ok((price, ok)) => ShoppingCartMessage::PriceChecked(id, price, ok).into(),
+                            Err(e) => {
+                                error!("查询最新价格失败, 不购买:{}, {:?}", id, e);
+                                ShoppingCartMessage::PriceChecked(id, String::new(), false).into()
+                            }
                         },
-                        valid_users.len(),
-                        cmds.len(),
-                        workers()
                     );
-                    return Command::batch(cmds);
                 }
             }
 
+            ShoppingCart(ShoppingCartMessage::PriceChecked(id, price, ok)) => {
+                let msg = ShoppingCartMessage::PriceChecked(id, price, ok);
+                let mut cmds = vec![self.shopping_cart_table.as_mut().unwrap().update(msg)];
+                if ok && self.is_activate() {
+                    cmds.push(self.submit_cart_item(id));
+                }
+                return Command::batch(cmds);
+            }
+
+            ShoppingCart(ShoppingCartMessage::ClaimFinish(id, ok)) => {
+                if !self.is_activate() {
+                    return Command::none();
+                }
+                if ok {
+                    return self.submit_claimed_item(id);
+                }
+                // 别的客户端正在抢购, 重新加载购物车显示最新的状态
+                return Command::batch(vec![
+                    Self::finish_job(id),
+                    Command::perform(async {}, |_| ShoppingCartMessage::Loading.into()),
+                ]);
+            }
+
+            ShoppingCart(ShoppingCartMessage::SubmitOrderFinish(id, status)) => {
+                let msg = ShoppingCartMessage::SubmitOrderFinish(id, status);
+                let mut cmds = vec![self.shopping_cart_table.as_mut().unwrap().update(msg)];
+                if status == "success" {
+                    // 抢到了就把新订单加到待付款列表里
+                    cmds.push(Command::perform(async {}, |_| OrderMessage::UnpaidRefresh.into()));
+                }
+                return Command::batch(cmds);
+            }
+
             ShoppingCart(msg) => {
                 return self.shopping_cart_table.as_mut().unwrap().update(msg);
             }
@@ -681,10 +900,10 @@ impl Application for JdMiaoshaApp {
                                 let account = u.account.clone();
                                 let cookie = u.cookie.clone();
                                 let cond = cond.clone();
+                                let platform = u.platform;
                                 Command::perform(
                                     async move {
-                                        OrderService::get_orders_by_user(account, cookie, cond)
-                                            .await
+                                        retailer(platform).get_orders(account, cookie, cond).await
                                     },
                                     |r| match r {
                                         Ok(orders) => OrderMessage::SearchFinish(orders).into(),
@@ -699,6 +918,81 @@ impl Application for JdMiaoshaApp {
                 }
             }
 
+            Order(OrderMessage::Export) => {
+                if self.is_activate_and_have_users() {
+                    let range = match self.order_table.as_ref().unwrap().export_range() {
+                        Ok(range) => range,
+                        Err(e) => {
+                            return self
+                                .order_table
+                                .as_mut()
+                                .unwrap()
+                                .update(OrderMessage::ExportFinish(Err(e)));
+                        }
+                    };
+                    let table = self.order_table.as_mut().unwrap();
+                    let _ = table.update(OrderMessage::Export);
+                    let path = order_export::default_path(table.export_format);
+                    let users: Vec<UserState> = self
+                        .user_table
+                        .as_ref()
+                        .unwrap()
+                        .user_info
+                        .users
+                        .records
+                        .iter()
+                        .filter(|u| UserState::maybe_valid_cookie(u.cookie_last_update_dt.clone()))
+                        .cloned()
+                        .collect();
+                    // 全部订单包含了所有状态的订单
+                    return Command::perform(
+                        order_export::export_orders(users, QueryCondition::All, range, path),
+                        |r| match r {
+                            Ok(report) => OrderMessage::ExportFinish(Ok(OrderComponent::export_tip(&report))).into(),
+                            Err(e) => {
+                                error!("导出订单失败:{:?}", e);
+                                OrderMessage::ExportFinish(Err(e.to_string())).into()
+                            }
+                        },
+                    );
+                }
+            }
+
+            Order(OrderMessage::UnpaidRefresh) => {
+                if self.is_activate_and_have_users() {
+                    return Command::batch(
+                        self.user_table
+                            .as_ref()
+                            .unwrap()
+                            .user_info
+                            .users
+                            .records
+                            .iter()
+                            .filter(|u| UserState::maybe_valid_cookie(u.cookie_last_update_dt.clone()))
+                            .map(|u| {
+                                let account = u.account.clone();
+                                let cookie = u.cookie.clone();
+                                let platform = u.platform;
+                                Command::perform(
+                                    async move {
+                                        let r = retailer(platform)
+                                            .get_orders(account.clone(), cookie, QueryCondition::Unpaid)
+                                            .await;
+                                        (account, r)
+                                    },
+                                    |(account, r)| match r {
+                                        Ok(orders) => OrderMessage::UnpaidLoaded(account, orders).into(),
+                                        Err(e) => {
+                                            error!("查询待付款订单失败:{}-{:?}", account, e);
+                                            JdMiaoshaAppMessage::GlobalNoop
+                                        }
+                                    },
+                                )
+                            }),
+                    );
+                }
+            }
+
             Order(msg) => {
                 return self.order_table.as_mut().unwrap().update(msg);
             }
@@ -719,26 +1013,55 @@ impl Application for JdMiaoshaApp {
                     .map(|_| JdMiaoshaAppMessage::InitDriverManager),
             );
         }
+        // 间隔在设置页修改后, 订阅会按新的间隔重新开始
+        let settings = settings::get();
         let check_cookie = self.is_activate_and_have_users();
         if check_cookie {
             // 每隔一段时间检查 cookie, 使cookie保活
             subscriptions.push(
-                time::every(Duration::from_secs(180)).map(|_| UserMessage::CookieChecking.into()),
+                time::every(Duration::from_secs(settings.cookie_check_secs))
+                    .map(|_| UserMessage::CookieChecking.into()),
             );
             // 定时检测商品库存
             subscriptions.push(
-                time::every(Duration::from_secs(3))
+                time::every(Duration::from_secs(settings.stock_check_secs))
                     .map(|_| ShoppingCartMessage::StockChecking.into()),
             );
+            // 定时续约正在抢购的商品
+            subscriptions.push(
+                time::every(Duration::from_secs(settings.claim_renew_secs))
+                    .map(|_| ShoppingCartMessage::RenewClaims.into()),
+            );
+            // 定时刷新未签收订单的物流
+            subscriptions.push(
+                time::every(Duration::from_secs(settings.track_refresh_secs))
+                    .map(|_| OrderMessage::TrackRefresh.into()),
+            );
+            // 定时刷新待付款订单, 已经付款的移出列表
+            subscriptions.push(
+                time::every(Duration::from_secs(unpaid::REFRESH_SECS))
+                    .map(|_| OrderMessage::UnpaidRefresh.into()),
+            );
+        }
+        // 到货提醒不用登录, 有监控的商品就定时查询
+        if self.watch_table.as_ref().map(|t| !t.watchlist.is_empty()).unwrap_or(false) {
+            subscriptions.push(
+                time::every(Duration::from_secs(settings.watch_check_secs))
+                    .map(|_| WatchMessage::Check.into()),
+            );
+        }
+        // 有待付款订单时每秒更新倒计时
+        if self.order_table.as_ref().map(|t| !t.unpaid.is_empty()).unwrap_or(false) {
+            subscriptions.push(time::every(Duration::from_secs(1)).map(|_| OrderMessage::PayTick.into()));
         }
         //每隔一段时间无条件检查激活条件
         subscriptions
-            .push(time::every(Duration::from_secs(300)).map(|_| UserMessage::HeartBeat.into()));
+            .push(time::every(Duration::from_secs(settings.heartbeat_secs)).map(|_| UserMessage::HeartBeat.into()));
         Subscription::batch(subscriptions)
     }
 
     fn view(&mut self) -> Element<Self::Message> {
-        let mut first_row_children = Vec::with_capacity(5);
+        let mut first_row_children = Vec::with_capacity(6);
         for child in vec![
             (
                 &mut self.goods_button,
@@ -760,6 +1083,31 @@ impl Application for JdMiaoshaApp {
                 "收货地址",
                 JdMiaoshaAppMessage::GlobalDeliveryAddressPressed,
             ),
+            (
+                &mut self.budget_button,
+                "预算",
+                JdMiaoshaAppMessage::GlobalBudgetPressed,
+            ),
+            (
+                &mut self.price_history_button,
+                "价格走势",
+                JdMiaoshaAppMessage::GlobalPriceHistoryPressed,
+            ),
+            (
+                &mut self.watch_button,
+                "提醒",
+                JdMiaoshaAppMessage::GlobalWatchPressed,
+            ),
+            (
+                &mut self.scheduler_button,
+                "定时任务",
+                JdMiaoshaAppMessage::GlobalSchedulerPressed,
+            ),
+            (
+                &mut self.settings_button,
+                "设置",
+                JdMiaoshaAppMessage::GlobalSettingsPressed,
+            ),
             (
                 &mut self.personal_center_button,
                 "个人中心",
@@ -805,6 +1153,17 @@ impl Application for JdMiaoshaApp {
                 self.address_table.as_mut().unwrap().view()
             }
             JdMiaoshaAppMessage::GlobalOrdersPressed => self.order_table.as_mut().unwrap().view(),
+            JdMiaoshaAppMessage::GlobalBudgetPressed => self.budget_table.as_mut().unwrap().view(),
+            JdMiaoshaAppMessage::GlobalPriceHistoryPressed => {
+                self.price_history_table.as_mut().unwrap().view()
+            }
+            JdMiaoshaAppMessage::GlobalWatchPressed => self.watch_table.as_mut().unwrap().view(),
+            JdMiaoshaAppMessage::GlobalSchedulerPressed => {
+                self.scheduler_table.as_mut().unwrap().view()
+            }
+            JdMiaoshaAppMessage::GlobalSettingsPressed => {
+                self.settings_table.as_mut().unwrap().view()
+            }
             _ => unreachable!(),
         };
         let layout = Column::new()
@@ -0,0 +1,74 @@
+//! 地区搜索框: 输入 "朝阳" 这样的名称, 在本地的地区树里搜索, 选中后得到完整的地区路径
+use iced::*;
+
+use super::style;
+use crate::models::AreaMatch;
+use crate::services::area::area_tree;
+use crate::ui::AreaPickerMessage;
+
+// 最多显示的候选地区个数
+const MAX_SUGGESTIONS: usize = 8;
+
+#[derive(Default)]
+pub struct AreaPicker {
+    pub query: String,
+    input_state: text_input::State,
+    suggestions: Vec<(AreaMatch, button::State)>,
+}
+
+impl AreaPicker {
+    /// 选中了地区时返回它
+    pub fn update(&mut self, message: AreaPickerMessage) -> Option<AreaMatch> {
+        use AreaPickerMessage::*;
+        match message {
+            Input(query) => {
+                self.suggestions = area_tree()
+                    .search(query.as_str(), MAX_SUGGESTIONS)
+                    .into_iter()
+                    .map(|m| (m, button::State::new()))
+                    .collect();
+                self.query = query;
+                None
+            }
+            Select(idx) => self.select(idx),
+            Submit => self.select(0),
+        }
+    }
+
+    fn select(&mut self, idx: usize) -> Option<AreaMatch> {
+        let selected = self.suggestions.get(idx).map(|(m, _)| m.clone());
+        if selected.is_some() {
+            self.clear();
+        }
+        selected
+    }
+
+    pub fn clear(&mut self) {
+        self.query.clear();
+        self.suggestions.clear();
+    }
+
+    /// to_msg 把搜索框的消息包装成所在页面的消息
+    pub fn view<'a, M: 'static + Clone>(&'a mut self, to_msg: fn(AreaPickerMessage) -> M) -> Element<'a, M> {
+        let mut col = Column::new().spacing(2).push(
+            TextInput::new(
+                &mut self.input_state,
+                "搜索地区, 如 朝阳",
+                self.query.as_str(),
+                move |s| to_msg(AreaPickerMessage::Input(s)),
+            )
+            .size(20)
+            .padding(3)
+            .on_submit(to_msg(AreaPickerMessage::Submit)),
+        );
+        for (idx, (m, st)) in self.suggestions.iter_mut().enumerate() {
+            col = col.push(
+                Button::new(st, Text::new(m.display()).size(18))
+                    .width(Length::Fill)
+                    .style(style::ActionButton)
+                    .on_press(to_msg(AreaPickerMessage::Select(idx))),
+            );
+        }
+        col.into()
+    }
+}
@@ -0,0 +1,166 @@
+use iced::*;
+use log::info;
+
+use super::super::*;
+use super::style;
+use super::PORTION_1;
+use crate::services::budget::{remaining_amount, remaining_num, BudgetSummary, Spend};
+
+#[derive(Default)]
+pub struct BudgetComponent {
+    refresh_button_state: button::State,
+    scroll_state: scrollable::State,
+    summary: BudgetSummary,
+}
+
+fn fmt_amount(v: Option<f64>) -> String {
+    v.map(|v| format!("{:.2}", v)).unwrap_or("不限".to_string())
+}
+
+fn fmt_num(v: Option<u32>) -> String {
+    v.map(|v| v.to_string()).unwrap_or("不限".to_string())
+}
+
+impl BudgetComponent {
+    pub const ACCOUNT_PORTION: u16 = 2;
+    pub const SPENT_PORTION: u16 = 1;
+    pub const BLOCKED_DT_PORTION: u16 = 2;
+    pub const REASON_PORTION: u16 = 4;
+
+    fn spend_row<'a>(name: &str, spent: Spend, limit: f64, num_limit: u32) -> Element<'a, JdMiaoshaAppMessage> {
+        Row::new()
+            .align_items(Align::Center)
+            .push(label_txt(name, Self::ACCOUNT_PORTION))
+            .push(label_txt(format!("{:.2}", spent.amount), Self::SPENT_PORTION))
+            .push(label_txt(
+                fmt_amount(remaining_amount(limit, spent.amount)),
+                Self::SPENT_PORTION,
+            ))
+            .push(label_txt(spent.num.to_string(), Self::SPENT_PORTION))
+            .push(label_txt(
+                fmt_num(remaining_num(num_limit, spent.num)),
+                Self::SPENT_PORTION,
+            ))
+            .into()
+    }
+
+    pub fn update(&mut self, message: BudgetMessage) -> Command<JdMiaoshaAppMessage> {
+        use BudgetMessage::*;
+        match message {
+            LoadFinish(summary) => {
+                self.summary = summary;
+            }
+            _ => {
+                info!("{:?}", message);
+            }
+        }
+        Command::none()
+    }
+
+    pub fn view(&mut self) -> Container<JdMiaoshaAppMessage> {
+        let budget = &self.summary.budget;
+        let top_row = Row::new()
+            .align_items(Align::Center)
+            .push(label_txt(
+                format!(
+                    "单笔上限: 金额 {}, 数量 {}",
+                    fmt_amount(Some(budget.max_per_order).filter(|v| *v > 0.0)),
+                    fmt_num(Some(budget.max_num_per_order).filter(|v| *v > 0)),
+                ),
+                4,
+            ))
+            .push(Space::with_width(Length::FillPortion(PORTION_1)))
+            .push(
+                Button::new(
+                    &mut self.refresh_button_state,
+                    Text::new("刷新").horizontal_alignment(HorizontalAlignment::Center),
+                )
+                .style(style::ActionButton)
+                .on_press(BudgetMessage::Refresh.into()),
+            )
+            .spacing(10)
+            .padding(3);
+
+        let spend_headers = ["账号", "今天金额", "剩余金额", "今天数量", "剩余数量"];
+        let spend_portions = [
+            Self::ACCOUNT_PORTION,
+            Self::SPENT_PORTION,
+            Self::SPENT_PORTION,
+            Self::SPENT_PORTION,
+            Self::SPENT_PORTION,
+        ];
+        let mut spends = Column::new().spacing(3).push(Self::spend_row(
+            "全部账号",
+            self.summary.global,
+            budget.max_global_daily,
+            budget.max_num_global_daily,
+        ));
+        for (account, spent) in self.summary.accounts.iter() {
+            spends = spends.push(Self::spend_row(
+                account,
+                *spent,
+                budget.max_per_account_daily,
+                budget.max_num_per_account_daily,
+            ));
+        }
+
+        let blocked_headers = ["时间", "账号", "商品SKU", "金额", "原因"];
+        let blocked_portions = [
+            Self::BLOCKED_DT_PORTION,
+            Self::ACCOUNT_PORTION,
+            Self::ACCOUNT_PORTION,
+            Self::SPENT_PORTION,
+            Self::REASON_PORTION,
+        ];
+        let blocked = self.summary.blocked.iter().fold(
+            Column::new().spacing(3),
+            |column, b| {
+                column.push(
+                    Row::new()
+                        .align_items(Align::Center)
+                        .push(label_txt(b.create_dt.as_str(), Self::BLOCKED_DT_PORTION))
+                        .push(label_txt(b.account.as_str(), Self::ACCOUNT_PORTION))
+                        .push(label_txt(b.sku.as_str(), Self::ACCOUNT_PORTION))
+                        .push(label_txt(format!("{:.2}", b.spend.amount), Self::SPENT_PORTION))
+                        .push(label_txt(b.reason.as_str(), Self::REASON_PORTION)),
+                )
+            },
+        );
+        let scroll = Scrollable::new(&mut self.scroll_state)
+            .scrollbar_margin(1)
+            .scrollbar_width(1)
+            .style(style::ScrollableBarStyle)
+            .max_height(super::MAX_SCROLL_HEIGHT)
+            .push(spends)
+            .push(Space::with_height(Length::Units(10)))
+            .push(Row::with_children(super::get_headers(
+                &blocked_headers,
+                &blocked_portions,
+            )))
+            .push(blocked);
+
+        Container::new(
+            Column::new()
+                .width(Length::Fill)
+                .spacing(4)
+                .push(top_row)
+                .push(Row::with_children(super::get_headers(
+                    &spend_headers,
+                    &spend_portions,
+                )))
+                .push(scroll),
+        )
+        .width(Length::Fill)
+    }
+}
+
+const TXT_COLOR: Color = Color::from_rgb(0.0, 191.0 / 255.0, 1.0);
+
+fn label_txt<'a, T: AsRef<str>>(txt: T, portion: u16) -> Element<'a, JdMiaoshaAppMessage> {
+    Text::new(txt.as_ref())
+        .horizontal_alignment(HorizontalAlignment::Center)
+        .vertical_alignment(VerticalAlignment::Center)
+        .color(TXT_COLOR)
+        .width(Length::FillPortion(portion))
+        .into()
+}
@@ -5,13 +5,16 @@ use serde_json::{json, to_string};
 use super::super::*;
 use super::style;
 use super::PORTION_1;
-use crate::models::{AddressInfo, UserInfo, UserState};
-use crate::services::delivery_address::DeliveryAddressService;
+use super::AreaPicker;
+use crate::models::{AddressForm, AddressInfo, Area, AreaMatch, UserInfo, UserState, PROVINCES, PROVINCE_NAMES};
+use crate::services::area::{area_tree, AreaService};
+use crate::services::delivery_address::ApplyOutcome;
+use crate::services::retailer::retailer;
 use crate::utils::*;
 use crate::{PKDate, PKDateTime, PKLocal};
 use iced::pane_grid::Line;
 use std::collections::hash_map::Entry;
-use std::collections::{BTreeMap, HashMap, LinkedList};
+use std::collections::{BTreeMap, BTreeSet, HashMap, LinkedList};
 use std::ops::{Deref, Sub};
 
 #[derive(Default)]
@@ -23,6 +26,235 @@ pub struct DeliveryAddressComponent {
     scroll_state: scrollable::State,
     // 记录用户的所有收货地址
    pub  user_address: BTreeMap<String, LinkedList<AddressInfo>>,
+    // 所有账号, 批量设置地址时选择
+    pub accounts: Vec<String>,
+    new_button_state: button::State,
+    // 新增、修改地址的表单
+    editor: Option<AddressEditor>,
+    // 等待再次点击确认删除的地址: 账号, 地址id
+    confirm_delete: Option<(String, String)>,
+    // 保存、删除的结果, 出错时是错误信息
+    tip: String,
+    tip_is_error: bool,
+}
+
+/// 新增、修改地址的表单
+#[derive(Default)]
+struct AddressEditor {
+    form: AddressForm,
+    // 正在修改的地址: 账号, 地址id; 新增时为 None
+    editing: Option<(String, String)>,
+    // 批量设置时选中的账号
+    selected: BTreeSet<String>,
+    // 第二到第四级地区
+    sub_areas: [Area; 3],
+    sub_area_names: [Vec<String>; 3],
+    area_pick_states: [pick_list::State<String>; 4],
+    area_picker: AreaPicker,
+    receiver_input_state: text_input::State,
+    address_input_state: text_input::State,
+    mobile_input_state: text_input::State,
+    phone_input_state: text_input::State,
+    email_input_state: text_input::State,
+    save_button_state: button::State,
+    close_button_state: button::State,
+    saving: bool,
+    // 批量设置的结果
+    report: Vec<(String, ApplyOutcome)>,
+}
+
+impl AddressEditor {
+    fn area_map(&self, level: usize) -> &Area {
+        if level == 0 {
+            &*PROVINCES
+        } else {
+            &self.sub_areas[level - 1]
+        }
+    }
+
+    // 选中的各级地区名称连起来, 如 北京朝阳区三环以内
+    fn area_zone(&self) -> String {
+        self.form
+            .area_ids
+            .iter()
+            .enumerate()
+            .filter_map(|(level, id)| self.area_map(level).get(id).cloned())
+            .collect::<Vec<_>>()
+            .concat()
+    }
+
+    fn update_area(&mut self, level: usize, name: &str) -> Command<JdMiaoshaAppMessage> {
+        let id = match self.area_map(level).iter().find(|(_, n)| n.as_str() == name) {
+            Some((id, _)) => *id,
+            None => return Command::none(),
+        };
+        self.form.area_ids[level] = id;
+        // 清空后面的选择
+        for idx in level + 1..self.form.area_ids.len() {
+            self.form.area_ids[idx] = 0;
+        }
+        for idx in level..self.sub_areas.len() {
+            self.sub_areas[idx].clear();
+            self.sub_area_names[idx].clear();
+        }
+        self.form.area_zone = self.area_zone();
+        if level + 1 == self.form.area_ids.len() {
+            return Command::none();
+        }
+        let level = level as u8;
+        Command::perform(AreaService::get_sub_areas(id), move |r| match r {
+            Ok(area) => DeliveryAddressMessage::AreaLoaded(level, area).into(),
+            Err(e) => {
+                error!("{:?}", e);
+                DeliveryAddressMessage::AreaLoaded(level, Area::new()).into()
+            }
+        })
+    }
+
+    // 搜索选中的地区, 各级的下拉框从本地地区树里填
+    fn pick_area(&mut self, m: AreaMatch) {
+        let tree = area_tree();
+        let ids = m.id_list();
+        for (idx, id) in self.form.area_ids.iter_mut().enumerate() {
+            *id = ids.get(idx).cloned().unwrap_or(0);
+        }
+        for idx in 0..self.sub_areas.len() {
+            let area = tree.children(self.form.area_ids[idx]).cloned().unwrap_or_default();
+            self.sub_area_names[idx] = area.values().cloned().collect();
+            self.sub_areas[idx] = area;
+        }
+        self.form.area_zone = m.names.concat();
+    }
+
+    fn view<'a>(&'a mut self, accounts: &'a [String]) -> Element<'a, DeliveryAddressMessage> {
+        use DeliveryAddressMessage::*;
+        let AddressEditor {
+            form,
+            editing,
+            selected,
+            sub_areas,
+            sub_area_names,
+            area_pick_states,
+            area_picker,
+            receiver_input_state,
+            address_input_state,
+            mobile_input_state,
+            phone_input_state,
+            email_input_state,
+            save_button_state,
+            close_button_state,
+            saving,
+            report,
+        } = self;
+        let title = match editing {
+            Some((account, _)) => format!("修改账号{}的地址, 需要重新选择地区和填写手机号", account),
+            None => "新增地址, 已经有相同收货人和详细地址的账号会修改原来的地址".to_string(),
+        };
+        let info_row = Row::new()
+            .align_items(Align::Center)
+            .spacing(10)
+            .padding(3)
+            .push(Text::new("收货人").color(TXT_COLOR))
+            .push(form_input(receiver_input_state, "收货人", form.receiver.as_str(), AddressField::Receiver))
+            .push(Text::new("手机").color(TXT_COLOR))
+            .push(form_input(mobile_input_state, "11位手机号", form.mobile_phone.as_str(), AddressField::MobilePhone))
+            .push(Text::new("固定电话").color(TXT_COLOR))
+            .push(form_input(phone_input_state, "选填", form.fixed_line_phone.as_str(), AddressField::FixedLinePhone))
+            .push(Text::new("电子邮箱").color(TXT_COLOR))
+            .push(form_input(email_input_state, "选填", form.email.as_str(), AddressField::Email));
+
+        let mut area_row = Row::new()
+            .align_items(Align::Center)
+            .spacing(10)
+            .padding(3)
+            .push(Text::new("所在地区").color(TXT_COLOR));
+        let sub_areas: &'a [Area; 3] = sub_areas;
+        let sub_area_names: &'a [Vec<String>; 3] = sub_area_names;
+        for (level, state) in area_pick_states.iter_mut().enumerate() {
+            let (area, names) = if level == 0 {
+                (&*PROVINCES, &*PROVINCE_NAMES)
+            } else {
+                (&sub_areas[level - 1], &sub_area_names[level - 1])
+            };
+            if names.is_empty() {
+                continue;
+            }
+            area_row = area_row.push(PickList::new(
+                state,
+                names,
+                area.get(&form.area_ids[level]).cloned(),
+                move |name| AreaChanged(level as u8, name),
+            ));
+        }
+        let area_row = area_row
+            .push(Container::new(area_picker.view(PickArea)).width(Length::Units(220)))
+            .push(Text::new("详细地址").color(TXT_COLOR))
+            .push(
+                form_input(address_input_state, "街道、门牌号", form.address.as_str(), AddressField::Address)
+                    .width(Length::FillPortion(3)),
+            );
+
+        let mut content = Column::new()
+            .spacing(3)
+            .push(label_txt(title, 1, Some(HorizontalAlignment::Left), None))
+            .push(info_row)
+            .push(area_row);
+        // 新增的地址可以同时设置到多个账号
+        if editing.is_none() {
+            let accounts_row = accounts.iter().fold(
+                Row::new()
+                    .align_items(Align::Center)
+                    .spacing(10)
+                    .padding(3)
+                    .push(Text::new("设置到账号").color(TXT_COLOR)),
+                |row, account| {
+                    let a = account.clone();
+                    row.push(Checkbox::new(selected.contains(account), account.as_str(), move |checked| {
+                        SelectAccount(a.clone(), checked)
+                    }))
+                },
+            );
+            content = content.push(accounts_row);
+        }
+        let mut save_button = Button::new(
+            save_button_state,
+            Text::new(if editing.is_some() { "保存" } else { "设置到选中的账号" })
+                .horizontal_alignment(HorizontalAlignment::Center),
+        )
+        .style(style::ActionButton);
+        // 保存完成前不能重复提交
+        if !*saving {
+            save_button = save_button.on_press(if editing.is_some() { Save } else { Apply });
+        }
+        content = content.push(
+            Row::new()
+                .align_items(Align::Center)
+                .spacing(10)
+                .padding(3)
+                .push(Space::with_width(Length::Fill))
+                .push(save_button)
+                .push(
+                    Button::new(
+                        close_button_state,
+                        Text::new("关闭").horizontal_alignment(HorizontalAlignment::Center),
+                    )
+                    .style(style::ActionButton)
+                    .on_press(FormClose),
+                ),
+        );
+        // 每个账号的设置结果
+        let content = report.iter().fold(content, |column, (account, outcome)| {
+            let (txt, color) = match outcome {
+                ApplyOutcome::Added(id) => (format!("{}: 新增了地址 {}", account, id), None),
+                ApplyOutcome::Updated(id) => (format!("{}: 修改了地址 {}", account, id), None),
+                ApplyOutcome::Failed(e) => (format!("{}: 失败, {}", account, e), Some(Color::from_rgb(1.0, 0.0, 0.0))),
+            };
+            column.push(label_txt(txt, 1, Some(HorizontalAlignment::Left), color))
+        });
+        Container::new(content.push(Rule::horizontal(6).style(style::RowSplitLineRule)))
+            .width(Length::Fill)
+            .into()
+    }
 }
 
 impl DeliveryAddressComponent {
@@ -33,7 +265,7 @@ impl DeliveryAddressComponent {
     pub const MOBILE_PHONE_PORTION: u16 = 3;
     pub const EMAIL_PORTION: u16 = 3;
     pub const FIXED_LINE_PHONE_PORTION: u16 = 3;
-    pub const OP_PORTION: u16 = 3;
+    pub const OP_PORTION: u16 = 5;
 
     fn headers_portions() -> (Vec<String>, Vec<u16>) {
         let mut headers = Vec::with_capacity(8);
@@ -66,6 +298,25 @@ impl DeliveryAddressComponent {
         )
     }
 
+    fn find_address(&self, account: &str, addr_id: &str) -> Option<&AddressInfo> {
+        self.user_address
+            .get(account)
+            .and_then(|addrs| addrs.iter().find(|addr| addr.addr_id == addr_id))
+    }
+
+    // 保存成功后重新加载所有账号的地址
+    fn reload() -> Command<JdMiaoshaAppMessage> {
+        Command::perform(async {}, |_| JdMiaoshaAppMessage::GlobalDeliveryAddressPressed)
+    }
+
+    /// 批量设置的地址和选中的账号, 表单没有打开或者正在修改已有的地址时为 None
+    pub fn apply_target(&self) -> Option<(AddressForm, BTreeSet<String>)> {
+        self.editor
+            .as_ref()
+            .filter(|editor| editor.editing.is_none())
+            .map(|editor| (editor.form.clone(), editor.selected.clone()))
+    }
+
     pub fn update(&mut self, message: DeliveryAddressMessage) -> Command<JdMiaoshaAppMessage> {
         use DeliveryAddressMessage::*;
         // 记录是否点击了选择按钮，避免发送过多的消息
@@ -98,8 +349,9 @@ impl DeliveryAddressComponent {
                                 let account = addr.account.clone();
                                 let ck = addr.cookie.clone();
                                 let addr_id = addr.addr_id.clone();
-                                cmds.push( Command::perform(async {
-                                    DeliveryAddressService::set_order_express_address(addr_id, account, ck).await
+                                let platform = addr.platform;
+                                cmds.push( Command::perform(async move {
+                                    retailer(platform).set_order_address(addr_id, account, ck).await
                                 }, |r|{
                                     match r {
                                         Ok(Some((account, addr_id))) =>{
@@ -129,6 +381,182 @@ impl DeliveryAddressComponent {
                         }).count();
                 }
             }
+            New => {
+                self.editor = Some(AddressEditor::default());
+            }
+            Edit(account, addr_id) => {
+                if let Some(addr) = self.find_address(account.as_str(), addr_id.as_str()) {
+                    self.editor = Some(AddressEditor {
+                        form: AddressForm::from_info(addr),
+                        editing: Some((account, addr_id)),
+                        ..Default::default()
+                    });
+                }
+            }
+            Delete(account, addr_id) => {
+                let key = (account, addr_id);
+                // 第一次点击只是让按钮变成确认删除
+                if self.confirm_delete.as_ref() != Some(&key) {
+                    self.confirm_delete = Some(key);
+                    return Command::none();
+                }
+                self.confirm_delete = None;
+                let (account, addr_id) = key;
+                if let Some(addr) = self.find_address(account.as_str(), addr_id.as_str()) {
+                    let cookie = addr.cookie.clone();
+                    let platform = addr.platform;
+                    return Command::perform(
+                        async move {
+                            let r = retailer(platform)
+                                .delete_address(account.clone(), cookie, addr_id.clone())
+                                .await;
+                            (account, addr_id, r)
+                        },
+                        |(account, addr_id, r)| {
+                            let r = r.map_err(|e| {
+                                error!("删除地址失败:{}-{}, {:?}", account, addr_id, e);
+                                format!("删除地址失败: {}", e)
+                            });
+                            DeleteFinish(account, addr_id, r).into()
+                        },
+                    );
+                }
+            }
+            DeleteFinish(account, addr_id, r) => match r {
+                Ok(()) => {
+                    if let Some(addrs) = self.user_address.get_mut(account.as_str()) {
+                        *addrs = std::mem::take(addrs)
+                            .into_iter()
+                            .filter(|addr| addr.addr_id != addr_id)
+                            .collect();
+                        if addrs.is_empty() {
+                            self.user_address.remove(account.as_str());
+                        }
+                    }
+                    self.tip_is_error = false;
+                    self.tip = format!("已删除账号{}的地址", account);
+                }
+                Err(e) => {
+                    self.tip_is_error = true;
+                    self.tip = e;
+                }
+            },
+            FormInput(field, txt) => {
+                if let Some(editor) = self.editor.as_mut() {
+                    let form = &mut editor.form;
+                    match field {
+                        AddressField::Receiver => form.receiver = txt,
+                        AddressField::Address => form.address = txt,
+                        AddressField::MobilePhone => form.mobile_phone = txt.trim().to_string(),
+                        AddressField::FixedLinePhone => form.fixed_line_phone = txt.trim().to_string(),
+                        AddressField::Email => form.email = txt.trim().to_string(),
+                    }
+                }
+            }
+            AreaChanged(level, name) => {
+                if let Some(editor) = self.editor.as_mut() {
+                    return editor.update_area(level as usize, name.as_str());
+                }
+            }
+            AreaLoaded(level, area) => {
+                if let Some(editor) = self.editor.as_mut() {
+                    let idx = level as usize;
+                    if idx < editor.sub_areas.len() {
+                        editor.sub_area_names[idx] = area.values().cloned().collect();
+                        editor.sub_areas[idx] = area;
+                    }
+                }
+            }
+            PickArea(msg) => {
+                if let Some(editor) = self.editor.as_mut() {
+                    if let Some(m) = editor.area_picker.update(msg) {
+                        editor.pick_area(m);
+                    }
+                }
+            }
+            SelectAccount(account, checked) => {
+                if let Some(editor) = self.editor.as_mut() {
+                    if checked {
+                        editor.selected.insert(account);
+                    } else {
+                        editor.selected.remove(account.as_str());
+                    }
+                }
+            }
+            Save => {
+                let (account, addr_id, form) = match self.editor.as_ref() {
+                    Some(AddressEditor { editing: Some((account, addr_id)), form, .. }) => {
+                        (account.clone(), addr_id.clone(), form.clone())
+                    }
+                    _ => return Command::none(),
+                };
+                if let Err(e) = form.validate() {
+                    return self.update(Invalid(e));
+                }
+                let (platform, cookie) = match self.find_address(account.as_str(), addr_id.as_str()) {
+                    Some(addr) => (addr.platform, addr.cookie.clone()),
+                    None => return Command::none(),
+                };
+                self.editor.as_mut().unwrap().saving = true;
+                return Command::perform(
+                    async move {
+                        retailer(platform)
+                            .update_address(account, cookie, addr_id, form)
+                            .await
+                    },
+                    |r| {
+                        SaveFinish(r.map_err(|e| {
+                            error!("修改地址失败:{:?}", e);
+                            format!("修改地址失败: {}", e)
+                        }))
+                        .into()
+                    },
+                );
+            }
+            SaveFinish(r) => match r {
+                Ok(_) => {
+                    self.editor = None;
+                    self.tip_is_error = false;
+                    self.tip = "地址已保存".to_string();
+                    return Self::reload();
+                }
+                Err(e) => {
+                    if let Some(editor) = self.editor.as_mut() {
+                        editor.saving = false;
+                    }
+                    self.tip_is_error = true;
+                    self.tip = e;
+                }
+            },
+            Apply => {
+                // app 里开始批量设置时转发过来
+                if let Some(editor) = self.editor.as_mut() {
+                    editor.saving = true;
+                    editor.report.clear();
+                }
+                self.tip_is_error = false;
+                self.tip = "正在设置地址...".to_string();
+            }
+            ApplyFinish(report) => {
+                let failed = report
+                    .iter()
+                    .filter(|(_, outcome)| matches!(outcome, ApplyOutcome::Failed(_)))
+                    .count();
+                self.tip_is_error = failed > 0;
+                self.tip = format!("设置了{}个账号, 失败{}个", report.len() - failed, failed);
+                if let Some(editor) = self.editor.as_mut() {
+                    editor.saving = false;
+                    editor.report = report;
+                }
+                return Self::reload();
+            }
+            Invalid(e) => {
+                self.tip_is_error = true;
+                self.tip = e;
+            }
+            FormClose => {
+                self.editor = None;
+            }
             _ => {
                 info!("{:?}", message);
             }
@@ -138,6 +566,7 @@ impl DeliveryAddressComponent {
 
     pub fn view<'a>(&'a mut self) -> Container<'a, JdMiaoshaAppMessage> {
         let (headers, portions) = Self::headers_portions();
+        let confirm_delete = &self.confirm_delete;
         let states: Element<_> = self
             .user_address
             .iter_mut()
@@ -146,7 +575,11 @@ impl DeliveryAddressComponent {
                 let one_user_row = Container::new(addrs.iter_mut().enumerate().fold(
                     Column::new().spacing(3),
                     |_column, (idx, addr)| {
-                        _column.push(addr.view(idx, total_addr_cnt).map(|msg| msg.into()))
+                        let confirming = confirm_delete
+                            .as_ref()
+                            .map(|(a, id)| a == account && id == &addr.addr_id)
+                            .unwrap_or(false);
+                        _column.push(addr.view(idx, total_addr_cnt, confirming).map(|msg| msg.into()))
                     },
                 ))
                 .width(Length::Fill);
@@ -155,7 +588,11 @@ impl DeliveryAddressComponent {
             .into();
         let search_row = Row::new()
             .align_items(Align::Center)
-            .push(Space::with_width(Length::FillPortion(1)))
+            .push(
+                Text::new(self.tip.as_str())
+                    .color(if self.tip_is_error { Color::from_rgb(1.0, 0.0, 0.0) } else { TXT_COLOR })
+                    .width(Length::FillPortion(1)),
+            )
             .push(
                 TextInput::new(
                     &mut self.search_input_state,
@@ -176,6 +613,14 @@ impl DeliveryAddressComponent {
                 .style(style::ActionButton)
                 .on_press(DeliveryAddressMessage::Search.into()),
             )
+            .push(
+                Button::new(
+                    &mut self.new_button_state,
+                    Text::new("新增地址").horizontal_alignment(HorizontalAlignment::Center),
+                )
+                .style(style::ActionButton)
+                .on_press(DeliveryAddressMessage::New.into()),
+            )
             .spacing(10)
             .padding(3);
         let scroll = Scrollable::new(&mut self.scroll_state)
@@ -185,11 +630,12 @@ impl DeliveryAddressComponent {
             .max_height(super::MAX_SCROLL_HEIGHT + 40)
             .push(Container::new(states).width(Length::Fill).center_x());
 
+        let mut content = Column::new().width(Length::Fill).spacing(4).push(search_row);
+        if let Some(editor) = self.editor.as_mut() {
+            content = content.push(editor.view(&self.accounts).map(|msg| msg.into()));
+        }
         return Container::new(
-            Column::new()
-                .width(Length::Fill)
-                .spacing(4)
-                .push(search_row)
+            content
                 .push(Row::with_children(super::get_headers(&headers, &portions)))
                 .push(scroll), // .push(Rule::horizontal(6).style(style::UserLineRule))
         )
@@ -199,13 +645,13 @@ impl DeliveryAddressComponent {
 
 const TXT_COLOR: Color = Color::from_rgb(0.0, 191.0 / 255.0, 1.0);
 
-fn label_txt(
-    txt: &str,
+fn label_txt<'a, T: AsRef<str>>(
+    txt: T,
     portion: u16,
     align_x: Option<HorizontalAlignment>,
     color:Option<Color>
-) -> Element<DeliveryAddressMessage> {
-    Text::new(txt)
+) -> Element<'a, DeliveryAddressMessage> {
+    Text::new(txt.as_ref())
         .horizontal_alignment(align_x.unwrap_or(HorizontalAlignment::Center))
         .vertical_alignment(VerticalAlignment::Center)
         .color(color.unwrap_or(TXT_COLOR))
@@ -213,6 +659,17 @@ fn label_txt(
         .into()
 }
 
+fn form_input<'a>(
+    state: &'a mut text_input::State,
+    placeholder: &str,
+    value: &str,
+    field: AddressField,
+) -> TextInput<'a, DeliveryAddressMessage> {
+    TextInput::new(state, placeholder, value, move |s| DeliveryAddressMessage::FormInput(field, s))
+        .padding(3)
+        .width(Length::FillPortion(1))
+}
+
 impl AddressInfo {
     fn new() -> Self {
         Default::default()
@@ -230,7 +687,7 @@ impl AddressInfo {
         Command::none()
     }
 
-    fn view(&mut self, idx: usize, total: usize) -> Element<DeliveryAddressMessage> {
+    fn view(&mut self, idx: usize, total: usize, confirming: bool) -> Element<DeliveryAddressMessage> {
         let mut row = Row::new().align_items(Align::Center);
         let mut op_box_row = Row::new()
             .push(Space::with_width(Length::Units(3)))
@@ -267,6 +724,20 @@ impl AddressInfo {
                 ))
                 .push(Space::with_width(Length::FillPortion(1)));
         }
+        op_box_row = op_box_row
+            .push(op_button(
+                &mut self.edit_button_state,
+                "修改",
+                Length::FillPortion(2),
+                DeliveryAddressMessage::Edit(self.account.to_string(), self.addr_id.to_string()),
+            ))
+            .push(op_button(
+                &mut self.delete_button_state,
+                // 点两次才删除
+                if confirming { "确认删除" } else { "删除" },
+                Length::FillPortion(2),
+                DeliveryAddressMessage::Delete(self.account.to_string(), self.addr_id.to_string()),
+            ));
         // 是否是偶数行
         let is_mid_row = idx == (total >> 1);
         if is_mid_row{
@@ -1,6 +1,7 @@
-use std::collections::LinkedList;
+use std::collections::{HashMap, LinkedList};
 use std::sync::Arc;
 
+use futures::future::join_all;
 use iced::*;
 use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
@@ -9,15 +10,18 @@ use serde_json::{json, to_string};
 use super::super::*;
 use super::style;
 use super::PORTION_1;
+use super::AreaPicker;
 use crate::models::{
-    Area, GoodsState, DEFAULT_ADDR, DEFAULT_ADDR_NAMES, PROVINCES, PROVINCE_NAMES,
+    Area, GoodsState, ProdPlatform, SearchPage, StockStatus, DEFAULT_ADDR, DEFAULT_ADDR_NAMES,
+    PROVINCES, PROVINCE_NAMES,
 };
 use crate::services::area::AreaService;
-use crate::services::goods::GoodsService;
+use crate::services::parser::parse_sku_input;
+use crate::services::retailer::retailer;
 use crate::services::shopping_cart::ShoppingCartService;
 use crate::ui::JdMiaoshaAppMessage::Goods;
 use crate::utils::icon::{plus_icon, sub_icon};
-use crate::{PRESALE, YUYUE};
+use crate::{IDType, PRESALE, YUYUE};
 use core::fmt::Alignment::Left;
 use std::borrow::Cow;
 use crate::utils::datetime_fmt_option;
@@ -26,6 +30,8 @@ use crate::utils::datetime_fmt_option;
 pub struct GoodsComponent {
     // 商品搜索输入框的内容
     pub search_input_txt: String,
+    // 查询商品的平台
+    pub platform: ProdPlatform,
     pub cur_state: Option<JdMiaoshaAppMessage>,
     // 地区选择相关 -- 开始
     level1_pick_state: pick_list::State<String>,
@@ -39,6 +45,8 @@ pub struct GoodsComponent {
     leve3_pick_list: Vec<String>,
     leve4_pick_list: Vec<String>,
     selected_area_ids: [i64; 4],
+    // 按名称搜索地区
+    area_picker: AreaPicker,
     // 地区选择相关 -- 结束
     // 滚动条
     pub scroll_state: scrollable::State,
@@ -46,6 +54,17 @@ pub struct GoodsComponent {
     pub goods_search_input_state: text_input::State,
     // 商品搜索按钮
     pub goods_search_button_state: button::State,
+    // 关键字搜索的结果, 输入的是 sku 或商品链接时为 None
+    search_result: Option<SearchPage>,
+    search_result_button_states: Vec<button::State>,
+    search_shop_txt: String,
+    search_shop_input_state: text_input::State,
+    search_prev_button_state: button::State,
+    search_next_button_state: button::State,
+    search_close_button_state: button::State,
+    search_scroll_state: scrollable::State,
+    // 搜索、加入购物车的提示
+    search_tip: String,
     pub clear_all_goods_button_state: button::State,
     pub activate_code: String,
     pub goods: LinkedList<GoodsState>,
@@ -137,22 +156,97 @@ impl GoodsComponent {
             }
             Search => {
                 self.cur_state = Some(GoodsMessage::Search.into());
-                if self.search_input_txt.len() > 0 {
-                    let search_txt = self.search_input_txt.to_string();
-                    let area_id = self.get_addr_str();
-                    return Command::perform(
-                        async move {
-                            GoodsService::get_prod_info(search_txt.as_str(), area_id.as_str()).await
-                        },
-                        |t| match t {
-                            Ok(gs) => GoodsMessage::SearchFinish(Some(gs)).into(),
-                            Err(e) => {
-                                error!("get_prod_info {:?}", e);
-                                GoodsMessage::SearchFinish(None).into()
-                            }
-                        },
-                    );
+                let input = self.search_input_txt.trim().to_string();
+                if input.is_empty() {
+                    return Command::none();
+                }
+                // 输入的是 sku 或商品链接时直接查这个商品, 否则按关键字搜索
+                let sku = match parse_sku_input(input.as_str()) {
+                    Some(sku) => sku,
+                    None => return self.keyword_search(input, 1),
+                };
+                self.search_input_txt = sku.clone();
+                let area_id = self.get_addr_str();
+                let platform = self.platform;
+                return Command::perform(
+                    async move {
+                        retailer(platform)
+                            .get_prod_info(sku.as_str(), area_id.as_str())
+                            .await
+                    },
+                    |t| match t {
+                        Ok(gs) => GoodsMessage::SearchFinish(Some(gs)).into(),
+                        Err(e) => {
+                            error!("get_prod_info {:?}", e);
+                            GoodsMessage::SearchFinish(None).into()
+                        }
+                    },
+                );
+            }
+            KeywordSearchFinish(r) => match r {
+                Ok(page) => {
+                    self.search_tip = if page.items.is_empty() {
+                        format!("没有找到\"{}\"相关的商品", page.keyword)
+                    } else {
+                        String::new()
+                    };
+                    self.search_result_button_states = vec![button::State::new(); page.items.len()];
+                    self.search_result = Some(page);
                 }
+                Err(e) => {
+                    self.search_tip = format!("搜索失败: {}", e);
+                }
+            },
+            SearchShopInput(s) => {
+                self.search_shop_txt = s;
+            }
+            SearchPageTo(page) => {
+                if let Some(keyword) = self.search_result.as_ref().map(|r| r.keyword.clone()) {
+                    return self.keyword_search(keyword, page);
+                }
+            }
+            AddSearchResult(sku) => {
+                let area_id = self.get_addr_str();
+                let platform = self.platform;
+                return Command::perform(
+                    async move {
+                        retailer(platform)
+                            .get_prod_info(sku.as_str(), area_id.as_str())
+                            .await
+                    },
+                    |r| {
+                        let r = r.map_err(|e| {
+                            error!("get_prod_info {:?}", e);
+                            e.to_string()
+                        });
+                        GoodsMessage::SearchResultFetched(r).into()
+                    },
+                );
+            }
+            SearchResultFetched(r) => match r {
+                Ok(data) => {
+                    let sku = data.sku.clone();
+                    self.search_tip = if code.is_empty() {
+                        format!("已把 {} 加入商品列表, 激活后才能加入购物车", sku)
+                    } else {
+                        format!("已把 {} 加入商品列表和购物车", sku)
+                    };
+                    if !self.goods.iter().any(|gs| gs.sku == sku) {
+                        if self.goods.len() > 50 {
+                            self.goods.pop_back();
+                        }
+                        self.goods.push_front(data);
+                    }
+                    return self.update(AddToShoppingCart(sku));
+                }
+                Err(e) => {
+                    self.search_tip = format!("获取商品信息失败: {}", e);
+                }
+            },
+            CloseSearchResults => {
+                self.search_result = None;
+                self.search_result_button_states.clear();
+                self.search_tip.clear();
             }
             SearchFinish(d) => {
                 if let Some(data) = d {
@@ -210,28 +304,47 @@ impl GoodsComponent {
                 self.goods.clear();
             }
 
-            MonitorStock(cart_goods_id, sku) => {
+            MonitorStock(goods) => {
                 // 商品的配送地址
                 let addr = self.get_addr_str();
+                // 按平台合并查询, 多个账号的购物车里有同一个商品时只查一次
+                let mut skus: HashMap<ProdPlatform, Vec<String>> = HashMap::new();
+                for (_, platform, sku) in goods.iter() {
+                    skus.entry(*platform).or_default().push(sku.clone());
+                }
                 return Command::perform(
-                    async move { GoodsService::get_prod_stock(cart_goods_id, sku, addr).await },
-                    |r| {
-                        match r {
-                            Ok((cart_goods_id, stock_status)) => {
-                                if stock_status.is_stock() {
-                                    // 有库存之后才去更新商品库存， 同时下单购买
-                                    return ShoppingCartMessage::UpdateStock(
-                                        cart_goods_id,
-                                        stock_status,
-                                    )
-                                    .into();
+                    async move {
+                        let queries = skus.into_iter().map(|(platform, mut skus)| {
+                            skus.sort();
+                            skus.dedup();
+                            let addr = addr.clone();
+                            async move { (platform, retailer(platform).get_prod_stocks(skus, addr).await) }
+                        });
+                        let mut stocks = HashMap::new();
+                        for (platform, r) in join_all(queries).await {
+                            match r {
+                                Ok(s) => {
+                                    stocks.insert(platform, s);
                                 }
+                                Err(e) => error!("{:?}", e),
                             }
-                            Err(e) => {
-                                error!("{:?}", e);
-                            }
                         }
-                        JdMiaoshaAppMessage::GlobalNoop
+                        goods
+                            .into_iter()
+                            .filter_map(|(id, platform, sku)| {
+                                stocks.get(&platform).and_then(|s| s.get(&sku)).map(|s| (id, *s))
+                            })
+                            .collect::<Vec<_>>()
+                    },
+                    |stocks| {
+                        // 有库存之后才去更新商品库存， 同时下单购买
+                        let in_stock: Vec<(IDType, StockStatus)> =
+                            stocks.into_iter().filter(|(_, status)| status.is_stock()).collect();
+                        if in_stock.is_empty() {
+                            JdMiaoshaAppMessage::GlobalNoop
+                        } else {
+                            GoodsMessage::MonitorStockFinish(in_stock).into()
+                        }
                     },
                 );
             }
@@ -243,6 +356,27 @@ impl GoodsComponent {
         Command::none()
     }
 
+    fn keyword_search(&mut self, keyword: String, page: u32) -> Command<JdMiaoshaAppMessage> {
+        let area_id = self.get_addr_str();
+        let shop = self.search_shop_txt.clone();
+        let platform = self.platform;
+        self.search_tip = format!("正在搜索\"{}\"...", keyword);
+        Command::perform(
+            async move {
+                retailer(platform)
+                    .search_goods(keyword, page, area_id, shop)
+                    .await
+            },
+            |r| {
+                let r = r.map_err(|e| {
+                    error!("search_goods {:?}", e);
+                    e.to_string()
+                });
+                GoodsMessage::KeywordSearchFinish(r).into()
+            },
+        )
+    }
+
     fn area_list(area: &Area) -> Vec<String> {
         area.values().map(|s| s.to_owned()).collect::<Vec<String>>()
     }
@@ -350,6 +484,23 @@ impl GoodsComponent {
                     |d| AreaLoadFinished(d).into(),
                 );
             }
+            Picker(msg) => {
+                if let Some(m) = self.area_picker.update(msg) {
+                    info!("选中地区:{} {}", m.ids, m.display());
+                    let addr = m.ids.clone();
+                    return Command::batch(vec![
+                        self.update_area(AreaLoad(m.ids)),
+                        Command::perform(async move { AreaService::store_area(addr).await }, |t| {
+                            if let Err(e) = t {
+                                error!("{:?}", e);
+                            }
+                            AreaStoreFinished.into()
+                        }),
+                    ]);
+                }
+            }
+            // 在 app 里处理
+            TreeRefresh | TreeRefreshed(_) => {}
             AreaLoadFinished(mut d) => {
                 info!("AreaLoadFinished:{}", d.len());
                 self.leve4_pick_area = d.pop().unwrap_or_default();
@@ -417,6 +568,10 @@ impl GoodsComponent {
             }
         }
 
+        area_row = area_row.push(
+            Container::new(self.area_picker.view(|m| JdMiaoshaAppMessage::Area(AreaMessage::Picker(m)))).width(Length::Units(240)),
+        );
+
         rows.push(
             Column::with_children(vec![
                 Row::new()
@@ -433,7 +588,7 @@ impl GoodsComponent {
                                     Container::new(
                                         TextInput::new(
                                             &mut self.goods_search_input_state,
-                                            "商品SKU",
+                                            "商品SKU、链接或关键字",
                                             self.search_input_txt.as_str(),
                                             |s| GoodsMessage::SearchInput(s).into(),
                                         )
@@ -468,6 +623,22 @@ impl GoodsComponent {
             .into(),
         );
 
+        if let Some(element) = search_result_view(
+            self.search_result.as_ref(),
+            &mut self.search_result_button_states,
+            self.search_shop_txt.as_str(),
+            &mut self.search_shop_input_state,
+            [
+                &mut self.search_prev_button_state,
+                &mut self.search_next_button_state,
+                &mut self.search_close_button_state,
+            ],
+            &mut self.search_scroll_state,
+            self.search_tip.as_str(),
+        ) {
+            rows.push(element);
+        }
+
         let goods: Element<_> = self
             .goods
             .iter_mut()
@@ -503,6 +674,96 @@ impl GoodsComponent {
     }
 }
 
+const TXT_COLOR: Color = Color::from_rgb(0.0, 191.0 / 255.0, 1.0);
+
+/// 关键字搜索的结果: 店铺过滤、翻页和每个商品的加入购物车按钮
+fn search_result_view<'a>(
+    result: Option<&'a SearchPage>,
+    button_states: &'a mut [button::State],
+    shop_txt: &str,
+    shop_input_state: &'a mut text_input::State,
+    [prev_state, next_state, close_state]: [&'a mut button::State; 3],
+    scroll_state: &'a mut scrollable::State,
+    tip: &str,
+) -> Option<Element<'a, JdMiaoshaAppMessage>> {
+    let result = match result {
+        Some(result) => result,
+        None if tip.is_empty() => return None,
+        None => return Some(Text::new(tip).color(TXT_COLOR).into()),
+    };
+    let mut prev = Button::new(prev_state, Text::new("上一页")).style(style::ActionButton);
+    if result.has_prev() {
+        prev = prev.on_press(GoodsMessage::SearchPageTo(result.page - 1).into());
+    }
+    let mut next = Button::new(next_state, Text::new("下一页")).style(style::ActionButton);
+    if result.has_next() {
+        next = next.on_press(GoodsMessage::SearchPageTo(result.page + 1).into());
+    }
+    let bar = Row::new()
+        .spacing(10)
+        .align_items(Align::Center)
+        .push(Text::new(format!("\"{}\"的搜索结果", result.keyword)).color(TXT_COLOR))
+        .push(
+            TextInput::new(shop_input_state, "店铺名称, 如 自营", shop_txt, |s| {
+                GoodsMessage::SearchShopInput(s).into()
+            })
+            .width(Length::Units(200))
+            .padding(3)
+            .on_submit(GoodsMessage::SearchPageTo(1).into()),
+        )
+        .push(Text::new(format!("第 {}/{} 页", result.page, result.total_pages)).color(TXT_COLOR))
+        .push(prev)
+        .push(next)
+        .push(
+            Button::new(close_state, Text::new("关闭"))
+                .style(style::ActionButton)
+                .on_press(GoodsMessage::CloseSearchResults.into()),
+        )
+        .push(Text::new(tip).color(TXT_COLOR));
+    let txt = |content: String, portion: u16| -> Element<'a, JdMiaoshaAppMessage> {
+        Text::new(content)
+            .width(Length::FillPortion(portion))
+            .horizontal_alignment(HorizontalAlignment::Center)
+            .into()
+    };
+    let items = result.items.iter().zip(button_states.iter_mut()).fold(
+        Column::new().spacing(2),
+        |col, (item, st)| {
+            col.push(
+                Row::new()
+                    .align_items(Align::Center)
+                    .push(txt(item.name.clone(), GoodsComponent::NAME_PORTION))
+                    .push(txt(item.sku.clone(), GoodsComponent::SKU_PORTION))
+                    .push(txt(item.shop.clone(), GoodsComponent::ORI_PRICE_PORTION))
+                    .push(txt(item.price.clone(), GoodsComponent::CUR_PRICE_PORTION))
+                    .push(txt(item.status.to_string(), GoodsComponent::STATUS_PORTION))
+                    .push(
+                        Container::new(
+                            Button::new(st, Text::new("加入购物车"))
+                                .style(style::ActionButton)
+                                .on_press(GoodsMessage::AddSearchResult(item.sku.clone()).into()),
+                        )
+                        .width(Length::FillPortion(GoodsComponent::PURCHASE_NUM_PORTION + GoodsComponent::OP_PORTION))
+                        .center_x(),
+                    ),
+            )
+        },
+    );
+    Some(
+        Column::new()
+            .spacing(4)
+            .push(bar)
+            .push(
+                Scrollable::new(scroll_state)
+                    .scrollbar_width(1)
+                    .style(style::ScrollableBarStyle)
+                    .push(items)
+                    .max_height(super::MAX_SCROLL_HEIGHT / 2),
+            )
+            .into(),
+    )
+}
+
 impl GoodsState {
     fn new() -> Self {
         Self {
@@ -513,18 +774,7 @@ impl GoodsState {
     }
 
     fn add_to_cart(&self, code:String) -> Command<JdMiaoshaAppMessage> {
-        let body = json!({
-            "sku":&self.sku,
-            "name":&self.name,
-            "purchase_num":self.purchase_num,
-            "yuyue_dt":datetime_fmt_option(&self.yuyue_dt),
-            "ori_price":&self.ori_price,
-            "cur_price":&self.cur_price,
-            "purchase_type":&self.purchase_type,
-            "is_stock":self.status.is_stock_u8(),
-            "purchase_url":&self.purchase_url,
-        })
-        .to_string();
+        let body = self.cart_body();
         return Command::perform(
             async move { ShoppingCartService::create_cart_goods(code, body).await },
             |r| {
@@ -542,8 +792,9 @@ impl GoodsState {
             AddToShoppingCart(_) => {
                 if self.purchase_type.eq(PRESALE) {
                     let sku = self.sku.to_owned();
+                    let platform = self.platform;
                     return Command::perform(
-                        async move { GoodsService::get_presale_info(sku).await },
+                        async move { retailer(platform).get_presale_info(sku).await },
                         |r| match r {
                             Ok((sku, yuyue_dt, href)) => {
                                 AddToShoppingCartByPresale(sku, yuyue_dt, href).into()
@@ -555,11 +806,11 @@ impl GoodsState {
                         },
                     );
                 } else {
-                    let sku = self.sku.clone();
+                    let item = (self.platform, self.sku.clone());
                     let num = self.purchase_num.clone();
                     return Command::batch(vec![
-                        Command::perform(async move {sku}, move|sku| {
-                            UserMessage::AddToCartAndLoadCartGoodsSkuUuids(num, Some(sku)).into()
+                        Command::perform(async move {item}, move|item| {
+                            UserMessage::AddToCartAndLoadCartGoodsSkuUuids(num, Some(item)).into()
                         }),
                         self.add_to_cart(code)
                     ]);
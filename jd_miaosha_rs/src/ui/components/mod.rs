@@ -3,6 +3,12 @@ mod user;
 mod delivery_address;
 mod shopping_cart;
 mod order;
+mod budget;
+mod scheduler;
+mod settings;
+mod area_picker;
+mod price_history;
+mod watch;
 
 use super::style;
 use super::JdMiaoshaAppMessage;
@@ -12,6 +18,12 @@ pub use user::UserComponent;
 pub use shopping_cart::ShoppingCartComponent;
 pub use delivery_address::DeliveryAddressComponent;
 pub use order::OrderComponent;
+pub use budget::BudgetComponent;
+pub use scheduler::SchedulerComponent;
+pub use settings::SettingsComponent;
+pub use area_picker::AreaPicker;
+pub use price_history::PriceHistoryComponent;
+pub use watch::WatchComponent;
 use iced::*;
 
 pub const MAX_SCROLL_HEIGHT: u32 = 420;
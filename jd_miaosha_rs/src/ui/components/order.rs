@@ -5,8 +5,12 @@ use serde_json::{json, to_string};
 use super::super::*;
 use super::style;
 use super::PORTION_1;
-use crate::models::{OrderInfo, ProdPlatform};
+use crate::models::{OrderInfo, OrderTrack, ProdPlatform};
 use crate::services::order::{OrderService, QueryCondition};
+use crate::services::order_export::{DateRange, ExportFormat, ExportReport};
+use crate::services::retailer::retailer;
+use crate::services::unpaid::{self, UnpaidOrder, Watchlist};
+use crate::services::notify;
 use crate::utils::*;
 use crate::{PKDate, PKDateTime, PKLocal};
 use iced::pane_grid::Line;
@@ -21,6 +25,28 @@ pub struct OrderComponent {
     scroll_state: scrollable::State,
     // 记录用户的所有收货地址
     pub orders: BTreeMap<String, BTreeMap<String, OrderInfo>>,
+    // 查询过的物流信息, 订单号 -> 物流
+    tracks: BTreeMap<String, OrderTrack>,
+    // 正在查看物流的订单号
+    track_order: Option<String>,
+    track_scroll_state: scrollable::State,
+    track_refresh_button_state: button::State,
+    track_close_button_state: button::State,
+    // 导出订单的日期范围和格式
+    export_from_txt: String,
+    export_to_txt: String,
+    pub export_format: ExportFormat,
+    export_from_input_state: text_input::State,
+    export_to_input_state: text_input::State,
+    export_csv_button_state: button::State,
+    export_json_button_state: button::State,
+    export_button_state: button::State,
+    exporting: bool,
+    // 导出和打开付款页的结果, 出错时是错误信息
+    tip: String,
+    tip_is_error: bool,
+    // 所有账号的待付款订单
+    pub unpaid: Watchlist,
 }
 
 impl Default for OrderComponent {
@@ -32,6 +58,23 @@ impl Default for OrderComponent {
             search_reset_button_state: button::State::default(),
             scroll_state: scrollable::State::default(),
             orders: BTreeMap::default(),
+            tracks: BTreeMap::default(),
+            track_order: None,
+            track_scroll_state: scrollable::State::default(),
+            track_refresh_button_state: button::State::default(),
+            track_close_button_state: button::State::default(),
+            export_from_txt: String::default(),
+            export_to_txt: String::default(),
+            export_format: ExportFormat::default(),
+            export_from_input_state: text_input::State::default(),
+            export_to_input_state: text_input::State::default(),
+            export_csv_button_state: button::State::default(),
+            export_json_button_state: button::State::default(),
+            export_button_state: button::State::default(),
+            exporting: false,
+            tip: String::default(),
+            tip_is_error: false,
+            unpaid: Watchlist::default(),
         }
     }
 }
@@ -44,9 +87,13 @@ impl OrderComponent {
     pub const TOTAL_PRICE_PORTION: u16 = 1;
     pub const STATUS_PORTION: u16 = 1;
     pub const RECEIVER_PORTION: u16 = 1;
+    pub const EXPRESS_PORTION: u16 = 1;
+    pub const PAY_PORTION: u16 = 1;
+    // 物流面板最多显示的高度
+    const TRACK_MAX_HEIGHT: u32 = 160;
 
     fn headers_portions() -> (Vec<String>, Vec<u16>) {
-        let mut headers = Vec::with_capacity(8);
+        let mut headers = Vec::with_capacity(9);
         for t in [
             "账号",
             "订单号",
@@ -55,6 +102,8 @@ impl OrderComponent {
             "总金额",
             "状态",
             "收货人",
+            "物流",
+            "付款",
         ]
         .iter()
         {
@@ -70,6 +119,8 @@ impl OrderComponent {
                 OrderComponent::TOTAL_PRICE_PORTION,
                 OrderComponent::STATUS_PORTION,
                 OrderComponent::RECEIVER_PORTION,
+                OrderComponent::EXPRESS_PORTION,
+                OrderComponent::PAY_PORTION,
             ],
         )
     }
@@ -93,6 +144,73 @@ impl OrderComponent {
         }
     }
 
+    /// 导出的日期范围, 格式不对时返回错误信息
+    pub fn export_range(&self) -> Result<DateRange, String> {
+        DateRange::parse(self.export_from_txt.as_str(), self.export_to_txt.as_str()).map_err(|e| e.to_string())
+    }
+
+    pub fn export_tip(report: &ExportReport) -> String {
+        let amount: f64 = report.totals.iter().map(|t| t.amount).sum();
+        let orders: usize = report.totals.iter().map(|t| t.orders).sum();
+        let mut tip = format!("导出了{}个订单, 共{:.2}元: {}", orders, amount, report.path.display());
+        if !report.failed.is_empty() {
+            tip = format!("{}; 查询失败的账号: {}", tip, report.failed.join(","));
+        }
+        tip
+    }
+
+    fn open_pay_page(order: &UnpaidOrder) -> Command<JdMiaoshaAppMessage> {
+        Command::perform(
+            unpaid::open_pay_page(order.order_no.clone(), order.pay_url.clone()),
+            |r| {
+                OrderMessage::PayOpened(r.map_err(|e| {
+                    error!("打开付款页失败:{:?}", e);
+                    format!("打开付款页失败: {}", e)
+                }))
+                .into()
+            },
+        )
+    }
+
+    fn find_order(&self, order_no: &str) -> Option<&OrderInfo> {
+        self.orders.values().find_map(|orders| orders.get(order_no))
+    }
+
+    fn load_track(order: &OrderInfo) -> Command<JdMiaoshaAppMessage> {
+        let account = order.account.clone();
+        let cookie = order.cookie.clone();
+        let order_no = order.order_no.clone();
+        let platform = order.platform;
+        Command::perform(
+            async move {
+                let r = retailer(platform)
+                    .get_order_track(account, cookie, order_no.clone())
+                    .await;
+                (order_no, r)
+            },
+            |(order_no, r)| match r {
+                Ok(track) => OrderMessage::TrackLoaded(order_no, Some(track)).into(),
+                Err(e) => {
+                    error!("Load order track error: {}-{:?}", order_no, e);
+                    OrderMessage::TrackLoaded(order_no, None).into()
+                }
+            },
+        )
+    }
+
+    /// 刷新还没签收的订单的物流, 待付款和已取消的订单没有物流
+    fn refresh_tracks(&self) -> Command<JdMiaoshaAppMessage> {
+        let tracks = &self.tracks;
+        Command::batch(
+            self.orders
+                .values()
+                .flat_map(|orders| orders.values())
+                .filter(|o| !o.status.contains("等待付款") && !o.status.contains("已取消"))
+                .filter(|o| tracks.get(&o.order_no).map(|t| !t.delivered()).unwrap_or(true))
+                .map(Self::load_track),
+        )
+    }
+
     pub fn update(&mut self, message: OrderMessage) -> Command<JdMiaoshaAppMessage> {
         use OrderMessage::*;
         // 记录是否点击了选择按钮，避免发送过多的消息
@@ -101,14 +219,75 @@ impl OrderComponent {
                 self.search_input_txt = input.trim().to_string();
             }
             LoadFinish(mut orders) => {
+                self.unpaid.merge(&orders, crate::settings::get().pay_window_minutes);
                 self.update_orders(orders, false);
+                return self.refresh_tracks();
             }
              SearchFinish(mut orders) =>{
+                 self.unpaid.merge(&orders, crate::settings::get().pay_window_minutes);
                  self.update_orders(orders, true);
+                 return self.refresh_tracks();
              }
+            ExpressInfo(order_no) => {
+                let cmd = self.find_order(order_no.as_str()).map(Self::load_track);
+                self.track_order = Some(order_no);
+                if let Some(cmd) = cmd {
+                    return cmd;
+                }
+            }
+            TrackLoaded(order_no, Some(track)) => {
+                self.tracks.insert(order_no, track);
+            }
+            TrackRefresh => {
+                return self.refresh_tracks();
+            }
+            TrackClose => {
+                self.track_order = None;
+            }
+            ExportFromInput(txt) => {
+                self.export_from_txt = txt.trim().to_string();
+            }
+            ExportToInput(txt) => {
+                self.export_to_txt = txt.trim().to_string();
+            }
+            ExportFormatSelected(format) => {
+                self.export_format = format;
+            }
+            Export => {
+                // app 里开始导出时转发过来
+                self.exporting = true;
+                self.tip = "正在导出...".to_string();
+                self.tip_is_error = false;
+            }
+            ExportFinish(r) => {
+                self.exporting = false;
+                self.tip_is_error = r.is_err();
+                self.tip = r.unwrap_or_else(|e| e);
+            }
+            UnpaidLoaded(account, orders) => {
+                self.unpaid
+                    .sync(account.as_str(), &orders, crate::settings::get().pay_window_minutes);
+            }
+            PayTick => {
+                // 每一级提醒只发一次
+                return Command::batch(self.unpaid.remind(now()).into_iter().map(|event| {
+                    Command::perform(notify::notify(event), |_| JdMiaoshaAppMessage::GlobalNoop)
+                }));
+            }
+            Pay(order_no) => {
+                if let Some(order) = self.unpaid.get(order_no.as_str()) {
+                    return Self::open_pay_page(order);
+                }
+            }
+            PayOpened(Err(e)) => {
+                self.tip_is_error = true;
+                self.tip = e;
+            }
             Reset =>{
                 self.search_input_txt.clear();
                 self.orders.clear();
+                self.tracks.clear();
+                self.track_order = None;
                 return Command::perform(async{}, |_|{
                     JdMiaoshaAppMessage::GlobalOrdersPressed
                 });
@@ -122,6 +301,10 @@ impl OrderComponent {
 
     pub fn view<'a>(&'a mut self) -> Container<'a, JdMiaoshaAppMessage> {
         let (headers, portions) = Self::headers_portions();
+        let now = now();
+        let stale_days = crate::settings::get().track_stale_days;
+        let tracks = &self.tracks;
+        let unpaid = &self.unpaid;
         let states: Element<_> = self
             .orders
             .iter_mut()
@@ -129,8 +312,13 @@ impl OrderComponent {
                 let total_addr_cnt = orders.len();
                 let one_user_row = Container::new(orders.iter_mut().enumerate().fold(
                     Column::new().spacing(3),
-                    |_column, (idx, (_order_id, addr))| {
-                        _column.push(addr.view(idx, total_addr_cnt).map(|msg| msg.into()))
+                    |_column, (idx, (order_no, addr))| {
+                        let stale = tracks
+                            .get(order_no.as_str())
+                            .map(|t| t.is_stale(now, stale_days))
+                            .unwrap_or(false);
+                        let pay = unpaid.get(order_no.as_str());
+                        _column.push(addr.view(idx, total_addr_cnt, stale, pay, now).map(|msg| msg.into()))
                     },
                 ))
                 .width(Length::Fill);
@@ -170,18 +358,83 @@ impl OrderComponent {
             )
             .spacing(10)
             .padding(3);
+        let mut export_button = Button::new(
+            &mut self.export_button_state,
+            Text::new("导出").horizontal_alignment(HorizontalAlignment::Center),
+        )
+        .style(style::ActionButton);
+        // 导出完成前不能重复导出
+        if !self.exporting {
+            export_button = export_button.on_press(OrderMessage::Export.into());
+        }
+        let export_row = Row::new()
+            .align_items(Align::Center)
+            .push(
+                Text::new(self.tip.as_str())
+                    .color(if self.tip_is_error { Color::from_rgb(1.0, 0.0, 0.0) } else { TXT_COLOR })
+                    .width(Length::FillPortion(1)),
+            )
+            .push(Text::new("下单日期").color(TXT_COLOR))
+            .push(
+                TextInput::new(
+                    &mut self.export_from_input_state,
+                    "2021-06-01",
+                    self.export_from_txt.as_str(),
+                    |s| OrderMessage::ExportFromInput(s).into(),
+                )
+                .padding(3)
+                .width(Length::Units(110)),
+            )
+            .push(Text::new("至").color(TXT_COLOR))
+            .push(
+                TextInput::new(
+                    &mut self.export_to_input_state,
+                    "2021-06-30",
+                    self.export_to_txt.as_str(),
+                    |s| OrderMessage::ExportToInput(s).into(),
+                )
+                .padding(3)
+                .width(Length::Units(110)),
+            )
+            .push(format_button(&mut self.export_csv_button_state, ExportFormat::Csv, self.export_format))
+            .push(format_button(&mut self.export_json_button_state, ExportFormat::Json, self.export_format))
+            .push(export_button)
+            .spacing(10)
+            .padding(3);
         let scroll = Scrollable::new(&mut self.scroll_state)
             .scrollbar_margin(1)
             .scrollbar_width(1)
             .style(style::ScrollableBarStyle)
-            .max_height(super::MAX_SCROLL_HEIGHT + 40)
+            // 打开物流面板时订单列表让出面板的高度
+            .max_height(if self.track_order.is_some() {
+                super::MAX_SCROLL_HEIGHT + 40 - Self::TRACK_MAX_HEIGHT
+            } else {
+                super::MAX_SCROLL_HEIGHT + 40
+            })
             .push(Container::new(states).width(Length::Fill).center_x());
 
+        let mut content = Column::new()
+            .width(Length::Fill)
+            .spacing(4)
+            .push(search_row)
+            .push(export_row);
+        if let Some(order_no) = self.track_order.clone() {
+            let stale = tracks
+                .get(order_no.as_str())
+                .map(|t| t.is_stale(now, stale_days))
+                .unwrap_or(false);
+            content = content.push(track_panel(
+                tracks,
+                order_no,
+                stale,
+                stale_days,
+                &mut self.track_scroll_state,
+                &mut self.track_refresh_button_state,
+                &mut self.track_close_button_state,
+            ));
+        }
         return Container::new(
-            Column::new()
-                .width(Length::Fill)
-                .spacing(4)
-                .push(search_row)
+            content
                 .push(Row::with_children(super::get_headers(&headers, &portions)))
                 .push(scroll),
         )
@@ -189,6 +442,118 @@ impl OrderComponent {
     }
 }
 
+/// 导出格式的按钮, 选中的前面加个点
+fn format_button<'a>(
+    state: &'a mut button::State,
+    format: ExportFormat,
+    selected: ExportFormat,
+) -> Button<'a, JdMiaoshaAppMessage> {
+    let txt = format.extension().to_uppercase();
+    Button::new(
+        state,
+        Text::new(if format == selected { format!("• {}", txt) } else { txt })
+            .horizontal_alignment(HorizontalAlignment::Center),
+    )
+    .style(style::ActionButton)
+    .on_press(OrderMessage::ExportFormatSelected(format).into())
+}
+
+/// 订单的物流详情: 承运商、运单号和物流轨迹
+fn track_panel<'a>(
+    tracks: &'a BTreeMap<String, OrderTrack>,
+    order_no: String,
+    stale: bool,
+    stale_days: u32,
+    scroll_state: &'a mut scrollable::State,
+    refresh_button_state: &'a mut button::State,
+    close_button_state: &'a mut button::State,
+) -> Element<'a, JdMiaoshaAppMessage> {
+    let mut title = Row::new()
+        .align_items(Align::Center)
+        .spacing(10)
+        .padding(3)
+        .push(label_txt(format!("订单{}的物流", order_no), 4, Some(HorizontalAlignment::Left), None).map(|m| m.into()));
+    if stale {
+        title = title.push(
+            label_txt(
+                format!("物流已超过{}天没有更新", stale_days),
+                2,
+                Some(HorizontalAlignment::Left),
+                Some(Color::from_rgb(1.0, 0.0, 0.0)),
+            )
+            .map(|m| m.into()),
+        );
+    }
+    let title = title
+        .push(
+            Button::new(
+                refresh_button_state,
+                Text::new("刷新").horizontal_alignment(HorizontalAlignment::Center),
+            )
+            .style(style::ActionButton)
+            .on_press(OrderMessage::ExpressInfo(order_no.clone()).into()),
+        )
+        .push(
+            Button::new(
+                close_button_state,
+                Text::new("关闭").horizontal_alignment(HorizontalAlignment::Center),
+            )
+            .style(style::ActionButton)
+            .on_press(OrderMessage::TrackClose.into()),
+        );
+
+    let body: Element<OrderMessage> = match tracks.get(order_no.as_str()) {
+        None => label_txt("正在查询物流...", 1, Some(HorizontalAlignment::Left), None),
+        Some(track) if track.packages.is_empty() => {
+            label_txt("暂无物流信息", 1, Some(HorizontalAlignment::Left), None)
+        }
+        Some(track) => track
+            .packages
+            .iter()
+            .fold(Column::new().spacing(3), |column, p| {
+                let head = format!(
+                    "承运商: {}    运单号: {}{}",
+                    p.carrier,
+                    p.tracking_no,
+                    if p.delivered { "    已签收" } else { "" }
+                );
+                let column = column.push(label_txt(head, 1, Some(HorizontalAlignment::Left), None));
+                p.events.iter().fold(column, |column, e| {
+                    column.push(
+                        Row::new()
+                            .push(label_txt(
+                                datetime_fmt_option(&e.dt),
+                                1,
+                                Some(HorizontalAlignment::Left),
+                                None,
+                            ))
+                            .push(label_txt(
+                                e.content.as_str(),
+                                4,
+                                Some(HorizontalAlignment::Left),
+                                None,
+                            )),
+                    )
+                })
+            })
+            .into(),
+    };
+    let body = Scrollable::new(scroll_state)
+        .scrollbar_margin(1)
+        .scrollbar_width(1)
+        .style(style::ScrollableBarStyle)
+        .max_height(OrderComponent::TRACK_MAX_HEIGHT)
+        .push(Container::new(body.map(|m| m.into())).width(Length::Fill).padding(3));
+    Container::new(
+        Column::new()
+            .push(title)
+            .push(body)
+            .push(Rule::horizontal(6).style(style::RowSplitLineRule)),
+    )
+    .width(Length::Fill)
+    .into()
+}
+
 const TXT_COLOR: Color = Color::from_rgb(0.0, 191.0 / 255.0, 1.0);
 
 fn label_txt<'a, T: AsRef<str>>(
@@ -216,7 +581,14 @@ impl OrderInfo {
         Command::none()
     }
 
-    fn view(&mut self, idx: usize, total: usize) -> Element<OrderMessage> {
+    fn view(
+        &mut self,
+        idx: usize,
+        total: usize,
+        stale: bool,
+        pay: Option<&UnpaidOrder>,
+        now: PKDateTime,
+    ) -> Element<OrderMessage> {
         let mut row = Row::new().align_items(Align::Center);
         // 是否是偶数行
         let is_mid_row = idx == (total >> 1);
@@ -268,7 +640,46 @@ impl OrderInfo {
                 OrderComponent::RECEIVER_PORTION,
                 None,
                 None,
-            ));
+            ))
+            .push(
+                Container::new(
+                    Button::new(
+                        &mut self.express_button_state,
+                        // 物流停滞的订单标红
+                        Text::new(if stale { "停滞" } else { "查看" })
+                            .color(if stale { Color::from_rgb(1.0, 0.0, 0.0) } else { TXT_COLOR })
+                            .horizontal_alignment(HorizontalAlignment::Center),
+                    )
+                    .style(style::ActionButton)
+                    .on_press(OrderMessage::ExpressInfo(self.order_no.clone())),
+                )
+                .width(Length::FillPortion(OrderComponent::EXPRESS_PORTION))
+                .center_x(),
+            );
+        // 待付款的订单显示付款倒计时, 点击打开付款页
+        row = match pay {
+            Some(pay) => {
+                let secs = pay.remaining_secs(now);
+                let urgent = pay.is_urgent(now);
+                let mut button = Button::new(
+                    &mut self.pay_button_state,
+                    Text::new(pay.countdown(now))
+                        .color(if urgent { Color::from_rgb(1.0, 0.0, 0.0) } else { TXT_COLOR })
+                        .horizontal_alignment(HorizontalAlignment::Center),
+                )
+                .style(style::ActionButton);
+                // 超时的订单已经不能付款了
+                if secs > 0 {
+                    button = button.on_press(OrderMessage::Pay(self.order_no.clone()));
+                }
+                row.push(
+                    Container::new(button)
+                        .width(Length::FillPortion(OrderComponent::PAY_PORTION))
+                        .center_x(),
+                )
+            }
+            None => row.push(Space::with_width(Length::FillPortion(OrderComponent::PAY_PORTION))),
+        };
         // 偶数个地址时， 需要插入一行只有账号名的空白行
         if idx + 1 == total {
             // 最后一行再加上一条水平线
@@ -0,0 +1,375 @@
+//! 价格走势: 查询过价格、库存的商品, 画出价格曲线和有货无货的色带
+use iced::canvas::{self, Cache, Cursor, Frame, Geometry, Path, Stroke};
+use iced::*;
+use log::error;
+
+use super::super::*;
+use super::style;
+use super::PORTION_1;
+use crate::models::StockStatus;
+use crate::services::parser::parse_sku_input;
+use crate::services::price_history::{self, stats, Observation, WatchedSku};
+
+const TXT_COLOR: Color = Color::from_rgb(0.0, 191.0 / 255.0, 1.0);
+const IN_STOCK_COLOR: Color = Color::from_rgb(0.2, 0.7, 0.3);
+const OUT_OF_STOCK_COLOR: Color = Color::from_rgb(0.85, 0.25, 0.25);
+const UNKNOWN_STOCK_COLOR: Color = Color::from_rgb(0.6, 0.6, 0.6);
+const AVG_COLOR: Color = Color::from_rgb(0.6, 0.6, 0.6);
+
+// 可选的统计天数
+const DAYS: [u32; 3] = [7, 30, 90];
+// 窗口只有 600 高, 商品列表和图表分着用
+const WATCHED_HEIGHT: u32 = 120;
+const CHART_HEIGHT: u16 = 260;
+
+pub struct PriceHistoryComponent {
+    refresh_button_state: button::State,
+    scroll_state: scrollable::State,
+    sku_input_state: text_input::State,
+    sku_input: String,
+    days_button_states: [button::State; 3],
+    days: u32,
+    watched: Vec<(WatchedSku, button::State)>,
+    // 当前显示的商品
+    selected: String,
+    chart: PriceChart,
+}
+
+impl Default for PriceHistoryComponent {
+    fn default() -> Self {
+        Self {
+            refresh_button_state: Default::default(),
+            scroll_state: Default::default(),
+            sku_input_state: Default::default(),
+            sku_input: String::new(),
+            days_button_states: Default::default(),
+            days: DAYS[1],
+            watched: vec![],
+            selected: String::new(),
+            chart: PriceChart::default(),
+        }
+    }
+}
+
+impl PriceHistoryComponent {
+    pub const SKU_PORTION: u16 = 2;
+    pub const NAME_PORTION: u16 = 5;
+    pub const COUNT_PORTION: u16 = 1;
+    pub const DT_PORTION: u16 = 3;
+
+    fn load(sku: String, days: u32) -> Command<JdMiaoshaAppMessage> {
+        Command::perform(
+            async move {
+                let history = price_history::history(sku.clone(), days).await.unwrap_or_else(|e| {
+                    error!("查询价格历史失败:{}, {:?}", sku, e);
+                    vec![]
+                });
+                (sku, history)
+            },
+            |(sku, history)| PriceHistoryMessage::Loaded(sku, history).into(),
+        )
+    }
+
+    pub fn update(&mut self, message: PriceHistoryMessage) -> Command<JdMiaoshaAppMessage> {
+        use PriceHistoryMessage::*;
+        match message {
+            Refresh => {
+                let mut cmds = vec![Command::perform(
+                    async {
+                        price_history::watched().await.unwrap_or_else(|e| {
+                            error!("查询价格历史失败:{:?}", e);
+                            vec![]
+                        })
+                    },
+                    |watched| WatchedLoaded(watched).into(),
+                )];
+                if !self.selected.is_empty() {
+                    cmds.push(Self::load(self.selected.clone(), self.days));
+                }
+                return Command::batch(cmds);
+            }
+            WatchedLoaded(watched) => {
+                self.watched = watched.into_iter().map(|w| (w, button::State::new())).collect();
+            }
+            SkuInput(input) => {
+                self.sku_input = input;
+            }
+            Select(input) => {
+                // 也可以粘贴商品链接
+                if let Some(sku) = parse_sku_input(input.as_str()) {
+                    self.selected = sku.clone();
+                    return Self::load(sku, self.days);
+                }
+            }
+            Days(days) => {
+                self.days = days;
+                if !self.selected.is_empty() {
+                    return Self::load(self.selected.clone(), days);
+                }
+            }
+            Loaded(sku, history) => {
+                if sku == self.selected {
+                    self.chart.set_history(history);
+                }
+            }
+        }
+        Command::none()
+    }
+
+    fn stats_text(&self) -> String {
+        match stats(&self.chart.history) {
+            Some(s) => {
+                let mut txt = format!(
+                    "最低 {:.2}  最高 {:.2}  平均 {:.2}  最新 {:.2}",
+                    s.min, s.max, s.avg, s.last
+                );
+                let discount = s.discount();
+                if discount > 0.0 {
+                    txt.push_str(format!(" (比平均低 {:.1}%)", discount * 100.0).as_str());
+                } else if discount < 0.0 {
+                    txt.push_str(format!(" (比平均高 {:.1}%)", -discount * 100.0).as_str());
+                }
+                if let Some(ratio) = s.in_stock_ratio {
+                    txt.push_str(format!("  有货 {:.0}%", ratio * 100.0).as_str());
+                }
+                txt.push_str(format!("  共 {} 次", s.count).as_str());
+                txt
+            }
+            None if self.selected.is_empty() => "选择商品查看价格走势".to_string(),
+            None => format!("{} 最近 {} 天没有价格记录", self.selected, self.days),
+        }
+    }
+
+    pub fn view(&mut self) -> Container<JdMiaoshaAppMessage> {
+        let stats_txt = self.stats_text();
+        let mut top_row = Row::new()
+            .align_items(Align::Center)
+            .spacing(10)
+            .padding(3)
+            .push(
+                TextInput::new(
+                    &mut self.sku_input_state,
+                    "商品SKU或链接",
+                    self.sku_input.as_str(),
+                    |s| JdMiaoshaAppMessage::from(PriceHistoryMessage::SkuInput(s)),
+                )
+                .width(Length::FillPortion(3))
+                .padding(3)
+                .on_submit(PriceHistoryMessage::Select(self.sku_input.clone()).into()),
+            );
+        for (st, days) in self.days_button_states.iter_mut().zip(DAYS.iter()) {
+            top_row = top_row.push(
+                Button::new(
+                    st,
+                    Text::new(format!("{}天", days)).horizontal_alignment(HorizontalAlignment::Center),
+                )
+                .style(if *days == self.days {
+                    style::Button::Primary
+                } else {
+                    style::Button::Secondary
+                })
+                .on_press(PriceHistoryMessage::Days(*days).into()),
+            );
+        }
+        let top_row = top_row
+            .push(Space::with_width(Length::FillPortion(PORTION_1)))
+            .push(
+                Button::new(
+                    &mut self.refresh_button_state,
+                    Text::new("刷新").horizontal_alignment(HorizontalAlignment::Center),
+                )
+                .style(style::ActionButton)
+                .on_press(PriceHistoryMessage::Refresh.into()),
+            );
+
+        let headers = ["商品SKU", "名称", "次数", "最近记录"];
+        let portions = [
+            Self::SKU_PORTION,
+            Self::NAME_PORTION,
+            Self::COUNT_PORTION,
+            Self::DT_PORTION,
+        ];
+        let selected = self.selected.clone();
+        let watched = self.watched.iter_mut().fold(Column::new().spacing(3), |column, (w, st)| {
+            column.push(
+                Button::new(
+                    st,
+                    Row::new()
+                        .align_items(Align::Center)
+                        .push(label_txt(w.sku.as_str(), Self::SKU_PORTION))
+                        .push(label_txt(w.name.as_str(), Self::NAME_PORTION))
+                        .push(label_txt(w.count.to_string(), Self::COUNT_PORTION))
+                        .push(label_txt(w.last_dt.as_str(), Self::DT_PORTION)),
+                )
+                .width(Length::Fill)
+                .style(if w.sku == selected {
+                    style::Button::Primary
+                } else {
+                    style::Button::Secondary
+                })
+                .on_press(PriceHistoryMessage::Select(w.sku.clone()).into()),
+            )
+        });
+        let scroll = Scrollable::new(&mut self.scroll_state)
+            .scrollbar_margin(1)
+            .scrollbar_width(1)
+            .style(style::ScrollableBarStyle)
+            .max_height(WATCHED_HEIGHT)
+            .push(watched);
+
+        Container::new(
+            Column::new()
+                .width(Length::Fill)
+                .spacing(4)
+                .push(top_row)
+                .push(Row::with_children(super::get_headers(&headers, &portions)))
+                .push(scroll)
+                .push(Space::with_height(Length::Units(10)))
+                .push(Text::new(stats_txt).color(TXT_COLOR))
+                .push(
+                    Canvas::new(&mut self.chart)
+                        .width(Length::Fill)
+                        .height(Length::Units(CHART_HEIGHT)),
+                ),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill)
+    }
+}
+
+fn label_txt<'a, T: AsRef<str>>(txt: T, portion: u16) -> Element<'a, JdMiaoshaAppMessage> {
+    Text::new(txt.as_ref())
+        .horizontal_alignment(HorizontalAlignment::Center)
+        .vertical_alignment(VerticalAlignment::Center)
+        .color(TXT_COLOR)
+        .width(Length::FillPortion(portion))
+        .into()
+}
+
+/// 价格曲线, 底部的色带是每次查询时的库存
+#[derive(Default)]
+pub struct PriceChart {
+    history: Vec<Observation>,
+    cache: Cache,
+}
+
+impl PriceChart {
+    // 左边留给价格, 下边留给库存色带和日期
+    const LEFT: f32 = 70.0;
+    const RIGHT: f32 = 10.0;
+    const TOP: f32 = 10.0;
+    const BAND_HEIGHT: f32 = 12.0;
+    const BOTTOM: f32 = 40.0;
+
+    pub fn set_history(&mut self, history: Vec<Observation>) {
+        self.history = history;
+        self.cache.clear();
+    }
+
+    fn label(content: String, position: Point, horizontal_alignment: HorizontalAlignment) -> canvas::Text {
+        canvas::Text {
+            content,
+            position,
+            color: TXT_COLOR,
+            size: 16.0,
+            horizontal_alignment,
+            vertical_alignment: VerticalAlignment::Center,
+            ..canvas::Text::default()
+        }
+    }
+
+    fn draw_chart(&self, frame: &mut Frame) {
+        let points: Vec<(i64, &Observation)> = self
+            .history
+            .iter()
+            .filter_map(|o| o.time().map(|t| (t.timestamp(), o)))
+            .collect();
+        let s = match stats(&self.history) {
+            Some(s) if !points.is_empty() => s,
+            _ => return,
+        };
+        let width = frame.width() - Self::LEFT - Self::RIGHT;
+        let height = frame.height() - Self::TOP - Self::BOTTOM;
+        let (t0, t1) = (points[0].0, points[points.len() - 1].0);
+        let span = std::cmp::max(t1 - t0, 1) as f32;
+        let x = |t: i64| Self::LEFT + (t - t0) as f32 / span * width;
+        // 价格都一样时上下留点空间
+        let (low, high) = if s.max > s.min { (s.min, s.max) } else { (s.min - 1.0, s.max + 1.0) };
+        let y = |p: f64| Self::TOP + ((high - p) / (high - low)) as f32 * height;
+        let right = Self::LEFT + width;
+
+        // 库存色带, 每段持续到下一次查询库存
+        let band_top = Self::TOP + height + 4.0;
+        let stocks: Vec<(i64, StockStatus)> = points
+            .iter()
+            .filter_map(|(t, o)| o.status.map(|s| (*t, s)))
+            .collect();
+        for (idx, (t, status)) in stocks.iter().enumerate() {
+            let end = stocks.get(idx + 1).map(|(t, _)| x(*t)).unwrap_or(right);
+            let color = if status.code() == StockStatus::Unknown.code() {
+                UNKNOWN_STOCK_COLOR
+            } else if status.is_stock() {
+                IN_STOCK_COLOR
+            } else {
+                OUT_OF_STOCK_COLOR
+            };
+            frame.fill_rectangle(
+                Point::new(x(*t), band_top),
+                Size::new((end - x(*t)).max(1.0), Self::BAND_HEIGHT),
+                color,
+            );
+        }
+
+        // 平均价格
+        frame.stroke(
+            &Path::line(Point::new(Self::LEFT, y(s.avg)), Point::new(right, y(s.avg))),
+            Stroke::default().with_color(AVG_COLOR).with_width(1.0),
+        );
+
+        // 价格阶梯线, 价格保持到下一次观测
+        let prices: Vec<(f32, f32)> = points
+            .iter()
+            .filter_map(|(t, o)| o.price.map(|p| (x(*t), y(p))))
+            .collect();
+        if prices.is_empty() {
+            return;
+        }
+        let line = Path::new(|b| {
+            b.move_to(Point::new(prices[0].0, prices[0].1));
+            for w in prices.windows(2) {
+                b.line_to(Point::new(w[1].0, w[0].1));
+                b.line_to(Point::new(w[1].0, w[1].1));
+            }
+            b.line_to(Point::new(right, prices[prices.len() - 1].1));
+        });
+        frame.stroke(&line, Stroke::default().with_color(TXT_COLOR).with_width(2.0));
+
+        frame.fill_text(Self::label(
+            format!("{:.2}", high),
+            Point::new(Self::LEFT - 6.0, Self::TOP),
+            HorizontalAlignment::Right,
+        ));
+        frame.fill_text(Self::label(
+            format!("{:.2}", low),
+            Point::new(Self::LEFT - 6.0, Self::TOP + height),
+            HorizontalAlignment::Right,
+        ));
+        let date_y = band_top + Self::BAND_HEIGHT + 12.0;
+        frame.fill_text(Self::label(
+            points[0].1.observe_dt.clone(),
+            Point::new(Self::LEFT, date_y),
+            HorizontalAlignment::Left,
+        ));
+        frame.fill_text(Self::label(
+            points[points.len() - 1].1.observe_dt.clone(),
+            Point::new(right, date_y),
+            HorizontalAlignment::Right,
+        ));
+    }
+}
+
+impl canvas::Program<JdMiaoshaAppMessage> for PriceChart {
+    fn draw(&self, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+        vec![self.cache.draw(bounds.size(), |frame| self.draw_chart(frame))]
+    }
+}
@@ -0,0 +1,207 @@
+use iced::*;
+use log::{info, warn};
+
+use super::super::*;
+use super::style;
+use super::PORTION_1;
+use crate::services::scheduler::{Job, JobStatus};
+use crate::utils::parse_datetime;
+use crate::{IDType, PKDateTime};
+
+#[derive(Default)]
+struct JobRow {
+    job: Job,
+    // 正在修改预约时间
+    editing: bool,
+    edit_txt: String,
+    edit_input_state: text_input::State,
+    edit_button_state: button::State,
+    cancel_button_state: button::State,
+}
+
+#[derive(Default)]
+pub struct SchedulerComponent {
+    refresh_button_state: button::State,
+    scroll_state: scrollable::State,
+    rows: Vec<JobRow>,
+}
+
+fn status_txt(status: JobStatus) -> &'static str {
+    match status {
+        JobStatus::Scheduled => "等待",
+        JobStatus::Running => "执行中",
+        JobStatus::Missed => "已错过",
+        JobStatus::Cancelled => "已取消",
+    }
+}
+
+impl SchedulerComponent {
+    pub const NAME_PORTION: u16 = 4;
+    pub const SKU_PORTION: u16 = 2;
+    pub const DT_PORTION: u16 = 3;
+    pub const STATUS_PORTION: u16 = 1;
+    pub const OP_PORTION: u16 = 3;
+
+    pub fn sku(&self, id: IDType) -> String {
+        self.rows
+            .iter()
+            .find(|r| r.job.cart_goods_id == id)
+            .map(|r| r.job.sku.clone())
+            .unwrap_or_default()
+    }
+
+    /// 修改预约时间时输入的时间
+    pub fn edit_dt(&self, id: IDType) -> Option<PKDateTime> {
+        self.rows
+            .iter()
+            .find(|r| r.job.cart_goods_id == id)
+            .and_then(|r| parse_datetime(r.edit_txt.trim()))
+    }
+
+    pub fn update(&mut self, message: SchedulerMessage) -> Command<JdMiaoshaAppMessage> {
+        use SchedulerMessage::*;
+        match message {
+            LoadFinish(jobs) => {
+                self.rows = jobs
+                    .into_iter()
+                    .map(|job| JobRow {
+                        job,
+                        ..Default::default()
+                    })
+                    .collect();
+            }
+            Editing(id) => {
+                if let Some(r) = self.rows.iter_mut().find(|r| r.job.cart_goods_id == id) {
+                    r.editing = true;
+                    r.edit_txt = r.job.yuyue_dt.clone();
+                }
+            }
+            EditInput(id, txt) => {
+                if let Some(r) = self.rows.iter_mut().find(|r| r.job.cart_goods_id == id) {
+                    r.edit_txt = txt;
+                }
+            }
+            EditCancel(id) => {
+                if let Some(r) = self.rows.iter_mut().find(|r| r.job.cart_goods_id == id) {
+                    r.editing = false;
+                }
+            }
+            EditFinish(id) => {
+                if let Some(r) = self.rows.iter_mut().find(|r| r.job.cart_goods_id == id) {
+                    if parse_datetime(r.edit_txt.trim()).is_none() {
+                        warn!("预约时间格式不对:{}", r.edit_txt);
+                    } else {
+                        r.editing = false;
+                    }
+                }
+            }
+            _ => {
+                info!("{:?}", message);
+            }
+        }
+        Command::none()
+    }
+
+    pub fn view(&mut self) -> Container<JdMiaoshaAppMessage> {
+        let top_row = Row::new()
+            .align_items(Align::Center)
+            .push(label_txt(format!("共{}个定时任务", self.rows.len()), 4))
+            .push(Space::with_width(Length::FillPortion(PORTION_1)))
+            .push(
+                Button::new(
+                    &mut self.refresh_button_state,
+                    Text::new("刷新").horizontal_alignment(HorizontalAlignment::Center),
+                )
+                .style(style::ActionButton)
+                .on_press(SchedulerMessage::Refresh.into()),
+            )
+            .spacing(10)
+            .padding(3);
+
+        let headers = ["商品名称", "商品SKU", "预约时间", "触发时间", "状态", "操作"];
+        let portions = [
+            Self::NAME_PORTION,
+            Self::SKU_PORTION,
+            Self::DT_PORTION,
+            Self::DT_PORTION,
+            Self::STATUS_PORTION,
+            Self::OP_PORTION,
+        ];
+        let jobs = self.rows.iter_mut().fold(Column::new().spacing(3), |column, r| {
+            let id = r.job.cart_goods_id;
+            let mut row = Row::new()
+                .align_items(Align::Center)
+                .push(label_txt(r.job.name.as_str(), Self::NAME_PORTION))
+                .push(label_txt(r.job.sku.as_str(), Self::SKU_PORTION));
+            let mut ops = Row::new().spacing(3).align_items(Align::Center);
+            if r.editing {
+                row = row.push(
+                    TextInput::new(
+                        &mut r.edit_input_state,
+                        "2021-12-21 09:00:09",
+                        r.edit_txt.as_str(),
+                        move |txt| JdMiaoshaAppMessage::from(SchedulerMessage::EditInput(id, txt)),
+                    )
+                    .on_submit(JdMiaoshaAppMessage::from(SchedulerMessage::EditFinish(id)))
+                    .width(Length::FillPortion(Self::DT_PORTION))
+                    .padding(3),
+                );
+                ops = ops
+                    .push(op_button(&mut r.edit_button_state, "确定", SchedulerMessage::EditFinish(id)))
+                    .push(op_button(&mut r.cancel_button_state, "取消", SchedulerMessage::EditCancel(id)));
+            } else {
+                row = row.push(label_txt(r.job.yuyue_dt.as_str(), Self::DT_PORTION));
+                ops = ops.push(op_button(&mut r.edit_button_state, "改时间", SchedulerMessage::Editing(id)));
+                ops = if r.job.status == JobStatus::Scheduled {
+                    ops.push(op_button(&mut r.cancel_button_state, "取消", SchedulerMessage::Cancel(id)))
+                } else {
+                    ops.push(op_button(&mut r.cancel_button_state, "删除", SchedulerMessage::Remove(id)))
+                };
+            }
+            column.push(
+                row.push(label_txt(r.job.fire_dt.as_str(), Self::DT_PORTION))
+                    .push(label_txt(status_txt(r.job.status), Self::STATUS_PORTION))
+                    .push(Container::new(ops).width(Length::FillPortion(Self::OP_PORTION))),
+            )
+        });
+        let scroll = Scrollable::new(&mut self.scroll_state)
+            .scrollbar_margin(1)
+            .scrollbar_width(1)
+            .style(style::ScrollableBarStyle)
+            .max_height(super::MAX_SCROLL_HEIGHT)
+            .push(jobs);
+
+        Container::new(
+            Column::new()
+                .width(Length::Fill)
+                .spacing(4)
+                .push(top_row)
+                .push(Row::with_children(super::get_headers(&headers, &portions)))
+                .push(scroll),
+        )
+        .width(Length::Fill)
+    }
+}
+
+const TXT_COLOR: Color = Color::from_rgb(0.0, 191.0 / 255.0, 1.0);
+
+fn label_txt<'a, T: AsRef<str>>(txt: T, portion: u16) -> Element<'a, JdMiaoshaAppMessage> {
+    Text::new(txt.as_ref())
+        .horizontal_alignment(HorizontalAlignment::Center)
+        .vertical_alignment(VerticalAlignment::Center)
+        .color(TXT_COLOR)
+        .width(Length::FillPortion(portion))
+        .into()
+}
+
+fn op_button<'a>(
+    state: &'a mut button::State,
+    name: &str,
+    msg: SchedulerMessage,
+) -> Element<'a, JdMiaoshaAppMessage> {
+    Button::new(state, Text::new(name).horizontal_alignment(HorizontalAlignment::Center))
+        .on_press(msg.into())
+        .width(Length::FillPortion(PORTION_1))
+        .style(style::ActionButton)
+        .into()
+}
@@ -0,0 +1,307 @@
+use iced::*;
+use log::{info, warn};
+
+use super::super::*;
+use super::style;
+use super::PORTION_1;
+use crate::services::notify::{EventKind, SinkKind, WebhookConfig};
+use crate::settings::Settings;
+
+const EVENTS: [(EventKind, &'static str); 8] = [
+    (EventKind::PurchaseSuccess, "抢购成功"),
+    (EventKind::PurchaseFail, "抢购失败"),
+    (EventKind::CookieExpired, "cookie失效"),
+    (EventKind::LicenseExpired, "激活过期"),
+    (EventKind::StockAvailable, "有货"),
+    (EventKind::PriceDrop, "降价"),
+    (EventKind::PaymentDue, "待付款提醒"),
+    (EventKind::PaymentUrgent, "付款即将超时"),
+];
+
+const SINKS: [(SinkKind, &'static str); 4] = [
+    (SinkKind::Desktop, "桌面"),
+    (SinkKind::Sound, "声音"),
+    (SinkKind::Webhook, "webhook"),
+    (SinkKind::Email, "邮件"),
+];
+
+struct FieldRow {
+    field: SettingsField,
+    label: &'static str,
+    placeholder: &'static str,
+    txt: String,
+    input_state: text_input::State,
+}
+
+struct RouteRow {
+    event: EventKind,
+    label: &'static str,
+    button_states: [button::State; 4],
+}
+
+#[derive(Default)]
+pub struct SettingsComponent {
+    // 打开设置页时生效的设置, 表单里没有的(如邮件配置)保存时原样带上
+    loaded: Settings,
+    fields: Vec<FieldRow>,
+    routes: Vec<RouteRow>,
+    scroll_state: scrollable::State,
+    save_button_state: button::State,
+    reset_button_state: button::State,
+    // 保存的结果, 出错时是错误信息
+    tip: String,
+    tip_is_error: bool,
+}
+
+fn parse_num<T: std::str::FromStr>(label: &str, txt: &str) -> Result<T, String> {
+    txt.trim().parse::<T>().map_err(|_| format!("{}要是数字: {}", label, txt))
+}
+
+impl SettingsComponent {
+    pub const LABEL_PORTION: u16 = 2;
+    pub const INPUT_PORTION: u16 = 6;
+
+    /// 用当前生效的设置填表单
+    pub fn load(&mut self, settings: Settings) {
+        use SettingsField::*;
+        let s = &settings;
+        let fields = vec![
+            (Addr, "服务端地址", "http://127.0.0.1:48180", s.addr.clone()),
+            (ProxyIpPoolUrl, "代理ip池地址", "不填不用代理", s.proxy_ip_pool_url.clone().unwrap_or_default()),
+            (Workers, "并发数", "5~100", s.workers.to_string()),
+            (AheadMillis, "提前抢购毫秒数", "500~10000", s.ahead_millis.to_string()),
+            (StockCheckSecs, "库存检查间隔(秒)", "1~600", s.stock_check_secs.to_string()),
+            (CookieCheckSecs, "cookie检查间隔(秒)", "30~3600", s.cookie_check_secs.to_string()),
+            (HeartbeatSecs, "激活检查间隔(秒)", "30~3600", s.heartbeat_secs.to_string()),
+            (ClaimRenewSecs, "租约续约间隔(秒)", "5~60", s.claim_renew_secs.to_string()),
+            (TrackRefreshSecs, "物流刷新间隔(秒)", "60~86400", s.track_refresh_secs.to_string()),
+            (TrackStaleDays, "物流停滞天数", "1~30", s.track_stale_days.to_string()),
+            (PayWindowMinutes, "付款时限(分钟)", "5~1440", s.pay_window_minutes.to_string()),
+            (WatchCheckSecs, "到货提醒检查间隔(秒)", "10~3600", s.watch_check_secs.to_string()),
+            (WatchDedupMinutes, "相同提醒间隔(分钟)", "0~1440, 0表示每次都提醒", s.watch_dedup_minutes.to_string()),
+            (WatchQuietHours, "免打扰时段", "如 23:00-07:00, 不填不设置", s.watch_quiet_hours.clone()),
+            (LogLevel, "日志级别", "off/error/warn/info/debug/trace", s.log_level.clone()),
+            (
+                WebhookUrl,
+                "webhook地址",
+                "不填不发webhook",
+                s.notify.webhook.as_ref().map(|w| w.url.clone()).unwrap_or_default(),
+            ),
+            (SoundFile, "提示音文件", "不填用终端响铃", s.notify.sound.file.clone().unwrap_or_default()),
+        ];
+        self.fields = fields
+            .into_iter()
+            .map(|(field, label, placeholder, txt)| FieldRow {
+                field,
+                label,
+                placeholder,
+                txt,
+                input_state: text_input::State::new(),
+            })
+            .collect();
+        self.routes = EVENTS
+            .iter()
+            .map(|(event, label)| RouteRow {
+                event: *event,
+                label: *label,
+                button_states: Default::default(),
+            })
+            .collect();
+        self.loaded = settings;
+    }
+
+    fn txt(&self, field: SettingsField) -> &str {
+        self.fields
+            .iter()
+            .find(|f| f.field == field)
+            .map(|f| f.txt.as_str())
+            .unwrap_or_default()
+    }
+
+    /// 表单里的设置, 数字格式不对时返回错误信息; 其他的校验在 settings::save 里做
+    pub fn edited(&self) -> Result<Settings, String> {
+        use SettingsField::*;
+        let mut s = self.loaded.clone();
+        s.addr = self.txt(Addr).trim().to_string();
+        s.proxy_ip_pool_url = Some(self.txt(ProxyIpPoolUrl).trim().to_string()).filter(|u| !u.is_empty());
+        s.workers = parse_num("并发数", self.txt(Workers))?;
+        s.ahead_millis = parse_num("提前抢购毫秒数", self.txt(AheadMillis))?;
+        s.stock_check_secs = parse_num("库存检查间隔", self.txt(StockCheckSecs))?;
+        s.cookie_check_secs = parse_num("cookie检查间隔", self.txt(CookieCheckSecs))?;
+        s.heartbeat_secs = parse_num("激活检查间隔", self.txt(HeartbeatSecs))?;
+        s.claim_renew_secs = parse_num("租约续约间隔", self.txt(ClaimRenewSecs))?;
+        s.track_refresh_secs = parse_num("物流刷新间隔", self.txt(TrackRefreshSecs))?;
+        s.track_stale_days = parse_num("物流停滞天数", self.txt(TrackStaleDays))?;
+        s.pay_window_minutes = parse_num("付款时限", self.txt(PayWindowMinutes))?;
+        s.watch_check_secs = parse_num("到货提醒检查间隔", self.txt(WatchCheckSecs))?;
+        s.watch_dedup_minutes = parse_num("相同提醒间隔", self.txt(WatchDedupMinutes))?;
+        s.watch_quiet_hours = self.txt(WatchQuietHours).trim().to_string();
+        s.log_level = self.txt(LogLevel).trim().to_lowercase();
+        let url = self.txt(WebhookUrl).trim().to_string();
+        s.notify.webhook = if url.is_empty() {
+            None
+        } else {
+            // 请求头只能在 conf.toml 里配置, 这里保留原来的
+            let headers = s.notify.webhook.take().map(|w| w.headers).unwrap_or_default();
+            Some(WebhookConfig { url, headers })
+        };
+        s.notify.sound.file = Some(self.txt(SoundFile).trim().to_string()).filter(|f| !f.is_empty());
+        Ok(s)
+    }
+
+    fn set_tip(&mut self, tip: String, is_error: bool) {
+        self.tip = tip;
+        self.tip_is_error = is_error;
+    }
+
+    pub fn update(&mut self, message: SettingsMessage) -> Command<JdMiaoshaAppMessage> {
+        use SettingsMessage::*;
+        match message {
+            Input(field, txt) => {
+                if let Some(f) = self.fields.iter_mut().find(|f| f.field == field) {
+                    f.txt = txt;
+                }
+            }
+            ToggleRoute(event, sink) => {
+                let sinks = self.loaded.notify.routes.entry(event).or_default();
+                if let Some(idx) = sinks.iter().position(|s| *s == sink) {
+                    sinks.remove(idx);
+                } else {
+                    sinks.push(sink);
+                }
+                if sinks.is_empty() {
+                    self.loaded.notify.routes.remove(&event);
+                }
+            }
+            Invalid(tip) => {
+                warn!("设置不合法:{}", tip);
+                self.set_tip(tip, true);
+            }
+            SaveFinish(None) => {
+                self.load(crate::settings::get());
+                self.set_tip("已保存, 已经生效".to_string(), false);
+            }
+            ResetFinish(None) => {
+                self.load(crate::settings::get());
+                self.set_tip("已恢复成 conf.toml 和环境变量的设置".to_string(), false);
+            }
+            SaveFinish(Some(e)) | ResetFinish(Some(e)) => {
+                self.set_tip(e, true);
+            }
+            _ => {
+                info!("{:?}", message);
+            }
+        }
+        Command::none()
+    }
+
+    pub fn view(&mut self) -> Container<JdMiaoshaAppMessage> {
+        let fields = self.fields.iter_mut().fold(Column::new().spacing(5), |column, f| {
+            let field = f.field;
+            column.push(
+                Row::new()
+                    .align_items(Align::Center)
+                    .push(label_txt(f.label, Self::LABEL_PORTION))
+                    .push(
+                        TextInput::new(&mut f.input_state, f.placeholder, f.txt.as_str(), move |txt| {
+                            JdMiaoshaAppMessage::from(SettingsMessage::Input(field, txt))
+                        })
+                        .on_submit(SettingsMessage::Save.into())
+                        .width(Length::FillPortion(Self::INPUT_PORTION))
+                        .padding(3),
+                    ),
+            )
+        });
+
+        let mut route_headers = vec!["通知事件"];
+        route_headers.extend(SINKS.iter().map(|(_, name)| *name));
+        let route_portions = [Self::LABEL_PORTION, PORTION_1, PORTION_1, PORTION_1, PORTION_1];
+        let loaded = &self.loaded;
+        let routes = self.routes.iter_mut().fold(Column::new().spacing(3), |column, r| {
+            let event = r.event;
+            let row = r.button_states.iter_mut().zip(SINKS.iter()).fold(
+                Row::new().align_items(Align::Center).push(label_txt(r.label, Self::LABEL_PORTION)),
+                |row, (state, (sink, _))| {
+                    let checked = loaded
+                        .notify
+                        .routes
+                        .get(&event)
+                        .map(|sinks| sinks.contains(sink))
+                        .unwrap_or(false);
+                    row.push(
+                        Container::new(
+                            Button::new(state, Text::new(if checked { "•" } else { "" }))
+                                .width(Length::Units(20))
+                                .height(Length::Units(20))
+                                .on_press(SettingsMessage::ToggleRoute(event, *sink).into())
+                                .style(if checked {
+                                    style::CheckBoxButton::Checked
+                                } else {
+                                    style::CheckBoxButton::UnChecked
+                                }),
+                        )
+                        .width(Length::FillPortion(PORTION_1))
+                        .center_x(),
+                    )
+                },
+            );
+            column.push(row)
+        });
+
+        let bottom_row = Row::new()
+            .align_items(Align::Center)
+            .spacing(10)
+            .padding(3)
+            .push(
+                Text::new(self.tip.as_str())
+                    .color(if self.tip_is_error { Color::from_rgb(1.0, 0.0, 0.0) } else { TXT_COLOR })
+                    .width(Length::FillPortion(4)),
+            )
+            .push(
+                Button::new(
+                    &mut self.save_button_state,
+                    Text::new("保存").horizontal_alignment(HorizontalAlignment::Center),
+                )
+                .style(style::ActionButton)
+                .on_press(SettingsMessage::Save.into()),
+            )
+            .push(
+                Button::new(
+                    &mut self.reset_button_state,
+                    Text::new("重置").horizontal_alignment(HorizontalAlignment::Center),
+                )
+                .style(style::ActionButton)
+                .on_press(SettingsMessage::Reset.into()),
+            );
+
+        let scroll = Scrollable::new(&mut self.scroll_state)
+            .scrollbar_margin(1)
+            .scrollbar_width(1)
+            .style(style::ScrollableBarStyle)
+            .max_height(super::MAX_SCROLL_HEIGHT)
+            .spacing(8)
+            .push(fields)
+            .push(Row::with_children(super::get_headers(&route_headers, &route_portions)))
+            .push(routes);
+
+        Container::new(
+            Column::new()
+                .width(Length::Fill)
+                .spacing(4)
+                .push(scroll)
+                .push(bottom_row),
+        )
+        .width(Length::Fill)
+    }
+}
+
+const TXT_COLOR: Color = Color::from_rgb(0.0, 191.0 / 255.0, 1.0);
+
+fn label_txt<'a, T: AsRef<str>>(txt: T, portion: u16) -> Element<'a, JdMiaoshaAppMessage> {
+    Text::new(txt.as_ref())
+        .horizontal_alignment(HorizontalAlignment::Center)
+        .vertical_alignment(VerticalAlignment::Center)
+        .color(TXT_COLOR)
+        .width(Length::FillPortion(portion))
+        .into()
+}
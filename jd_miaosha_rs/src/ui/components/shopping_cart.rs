@@ -18,6 +18,11 @@ use tokio::time::{Duration, Instant, Sleep, timeout};
 
 use crate::{IDType, NORMAL, PKDate, PKDateTime, PKLocal, YUYUE};
 use crate::models::*;
+use crate::services::category::CategoryService;
+use crate::services::dry_run::{self, DRY_RUN};
+use crate::services::notify::{self, Event, EventKind};
+use crate::services::retailer::PRICE_TOO_HIGH;
+use crate::services::scheduler::{scheduler, Job};
 use crate::services::shopping_cart::ShoppingCartService;
 use crate::ui::ShoppingCartMessage::Noop;
 use crate::utils::*;
@@ -27,39 +32,17 @@ use super::PORTION_1;
 use super::style;
 use super::super::*;
 
-type YuyueSleeps = Arc<RwLock<HashMap<IDType, (Instant, i64)>>>;
-lazy_static! {
-    static ref SHOPPING_CARTS_YUYUE_SLEEPS: YuyueSleeps = YuyueSleeps::default();
-}
-
-async fn set_yuyue_sleep(id: IDType, deadline_timestamp_millis: i64, ins: Instant) -> bool {
-    let mut guard = SHOPPING_CARTS_YUYUE_SLEEPS.write().await;
-    if let Some(entry) = guard.get_mut(&id) {
-        if entry.1 == deadline_timestamp_millis {
-            return false;
-        }
-        *entry = (ins, deadline_timestamp_millis);
-    } else {
-        guard.insert(id, (ins, deadline_timestamp_millis));
+// 定时购买: 任务保存到本地数据库里, 预约时间没变的不会重复等待
+async fn yuyue_sleep(job: Job) -> bool {
+    let id = job.cart_goods_id;
+    match scheduler().schedule(job).await {
+        Some(rev) => scheduler().wait(id, rev).await,
+        None => false,
     }
-    true
 }
 
 async fn remove_yuyue_sleep(id: IDType) {
-    let mut guard = SHOPPING_CARTS_YUYUE_SLEEPS.write().await;
-    guard.remove(&id);
-}
-
-async fn yuyue_sleep(id: IDType, deadline_timestamp_millis: i64, ins: Instant) -> bool {
-    let changed = set_yuyue_sleep(id, deadline_timestamp_millis, ins).await;
-    if changed {
-        let ins = {
-            let guard = SHOPPING_CARTS_YUYUE_SLEEPS.read().await;
-            guard.get(&id).unwrap().0.clone()
-        };
-        sleep_until(ins).await;
-    }
-    changed
+    scheduler().complete(id).await;
 }
 
 #[derive(Default)]
@@ -91,6 +74,10 @@ pub struct ShoppingCartComponent {
     search_input_state: text_input::State,
     search_button_state: button::State,
     search_reset_button_state: button::State,
+    // 分类过滤
+    categories: Vec<CategoryInfo>,
+    category_pick_state: pick_list::State<String>,
+    category_selected: Option<u8>,
     // 激活码
     pub activate_code: String,
     // 购物车信息
@@ -100,6 +87,8 @@ pub struct ShoppingCartComponent {
 impl ShoppingCartComponent {
     pub const NAME_PORTION: u16 = 4;
     pub const SKU_PORTION: u16 = 2;
+    pub const CATEGORY_PORTION: u16 = 1;
+    pub const ALL_CATEGORY: &'static str = "全部分类";
     pub const PRICE_PORTION: u16 = 2;
     pub const STOCK_PORTION: u16 = 1;
     pub const TIMEOUT_PORTION: u16 = 3;
@@ -117,6 +106,7 @@ impl ShoppingCartComponent {
             vec![
                 "商品名称".to_string(),
                 "商品SKU".to_string(),
+                "分类".to_string(),
                 "价格".to_string(),
                 "库存".to_string(),
                 "数量".to_string(),
@@ -126,6 +116,7 @@ impl ShoppingCartComponent {
             vec![
                 Self::NAME_PORTION,
                 Self::SKU_PORTION,
+                Self::CATEGORY_PORTION,
                 Self::PRICE_PORTION,
                 Self::STOCK_PORTION,
                 Self::AMOUNT_PORTION,
@@ -136,20 +127,18 @@ impl ShoppingCartComponent {
     }
 
     fn monitor_goods_stock(&self) -> Command<JdMiaoshaAppMessage> {
-        Command::batch(
-            self.prods
-                .records
-                .iter()
-                .filter(|item| item.is_stock == 0)
-                .map(|item| {
-                    let sku = item.sku.clone();
-                    let id = item.id.clone();
-                    Command::perform(async move { (id, sku) }, |(id, sku)| {
-                        // 检查商品库存的消息
-                        GoodsMessage::MonitorStock(id, sku).into()
-                    })
-                }),
-        )
+        let goods: Vec<(IDType, ProdPlatform, String)> = self
+            .prods
+            .records
+            .iter()
+            .filter(|item| item.is_stock == 0)
+            .map(|item| (item.id.clone(), item.platform, item.sku.clone()))
+            .collect();
+        if goods.is_empty() {
+            return Command::none();
+        }
+        // 检查商品库存的消息, 所有商品合并查询
+        Command::perform(async move { goods }, |goods| GoodsMessage::MonitorStock(goods).into())
     }
 
     fn get_goods_coupons(&self) -> Command<JdMiaoshaAppMessage> {
@@ -179,7 +168,6 @@ impl ShoppingCartComponent {
             .map(|record| {
                 let mut yuyue_timeout = 0;
                 let now = now();
-                let instant_now = tokio::time::Instant::now();
                 let id = record.id;
                 let mut deadline_timestamp_millis = 0;
                 if (record.purchase_type.eq(YUYUE) && record.purchase_status.eq("yuyueing"))
@@ -191,7 +179,6 @@ impl ShoppingCartComponent {
                         if yuyue_timeout > 0 {
                             yuyue_timeout -= ahead_purchase_millis(); // 定时的时候，提前一定时间就开始抢了
                             yuyue_timeout = max(1, yuyue_timeout);
-                            deadline_timestamp_millis -= yuyue_timeout;
                             info!(
                                 "定时预约购买:{}-{}-{}-{}-[{}-{}, {}]",
                                 id,
@@ -212,13 +199,18 @@ impl ShoppingCartComponent {
                         ShoppingCartMessage::SubmitOrder(id).into()
                     });
                 }
-                let yuyue_timeout = yuyue_timeout as u64;
                 if yuyue_timeout > 0 {
-                    let ins = instant_now + Duration::from_millis(yuyue_timeout);
+                    let job = Job::new(
+                        id,
+                        record.sku.as_str(),
+                        record.name.as_str(),
+                        deadline_timestamp_millis,
+                        now.timestamp_millis() + yuyue_timeout,
+                    );
                     Command::perform(
-                        yuyue_sleep(id, deadline_timestamp_millis, ins),
-                        move |changed| {
-                            if changed {
+                        yuyue_sleep(job),
+                        move |fired| {
+                            if fired {
                                 ShoppingCartMessage::SubmitOrder(id).into()
                             } else {
                                 ShoppingCartMessage::Noop.into()
@@ -251,10 +243,12 @@ impl ShoppingCartComponent {
                 let code = self.activate_code.clone();
                 let page_no = self.prods.page_no.clone();
                 let key_word = self.search_input_txt.clone();
-                return Command::batch(vec![
+                let category = self.category_selected;
+                let mut cmds = vec![
                     Command::perform(
                         async move {
-                            ShoppingCartService::list_cart_goods(code, key_word, page_no).await
+                            ShoppingCartService::list_cart_goods(code, key_word, page_no, category)
+                                .await
                         },
                         |r| match r {
                             Ok(rdata) => LoadFinish(rdata).into(),
@@ -267,7 +261,36 @@ impl ShoppingCartComponent {
                     Command::perform(async {}, |_| {
                         UserMessage::AddToCartAndLoadCartGoodsSkuUuids(0, None).into()
                     }),
-                ]);
+                ];
+                if self.categories.is_empty() {
+                    let code = self.activate_code.clone();
+                    cmds.push(Command::perform(
+                        CategoryService::list_categories(code),
+                        |r| match r {
+                            Ok(data) => CategoryLoadFinish(data).into(),
+                            Err(e) => {
+                                error!("{:?}", e);
+                                Noop.into()
+                            }
+                        },
+                    ));
+                }
+                return Command::batch(cmds);
+            }
+            CategoryLoadFinish(data) => {
+                self.categories = data;
+            }
+            CategorySelected(name) => {
+                self.category_selected = self
+                    .categories
+                    .iter()
+                    .find(|c| c.name == name)
+                    .map(|c| c.id);
+                self.prods.page_no = 1;
+                if self.activate_code.is_empty() {
+                    return Command::none();
+                }
+                return Command::perform(async move {}, |_| ShoppingCartMessage::Loading.into());
             }
             LoadFinish(data) => {
                 if let Some(d) = data {
@@ -292,6 +315,14 @@ impl ShoppingCartComponent {
             StockChecking => {
                 return self.monitor_goods_stock();
             }
+            RenewClaims => {
+                return Command::perform(ShoppingCartService::renew_cart_goods_claims(), |r| {
+                    if let Err(e) = r {
+                        error!("{:?}", e);
+                    }
+                    Noop.into()
+                });
+            }
 
             LoadFail => {
                 warn!("数据加载失败-LoadFail");
@@ -321,6 +352,7 @@ impl ShoppingCartComponent {
                 // 重置搜索的话会把页号置  1, 并把 搜索内容清空
                 self.prods.page_no = 1;
                 self.search_input_txt.clear();
+                self.category_selected = None;
                 if self.activate_code.is_empty() {
                     return Command::none();
                 }
@@ -333,6 +365,10 @@ impl ShoppingCartComponent {
             | UpdateStock(_id, _)
             | EditCancel(_id)
             | SelectOne(_id)
+            | ToggleDryRun(_id)
+            | EditMaxPrice(_id)
+            | EditInputMaxPrice(_id, _)
+            | EditMaxPriceFinish(_id)
             | ImmediatelyPurchase(_id)
             | SubNum(_id)
             | AddNum(_id) => {
@@ -369,6 +405,32 @@ impl ShoppingCartComponent {
                 );
                 return Command::batch(cmds);
             }
+            PriceChecked(_id, _, ok) => {
+                if self.activate_code.is_empty() {
+                    return Command::none();
+                }
+                let mut cmds = vec![];
+                if !ok {
+                    // 超过最高价格不会再提交订单, 从定时任务集里面删掉
+                    cmds.push(Command::perform(remove_yuyue_sleep(_id), |_| {
+                        ShoppingCartMessage::Noop.into()
+                    }));
+                }
+                let code = self.activate_code.clone();
+                cmds.extend(
+                    self.prods
+                        .records
+                        .iter_mut()
+                        .filter(|p| p.id == _id)
+                        .map(|p| p.update(code.clone(), &msg).map(|m| m.into())),
+                );
+                return Command::batch(cmds);
+            }
+            DryRunFinish(_id, payload) => {
+                if let Some(p) = self.prods.records.iter_mut().find(|p| p.id == _id) {
+                    p.dry_run_payload = payload;
+                }
+            }
             DeleteOneState(_id) => {
                 if self.activate_code.is_empty() {
                     return Command::none();
@@ -434,16 +496,32 @@ impl ShoppingCartComponent {
     pub fn view(&mut self) -> Container<JdMiaoshaAppMessage> {
         let is_selecting = self.is_selecting;
         let (headers, portions) = Self::header_portions();
-
+        let categories = &self.categories;
         let states: Element<_> = self
             .prods
             .records
             .iter_mut()
             .enumerate()
             .fold(Column::new().spacing(3), |column, (idx, state)| {
-                column.push(state.view(idx, is_selecting).map(move |msg| msg.into()))
+                let category_name = categories
+                    .iter()
+                    .find(|c| c.id == state.category)
+                    .map(|c| c.name.clone())
+                    .unwrap_or_default();
+                column.push(
+                    state
+                        .view(idx, is_selecting, category_name)
+                        .map(move |msg| msg.into()),
+                )
             })
             .into();
+        let mut category_names = vec![Self::ALL_CATEGORY.to_string()];
+        category_names.extend(self.categories.iter().map(|c| c.name.clone()));
+        let category_selected = self
+            .category_selected
+            .and_then(|id| self.categories.iter().find(|c| c.id == id))
+            .map(|c| c.name.clone())
+            .unwrap_or_else(|| Self::ALL_CATEGORY.to_string());
         let mut search_row = Row::new().align_items(Align::Center);
 
         if self.prods.pages > 0 {
@@ -459,6 +537,12 @@ impl ShoppingCartComponent {
         }
         search_row = search_row
             .push(Space::with_width(Length::FillPortion(1)))
+            .push(PickList::new(
+                &mut self.category_pick_state,
+                category_names,
+                Some(category_selected),
+                |t| ShoppingCartMessage::CategorySelected(t).into(),
+            ))
             .push(
                 TextInput::new(
                     &mut self.search_input_state,
@@ -603,6 +687,15 @@ fn label_txt<'a, T: AsRef<str>>(
 }
 
 impl CartProdState {
+    fn notify(&self, kind: EventKind, title: &str) -> Command<ShoppingCartMessage> {
+        let event = Event::new(
+            kind,
+            format!("{}: {}", title, self.name),
+            format!("商品:{}, sku:{}, 价格:{}", self.name, self.sku, self.cur_price),
+        );
+        Command::perform(notify::notify(event), |_| Noop)
+    }
+
     fn update(&mut self, code: String, msg: &ShoppingCartMessage) -> Command<ShoppingCartMessage> {
         use ShoppingCartMessage::*;
 
@@ -617,6 +710,9 @@ impl CartProdState {
                         YuyueChecking(id)
                     } else if mt == 2 {
                         Loading
+                    } else if mt == 3 {
+                        // 状态写完了再释放抢购租约
+                        MaybeRemoveShoppingCartLock(id)
                     } else {
                         Noop
                     }
@@ -625,7 +721,9 @@ impl CartProdState {
         };
         match msg {
             EditCancel(_) => {
-                if self.status == CartProdStatus::Editing {
+                if self.status == CartProdStatus::Editing
+                    || self.status == CartProdStatus::EditingMaxPrice
+                {
                     self.status = CartProdStatus::Done;
                 }
             }
@@ -638,8 +736,85 @@ impl CartProdState {
             EditInputTimeoutDt(_, s) => {
                 self.yuyue_txt = s.to_owned();
             }
+            EditMaxPrice(_) => {
+                self.status = CartProdStatus::EditingMaxPrice;
+                self.max_price_txt = self.max_price.clone().unwrap_or_default();
+            }
+            EditInputMaxPrice(_, s) => {
+                self.max_price_txt = s.to_owned();
+            }
+            EditMaxPriceFinish(_) => {
+                if self.status != CartProdStatus::EditingMaxPrice {
+                    return Command::none();
+                }
+                let txt = self.max_price_txt.trim();
+                // 清空就是不限制价格
+                let max_price = if txt.is_empty() {
+                    None
+                } else {
+                    match parse_amount(txt) {
+                        Some(v) if v > 0.0 => Some(format!("{:.2}", v)),
+                        _ => {
+                            warn!("最高价格格式不对:{}", txt);
+                            return Command::none();
+                        }
+                    }
+                };
+                self.status = CartProdStatus::Done;
+                self.max_price = max_price;
+                let id = self.id.clone();
+                let body = json!({
+                    "op":13,
+                    "yuyue_dt":null,
+                    "max_price":self.max_price,
+                })
+                .to_string();
+                return update_cart_goods(code, id, body, 0);
+            }
+            PriceChecked(_, price, ok) => {
+                let known = parse_amount(price).is_some();
+                if known {
+                    self.cur_price = price.to_owned();
+                }
+                let id = self.id.clone();
+                let mut cmds = vec![];
+                let body = if *ok {
+                    // 可以购买, 状态由接下来的提交订单决定
+                    self.purchase_status = "ready".to_string();
+                    json!({
+                        "op":14,
+                        "yuyue_dt":null,
+                        "cur_price":price,
+                    })
+                } else {
+                    info!(
+                        "商品:{}{}的最新价格:{}超过最高价格:{:?}, 不购买!",
+                        self.id, self.sku, price, self.max_price
+                    );
+                    cmds.push(self.notify(EventKind::PurchaseFail, "超过最高价格, 没有购买"));
+                    if !known {
+                        // 查不到价格, 按失败处理
+                        self.purchase_status = "fail".to_string();
+                        json!({
+                            "op":6,
+                            "yuyue_dt":null,
+                        })
+                    } else {
+                        self.purchase_status = PRICE_TOO_HIGH.to_string();
+                        json!({
+                            "op":15,
+                            "yuyue_dt":null,
+                            "cur_price":price,
+                        })
+                    }
+                };
+                // 不购买的等状态写完再释放抢购租约
+                cmds.push(update_cart_goods(code, id, body.to_string(), if *ok { 0 } else { 3 }));
+                return Command::batch(cmds);
+            }
 
             UpdateStock(_id, _stock_status) => {
+                let was_out_of_stock = self.is_stock == 0;
                 // 把该商品设为有货状态
                 self.is_stock = 1;
                 let id = _id.clone();
@@ -652,6 +827,9 @@ impl CartProdState {
                     .to_string();
                 // 执行立即购买和 在数据库更新为有货状态
                 let mut cmds = vec![update_cart_goods(code, id, body, 0)];
+                if was_out_of_stock {
+                    cmds.push(self.notify(EventKind::StockAvailable, "有货了"));
+                }
                 if self.yuyue_dt.is_none() && self.purchase_status != "purchasing" {
                     // 避免无条件的执行立即购买
                     cmds.push(Command::perform(async move {}, move |_| {
@@ -664,6 +842,8 @@ impl CartProdState {
             SubmitOrderFinish(_id, status) => {
                 let mut op = 0;
                 self.cur_check += 1;
+                // 所有账号都结束了才能释放抢购租约
+                let finished = self.cur_check >= JdMiaoshaApp::get_check_times(self.id);
                 if status == &"success" {
                     // 只要有一个抢购成功， 那就是成功了
                     op = 5;
@@ -672,18 +852,30 @@ impl CartProdState {
                     // 预约中
                     op = 7;
                     self.purchase_status = "yuyueing".to_string()
+                } else if status == &DRY_RUN {
+                    // 试运行, 没有真正提交订单
+                    op = 10;
+                    self.purchase_status = DRY_RUN.to_string()
                 } else {
                     let max_check = JdMiaoshaApp::get_check_times(self.id);
                     info!(
                         "id:{},cur_check:{},max_check:{}",
                         self.id, self.cur_check, max_check
                     );
-                    if self.cur_check >= max_check && self.purchase_status.eq("purchasing") {
+                    if finished && self.purchase_status.eq("purchasing") {
                         // 所有的抢购都没成功，那就是抢购失败了, 且状态还是为 purchasing 才修改为失败状态
                         op = 6;
                         self.purchase_status = "fail".to_string()
                     }
                 }
+                let id = _id.clone();
+                let mut cmds = vec![];
+                if finished && op == 0 {
+                    // 所有账号都结束了才释放抢购租约, 不管最后一个账号的结果
+                    cmds.push(Command::perform(async {}, move |_| {
+                        MaybeRemoveShoppingCartLock(id).into()
+                    }));
+                }
                 if op > 0 {
                     self.purchase_status = status.to_string();
                     let body = json!({
@@ -691,15 +883,25 @@ impl CartProdState {
                         "yuyue_dt":null,
                     })
                         .to_string();
-                    let id = _id.clone();
-                    let mut cmds = vec![update_cart_goods(code, id, body, 0)];
-                    if op == 5 || op == 6 {
-                        cmds.push(Command::perform(async {}, move |_| {
-                            MaybeRemoveShoppingCartLock(id).into()
+                    // 最后一个账号的状态写到服务端之后再释放, 免得别的客户端趁机再抢一次
+                    cmds.push(update_cart_goods(code, id, body, if finished { 3 } else { 0 }));
+                    if op == 5 {
+                        cmds.push(self.notify(EventKind::PurchaseSuccess, "抢购成功"));
+                    } else if op == 6 {
+                        cmds.push(self.notify(EventKind::PurchaseFail, "抢购失败"));
+                    }
+                    if op == 10 {
+                        cmds.push(Command::perform(dry_run::orders(id), move |orders| {
+                            let payload = orders
+                                .iter()
+                                .map(|o| o.payload())
+                                .collect::<Vec<_>>()
+                                .join("\n\n");
+                            DryRunFinish(id, payload)
                         }));
                     }
-                    return Command::batch(cmds);
                 }
+                return Command::batch(cmds);
             }
 
             EditFinish(_) => {
@@ -745,6 +947,17 @@ impl CartProdState {
                 return update_cart_goods(code, id, body, 0);
             }
 
+            ToggleDryRun(_) => {
+                self.dry_run = if self.is_dry_run() { 0 } else { 1 };
+                let id = self.id.clone();
+                let body = json!({
+                    "op": if self.is_dry_run() { 11 } else { 12 },
+                    "yuyue_dt":null,
+                })
+                .to_string();
+                return update_cart_goods(code, id, body, 0);
+            }
+
             ImmediatelyPurchase(_) => {
                 if self.is_stock == 0 {
                     // 无货则啥都不做
@@ -768,7 +981,12 @@ impl CartProdState {
         return Command::none();
     }
 
-    fn view(&mut self, idx: usize, is_selecting: bool) -> Element<ShoppingCartMessage> {
+    fn view(
+        &mut self,
+        idx: usize,
+        is_selecting: bool,
+        category_name: String,
+    ) -> Element<ShoppingCartMessage> {
         let mut row = Row::new().align_items(Align::Center);
         let mut op_box_row = Row::new()
             .push(Space::with_width(Length::Units(3)))
@@ -806,6 +1024,16 @@ impl CartProdState {
                 ele = img::right_image().into();
             } else if self.purchase_status.eq("fail") {
                 ele = img::wrong_image().into();
+            } else if self.purchase_status.eq(DRY_RUN) {
+                // 鼠标移上去显示本来要提交的订单
+                ele = Tooltip::new(
+                    Text::new("试运行").size(14).color(Color::from_rgb8(0, 128, 255)),
+                    self.dry_run_payload.as_str(),
+                    tooltip::Position::FollowCursor,
+                )
+                .into();
+            } else if self.purchase_status.eq(PRICE_TOO_HIGH) {
+                ele = Text::new("超价").size(14).color(Color::from_rgb8(255, 0, 0)).into();
             } else if self.purchase_status.eq("yuyueing")
                 || self.purchase_status.eq("ready") && self.yuyue_dt.is_some()
             {
@@ -839,11 +1067,27 @@ impl CartProdState {
                 None,
             ))
             .push(label_txt(
-                format!("{}/{}", &self.cur_price, &self.ori_price),
-                ShoppingCartComponent::PRICE_PORTION,
+                category_name,
+                ShoppingCartComponent::CATEGORY_PORTION,
                 None,
                 None,
             ))
+            .push(label_txt(
+                match self.max_price.as_ref() {
+                    Some(max_price) => format!(
+                        "{}/{}\n最高{}",
+                        &self.cur_price, &self.ori_price, max_price
+                    ),
+                    None => format!("{}/{}", &self.cur_price, &self.ori_price),
+                },
+                ShoppingCartComponent::PRICE_PORTION,
+                None,
+                if self.purchase_status.eq(PRICE_TOO_HIGH) {
+                    Some(Color::from_rgb8(255, 0, 0))
+                } else {
+                    None
+                },
+            ))
             .push(label_txt(
                 if self.is_stock == 1 {
                     "有货"
@@ -915,6 +1159,35 @@ impl CartProdState {
                     ),
                 ]
             }
+            CartProdStatus::EditingMaxPrice => {
+                let id = self.id;
+                row = row.push(
+                    TextInput::new(
+                        &mut self.max_price_input_state,
+                        "最高价格, 不填不限制",
+                        self.max_price_txt.as_str(),
+                        move |input_txt| ShoppingCartMessage::EditInputMaxPrice(id, input_txt),
+                    )
+                    .on_submit(ShoppingCartMessage::EditMaxPriceFinish(id))
+                    .width(Length::FillPortion(ShoppingCartComponent::TIMEOUT_PORTION))
+                    .padding(3),
+                );
+                vec![
+                    op_button(
+                        &mut self.immediately_button_state,
+                        "确定",
+                        Length::FillPortion(PORTION_1),
+                        ShoppingCartMessage::EditMaxPriceFinish(id),
+                    ),
+                    Space::with_width(Length::FillPortion(PORTION_1)).into(),
+                    op_button(
+                        &mut self.delete_button_state,
+                        "取消",
+                        Length::FillPortion(PORTION_1),
+                        ShoppingCartMessage::EditCancel(id),
+                    ),
+                ]
+            }
             _ => {
                 row = row.push(
                     Row::new()
@@ -943,6 +1216,22 @@ impl CartProdState {
                             Length::FillPortion(1),
                             ShoppingCartMessage::ImmediatelyPurchase(self.id),
                         ),
+                        op_button(
+                            &mut self.dry_run_button_state,
+                            if self.is_dry_run() {
+                                "取消试运行"
+                            } else {
+                                "试运行"
+                            },
+                            Length::FillPortion(1),
+                            ShoppingCartMessage::ToggleDryRun(self.id),
+                        ),
+                        op_button(
+                            &mut self.max_price_button_state,
+                            "限价",
+                            Length::FillPortion(1),
+                            ShoppingCartMessage::EditMaxPrice(self.id),
+                        ),
                         op_button(
                             &mut self.delete_button_state,
                             "删除",
@@ -10,7 +10,9 @@ use serde_json::{json, to_string};
 use thirtyfour::common::command::By::Id;
 
 use crate::{IDType, PKDate, PKDateTime, PKLocal};
-use crate::models::{UserInfo, UserInfoStatus, UserState};
+use crate::models::{ProdPlatform, UserInfo, UserInfoStatus, UserState};
+use crate::services::notify::{self, Event, EventKind};
+use crate::services::retailer::retailer;
 use crate::services::shopping_cart::ShoppingCartService;
 use crate::services::user::UserService;
 use crate::utils::*;
@@ -54,6 +56,8 @@ pub struct UserComponent {
     clear_button_state: button::State,
     // 用户信息
     pub user_info: UserInfo,
+    // 已经提醒过 cookie 失效的账号, 重新登录或 cookie 恢复有效之前不再提醒
+    expired_notified: HashSet<String>,
 }
 
 impl UserComponent {
@@ -142,10 +146,22 @@ impl UserComponent {
             }
             LoadFinish(d) => {
                 self.user_info.users = d;
+                // 账号列表缓存到本地数据库
+                let records = self.user_info.users.records.clone();
+                let store_cmd = Command::perform(UserService::store_user_data(records), |t| {
+                    if let Err(e) = t {
+                        error!("{:?}", e);
+                    }
+                    Noop.into()
+                });
                 if self.user_info.users.records.len() > 0 {
                     // 加载出了用户后，需要执行 cookie 验证
-                    return Command::perform(async {}, |_| UserMessage::CookieChecking.into());
+                    return Command::batch(vec![
+                        store_cmd,
+                        Command::perform(async {}, |_| UserMessage::CookieChecking.into()),
+                    ]);
                 }
+                return store_cmd;
             }
             LoadCodeFinish(d) => {
                 if let Some(uinfo) = d {
@@ -198,21 +214,26 @@ impl UserComponent {
                 self.user_info.activate_code = code.trim().to_string();
             }
 
-            AddToCartAndLoadCartGoodsSkuUuids(num, sku) => {
-                return Command::perform(
-                    ShoppingCartService::add_to_cat_and_get_sku_uuid_from_account_cart(
-                        num as u32,
-                        sku,
-                        self.user_info
-                            .users
-                            .records
-                            .iter()
-                            .filter(|u| u.maybe_valid_cookie2())
-                            .map(|u| (u.id.clone(), u.cookie.clone()))
-                            .collect(),
-                    ),
-                    |_| JdMiaoshaAppMessage::GlobalNoop,
-                );
+            AddToCartAndLoadCartGoodsSkuUuids(num, item) => {
+                // 商品只加入同一平台账号的购物车; 没有商品时每个平台都加载一遍购物车
+                let mut account_cookies: HashMap<ProdPlatform, Vec<(IDType, Arc<String>)>> = HashMap::new();
+                for u in self.user_info.users.records.iter().filter(|u| u.maybe_valid_cookie2()) {
+                    if item.as_ref().map(|(platform, _)| *platform == u.platform).unwrap_or(true) {
+                        account_cookies.entry(u.platform).or_default().push((u.id.clone(), u.cookie.clone()));
+                    }
+                }
+                let sku = item.map(|(_, sku)| sku);
+                return Command::batch(account_cookies.into_iter().map(|(platform, account_cookies)| {
+                    let sku = sku.clone();
+                    Command::perform(
+                        async move {
+                            retailer(platform)
+                                .add_to_cart(num as u32, sku, account_cookies)
+                                .await
+                        },
+                        |_| JdMiaoshaAppMessage::GlobalNoop,
+                    )
+                }));
             }
             GetCoupon(sku) => {
                 return Command::batch(
@@ -448,6 +469,7 @@ impl UserComponent {
                     let id = us.id;
                     us.cookie = info.cookie;
                     us.cookie_last_update_dt = Some(PKLocal::now());
+                    self.expired_notified.remove(us.account.as_str());
                     let body = json!({
                         "cookie":us.cookie.as_str(),
                         "eid":info.eid.as_str(),
@@ -552,6 +574,18 @@ impl UserComponent {
                         }
                     })
                     .count();
+                if is_valid {
+                    self.expired_notified.remove(account.as_str());
+                } else if self.expired_notified.insert(account.clone()) {
+                    let event = Event::new(
+                        EventKind::CookieExpired,
+                        "cookie 失效",
+                        format!("账号 {} 的 cookie 已失效, 请重新登录", account),
+                    );
+                    return Command::perform(notify::notify(event), |_| {
+                        JdMiaoshaAppMessage::GlobalNoop
+                    });
+                }
             }
             _ => {
                 info!("{:?}", message);
@@ -0,0 +1,310 @@
+//! 到货提醒: 和购物车分开的监控列表, 有货或降价时只发通知, 不下单
+use iced::*;
+use log::{error, info};
+
+use super::super::*;
+use super::style;
+use super::AreaPicker;
+use crate::models::{AreaMatch, ProdPlatform};
+use crate::services::area::AreaService;
+use crate::services::notify;
+use crate::services::parser::parse_sku_input;
+use crate::services::retailer::retailer;
+use crate::services::watch::{self, RestockWatchlist, WatchItem, WatchRules};
+use crate::utils::{datetime_fmt, now, parse_amount};
+
+const TXT_COLOR: Color = Color::from_rgb(0.0, 191.0 / 255.0, 1.0);
+const ERROR_COLOR: Color = Color::from_rgb(0.85, 0.25, 0.25);
+
+#[derive(Default)]
+pub struct WatchComponent {
+    pub watchlist: RestockWatchlist,
+    sku_input_state: text_input::State,
+    sku_input: String,
+    price_input_state: text_input::State,
+    price_input: String,
+    area_picker: AreaPicker,
+    // 选中的地区, 没有选时用商品页的地区
+    area: Option<AreaMatch>,
+    add_button_state: button::State,
+    remove_button_states: Vec<button::State>,
+    scroll_state: scrollable::State,
+    // 上一次查询还没结束时跳过
+    checking: bool,
+    tip: String,
+    tip_is_error: bool,
+}
+
+fn notify_all(events: Vec<notify::Event>) -> Command<JdMiaoshaAppMessage> {
+    Command::batch(
+        events
+            .into_iter()
+            .map(|e| Command::perform(notify::notify(e), |_| JdMiaoshaAppMessage::Noop)),
+    )
+}
+
+impl WatchComponent {
+    pub const SKU_PORTION: u16 = 2;
+    pub const NAME_PORTION: u16 = 4;
+    pub const AREA_PORTION: u16 = 3;
+    pub const PRICE_PORTION: u16 = 1;
+    pub const DT_PORTION: u16 = 2;
+
+    fn set_tip<T: Into<String>>(&mut self, tip: T, is_error: bool) {
+        self.tip = tip.into();
+        self.tip_is_error = is_error;
+    }
+
+    /// 加入监控, 先查一次商品名称、价格和库存作为基准
+    pub fn add(&mut self, default_area: String) -> Command<JdMiaoshaAppMessage> {
+        let sku = match parse_sku_input(self.sku_input.as_str()) {
+            Some(sku) => sku,
+            None => {
+                self.set_tip(format!("商品SKU或链接不对: {}", self.sku_input), true);
+                return Command::none();
+            }
+        };
+        let target_price = if self.price_input.trim().is_empty() {
+            None
+        } else {
+            match parse_amount(self.price_input.as_str()).filter(|p| *p > 0.0) {
+                Some(p) => Some(p),
+                None => {
+                    self.set_tip(format!("提醒价格要是数字: {}", self.price_input), true);
+                    return Command::none();
+                }
+            }
+        };
+        let area = self.area.clone().unwrap_or_else(|| {
+            AreaService::resolve(default_area.as_str()).unwrap_or(AreaMatch {
+                ids: default_area,
+                names: vec![],
+            })
+        });
+        // 已经在监控的保留加入时间
+        let create_dt = self
+            .watchlist
+            .get(sku.as_str(), area.ids.as_str())
+            .map(|w| w.create_dt.clone())
+            .unwrap_or_else(|| datetime_fmt(now()));
+        // 界面上还不能选平台, 用默认平台
+        let platform = ProdPlatform::default();
+        Command::perform(
+            async move {
+                let goods = retailer(platform)
+                    .get_prod_info(sku.as_str(), area.ids.as_str())
+                    .await
+                    .map_err(|e| format!("查询商品失败:{}, {:?}", sku, e))?;
+                let item = WatchItem {
+                    sku: sku.clone(),
+                    platform,
+                    name: goods.name.clone(),
+                    area_name: area.display(),
+                    area: area.ids,
+                    target_price,
+                    create_dt,
+                    price: parse_amount(goods.cur_price.as_str()),
+                    status: Some(goods.status),
+                    check_dt: datetime_fmt(now()),
+                };
+                watch::add(item).await.map_err(|e| format!("保存失败:{:?}", e))
+            },
+            |r| WatchMessage::Added(r).into(),
+        )
+    }
+
+    pub fn update(&mut self, message: WatchMessage) -> Command<JdMiaoshaAppMessage> {
+        use WatchMessage::*;
+        match message {
+            Load => {
+                return Command::perform(watch::load(), |r| {
+                    let items = r.unwrap_or_else(|e| {
+                        error!("读取到货提醒失败:{:?}", e);
+                        vec![]
+                    });
+                    WatchMessage::Loaded(items).into()
+                });
+            }
+            Loaded(items) => {
+                self.watchlist = RestockWatchlist::new(items);
+                // 马上查一次作为基准
+                return self.update(Check);
+            }
+            SkuInput(input) => {
+                self.sku_input = input;
+            }
+            PriceInput(input) => {
+                self.price_input = input;
+            }
+            Picker(msg) => {
+                if let Some(m) = self.area_picker.update(msg) {
+                    self.area = Some(m);
+                }
+            }
+            Add => {
+                info!("到货提醒要通过 add 加入: {:?}", message);
+            }
+            Added(Ok(item)) => {
+                self.set_tip(format!("已加入: {} {}", item.name, item.area_name), false);
+                self.sku_input.clear();
+                self.price_input.clear();
+                self.watchlist.insert(item);
+            }
+            Added(Err(e)) => {
+                self.set_tip(e, true);
+            }
+            Remove(sku, area) => {
+                self.watchlist.remove(sku.as_str(), area.as_str());
+                return Command::perform(watch::remove(sku, area), |r| {
+                    if let Err(e) = r {
+                        error!("删除到货提醒失败:{:?}", e);
+                    }
+                    JdMiaoshaAppMessage::Noop
+                });
+            }
+            Check => {
+                if self.checking || self.watchlist.is_empty() {
+                    return Command::none();
+                }
+                self.checking = true;
+                // 免打扰时段结束后先把攒下的发出去
+                let events = self.watchlist.flush(now(), &WatchRules::current());
+                return Command::batch(vec![
+                    notify_all(events),
+                    Command::perform(watch::check(self.watchlist.targets()), |obs| {
+                        WatchMessage::Checked(obs).into()
+                    }),
+                ]);
+            }
+            Checked(observations) => {
+                self.checking = false;
+                let rules = WatchRules::current();
+                let now = now();
+                let events = observations
+                    .iter()
+                    .flat_map(|o| self.watchlist.observe(o, now, &rules))
+                    .collect();
+                return notify_all(events);
+            }
+        }
+        Command::none()
+    }
+
+    pub fn view(&mut self) -> Container<JdMiaoshaAppMessage> {
+        let area_txt = self
+            .area
+            .as_ref()
+            .map(|a| a.display())
+            .unwrap_or("默认用商品页的地区".to_string());
+        let top_row = Row::new()
+            .align_items(Align::Start)
+            .spacing(10)
+            .padding(3)
+            .push(
+                TextInput::new(
+                    &mut self.sku_input_state,
+                    "商品SKU或链接",
+                    self.sku_input.as_str(),
+                    |s| JdMiaoshaAppMessage::from(WatchMessage::SkuInput(s)),
+                )
+                .width(Length::FillPortion(3))
+                .padding(3),
+            )
+            .push(
+                TextInput::new(
+                    &mut self.price_input_state,
+                    "低于多少钱提醒, 可不填",
+                    self.price_input.as_str(),
+                    |s| JdMiaoshaAppMessage::from(WatchMessage::PriceInput(s)),
+                )
+                .width(Length::FillPortion(2))
+                .padding(3),
+            )
+            .push(
+                Container::new(self.area_picker.view(|m| JdMiaoshaAppMessage::Watch(WatchMessage::Picker(m))))
+                    .width(Length::Units(240)),
+            )
+            .push(label_txt(area_txt, 2))
+            .push(
+                Button::new(
+                    &mut self.add_button_state,
+                    Text::new("加入提醒").horizontal_alignment(HorizontalAlignment::Center),
+                )
+                .style(style::ActionButton)
+                .on_press(WatchMessage::Add.into()),
+            );
+
+        let mut tip = self.tip.clone();
+        if self.watchlist.pending_len() > 0 {
+            tip.push_str(format!("  免打扰中, 有{}条提醒稍后发送", self.watchlist.pending_len()).as_str());
+        }
+        let tip = Text::new(tip).color(if self.tip_is_error { ERROR_COLOR } else { TXT_COLOR });
+
+        let headers = ["商品SKU", "名称", "地区", "提醒价格", "当前价格", "库存", "检查时间", ""];
+        let portions = [
+            Self::SKU_PORTION,
+            Self::NAME_PORTION,
+            Self::AREA_PORTION,
+            Self::PRICE_PORTION,
+            Self::PRICE_PORTION,
+            Self::PRICE_PORTION,
+            Self::DT_PORTION,
+            Self::PRICE_PORTION,
+        ];
+        self.remove_button_states
+            .resize_with(self.watchlist.len(), button::State::new);
+        let fmt_price = |p: Option<f64>| p.map(|p| format!("{:.2}", p)).unwrap_or_default();
+        let rows = self.watchlist.items().zip(self.remove_button_states.iter_mut()).fold(
+            Column::new().spacing(3),
+            |column, (w, st)| {
+                column.push(
+                    Row::new()
+                        .align_items(Align::Center)
+                        .push(label_txt(w.sku.as_str(), Self::SKU_PORTION))
+                        .push(label_txt(w.name.as_str(), Self::NAME_PORTION))
+                        .push(label_txt(w.area_name.as_str(), Self::AREA_PORTION))
+                        .push(label_txt(fmt_price(w.target_price), Self::PRICE_PORTION))
+                        .push(label_txt(fmt_price(w.price), Self::PRICE_PORTION))
+                        .push(label_txt(
+                            w.status.map(|s| s.to_string()).unwrap_or_default(),
+                            Self::PRICE_PORTION,
+                        ))
+                        .push(label_txt(w.check_dt.as_str(), Self::DT_PORTION))
+                        .push(
+                            Button::new(st, Text::new("删除").horizontal_alignment(HorizontalAlignment::Center))
+                                .width(Length::FillPortion(Self::PRICE_PORTION))
+                                .style(style::ActionButton)
+                                .on_press(WatchMessage::Remove(w.sku.clone(), w.area.clone()).into()),
+                        ),
+                )
+            },
+        );
+        let scroll = Scrollable::new(&mut self.scroll_state)
+            .scrollbar_margin(1)
+            .scrollbar_width(1)
+            .style(style::ScrollableBarStyle)
+            .max_height(super::MAX_SCROLL_HEIGHT)
+            .push(rows);
+
+        Container::new(
+            Column::new()
+                .width(Length::Fill)
+                .spacing(4)
+                .push(top_row)
+                .push(tip)
+                .push(Row::with_children(super::get_headers(&headers, &portions)))
+                .push(scroll),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill)
+    }
+}
+
+fn label_txt<'a, T: AsRef<str>>(txt: T, portion: u16) -> Element<'a, JdMiaoshaAppMessage> {
+    Text::new(txt.as_ref())
+        .horizontal_alignment(HorizontalAlignment::Center)
+        .vertical_alignment(VerticalAlignment::Center)
+        .color(TXT_COLOR)
+        .width(Length::FillPortion(portion))
+        .into()
+}
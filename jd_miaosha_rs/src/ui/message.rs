@@ -6,6 +6,13 @@ use crate::*;
 use crate::error::Result;
 use crate::models::{AddressInfo, GoodsState, OrderInfo, UserInfo, UserState};
 use crate::models::*;
+use crate::services::budget::BudgetSummary;
+use crate::services::delivery_address::ApplyOutcome;
+use crate::services::notify::{EventKind, SinkKind};
+use crate::services::order_export::ExportFormat;
+use crate::services::price_history::{Observation, WatchedSku};
+use crate::services::scheduler::{Job, Recovery};
+use crate::services::watch::WatchItem;
 
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
 pub enum JdMiaoshaAppMessage {
@@ -20,6 +27,16 @@ pub enum JdMiaoshaAppMessage {
     GlobalDeliveryAddressPressed,
     // 点击了个人中心按钮
     GlobalPersonalCenterPressed,
+    // 点击了预算按钮
+    GlobalBudgetPressed,
+    // 点击了定时任务按钮
+    GlobalSchedulerPressed,
+    // 点击了设置按钮
+    GlobalSettingsPressed,
+    // 点击了价格走势按钮
+    GlobalPriceHistoryPressed,
+    // 点击了到货提醒按钮
+    GlobalWatchPressed,
     // 因为选择的地方比较多，所以整个全局选择的消息
     // 点击选择按钮
     GlobalClickSelection,
@@ -53,6 +70,16 @@ pub enum JdMiaoshaAppMessage {
 
     // 收货地址
     DeliveryAddress(DeliveryAddressMessage),
+    // 预算
+    Budget(BudgetMessage),
+    // 定时任务
+    Scheduler(SchedulerMessage),
+    // 设置
+    Settings(SettingsMessage),
+    // 价格走势
+    PriceHistory(PriceHistoryMessage),
+    // 到货提醒
+    Watch(WatchMessage),
     // 个人中心消息
     PersonalCenterInputActivationCode,
     // 输入激活码
@@ -72,6 +99,16 @@ pub enum GoodsMessage {
     Search,
     SearchFinish(Option<GoodsState>),
     ClearAll,
+    // 关键字搜索的结果, 出错时是错误信息
+    KeywordSearchFinish(std::result::Result<SearchPage, String>),
+    // 只看店铺名称包含输入内容的商品
+    SearchShopInput(String),
+    // 翻到搜索结果的第几页
+    SearchPageTo(u32),
+    // 把搜索结果里的商品加入商品列表和购物车
+    AddSearchResult(String),
+    SearchResultFetched(std::result::Result<GoodsState, String>),
+    CloseSearchResults,
 
     QueryPurchaseLinkFinish(Option<(String, String)>),
     // 更新购买链接
@@ -88,8 +125,10 @@ pub enum GoodsMessage {
     // 预约下单
     TimeoutPurchase(String),
 
-    // 监控商品库存
-    MonitorStock(IDType, String),
+    // 监控商品库存, 所有无货商品的 (购物车商品id, sku) 一起查询
+    MonitorStock(Vec<(IDType, ProdPlatform, String)>),
+    // 查到的有货商品
+    MonitorStockFinish(Vec<(IDType, StockStatus)>),
     Noop,
 }
 
@@ -102,6 +141,21 @@ pub enum AreaMessage {
     AreaStoreFinished,
     AreaLoad(String),
     AreaLoadFinished(Vec<Area>),
+    // 按名称搜索地区
+    Picker(AreaPickerMessage),
+    // 后台刷新完整的地区树, 结果是地区个数或者错误信息
+    TreeRefresh,
+    TreeRefreshed(std::result::Result<usize, String>),
+}
+
+/// 地区搜索框的消息, 商品、收货地址等页面共用
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub enum AreaPickerMessage {
+    Input(String),
+    // 选中第几个候选地区
+    Select(usize),
+    // 回车选中第一个
+    Submit,
 }
 
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
@@ -120,6 +174,28 @@ pub enum OrderMessage {
 
     // 订单物流信息
     ExpressInfo(String),
+    // 物流查询结果, 查询失败时是 None
+    TrackLoaded(String, Option<OrderTrack>),
+    // 定时刷新未签收订单的物流
+    TrackRefresh,
+    TrackClose,
+
+    // 导出所有账号的订单
+    ExportFromInput(String),
+    ExportToInput(String),
+    ExportFormatSelected(ExportFormat),
+    Export,
+    // 成功时是导出的文件和合计, 失败时是错误信息
+    ExportFinish(std::result::Result<String, String>),
+
+    // 定时刷新所有账号的待付款订单
+    UnpaidRefresh,
+    UnpaidLoaded(String, BTreeMap<String, OrderInfo>),
+    // 每秒更新付款倒计时, 到时间了发提醒
+    PayTick,
+    // 打开订单的付款页
+    Pay(String),
+    PayOpened(std::result::Result<(), String>),
 }
 
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
@@ -138,6 +214,9 @@ pub enum ShoppingCartMessage {
     SearchInput(String),
     Search,
     SearchReset,
+    // 商品分类
+    CategoryLoadFinish(Vec<CategoryInfo>),
+    CategorySelected(String),
     YuyueChecking(IDType),
 
     StockChecking,
@@ -154,11 +233,121 @@ pub enum ShoppingCartMessage {
     AddNum(IDType),
     // 加入购物车并提交订单
     SubmitOrder(IDType),
+    // 获取抢购租约的结果, 拿到了才提交订单
+    ClaimFinish(IDType, bool),
     MaybeRemoveShoppingCartLock(IDType),
     SubmitOrderFinish(IDType, &'static str),
+    // 切换单个商品的试运行开关
+    ToggleDryRun(IDType),
+    // 试运行结束, 展示本来要提交的订单
+    DryRunFinish(IDType, String),
+    // 设置最高价格
+    EditMaxPrice(IDType),
+    EditInputMaxPrice(IDType, String),
+    EditMaxPriceFinish(IDType),
+    // 提交前查询到的最新价格, 是否不超过最高价格
+    PriceChecked(IDType, String, bool),
+    // 抢购租约续约
+    RenewClaims,
     Noop,
 }
 
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub enum BudgetMessage {
+    // 查询各个账号的订单, 重新统计花费
+    Refresh,
+    LoadFinish(BudgetSummary),
+}
+
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub enum PriceHistoryMessage {
+    // 重新查询有价格记录的商品
+    Refresh,
+    WatchedLoaded(Vec<WatchedSku>),
+    SkuInput(String),
+    // 选中商品, 可以是 sku 或商品链接
+    Select(String),
+    // 切换统计天数
+    Days(u32),
+    Loaded(String, Vec<Observation>),
+}
+
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub enum WatchMessage {
+    // 读取保存的监控商品
+    Load,
+    Loaded(Vec<WatchItem>),
+    SkuInput(String),
+    PriceInput(String),
+    Picker(AreaPickerMessage),
+    // 加入监控, 没有选地区时用商品页选的地区
+    Add,
+    // 加入的商品, 出错时是错误信息
+    Added(std::result::Result<WatchItem, String>),
+    // 移出监控: sku, 地区
+    Remove(String, String),
+    // 定时查询库存和价格
+    Check,
+    Checked(Vec<Observation>),
+}
+
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub enum SchedulerMessage {
+    Refresh,
+    LoadFinish(Vec<Job>),
+    // 启动时恢复上次留下的任务
+    Recovered(Recovery),
+    // 任务到时间了: 购物车商品id, sku
+    Fire(IDType, String),
+    // 触发的商品不在当前页, 从服务端查到之后再下单
+    FireLoaded(IDType, Option<CartProdState>),
+    Cancel(IDType),
+    Remove(IDType),
+    // 修改预约时间
+    Editing(IDType),
+    EditInput(IDType, String),
+    EditFinish(IDType),
+    EditCancel(IDType),
+    // 改了时间之后的任务版本, 要重新开始等待
+    Rescheduled(IDType, Option<u64>),
+}
+
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq)]
+pub enum SettingsField {
+    Addr,
+    ProxyIpPoolUrl,
+    Workers,
+    AheadMillis,
+    StockCheckSecs,
+    CookieCheckSecs,
+    HeartbeatSecs,
+    ClaimRenewSecs,
+    TrackRefreshSecs,
+    TrackStaleDays,
+    PayWindowMinutes,
+    WatchCheckSecs,
+    WatchDedupMinutes,
+    WatchQuietHours,
+    LogLevel,
+    WebhookUrl,
+    SoundFile,
+}
+
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub enum SettingsMessage {
+    Input(SettingsField, String),
+    // 切换某个事件是否发到某种通知方式
+    ToggleRoute(EventKind, SinkKind),
+    Save,
+    // 表单里的值不对, 没有保存
+    Invalid(String),
+    // 保存结果, 出错时是错误信息
+    SaveFinish(Option<String>),
+    // 删掉设置页保存的设置
+    Reset,
+    ResetFinish(Option<String>),
+}
+
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
 pub enum DeliveryAddressMessage {
     // 设置收货地址
@@ -170,6 +359,40 @@ pub enum DeliveryAddressMessage {
     SearchReset,
     LoadFinish(LinkedList<AddressInfo>),
     LoadFinishFailed,
+
+    // 打开新增地址的表单
+    New,
+    // 修改某个账号的地址: 账号, 地址id
+    Edit(String, String),
+    // 删除地址, 点两次才删除
+    Delete(String, String),
+    DeleteFinish(String, String, std::result::Result<(), String>),
+    FormInput(AddressField, String),
+    // 选择所在地区
+    AreaChanged(Level, String),
+    AreaLoaded(Level, Area),
+    // 按名称搜索所在地区
+    PickArea(AreaPickerMessage),
+    // 选择批量设置的账号
+    SelectAccount(String, bool),
+    // 保存正在修改的地址
+    Save,
+    SaveFinish(std::result::Result<String, String>),
+    // 把表单里的地址设置到选中的账号
+    Apply,
+    ApplyFinish(Vec<(String, ApplyOutcome)>),
+    // 表单里的值不对, 没有保存
+    Invalid(String),
+    FormClose,
+}
+
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq)]
+pub enum AddressField {
+    Receiver,
+    Address,
+    MobilePhone,
+    FixedLinePhone,
+    Email,
 }
 
 macro_rules! msg_from {
@@ -217,7 +440,7 @@ pub enum UserMessage {
     Import,
     ImportFinish,
 
-    AddToCartAndLoadCartGoodsSkuUuids(u8, Option<String>),
+    AddToCartAndLoadCartGoodsSkuUuids(u8, Option<(ProdPlatform, String)>),
 
     CookieChecking,
     HeartBeat,
@@ -284,5 +507,10 @@ msg_from!(AreaMessage, JdMiaoshaAppMessage, Area);
 msg_from!(OrderMessage, JdMiaoshaAppMessage, Order);
 msg_from!(ShoppingCartMessage, JdMiaoshaAppMessage, ShoppingCart);
 msg_from!(DeliveryAddressMessage, JdMiaoshaAppMessage, DeliveryAddress);
+msg_from!(BudgetMessage, JdMiaoshaAppMessage, Budget);
+msg_from!(SchedulerMessage, JdMiaoshaAppMessage, Scheduler);
+msg_from!(SettingsMessage, JdMiaoshaAppMessage, Settings);
+msg_from!(PriceHistoryMessage, JdMiaoshaAppMessage, PriceHistory);
+msg_from!(WatchMessage, JdMiaoshaAppMessage, Watch);
 msg_from!(UserMessage, JdMiaoshaAppMessage, User);
 msg_from!(EUserOpMessage, UserMessage, UserOpMessage);
@@ -1,5 +1,4 @@
 use std::borrow::Borrow;
-use std::cmp::{max, min};
 use std::option::Option::Some;
 
 use chrono::prelude::*;
@@ -52,20 +51,14 @@ pub fn now() -> PKDateTime{
 }
 
 pub fn workers() ->usize{
-    let workers = env::var("workers")
-        .unwrap_or("15".to_string()).parse::<usize>()
-        .unwrap_or(15);
-    // 5 <= workers <= 1000
-    min(100, max(5, workers))
+    // 5 <= workers <= 100, 在 settings 里校验
+    settings::workers()
     // 对购物车都是加锁的， 并发抢没得意义，暂时去掉，等待找到更好的方式
     // 1
 }
 
 pub fn ahead_purchase_millis() ->i64{
-    let ahead_purchase_millis = env::var("ahead")
-        .unwrap_or("1200".to_string()).parse::<i64>()
-        .unwrap_or(1200);
-    min(10000, max(ahead_purchase_millis, 500))
+    settings::ahead_millis()
 }
 
 pub fn timestamp2datetime(ts: i64) -> PKDateTime {
@@ -95,6 +88,15 @@ pub fn serialize_timestamp2datetime_str<S>(ts: &Option<i64>, serializer: S) -> R
     serializer.serialize_none()
 }
 
+/// 从 "¥5999.00" 这样的金额里取出数字
+pub fn parse_amount(price: &str) -> Option<f64> {
+    let s: String = price
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    s.parse::<f64>().ok()
+}
+
 pub fn parse_json(txt:&str) -> serde_json::Value{
     if txt.len() < 2 {
         return serde_json::json!({});
@@ -104,6 +106,43 @@ pub fn parse_json(txt:&str) -> serde_json::Value{
     serde_json::from_str(&txt[left..=right]).unwrap_or(serde_json::json!({}))
 }
 
+/// 转义 cmd 的特殊字符, 不转义时链接会在 & 处被 cmd 截断
+pub fn cmd_escape(arg: &str) -> String {
+    let mut ret = String::with_capacity(arg.len());
+    for c in arg.chars() {
+        if "^&|<>()".contains(c) {
+            ret.push('^');
+        }
+        ret.push(c);
+    }
+    ret
+}
+
+/// 用系统默认浏览器打开链接
+pub fn open_in_browser(url: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        use std::os::windows::process::CommandExt;
+        let mut cmd = std::process::Command::new("cmd");
+        // 原样传给 cmd, 加了引号的话 ^ 就不是转义了
+        cmd.args(&["/C", "start", ""]).raw_arg(cmd_escape(url));
+        cmd
+    };
+    #[cfg(target_os = "macos")]
+    let mut cmd = {
+        let mut cmd = std::process::Command::new("open");
+        cmd.arg(url);
+        cmd
+    };
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut cmd = {
+        let mut cmd = std::process::Command::new("xdg-open");
+        cmd.arg(url);
+        cmd
+    };
+    cmd.spawn().map(|_| ())
+}
+
 pub fn get_useragent() -> &'static str{
     "Mozilla/5.0 (Macintosh; Intel Mac OS X 11_1_0) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/89.0.4389.114 Safari/537.36"
 }
@@ -245,3 +284,6 @@ pub mod update_create_dt_date_format {
         }
     }
 }
+
+#[cfg(test)]
+mod tests;
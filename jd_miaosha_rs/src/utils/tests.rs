@@ -0,0 +1,11 @@
+use super::cmd_escape;
+
+#[test]
+fn test_cmd_escape() {
+    assert_eq!(cmd_escape("https://item.jd.com/100012043978.html"), "https://item.jd.com/100012043978.html");
+    assert_eq!(
+        cmd_escape("https://search.jd.com/Search?keyword=a&enc=utf-8&page=(1)"),
+        "https://search.jd.com/Search?keyword=a^&enc=utf-8^&page=^(1^)"
+    );
+    assert_eq!(cmd_escape("a^b|c<d>"), "a^^b^|c^<d^>");
+}
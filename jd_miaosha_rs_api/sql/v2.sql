@@ -0,0 +1,42 @@
+use miaosha;
+
+create table if not exists miaosha.workspace(
+    id int unsigned PRIMARY KEY AUTO_INCREMENT,
+    name varchar(64) not null default '' comment '团队名称',
+    owner_id int unsigned not null comment '创建者的用户id',
+    create_time datetime not null,
+    update_time datetime not null,
+    index ix_ownerid(`owner_id`)
+)DEFAULT CHARSET=utf8mb4;
+
+create table if not exists miaosha.workspace_member(
+    id int unsigned PRIMARY KEY AUTO_INCREMENT,
+    workspace_id int unsigned not null comment '团队id',
+    user_id int unsigned not null comment '用户id。一个激活码同时只能属于一个团队',
+    role tinyint unsigned not null default 2 comment '成员角色。0: 所有者;1: 管理员;2: 成员;3: 只读',
+    create_time datetime not null,
+    update_time datetime not null,
+    unique ux_userid(`user_id`),
+    index ix_workspaceid(`workspace_id`)
+)DEFAULT CHARSET=utf8mb4;
+
+create table if not exists miaosha.workspace_invitation(
+    id int unsigned PRIMARY KEY AUTO_INCREMENT,
+    workspace_id int unsigned not null comment '团队id',
+    inviter_id int unsigned not null comment '邀请人的用户id',
+    invite_code char(32) not null comment '邀请码',
+    role tinyint unsigned not null default 2 comment '加入后的角色',
+    status tinyint unsigned not null default 0 comment '0: 未使用;1: 已使用',
+    expire_dt datetime not null comment '过期时间',
+    create_time datetime not null,
+    update_time datetime not null,
+    unique ux_invitecode(`invite_code`)
+)DEFAULT CHARSET=utf8mb4;
+
+-- 购物车和账号归属于团队, 0 表示个人数据
+alter table shopping_cart add column workspace_id int unsigned not null default 0
+    comment '所属团队id。0: 个人' after `user_id`;
+alter table shopping_cart add index ix_workspaceid(`workspace_id`);
+alter table platform_account add column workspace_id int unsigned not null default 0
+    comment '所属团队id。0: 个人' after `user_id`;
+alter table platform_account add index ix_workspaceid(`workspace_id`);
@@ -0,0 +1,8 @@
+use miaosha;
+
+-- 购物车商品的抢购租约, 避免多个客户端同时抢购同一个商品
+alter table shopping_cart add column lease_owner varchar(64) not null default ''
+    comment '当前持有抢购租约的客户端' after `status`;
+alter table shopping_cart add column lease_expire_dt datetime
+    comment '抢购租约的过期时间, 过期后商品自动回到 ready 状态' after `lease_owner`;
+alter table shopping_cart add index ix_status_leaseexpiredt(`status`, `lease_expire_dt`);
@@ -0,0 +1,5 @@
+use miaosha;
+
+-- 购物车和账号按平台区分, 同一个sku在不同平台是不同的商品
+alter table shopping_cart add index ix_workspaceid_platform_sku(`workspace_id`, `platform`, `sku`);
+alter table platform_account add index ix_workspaceid_platform(`workspace_id`, `platform`);
@@ -0,0 +1,27 @@
+use miaosha;
+
+-- 商品分类
+create table if not exists miaosha.goods_category(
+    id tinyint unsigned PRIMARY KEY comment '分类id, 和 shopping_cart.category 对应',
+    name varchar(32) not null comment '分类名称',
+    keywords varchar(1024) not null default '' comment '推断分类用的关键字, 逗号分隔',
+    sort int unsigned not null default 0 comment '排序, 越小越靠前',
+    create_time datetime not null,
+    update_time datetime not null,
+    unique ux_name(`name`)
+)DEFAULT CHARSET=utf8mb4;
+
+insert ignore into miaosha.goods_category(id, name, keywords, sort, create_time, update_time) values
+    (0, '电脑办公', '电脑,笔记本,台式机,游戏本,显卡,CPU,处理器,主板,硬盘,固态,内存,显示器,键盘,鼠标,打印机,路由器', 10, now(), now()),
+    (1, '手机通讯', '手机,iPhone,华为,小米,荣耀,OPPO,vivo,一加,realme,Redmi', 20, now(), now()),
+    (2, '数码', '数码,相机,摄像,耳机,平板,iPad,手表,手环,音箱,游戏机,Switch,PS5,无人机', 30, now(), now()),
+    (3, '家用电器', '家用电器,家电,电视,冰箱,洗衣机,空调,热水器,微波炉,吸尘器,扫地机,净水器,油烟机', 40, now(), now()),
+    (4, '酒类', '酒类,茅台,五粮液,泸州老窖,白酒,红酒,葡萄酒,啤酒,洋酒,威士忌', 50, now(), now()),
+    (5, '食品饮料', '食品,饮料,零食,牛奶,咖啡,茶,粮油,月饼,坚果', 60, now(), now()),
+    (6, '美妆个护', '美妆,个护,个人护理,护肤,面膜,口红,香水,洗发,沐浴,牙膏,剃须', 70, now(), now()),
+    (7, '母婴', '母婴,奶粉,纸尿裤,尿不湿,婴儿,童装,玩具', 80, now(), now()),
+    (8, '服饰鞋包', '服饰,服装,男装,女装,内衣,鞋,运动鞋,箱包,背包,手提包', 90, now(), now()),
+    (255, '其他', '', 1000, now(), now());
+
+alter table shopping_cart modify column category tinyint unsigned not null default '0' comment '商品分类, 对应 goods_category.id';
+alter table shopping_cart add index ix_workspaceid_category(`workspace_id`, `category`);
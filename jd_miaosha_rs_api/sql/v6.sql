@@ -0,0 +1,7 @@
+use miaosha;
+
+-- 试运行: 走完下单前的所有步骤, 但不真正提交订单
+alter table shopping_cart add column dry_run tinyint unsigned not null default 0
+    comment '是否试运行:0,否;1,是' after `is_stock`;
+alter table shopping_cart modify column status varchar(20) not null
+    comment '状态:ready, yuyueing, purchasing, success, fail, dry-run';
@@ -0,0 +1,7 @@
+use miaosha;
+
+-- 最高价格: 提交订单前查询到的价格超过它就不购买
+alter table shopping_cart add column max_price varchar(20) null default null
+    comment '可接受的最高价格, 空表示不限制' after `cur_price`;
+alter table shopping_cart modify column status varchar(20) not null
+    comment '状态:ready, yuyueing, purchasing, success, fail, dry-run, price-high';
@@ -0,0 +1,24 @@
+use tide::Server;
+use tide::StatusCode;
+use crate::utils::check;
+use crate::services::category;
+use crate::*;
+
+
+pub fn api(app: &mut Server<AppState>)
+{
+    app.at("/api/category")
+        .get(list_categories);
+}
+
+
+async fn list_categories(req: MRequest) -> tide::Result
+{
+    check::check_token_header(&req)?;
+    let data = category::list_categories()
+        .await.map_err(|e| e.into_tide_error(false))?;
+    Ok(tide::Response::builder(StatusCode::Ok)
+        .content_type(tide::http::mime::JSON)
+        .body(serde_json::json!(data))
+        .build())
+}
@@ -1,3 +1,5 @@
 pub mod user;
 pub mod shopping_cart;
+pub mod workspace;
+pub mod category;
 
@@ -19,6 +19,10 @@ pub fn api(app: &mut Server<AppState>)
         .delete(delete_goods_cart);
     service.at("/yuyue/:id")
         .put(update_yuyue_goods_cart);
+    service.at("/claim/:id")
+        .post(claim_goods_cart)
+        .put(renew_goods_cart_claim)
+        .delete(release_goods_cart_claim);
     app.at("/api/shopping_cart")
         .get(list_goods_cart)
         .post(add_goods_cart)
@@ -57,6 +61,36 @@ async fn update_yuyue_goods_cart(mut req: MRequest) -> tide::Result
     Ok("".into())
 }
 
+async fn claim_goods_cart(mut req: MRequest) -> tide::Result
+     {
+    let id = check::check_id(&req)?;
+    let code = check::check_token_header(&req)?;
+    let query: ClaimGoodsCartReq = req.body_json().await?;
+    shopping_cart::claim_goods_cart(code, id, query)
+        .await.map_err(|e| e.into_tide_error(false))?;
+    Ok("".into())
+}
+
+async fn renew_goods_cart_claim(mut req: MRequest) -> tide::Result
+     {
+    let id = check::check_id(&req)?;
+    let code = check::check_token_header(&req)?;
+    let query: ClaimGoodsCartReq = req.body_json().await?;
+    shopping_cart::renew_goods_cart_claim(code, id, query)
+        .await.map_err(|e| e.into_tide_error(false))?;
+    Ok("".into())
+}
+
+async fn release_goods_cart_claim(mut req: MRequest) -> tide::Result
+     {
+    let id = check::check_id(&req)?;
+    let code = check::check_token_header(&req)?;
+    let query: ClaimGoodsCartReq = req.body_json().await?;
+    shopping_cart::release_goods_cart_claim(code, id, query)
+        .await.map_err(|e| e.into_tide_error(false))?;
+    Ok("".into())
+}
+
 async fn update_goods_cart_purchase_url(mut req: MRequest) -> tide::Result
      {
     let code = check::check_token_header(&req)?;
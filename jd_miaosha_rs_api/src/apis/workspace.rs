@@ -0,0 +1,86 @@
+use tide::Server;
+use tide::StatusCode;
+use crate::req::*;
+use crate::utils::check;
+use crate::services::workspace;
+use crate::*;
+
+
+pub fn api(app: &mut Server<AppState>)
+{
+    let mut member_service = tide::with_state(app.state().clone());
+    member_service
+        .at("/:id")
+        .put(update_member)
+        .delete(remove_member);
+    app.at("/api/workspace")
+        .get(workspace_info)
+        .post(create_workspace);
+    app.at("/api/workspace/invitation").post(create_invitation);
+    app.at("/api/workspace/join").post(join_workspace);
+    app.at("/api/workspace/member").nest(member_service);
+}
+
+
+async fn create_workspace(mut req: MRequest) -> tide::Result
+{
+    let code = check::check_token_header(&req)?;
+    let query: CreateWorkspaceReq = req.body_json().await?;
+    let data = workspace::create_workspace(code, query)
+        .await.map_err(|e| e.into_tide_error(false))?;
+    Ok(tide::Response::builder(StatusCode::Ok)
+        .content_type(tide::http::mime::JSON)
+        .body(serde_json::json!(data))
+        .build())
+}
+
+async fn workspace_info(req: MRequest) -> tide::Result
+{
+    let code = check::check_token_header(&req)?;
+    let data = workspace::workspace_info(code)
+        .await.map_err(|e| e.into_tide_error(false))?;
+    Ok(tide::Response::builder(StatusCode::Ok)
+        .content_type(tide::http::mime::JSON)
+        .body(data)
+        .build())
+}
+
+async fn create_invitation(mut req: MRequest) -> tide::Result
+{
+    let code = check::check_token_header(&req)?;
+    let query: CreateInvitationReq = req.body_json().await?;
+    let data = workspace::create_invitation(code, query)
+        .await.map_err(|e| e.into_tide_error(false))?;
+    Ok(tide::Response::builder(StatusCode::Ok)
+        .content_type(tide::http::mime::JSON)
+        .body(data)
+        .build())
+}
+
+async fn join_workspace(mut req: MRequest) -> tide::Result
+{
+    let code = check::check_token_header(&req)?;
+    let query: JoinWorkspaceReq = req.body_json().await?;
+    workspace::join_workspace(code, query)
+        .await.map_err(|e| e.into_tide_error(false))?;
+    Ok("".into())
+}
+
+async fn update_member(mut req: MRequest) -> tide::Result
+{
+    let id = check::check_id(&req)?;
+    let code = check::check_token_header(&req)?;
+    let query: UpdateMemberReq = req.body_json().await?;
+    workspace::update_member(code, id, query)
+        .await.map_err(|e| e.into_tide_error(false))?;
+    Ok("".into())
+}
+
+async fn remove_member(req: MRequest) -> tide::Result
+{
+    let id = check::check_id(&req)?;
+    let code = check::check_token_header(&req)?;
+    workspace::remove_member(code, id)
+        .await.map_err(|e| e.into_tide_error(false))?;
+    Ok("".into())
+}
@@ -71,8 +71,16 @@ impl MiaoshaError {
                         | CookieExpired(_)
                         | ActivationCodeExpired(_) => TideError::from_str(StatusCode::Unauthorized, emsg),
 
+                        WorkspacePermissionDenied(_) => TideError::from_str(StatusCode::Forbidden, emsg),
+
+                        CartGoodsClaimed(_) => TideError::from_str(StatusCode::Conflict, emsg),
+
                         CreateActivationCode(_)
                         | ExceedMaxAccountLimits(_)
+                        | InvalidInvitation(_)
+                        | AlreadyInWorkspace(_)
+                        | NotInWorkspace(_)
+                        | InvalidCategory(_)
                         => {
                             TideError::from_str(StatusCode::BadRequest, emsg)
                         }
@@ -113,6 +121,24 @@ pub enum EOpError {
 
     #[error("Create activation code failed, level {0}!")]
     CreateActivationCode(u8),
+
+    #[error("User {0} has no permission for this workspace operation!")]
+    WorkspacePermissionDenied(u32), // 团队内的角色权限不足
+
+    #[error("Invitation code[{0}] is invalid or expired!")]
+    InvalidInvitation(String),
+
+    #[error("User {0} already belongs to a workspace!")]
+    AlreadyInWorkspace(u32),
+
+    #[error("User {0} doesn't belong to any workspace!")]
+    NotInWorkspace(u32),
+
+    #[error("Category {0} doesn't exist!")]
+    InvalidCategory(u8),
+
+    #[error("Cart goods {0} is being purchased by another client!")]
+    CartGoodsClaimed(u32), // 商品的抢购租约被其他客户端持有
 }
 
 
@@ -74,7 +74,7 @@ async fn init() -> Config {
     let mut opts = DBPoolOptions::default();
     opts.max_connections = 50;
     DB_CLIENT.link_opt(config.db_uri.as_str(), &opts).await.unwrap();
-    // log4rs::init_file(log_yaml, Default::default()).unwrap();
+    log4rs::init_file(log_yaml, Default::default()).unwrap();
     config
 }
 
@@ -87,11 +87,21 @@ async fn get_redis_conn() -> redis::RedisResult<redis::aio::MultiplexedConnectio
     }
 }
 
+// 定期回收过期的抢购租约
+async fn expire_leases_loop() {
+    loop {
+        match services::shopping_cart::expire_goods_cart_leases().await {
+            Ok(n) if n > 0 => info!("回收过期的抢购租约:{}个", n),
+            Ok(_) => {}
+            Err(e) => error!("回收抢购租约失败:{:?}", e),
+        }
+        async_std::task::sleep(std::time::Duration::from_secs(30)).await;
+    }
+}
+
 pub async fn start_server() -> tide::Result<()> {
     let config = init().await;
     let bind_addr = format!("{}:{}", config.ip.as_str(), config.port);
-    // tide 的 femme 不能跟log4rs集成，故暂时去掉以下的配置
-    tide::log::with_level(tide::log::LevelFilter::Debug);
     // 把 - 替换为 :
     let state = Arc::new(RwLock::new(
         State {
@@ -102,10 +112,14 @@ pub async fn start_server() -> tide::Result<()> {
                 .collect(),
             vip_level_users: config.vip_level_users,
         }));
+    async_std::task::spawn(expire_leases_loop());
     let mut app = tide::with_state(state);
-    app.with(tide::log::LogMiddleware::new());
+    // tide 自带的 femme 日志不能跟 log4rs 集成, 访问日志由 AccessLogMiddleware 输出
+    app.with(utils::access_log::AccessLogMiddleware);
     apis::user::api(&mut app);
     apis::shopping_cart::api(&mut app);
+    apis::workspace::api(&mut app);
+    apis::category::api(&mut app);
     app.listen(bind_addr.as_str()).await?;
     Ok(())
 }
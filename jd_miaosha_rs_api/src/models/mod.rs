@@ -48,6 +48,7 @@ pub struct User {
 pub struct PlatformAccount {
     pub id: Option<IDType>,
     pub user_id: IDType,
+    pub workspace_id: IDType,
     pub account: String,
     #[serde(with = "Platform")]
     pub platform: Platform,
@@ -97,10 +98,11 @@ pub struct VipHistory {
 pub struct ShoppingCart {
     pub id: Option<IDType>,
     pub user_id: IDType,
+    pub workspace_id: IDType,
     #[serde(with = "Platform")]
     pub platform: Platform,
-    #[serde(with = "Category")]
-    pub category: Category,
+    // 商品分类, 对应 goods_category 表的 id
+    pub category: u8,
     pub sku: String,
     pub name: String,
     pub purchase_num: u32,
@@ -120,11 +122,21 @@ pub struct ShoppingCart {
     ]
     pub yuyue_end_dt: Option<PKDateTime>,
     pub status: String,
+    pub lease_owner: String,
+    #[serde(
+    serialize_with = "update_create_dt_date_format::serialize_none",
+    deserialize_with = "update_create_dt_date_format::deserialize_pk_dt")
+    ]
+    pub lease_expire_dt: Option<PKDateTime>,
     pub ori_price: String,
     pub cur_price: String,
+    // 可接受的最高价格, None 表示不限制
+    pub max_price: Option<String>,
     pub purchase_url: String,
     pub purchase_type: String,
     pub is_stock: u8,
+    // 试运行: 走完下单流程但不提交订单
+    pub dry_run: u8,
     pub is_delete: u8,
     #[serde(with = "update_create_dt_date_format")]
     pub create_time: Option<PKDateTime>,
@@ -132,3 +144,121 @@ pub struct ShoppingCart {
     pub update_time: Option<PKDateTime>,
 }
 
+
+#[derive(Default, Debug, CRUDTable, Clone, Serialize, Deserialize)]
+pub struct Workspace {
+    pub id: Option<IDType>,
+    pub name: String,
+    pub owner_id: IDType,
+    #[serde(with = "update_create_dt_date_format")]
+    pub create_time: Option<PKDateTime>,
+    #[serde(with = "update_create_dt_date_format")]
+    pub update_time: Option<PKDateTime>,
+}
+
+#[derive(Default, Debug, CRUDTable, Clone, Serialize, Deserialize)]
+pub struct WorkspaceMember {
+    pub id: Option<IDType>,
+    pub workspace_id: IDType,
+    pub user_id: IDType,
+    #[serde(with = "WorkspaceRole")]
+    pub role: WorkspaceRole,
+    #[serde(with = "update_create_dt_date_format")]
+    pub create_time: Option<PKDateTime>,
+    #[serde(with = "update_create_dt_date_format")]
+    pub update_time: Option<PKDateTime>,
+}
+
+#[derive(Default, Debug, CRUDTable, Clone, Serialize, Deserialize)]
+pub struct WorkspaceInvitation {
+    pub id: Option<IDType>,
+    pub workspace_id: IDType,
+    pub inviter_id: IDType,
+    pub invite_code: String,
+    #[serde(with = "WorkspaceRole")]
+    pub role: WorkspaceRole,
+    pub status: u8,
+    #[serde(with = "update_create_dt_date_format")]
+    pub expire_dt: Option<PKDateTime>,
+    #[serde(with = "update_create_dt_date_format")]
+    pub create_time: Option<PKDateTime>,
+    #[serde(with = "update_create_dt_date_format")]
+    pub update_time: Option<PKDateTime>,
+}
+
+#[derive(Default, Debug, CRUDTable, Clone, Serialize, Deserialize)]
+pub struct GoodsCategory {
+    pub id: Option<u8>,
+    pub name: String,
+    // 用于自动推断分类的关键字, 逗号分隔
+    pub keywords: String,
+    pub sort: u32,
+    #[serde(with = "update_create_dt_date_format")]
+    pub create_time: Option<PKDateTime>,
+    #[serde(with = "update_create_dt_date_format")]
+    pub update_time: Option<PKDateTime>,
+}
+
+impl GoodsCategory {
+    // 历史数据都是 0
+    pub const COMPUTER: u8 = 0;
+    // 推断不出来的商品
+    pub const OTHER: u8 = 255;
+
+    pub fn keyword_list(&self) -> impl Iterator<Item=&str> {
+        self.keywords.split(',')
+            .map(|k| k.trim())
+            .filter(|k| k.len() > 0)
+    }
+}
+
+// 当前激活码能看到的数据范围：加入团队后看团队的数据，否则只看自己的数据
+#[derive(Clone, Debug)]
+pub struct OwnerScope {
+    pub user: CachedUserInfo,
+    pub workspace_id: IDType,
+    pub role: WorkspaceRole,
+}
+
+impl OwnerScope {
+    // 用于 sql 过滤的列名和值; 个人数据还要求 workspace_id = 0, 退出团队后就碰不到团队里的数据了
+    pub fn columns(&self) -> Vec<(&'static str, IDType)> {
+        if self.workspace_id > 0 {
+            vec![("workspace_id", self.workspace_id)]
+        } else {
+            vec![("user_id", self.user.id), ("workspace_id", 0)]
+        }
+    }
+
+    // columns 拼成的 where 条件, 参数用 where_args 按顺序传
+    pub fn where_sql(&self) -> String {
+        self.columns()
+            .iter()
+            .map(|(col, _)| format!("{}=?", col))
+            .collect::<Vec<_>>()
+            .join(" and ")
+    }
+
+    pub fn where_args(&self) -> Vec<serde_json::Value> {
+        self.columns()
+            .into_iter()
+            .map(|(_, v)| serde_json::json!(v))
+            .collect()
+    }
+
+    pub fn check_write(&self) -> Result<()> {
+        if self.role.can_write() {
+            Ok(())
+        } else {
+            Err(MiaoshaError::OpError(EOpError::WorkspacePermissionDenied(self.user.id)))
+        }
+    }
+
+    pub fn check_manage(&self) -> Result<()> {
+        if self.workspace_id > 0 && self.role.can_manage() {
+            Ok(())
+        } else {
+            Err(MiaoshaError::OpError(EOpError::WorkspacePermissionDenied(self.user.id)))
+        }
+    }
+}
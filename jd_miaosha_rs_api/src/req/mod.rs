@@ -1,10 +1,14 @@
 mod user;
 mod shopping_cart;
+mod workspace;
 
-use validator::Validate;
+use std::convert::TryFrom;
+use validator::{Validate, ValidationError};
 use serde::Deserialize;
+use crate::Platform;
 pub use user::*;
 pub use shopping_cart::*;
+pub use workspace::*;
 
 #[derive(Clone, Validate, Deserialize, Debug)]
 pub struct KeyWordPageReq {
@@ -14,4 +18,29 @@ pub struct KeyWordPageReq {
     pub page_no: Option<u64>,
     #[validate(range(min = 5, max = 30))]
     pub page_size: Option<u64>,
+    // 按平台过滤, 不传则查询全部平台
+    #[validate(custom = "validate_platform")]
+    pub platform: Option<u8>,
+    // 按分类过滤, 需要是 goods_category 里存在的分类
+    pub category: Option<u8>,
+}
+
+pub(crate) fn validate_platform(platform: u8) -> Result<(), ValidationError> {
+    match Platform::try_from(platform) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(ValidationError::new("unknown platform")),
+    }
+}
+
+// 价格必须是大于 0 的数字, 如: 99.00
+pub(crate) fn validate_price(price: &str) -> Result<(), ValidationError> {
+    match price.trim().parse::<f64>() {
+        Ok(p) if p > 0.0 => Ok(()),
+        _ => Err(ValidationError::new("invalid price")),
+    }
+}
+
+// 不传平台时默认是京东
+pub(crate) fn platform_or_default(platform: Option<u8>) -> Platform {
+    platform.and_then(|p| Platform::try_from(p).ok()).unwrap_or_default()
 }
@@ -2,9 +2,17 @@ use serde::Deserialize;
 use validator::{Validate, ValidationError};
 use crate::{Platform, IDType, PKDateTime};
 use crate::utils::update_create_dt_date_format;
+use super::{validate_platform, validate_price};
 
 #[derive(Clone, Validate, Deserialize)]
 pub struct AddGoodsCartReq {
+    #[validate(custom = "validate_platform")]
+    pub platform: Option<u8>,
+    // 不传时根据 category_hint 和商品名称推断
+    pub category: Option<u8>,
+    // 商品详情页上的分类名称, 如: 手机通讯,手机,手机
+    #[validate(length(max = 256))]
+    pub category_hint: Option<String>,
     #[validate(length(min = 1))]
     pub sku: String,
     #[validate(length(min = 1))]
@@ -23,12 +31,18 @@ pub struct AddGoodsCartReq {
     pub ori_price: String,
     #[validate(length(min = 1))]
     pub cur_price: String,
+    // 可接受的最高价格, 不传表示不限制
+    #[serde(default)]
+    #[validate(custom = "validate_price")]
+    pub max_price: Option<String>,
     pub purchase_url: String,
     pub purchase_type: String,
 }
 
 #[derive(Clone, Validate, Deserialize)]
 pub struct UpdateGoodsCartUrlReq {
+    #[validate(custom = "validate_platform")]
+    pub platform: Option<u8>,
     #[validate(length(min = 1))]
     pub sku: String,
     #[validate(length(min = 1))]
@@ -41,19 +55,32 @@ fn validate_req(req: &UpdateGoodsCartReq) -> Result<(), ValidationError> {
             return Err(ValidationError::new("Please type the yuyue_dt!"))
         }
     }
+    if req.op == 14 || req.op == 15 {
+        if req.cur_price.is_none() {
+            return Err(ValidationError::new("Please type the cur_price!"))
+        }
+    }
     Ok(())
 }
 
-#[derive(Copy, Clone, Validate, Deserialize)]
+#[derive(Clone, Validate, Deserialize)]
 #[validate(schema(function = "validate_req", skip_on_field_errors = false))]
 pub struct UpdateGoodsCartReq {
     #[validate(range(min = 1))]
-    pub op:u8, // 操作码: 1,加；2减；3，立即购买；4，预约购买 5, 成功; 6, 失败; 7, 预约中
+    pub op:u8, // 操作码: 1,加；2减；3，立即购买；4，预约购买 5, 成功; 6, 失败; 7, 预约中; 8, 有货; 9, 无货; 10, 试运行结束; 11, 打开试运行; 12, 关闭试运行; 13, 设置最高价格; 14, 更新价格; 15, 价格太高
     #[serde(
     serialize_with = "update_create_dt_date_format::serialize_none",
     deserialize_with = "update_create_dt_date_format::deserialize_pk_dt")
     ]
     pub yuyue_dt: Option<PKDateTime>,
+    // op 为 13 时的最高价格, 不传表示不限制
+    #[serde(default)]
+    #[validate(custom = "validate_price")]
+    pub max_price: Option<String>,
+    // op 为 14、15 时查询到的最新价格
+    #[serde(default)]
+    #[validate(custom = "validate_price")]
+    pub cur_price: Option<String>,
 }
 
 
@@ -84,3 +111,14 @@ pub struct BatchDeleteGoodsCartReq {
     #[validate(length(min = 1))]
     pub ids: Option<Vec<IDType>>,
 }
+
+
+#[derive(Clone, Validate, Deserialize, Debug)]
+pub struct ClaimGoodsCartReq {
+    // 持有租约的客户端标识
+    #[validate(length(min = 1, max = 64))]
+    pub owner: String,
+    // 租约时长(秒), 默认 120
+    #[validate(range(min = 5, max = 600))]
+    pub lease_seconds: Option<i64>,
+}
@@ -1,6 +1,7 @@
 use serde::Deserialize;
 use validator::{Validate, ValidationError};
 use crate::{Platform, IDType};
+use super::validate_platform;
 
 #[derive(Copy, Clone, Validate, Deserialize)]
 pub struct CreateActivateCodeReq {
@@ -32,6 +33,8 @@ pub struct RefreshReq {
 
 #[derive(Clone, Validate, Deserialize)]
 pub struct AddAccountReq {
+    #[validate(custom = "validate_platform")]
+    pub platform: Option<u8>,
     #[validate(length(min = 2))]
     pub account: String,
     #[validate(length(min = 6))]
@@ -0,0 +1,27 @@
+use serde::Deserialize;
+use validator::Validate;
+
+#[derive(Clone, Validate, Deserialize)]
+pub struct CreateWorkspaceReq {
+    #[validate(length(min = 1, max = 64))]
+    pub name: String,
+}
+
+#[derive(Copy, Clone, Validate, Deserialize)]
+pub struct CreateInvitationReq {
+    // 被邀请人加入后的角色: 1,管理员；2,成员；3,只读
+    #[validate(range(min = 1, max = 3))]
+    pub role: u8,
+}
+
+#[derive(Clone, Validate, Deserialize)]
+pub struct JoinWorkspaceReq {
+    #[validate(length(equal = 32))]
+    pub invite_code: String,
+}
+
+#[derive(Copy, Clone, Validate, Deserialize)]
+pub struct UpdateMemberReq {
+    #[validate(range(min = 1, max = 3))]
+    pub role: u8,
+}
@@ -0,0 +1,63 @@
+use log::debug;
+use crate::DB_CLIENT;
+use crate::error::*;
+use crate::models::*;
+use rbatis::crud::CRUD;
+
+pub async fn list_categories() -> Result<Vec<GoodsCategory>> {
+    let w = DB_CLIENT.new_wrapper()
+        .order_by(true, &["sort", "id"]);
+    let data = DB_CLIENT.fetch_list_by_wrapper::<GoodsCategory>("", &w).await?;
+    Ok(data)
+}
+
+pub async fn check_category(category: u8) -> Result<()> {
+    let w = DB_CLIENT.new_wrapper()
+        .eq("id", category)
+        .limit(1);
+    let data = DB_CLIENT.fetch_by_wrapper::<Option<GoodsCategory>>("", &w).await?;
+    match data {
+        Some(_) => Ok(()),
+        None => Err(MiaoshaError::OpError(EOpError::InvalidCategory(category))),
+    }
+}
+
+// 文本里最先出现关键字的分类, 同一位置按分类的排序;
+// 如手机名称里常带 "8GB内存", 不能因为电脑办公排在前面就归到电脑办公
+fn match_category<'a>(categories: &'a [GoodsCategory], txt: &str) -> Option<&'a GoodsCategory> {
+    let txt = txt.to_lowercase();
+    categories.iter()
+        .filter_map(|c| {
+            c.keyword_list()
+                .filter_map(|k| txt.find(k.to_lowercase().as_str()))
+                .min()
+                .map(|pos| (pos, c))
+        })
+        .min_by_key(|(pos, _)| *pos)
+        .map(|(_, c)| c)
+}
+
+// 按关键字推断商品分类: 有商品详情页的分类(面包屑)时以它为准, 匹配不上再匹配商品名称
+pub async fn infer_category(name: &str, hint: Option<&str>) -> Result<u8> {
+    let categories = list_categories().await?;
+    let found = match_category(&categories, hint.unwrap_or(""))
+        .or_else(|| match_category(&categories, name));
+    match found {
+        Some(c) => {
+            debug!("infer category {:?} for {}", c.id, name);
+            Ok(c.id.unwrap_or(GoodsCategory::OTHER))
+        }
+        None => Ok(GoodsCategory::OTHER),
+    }
+}
+
+// 显式指定的分类需要存在, 否则根据商品信息推断
+pub async fn resolve_category(category: Option<u8>, name: &str, hint: Option<&str>) -> Result<u8> {
+    match category {
+        Some(c) => {
+            check_category(c).await?;
+            Ok(c)
+        }
+        None => infer_category(name, hint).await,
+    }
+}
@@ -1,4 +1,6 @@
 pub mod user;
 pub mod shopping_cart;
+pub mod workspace;
+pub mod category;
 
 
@@ -4,6 +4,7 @@ use log::{error, debug};
 use crate::{get_redis_conn, DB_CLIENT, IDType};
 use crate::error::*;
 use crate::utils::{token, datetime_fmt};
+use crate::services::{workspace, category};
 use crate::models::*;
 use crate::req::*;
 use crate::*;
@@ -18,6 +19,8 @@ impl ShoppingCart {
     pub const STATUS_PURCHASING: &'static str = "purchasing";
     pub const STATUS_SUCCESS: &'static str = "success";
     pub const STATUS_FAIL: &'static str = "fail";
+    pub const STATUS_DRY_RUN: &'static str = "dry-run";
+    pub const STATUS_PRICE_HIGH: &'static str = "price-high";
 
     pub const OP_ADD: u8 = 1;
     pub const OP_SUB: u8 = 2;
@@ -29,21 +32,54 @@ impl ShoppingCart {
     pub const OP_IN_STOCK: u8 = 8;
     // 有货
     pub const OP_OUT_OF_STOCK: u8 = 9; // 无货
+    // 试运行结束, 没有真正提交订单
+    pub const OP_PURCHASE_DRY_RUN: u8 = 10;
+    // 打开、关闭商品的试运行
+    pub const OP_DRY_RUN_ON: u8 = 11;
+    pub const OP_DRY_RUN_OFF: u8 = 12;
+    // 设置最高价格
+    pub const OP_MAX_PRICE: u8 = 13;
+    // 提交订单前查询到的最新价格
+    pub const OP_CUR_PRICE: u8 = 14;
+    // 最新价格超过了最高价格, 不购买
+    pub const OP_PRICE_HIGH: u8 = 15;
+
+    // 抢购租约的默认时长(秒)
+    pub const DEFAULT_LEASE_SECONDS: i64 = 120;
+    // 没有租约却一直处于 purchasing 状态的商品, 超过这个时间(秒)也会被回收
+    pub const STALE_PURCHASING_SECONDS: i64 = 600;
+    // 可以抢的状态, 已经抢购成功的不能再抢
+    pub const CLAIMABLE_STATUSES: [&'static str; 5] = [
+        Self::STATUS_READY,
+        Self::STATUS_PURCHASING,
+        Self::STATUS_FAIL,
+        Self::STATUS_DRY_RUN,
+        Self::STATUS_PRICE_HIGH,
+    ];
 }
 
 pub async fn add_goods_cart(code: String, req: AddGoodsCartReq) -> Result<()> {
     req.validate()?;
-    let u = token::get_cached_user(code.as_str(), false).await?;
-    let w = DB_CLIENT.new_wrapper()
-        .eq("sku", req.sku.as_str())
-        .eq("user_id", u.id);
+    let scope = workspace::get_scope(code.as_str()).await?;
+    scope.check_write()?;
+    let platform = platform_or_default(req.platform);
+    let category = category::resolve_category(
+        req.category, req.name.as_str(), req.category_hint.as_deref()).await?;
+    let mut w = DB_CLIENT.new_wrapper()
+        .eq("platform", platform.into_u8())
+        .eq("sku", req.sku.as_str());
+    for (col, owner_id) in scope.columns() {
+        w = w.eq(col, owner_id);
+    }
     let tx = DB_CLIENT.begin_tx().await?;
     let rdata = DB_CLIENT.fetch_by_wrapper::<Option<ShoppingCart>>(tx.as_str(), &w).await?;
     if let Some(mut data) = rdata {
         data.name = req.name;
+        data.category = category;
         data.purchase_num = req.purchase_num;
         data.ori_price = req.ori_price;
         data.cur_price = req.cur_price;
+        data.max_price = req.max_price;
         data.is_delete = 0;
         data.is_stock = req.is_stock;
         data.yuyue_dt = req.yuyue_dt;
@@ -53,18 +89,24 @@ pub async fn add_goods_cart(code: String, req: AddGoodsCartReq) -> Result<()> {
         data.purchase_type = req.purchase_type;
         data.update_time = Some(PKLocal::now());
         data.status = "ready".to_owned();
+        data.lease_owner.clear();
+        data.lease_expire_dt = None;
         DB_CLIENT.update_by_id::<ShoppingCart>(tx.as_str(), &mut data).await?;
         DB_CLIENT.commit(tx.as_str()).await?;
         return Ok(());
     }
     let mut pa = ShoppingCart {
-        user_id: u.id,
+        user_id: scope.user.id,
+        workspace_id: scope.workspace_id,
+        platform,
+        category,
         sku: req.sku,
         name: req.name,
         purchase_num: req.purchase_num,
         purchase_type: req.purchase_type,
         ori_price: req.ori_price,
         cur_price: req.cur_price,
+        max_price: req.max_price,
         purchase_url: req.purchase_url,
         is_stock: req.is_stock,
         status: ShoppingCart::STATUS_READY.to_string(),
@@ -81,13 +123,15 @@ pub async fn add_goods_cart(code: String, req: AddGoodsCartReq) -> Result<()> {
 
 pub async fn update_goods_cart(code: String, id: IDType, req: UpdateGoodsCartReq) -> Result<()> {
     req.validate()?;
-    let u = token::get_cached_user(code.as_str(), false).await?;
+    let scope = workspace::get_scope(code.as_str()).await?;
+    scope.check_write()?;
     let mut args = Vec::with_capacity(3);
     let mut sqlv = Vec::with_capacity(3);
     let mut sql = String::with_capacity(256);
     sql.push_str("update shopping_cart set ");
-    let mut where_sql = " where is_delete = 0 and user_id=? and id= ? ".to_string();
-    let mut where_args = vec![json!(u.id), json!(id)];
+    let mut where_sql = format!(" where is_delete = 0 and {} and id= ? ", scope.where_sql());
+    let mut where_args = scope.where_args();
+    where_args.push(json!(id));
     match req.op {
         ShoppingCart::OP_ADD => {
             sql.push_str(" purchase_num = purchase_num + 1,");
@@ -122,6 +166,27 @@ pub async fn update_goods_cart(code: String, id: IDType, req: UpdateGoodsCartReq
         ShoppingCart::OP_OUT_OF_STOCK => {
             sql.push_str(" is_stock = 0,");
         }
+        ShoppingCart::OP_PURCHASE_DRY_RUN => {
+            sql.push_str(" status = 'dry-run', ");
+        }
+        ShoppingCart::OP_DRY_RUN_ON => {
+            sql.push_str(" dry_run = 1,");
+        }
+        ShoppingCart::OP_DRY_RUN_OFF => {
+            sql.push_str(" dry_run = 0,");
+        }
+        ShoppingCart::OP_MAX_PRICE => {
+            sql.push_str(" max_price = ?,");
+            args.push(json!(req.max_price.as_ref().map(|p| p.trim())));
+        }
+        ShoppingCart::OP_CUR_PRICE => {
+            sql.push_str(" cur_price = ?,");
+            args.push(json!(req.cur_price.as_ref().unwrap().trim()));
+        }
+        ShoppingCart::OP_PRICE_HIGH => {
+            sql.push_str(" status = 'price-high', cur_price = ?,");
+            args.push(json!(req.cur_price.as_ref().unwrap().trim()));
+        }
         _ => {
             unreachable!()
         }
@@ -148,73 +213,92 @@ pub async fn update_yuyue_goods_cart(code: String, id: IDType, req: UpdateYuyueG
     if req.op != ShoppingCart::OP_YUYUE {
         return Ok(());
     }
-    let u = token::get_cached_user(code.as_str(), false).await?;
-    let sql = "update shopping_cart \
+    let scope = workspace::get_scope(code.as_str()).await?;
+    scope.check_write()?;
+    let sql = format!("update shopping_cart \
             set  yuyue_dt=?, status='ready', yuyue_start_dt=?, yuyue_end_dt=?, update_time=? \
-            where is_delete = 0 and user_id=? and id= ? ;";
-    let args = vec![
+            where is_delete = 0 and {} and id= ? ;", scope.where_sql());
+    let mut args = vec![
         json!(datetime_fmt(&req.yuyue_dt.unwrap())),
         json!(datetime_fmt(&req.yuyue_start_dt.unwrap())),
         json!(datetime_fmt(&req.yuyue_end_dt.unwrap())),
         json!(datetime_fmt(&PKLocal::now())),
-        json!(u.id),
-        json!(id),
     ];
-    DB_CLIENT.exec_prepare("", sql, &args).await?;
+    args.extend(scope.where_args());
+    args.push(json!(id));
+    DB_CLIENT.exec_prepare("", sql.as_str(), &args).await?;
     Ok(())
 }
 
 pub async fn update_cart_goods_purchase_url(code: String, req: UpdateGoodsCartUrlReq) -> Result<()> {
     req.validate()?;
-    let u = token::get_cached_user(code.as_str(), false).await?;
-    let sql = "update shopping_cart set purchase_url=?, update_time=?  where user_id=? and sku =? and is_delete=0 ";
-    let args = vec![json!(req.purchase_url), json!(datetime_fmt(&PKLocal::now())), json!(u.id), json!(req.sku)];
+    let scope = workspace::get_scope(code.as_str()).await?;
+    scope.check_write()?;
+    let platform = platform_or_default(req.platform);
+    let sql = format!("update shopping_cart set purchase_url=?, update_time=?  where {} and platform=? and sku =? and is_delete=0 ", scope.where_sql());
+    let mut args = vec![json!(req.purchase_url), json!(datetime_fmt(&PKLocal::now()))];
+    args.extend(scope.where_args());
+    args.append(&mut vec![json!(platform.into_u8()), json!(req.sku)]);
     let tx = DB_CLIENT.begin_tx().await?;
-    DB_CLIENT.exec_prepare(tx.as_str(), sql, &args).await?;
+    DB_CLIENT.exec_prepare(tx.as_str(), sql.as_str(), &args).await?;
     DB_CLIENT.commit(tx.as_str()).await?;
     Ok(())
 }
 
 pub async fn delete_goods_cart(code: String, id: IDType) -> Result<()> {
-    let u = token::get_cached_user(code.as_str(), false).await?;
+    let scope = workspace::get_scope(code.as_str()).await?;
+    scope.check_write()?;
     // 物理删除
     // let sql = "update shopping_cart set is_delete=1 where id = ?";
-    let sql = "delete from shopping_cart where id = ? and user_id = ?";
-    DB_CLIENT.exec_prepare("", sql, &vec![json!(id), json!(u.id)]).await?;
+    let sql = format!("delete from shopping_cart where id = ? and {}", scope.where_sql());
+    let mut args = vec![json!(id)];
+    args.extend(scope.where_args());
+    DB_CLIENT.exec_prepare("", sql.as_str(), &args).await?;
     Ok(())
 }
 
 pub async fn batch_delete_goods_carts(code: String, req: BatchDeleteGoodsCartReq) -> Result<()> {
     req.validate()?;
-    let u = token::get_cached_user(code.as_str(), false).await?;
+    let scope = workspace::get_scope(code.as_str()).await?;
+    scope.check_write()?;
     // 物理删除
     let sql;
-    let args;
+    let mut args;
     if let Some(ref ids) = req.ids {
         // 删除某些
         // sql = "update shopping_cart set is_delete=1 where id in (?) and user_id = ?";
-        sql = "delete from shopping_cart where id in (?) and user_id = ?";
-        args = vec![json!(ids.iter().map(|id|id.to_string()).collect::<Vec<String>>().join(",")),
-                    json!(u.id)];
+        sql = format!("delete from shopping_cart where id in (?) and {}", scope.where_sql());
+        args = vec![json!(ids.iter().map(|id|id.to_string()).collect::<Vec<String>>().join(","))];
+        args.extend(scope.where_args());
     } else {
         // None时删除全部
         // sql = "update shopping_cart set is_delete=1 where user_id = ?";
-        sql = "delete from shopping_cart where user_id = ?";
-        args = vec![json!(u.id)];
+        sql = format!("delete from shopping_cart where {}", scope.where_sql());
+        args = scope.where_args();
     }
-    DB_CLIENT.exec_prepare("", sql, &args).await?;
+    DB_CLIENT.exec_prepare("", sql.as_str(), &args).await?;
     Ok(())
 }
 
 pub async fn list_goods_carts(code: String, req: KeyWordPageReq) -> Result<Page<ShoppingCart>> {
     req.validate()?;
-    let u = token::get_cached_user(code.as_str(), false).await?;
+    let scope = workspace::get_scope(code.as_str()).await?;
     let page_size = req.page_size.unwrap_or(50);
     let page_no = req.page_no.unwrap_or(1);
     let page_req = PageRequest::new(page_no, page_size);//分页请求，页码，条数
-    let mut w = DB_CLIENT.new_wrapper()
-        .eq("user_id", u.id)
-        .eq("is_delete", 0);
+    let mut w = DB_CLIENT.new_wrapper();
+    for (col, owner_id) in scope.columns() {
+        w = w.eq(col, owner_id);
+    }
+    w = w.eq("is_delete", 0);
+    if let Some(platform) = req.platform {
+        w = w.eq("platform", platform);
+    }
+    if let Some(category) = req.category {
+        // 不存在的分类直接报错, 不返回空列表
+        category::check_category(category).await?;
+        w = w.eq("category", category);
+    }
     debug!("{:?}", req);
     if let Some(ref kw) = req.key_word {
         let kw = kw.trim();
@@ -231,4 +315,92 @@ pub async fn list_goods_carts(code: String, req: KeyWordPageReq) -> Result<Page<
     let data: Page<ShoppingCart> = DB_CLIENT.fetch_page_by_wrapper(
         "", &w, &page_req).await?;
     Ok(data)
-}
\ No newline at end of file
+}
+
+
+pub async fn claim_goods_cart(code: String, id: IDType, req: ClaimGoodsCartReq) -> Result<()> {
+    req.validate()?;
+    let scope = workspace::get_scope(code.as_str()).await?;
+    scope.check_write()?;
+    let now = PKLocal::now();
+    let lease_seconds = req.lease_seconds.unwrap_or(ShoppingCart::DEFAULT_LEASE_SECONDS);
+    let expire_dt = now.checked_add_signed(Duration::seconds(lease_seconds)).unwrap_or(now);
+    // 状态可以抢, 并且没有租约、租约已过期或者租约本来就是自己的, 才能抢到; 单条 update 保证原子性
+    let status_marks = vec!["?"; ShoppingCart::CLAIMABLE_STATUSES.len()].join(",");
+    let sql = format!("update shopping_cart \
+            set status='purchasing', lease_owner=?, lease_expire_dt=?, update_time=? \
+            where is_delete = 0 and {} and id=? and status in ({}) \
+            and (lease_owner='' or lease_owner=? or lease_expire_dt is null or lease_expire_dt < ?)",
+                      scope.where_sql(), status_marks);
+    let mut args = vec![
+        json!(req.owner.as_str()),
+        json!(datetime_fmt(&expire_dt)),
+        json!(datetime_fmt(&now)),
+    ];
+    args.extend(scope.where_args());
+    args.push(json!(id));
+    args.extend(ShoppingCart::CLAIMABLE_STATUSES.iter().map(|s| json!(s)));
+    args.push(json!(req.owner.as_str()));
+    args.push(json!(datetime_fmt(&now)));
+    let r = DB_CLIENT.exec_prepare("", sql.as_str(), &args).await?;
+    if r.rows_affected == 0 {
+        return Err(MiaoshaError::OpError(EOpError::CartGoodsClaimed(id)));
+    }
+    debug!("claimed:{}-{}, expire:{}", id, req.owner.as_str(), datetime_fmt(&expire_dt));
+    Ok(())
+}
+
+pub async fn renew_goods_cart_claim(code: String, id: IDType, req: ClaimGoodsCartReq) -> Result<()> {
+    req.validate()?;
+    let scope = workspace::get_scope(code.as_str()).await?;
+    let now = PKLocal::now();
+    let lease_seconds = req.lease_seconds.unwrap_or(ShoppingCart::DEFAULT_LEASE_SECONDS);
+    let expire_dt = now.checked_add_signed(Duration::seconds(lease_seconds)).unwrap_or(now);
+    let sql = format!("update shopping_cart set lease_expire_dt=?, update_time=? \
+            where is_delete = 0 and {} and id=? and lease_owner=?", scope.where_sql());
+    let mut args = vec![
+        json!(datetime_fmt(&expire_dt)),
+        json!(datetime_fmt(&now)),
+    ];
+    args.extend(scope.where_args());
+    args.append(&mut vec![json!(id), json!(req.owner.as_str())]);
+    let r = DB_CLIENT.exec_prepare("", sql.as_str(), &args).await?;
+    if r.rows_affected == 0 {
+        // 租约已经丢了(过期后被回收或被别人抢走)
+        return Err(MiaoshaError::OpError(EOpError::CartGoodsClaimed(id)));
+    }
+    Ok(())
+}
+
+pub async fn release_goods_cart_claim(code: String, id: IDType, req: ClaimGoodsCartReq) -> Result<()> {
+    req.validate()?;
+    let scope = workspace::get_scope(code.as_str()).await?;
+    // 释放时若还停留在 purchasing, 则回到 ready
+    let sql = format!("update shopping_cart \
+            set status=if(status='purchasing', 'ready', status), lease_owner='', lease_expire_dt=null, update_time=? \
+            where {} and id=? and lease_owner=?", scope.where_sql());
+    let mut args = vec![json!(datetime_fmt(&PKLocal::now()))];
+    args.extend(scope.where_args());
+    args.append(&mut vec![json!(id), json!(req.owner.as_str())]);
+    DB_CLIENT.exec_prepare("", sql.as_str(), &args).await?;
+    Ok(())
+}
+
+pub async fn expire_goods_cart_leases() -> Result<u64> {
+    let now = PKLocal::now();
+    let stale_dt = now.checked_sub_signed(Duration::seconds(ShoppingCart::STALE_PURCHASING_SECONDS)).unwrap_or(now);
+    // 租约过期, 或者没有租约但长时间卡在 purchasing 的商品(比如客户端崩溃了), 都回到 ready
+    let sql = "update shopping_cart \
+            set status='ready', lease_owner='', lease_expire_dt=null, update_time=? \
+            where status='purchasing' \
+            and ((lease_expire_dt is not null and lease_expire_dt < ?) \
+                or (lease_owner='' and update_time < ?))";
+    let args = vec![
+        json!(datetime_fmt(&now)),
+        json!(datetime_fmt(&now)),
+        json!(datetime_fmt(&stale_dt)),
+    ];
+    let r = DB_CLIENT.exec_prepare("", sql, &args).await?;
+    Ok(r.rows_affected)
+}
+
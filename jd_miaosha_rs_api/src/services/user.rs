@@ -4,6 +4,7 @@ use log::{error, debug};
 use crate::{get_redis_conn, DB_CLIENT, IDType};
 use crate::error::*;
 use crate::utils::{token, datetime_fmt};
+use crate::services::workspace;
 use crate::models::*;
 use crate::req::*;
 use crate::*;
@@ -149,11 +150,13 @@ pub async fn refresh(req: RefreshReq) -> Result<()> {
 
 pub async fn add_account(code: String, req: AddAccountReq, vip_users:&Vec<u32>) -> Result<()> {
     req.validate()?;
-    let u = token::get_cached_user(code.as_str(), false).await?;
+    let scope = workspace::get_scope(code.as_str()).await?;
+    scope.check_write()?;
+    let u = &scope.user;
     let max_account_num = u.vip_level.vip_level_max_accounts(vip_users);
     let tx = DB_CLIENT.begin_tx().await?;
-    let cnt_sql = "select count(*) as cnt from platform_account where user_id = ?";
-    let mut result: serde_json::Value = DB_CLIENT.fetch_prepare(tx.as_str(), cnt_sql, &vec![json!(u.id)]).await?;
+    let cnt_sql = format!("select count(*) as cnt from platform_account where {}", scope.where_sql());
+    let mut result: serde_json::Value = DB_CLIENT.fetch_prepare(tx.as_str(), cnt_sql.as_str(), &scope.where_args()).await?;
     let cnt;
     if result.is_array() {
         cnt = result[0].get("cnt").unwrap_or(&serde_json::json!(0)).as_i64().unwrap_or(0) as usize;
@@ -167,6 +170,8 @@ pub async fn add_account(code: String, req: AddAccountReq, vip_users:&Vec<u32>)
     }
     let pa = PlatformAccount {
         user_id: u.id,
+        workspace_id: scope.workspace_id,
+        platform: platform_or_default(req.platform),
         account: req.account,
         pwd: req.pwd,
         ..Default::default()
@@ -182,7 +187,8 @@ pub async fn add_account(code: String, req: AddAccountReq, vip_users:&Vec<u32>)
 
 pub async fn update_account(code: String, id: IDType, req: UpdateAccountReq) -> Result<()> {
     req.validate()?;
-    let u = token::get_cached_user(code.as_str(), false).await?;
+    let scope = workspace::get_scope(code.as_str()).await?;
+    scope.check_write()?;
     let mut args = Vec::with_capacity(3);
     let mut sql = String::with_capacity(256);
     sql.push_str("update platform_account set ");
@@ -214,8 +220,9 @@ pub async fn update_account(code: String, id: IDType, req: UpdateAccountReq) ->
         sqlv.push(" update_time=? ");
         args.push(json!(datetime_fmt(&PKLocal::now())));
         sql.push_str(sqlv.join(",").as_str());
-        sql.push_str(" where user_id=? and id= ?");
-        args.append(&mut vec![json!(u.id), json!(id)]);
+        sql.push_str(format!(" where {} and id= ?", scope.where_sql()).as_str());
+        args.extend(scope.where_args());
+        args.push(json!(id));
         DB_CLIENT.exec_prepare(tx.as_str(), sql.as_str(), &args).await?;
         DB_CLIENT.commit(tx.as_str()).await?;
     }
@@ -223,10 +230,14 @@ pub async fn update_account(code: String, id: IDType, req: UpdateAccountReq) ->
 }
 
 pub async fn delete_account(code: String, id: IDType) -> Result<()> {
-    let u = token::get_cached_user(code.as_str(), false).await?;
+    let scope = workspace::get_scope(code.as_str()).await?;
+    scope.check_write()?;
     // 物理删除账户
     let tx = DB_CLIENT.begin_tx().await?;
-    let w = DB_CLIENT.new_wrapper().eq("id", id).eq("user_id", u.id);
+    let mut w = DB_CLIENT.new_wrapper().eq("id", id);
+    for (col, owner_id) in scope.columns() {
+        w = w.eq(col, owner_id);
+    }
     DB_CLIENT.remove_by_wrapper::<PlatformAccount>(tx.as_str(), &w).await?;
     DB_CLIENT.commit(tx.as_str()).await?;
     Ok(())
@@ -234,16 +245,20 @@ pub async fn delete_account(code: String, id: IDType) -> Result<()> {
 
 pub async fn batch_delete_accounts(code: String, req: BatchDeleteAccountReq) -> Result<()> {
     req.validate()?;
-    let u = token::get_cached_user(code.as_str(), false).await?;
+    let scope = workspace::get_scope(code.as_str()).await?;
+    scope.check_write()?;
     // 物理删除账户
     let tx = DB_CLIENT.begin_tx().await?;
-    let w;
+    let mut w;
     if let Some(ref ids) = req.ids {
         // 删除某些
-        w = DB_CLIENT.new_wrapper().r#in("id", &ids).eq("user_id", u.id);
+        w = DB_CLIENT.new_wrapper().r#in("id", &ids);
     } else {
         // None时删除全部
-        w = DB_CLIENT.new_wrapper().eq("user_id", u.id);
+        w = DB_CLIENT.new_wrapper();
+    }
+    for (col, owner_id) in scope.columns() {
+        w = w.eq(col, owner_id);
     }
     DB_CLIENT.remove_by_wrapper::<PlatformAccount>(tx.as_str(), &w).await?;
     DB_CLIENT.commit(tx.as_str()).await?;
@@ -252,12 +267,17 @@ pub async fn batch_delete_accounts(code: String, req: BatchDeleteAccountReq) ->
 
 pub async fn list_accounts(code: String, req: KeyWordPageReq) -> Result<Page<PlatformAccount>> {
     req.validate()?;
-    let u = token::get_cached_user(code.as_str(), false).await?;
+    let scope = workspace::get_scope(code.as_str()).await?;
     let page_size = req.page_size.unwrap_or(450);
     let page_no = req.page_no.unwrap_or(1);
     let page_req = PageRequest::new(page_no, page_size);//分页请求，页码，条数
-    let mut w = DB_CLIENT.new_wrapper()
-        .eq("user_id", u.id);
+    let mut w = DB_CLIENT.new_wrapper();
+    for (col, owner_id) in scope.columns() {
+        w = w.eq(col, owner_id);
+    }
+    if let Some(platform) = req.platform {
+        w = w.eq("platform", platform);
+    }
     debug!("{:?}", req);
     if let Some(ref kw) = req.key_word {
         let kw = kw.trim();
@@ -0,0 +1,237 @@
+use validator::Validate;
+use log::{info, debug};
+use crate::{DB_CLIENT, IDType};
+use crate::error::*;
+use crate::utils::{token, datetime_fmt};
+use crate::models::*;
+use crate::req::*;
+use crate::*;
+use chrono::Duration;
+use rand::Rng;
+use serde_json::{Value, json};
+use rbatis::crud::CRUD;
+
+// 邀请码的有效期
+const INVITATION_EXPIRE_SECONDS: i64 = 3 * 24 * 3600;
+
+async fn fetch_member(user_id: IDType) -> Result<Option<WorkspaceMember>> {
+    let w = DB_CLIENT.new_wrapper()
+        .eq("user_id", user_id)
+        .limit(1);
+    let m = DB_CLIENT.fetch_by_wrapper::<Option<WorkspaceMember>>("", &w).await?;
+    Ok(m)
+}
+
+pub async fn get_scope(code: &str) -> Result<OwnerScope> {
+    let u = token::get_cached_user(code, false).await?;
+    let scope = match fetch_member(u.id).await? {
+        Some(m) => OwnerScope {
+            user: u,
+            workspace_id: m.workspace_id,
+            role: m.role,
+        },
+        // 没有加入团队时，自己就是自己数据的所有者
+        None => OwnerScope {
+            user: u,
+            workspace_id: 0,
+            role: WorkspaceRole::Owner,
+        },
+    };
+    debug!("{:?}", scope);
+    Ok(scope)
+}
+
+pub async fn create_workspace(code: String, req: CreateWorkspaceReq) -> Result<Workspace> {
+    req.validate()?;
+    let u = token::get_cached_user(code.as_str(), false).await?;
+    if fetch_member(u.id).await?.is_some() {
+        return Err(MiaoshaError::OpError(EOpError::AlreadyInWorkspace(u.id)));
+    }
+    let tx = DB_CLIENT.begin_tx().await?;
+    // 团队、所有者和个人数据的归属要么都成功，要么都不生效
+    let ws = match save_workspace(tx.as_str(), u.id, req.name).await {
+        Ok(ws) => ws,
+        Err(e) => {
+            DB_CLIENT.rollback(tx.as_str()).await?;
+            return Err(e);
+        }
+    };
+    DB_CLIENT.commit(tx.as_str()).await?;
+    info!("Created workspace:{}-{} by {}", ws.id.unwrap_or_default(), ws.name.as_str(), u.id);
+    Ok(ws)
+}
+
+async fn save_workspace(tx: &str, user_id: IDType, name: String) -> Result<Workspace> {
+    let now = PKLocal::now();
+    let ws = Workspace {
+        id: None,
+        name,
+        owner_id: user_id,
+        create_time: Some(now.clone()),
+        update_time: Some(now.clone()),
+    };
+    DB_CLIENT.save(tx, &ws).await?;
+    // 在同一个事务里，查询最新插入的数据
+    let ws: Workspace = DB_CLIENT.fetch(
+        tx, "select * from workspace where id = (select LAST_INSERT_ID())").await?;
+    let workspace_id = ws.id.unwrap();
+    let member = WorkspaceMember {
+        id: None,
+        workspace_id,
+        user_id,
+        role: WorkspaceRole::Owner,
+        create_time: Some(now.clone()),
+        update_time: Some(now.clone()),
+    };
+    DB_CLIENT.save(tx, &member).await?;
+    // 把个人的购物车和账号都归到新建的团队下
+    let args = vec![json!(workspace_id), json!(datetime_fmt(&now)), json!(user_id)];
+    DB_CLIENT.exec_prepare(
+        tx,
+        "update shopping_cart set workspace_id=?, update_time=? where user_id=? and workspace_id=0",
+        &args).await?;
+    DB_CLIENT.exec_prepare(
+        tx,
+        "update platform_account set workspace_id=?, update_time=? where user_id=? and workspace_id=0",
+        &args).await?;
+    Ok(ws)
+}
+
+pub async fn workspace_info(code: String) -> Result<Value> {
+    let scope = get_scope(code.as_str()).await?;
+    if scope.workspace_id == 0 {
+        return Err(MiaoshaError::OpError(EOpError::NotInWorkspace(scope.user.id)));
+    }
+    let ws: Workspace = DB_CLIENT.fetch_by_id("", &scope.workspace_id).await?;
+    let w = DB_CLIENT.new_wrapper()
+        .eq("workspace_id", scope.workspace_id)
+        .order_by(true, &["role", "id"]);
+    let members = DB_CLIENT.fetch_list_by_wrapper::<WorkspaceMember>("", &w).await?;
+    Ok(json!({
+        "workspace": ws,
+        "role": scope.role.into_u8(),
+        "members": members,
+    }))
+}
+
+pub async fn create_invitation(code: String, req: CreateInvitationReq) -> Result<String> {
+    req.validate()?;
+    let scope = get_scope(code.as_str()).await?;
+    scope.check_manage()?;
+    let role: WorkspaceRole = req.role.into();
+    if role < scope.role {
+        // 不能邀请比自己权限更大的角色
+        return Err(MiaoshaError::OpError(EOpError::WorkspacePermissionDenied(scope.user.id)));
+    }
+    let now = PKLocal::now();
+    let salt: u64 = rand::thread_rng().gen();
+    let invite_code = format!("{:X}", md5::compute(
+        format!("{}-{}-{}-{}", scope.workspace_id, scope.user.id, now.timestamp_millis(), salt)));
+    let invitation = WorkspaceInvitation {
+        id: None,
+        workspace_id: scope.workspace_id,
+        inviter_id: scope.user.id,
+        invite_code: invite_code.clone(),
+        role,
+        status: 0,
+        expire_dt: now.checked_add_signed(Duration::seconds(INVITATION_EXPIRE_SECONDS)),
+        create_time: Some(now.clone()),
+        update_time: Some(now.clone()),
+    };
+    DB_CLIENT.save("", &invitation).await?;
+    Ok(json!({"invite_code":invite_code.as_str()}).to_string())
+}
+
+pub async fn join_workspace(code: String, req: JoinWorkspaceReq) -> Result<()> {
+    req.validate()?;
+    let u = token::get_cached_user(code.as_str(), false).await?;
+    if fetch_member(u.id).await?.is_some() {
+        return Err(MiaoshaError::OpError(EOpError::AlreadyInWorkspace(u.id)));
+    }
+    let tx = DB_CLIENT.begin_tx().await?;
+    let workspace_id = match use_invitation(tx.as_str(), u.id, req.invite_code.as_str()).await {
+        Ok(workspace_id) => workspace_id,
+        Err(e) => {
+            DB_CLIENT.rollback(tx.as_str()).await?;
+            return Err(e);
+        }
+    };
+    DB_CLIENT.commit(tx.as_str()).await?;
+    info!("User {} joined workspace:{}", u.id, workspace_id);
+    Ok(())
+}
+
+async fn use_invitation(tx: &str, user_id: IDType, invite_code: &str) -> Result<IDType> {
+    let invalid = || MiaoshaError::OpError(EOpError::InvalidInvitation(invite_code.to_string()));
+    let now = PKLocal::now();
+    // 锁住邀请码，同时使用同一个邀请码的请求排队
+    let invitation: Option<WorkspaceInvitation> = DB_CLIENT.fetch_prepare(
+        tx,
+        "select * from workspace_invitation where invite_code=? and status=0 limit 1 for update",
+        &vec![json!(invite_code)]).await?;
+    let invitation = match invitation {
+        Some(inv) if inv.expire_dt.map(|dt| dt > now).unwrap_or(false) => inv,
+        _ => return Err(invalid()),
+    };
+    // 邀请码只能使用一次
+    let r = DB_CLIENT.exec_prepare(
+        tx,
+        "update workspace_invitation set status=1, update_time=? where id=? and status=0",
+        &vec![json!(datetime_fmt(&now)), json!(invitation.id)]).await?;
+    if r.rows_affected != 1 {
+        return Err(invalid());
+    }
+    let member = WorkspaceMember {
+        id: None,
+        workspace_id: invitation.workspace_id,
+        user_id,
+        role: invitation.role,
+        create_time: Some(now.clone()),
+        update_time: Some(now.clone()),
+    };
+    DB_CLIENT.save(tx, &member).await?;
+    Ok(invitation.workspace_id)
+}
+
+async fn fetch_workspace_member(workspace_id: IDType, user_id: IDType) -> Result<WorkspaceMember> {
+    match fetch_member(user_id).await? {
+        Some(m) if m.workspace_id == workspace_id => Ok(m),
+        _ => Err(MiaoshaError::OpError(EOpError::NotInWorkspace(user_id))),
+    }
+}
+
+pub async fn update_member(code: String, user_id: IDType, req: UpdateMemberReq) -> Result<()> {
+    req.validate()?;
+    let scope = get_scope(code.as_str()).await?;
+    scope.check_manage()?;
+    let member = fetch_workspace_member(scope.workspace_id, user_id).await?;
+    let role: WorkspaceRole = req.role.into();
+    // 不能修改所有者，也不能把别人提升到比自己更高的角色
+    if member.role == WorkspaceRole::Owner || member.role < scope.role || role < scope.role {
+        return Err(MiaoshaError::OpError(EOpError::WorkspacePermissionDenied(scope.user.id)));
+    }
+    let sql = "update workspace_member set role=?, update_time=? where id=?";
+    let args = vec![json!(role.into_u8()), json!(datetime_fmt(&PKLocal::now())), json!(member.id)];
+    DB_CLIENT.exec_prepare("", sql, &args).await?;
+    Ok(())
+}
+
+pub async fn remove_member(code: String, user_id: IDType) -> Result<()> {
+    let scope = get_scope(code.as_str()).await?;
+    let member = fetch_workspace_member(scope.workspace_id, user_id).await?;
+    if member.role == WorkspaceRole::Owner {
+        // 所有者不能退出团队
+        return Err(MiaoshaError::OpError(EOpError::WorkspacePermissionDenied(scope.user.id)));
+    }
+    if user_id != scope.user.id {
+        // 移除别人需要管理权限，且只能移除权限不高于自己的成员
+        scope.check_manage()?;
+        if member.role < scope.role {
+            return Err(MiaoshaError::OpError(EOpError::WorkspacePermissionDenied(scope.user.id)));
+        }
+    }
+    let w = DB_CLIENT.new_wrapper().eq("id", member.id);
+    DB_CLIENT.remove_by_wrapper::<WorkspaceMember>("", &w).await?;
+    info!("User {} left workspace:{}", user_id, scope.workspace_id);
+    Ok(())
+}
@@ -1,4 +1,6 @@
+use std::convert::TryFrom;
 use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use serde::de::Error as DeError;
 
 #[derive(Copy, Clone, Debug)]
 #[repr(u8)]
@@ -26,15 +28,23 @@ impl Platform {
             D: Deserializer<'de>,
     {
         let s = u8::deserialize(deserializer)?;
-        Ok(s.into())
+        Platform::try_from(s).map_err(DeError::custom)
+    }
+
+    // 目前支持的所有平台
+    pub fn all() -> &'static [Platform] {
+        &[Platform::JD]
     }
 }
 
-impl From<u8> for Platform {
-    fn from(level: u8) -> Self {
-        match level {
-            0 => Platform::JD,
-            _ => unreachable!()
+impl TryFrom<u8> for Platform {
+    type Error = String;
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(Platform::JD),
+            // 数据损坏或者新版本才有的平台: 返回错误而不是 panic, 但含有这一行的整页数据仍然会反序列化失败
+            _ => Err(format!("unknown platform: {}", v))
         }
     }
 }
@@ -186,17 +196,24 @@ impl From<VipLevel> for u8 {
 
 
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
 #[repr(u8)]
-pub enum Category {
-    Computer = 0
+pub enum WorkspaceRole {
+    // 数值越小权限越大
+    Owner = 0,
+    Admin = 1,
+    Member = 2,
+    Viewer = 3,
 }
 
 
-impl Category {
+impl WorkspaceRole {
     pub fn into_u8(self) -> u8 {
         match self {
-            Category::Computer => 0,
+            WorkspaceRole::Owner => 0,
+            WorkspaceRole::Admin => 1,
+            WorkspaceRole::Member => 2,
+            WorkspaceRole::Viewer => 3,
         }
     }
 
@@ -207,34 +224,44 @@ impl Category {
         serializer.serialize_u8(self.into_u8())
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Category, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<WorkspaceRole, D::Error>
         where
             D: Deserializer<'de>,
     {
         let s = u8::deserialize(deserializer)?;
         Ok(s.into())
     }
+
+    // 能否管理成员和邀请
+    pub fn can_manage(self) -> bool {
+        self <= WorkspaceRole::Admin
+    }
+
+    // 能否修改购物车和账号
+    pub fn can_write(self) -> bool {
+        self <= WorkspaceRole::Member
+    }
 }
 
-impl From<u8> for Category {
-    fn from(level: u8) -> Self {
-        match level {
-            0 => Category::Computer,
-            _ => unreachable!()
+impl From<u8> for WorkspaceRole {
+    fn from(role: u8) -> Self {
+        match role {
+            0 => WorkspaceRole::Owner,
+            1 => WorkspaceRole::Admin,
+            2 => WorkspaceRole::Member,
+            _ => WorkspaceRole::Viewer
         }
     }
 }
 
-impl From<Category> for u8 {
-    fn from(pt: Category) -> u8 {
-        match pt {
-            Category::Computer => 0,
-        }
+impl From<WorkspaceRole> for u8 {
+    fn from(role: WorkspaceRole) -> u8 {
+        role.into_u8()
     }
 }
 
-impl Default for Category {
+impl Default for WorkspaceRole {
     fn default() -> Self {
-        Category::Computer
+        WorkspaceRole::Member
     }
-}
\ No newline at end of file
+}
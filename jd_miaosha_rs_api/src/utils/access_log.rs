@@ -0,0 +1,60 @@
+use std::cell::Cell;
+use std::time::Instant;
+use log::info;
+use serde_json::json;
+use tide::{Middleware, Next, Request};
+use crate::IDType;
+
+pub const REQUEST_ID_HEADER: &'static str = "X-Request-Id";
+// 请求id的最大长度, 超过的话由服务端重新生成
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+async_std::task_local! {
+    // 当前请求对应的用户id, 在校验激活码时设置
+    static CURRENT_USER_ID: Cell<Option<IDType>> = Cell::new(None);
+}
+
+pub fn set_current_user_id(user_id: IDType) {
+    let _ = CURRENT_USER_ID.try_with(|c| c.set(Some(user_id)));
+}
+
+fn new_request_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+/// 访问日志: 每个请求输出一行 json, 包含请求id、路由、用户id、耗时和状态码
+#[derive(Default)]
+pub struct AccessLogMiddleware;
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for AccessLogMiddleware {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let start = Instant::now();
+        // 优先使用客户端生成的请求id, 这样客户端和服务端的日志可以对应起来
+        let request_id = req.header(REQUEST_ID_HEADER)
+            .map(|v| v.last().as_str().trim().to_string())
+            .filter(|v| v.len() > 0 && v.len() <= MAX_REQUEST_ID_LEN)
+            .unwrap_or_else(new_request_id);
+        req.set_ext(RequestId(request_id.clone()));
+        let method = req.method().to_string();
+        let route = req.url().path().to_string();
+        let _ = CURRENT_USER_ID.try_with(|c| c.set(None));
+        let mut res = next.run(req).await;
+        res.insert_header(REQUEST_ID_HEADER, request_id.as_str());
+        let user_id = CURRENT_USER_ID.try_with(|c| c.get()).unwrap_or(None);
+        let status = res.status();
+        info!(target: "access", "{}", json!({
+            "request_id": request_id,
+            "method": method,
+            "route": route,
+            "user_id": user_id,
+            "status": status as u16,
+            "latency_ms": start.elapsed().as_millis() as u64,
+            "error": res.error().map(|e| e.to_string()),
+        }));
+        Ok(res)
+    }
+}
@@ -1,5 +1,6 @@
 pub mod token;
 pub mod check;
+pub mod access_log;
 
 use chrono::prelude::*;
 use serde::Serializer;
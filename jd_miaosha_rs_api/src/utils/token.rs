@@ -7,6 +7,7 @@ use crate::models::CachedUserInfo;
 use crate::error::{Result, MiaoshaError, EOpError};
 use crate::{PKDateTime, PKLocal, IDType, get_redis_conn};
 use crate::access::{Role, Permission};
+use super::access_log;
 
 const PRIVATE_KEY: &'static str = "ruwoeiruwoccxczd5646";
 
@@ -102,6 +103,7 @@ pub async fn get_cached_user(code:&str, is_activate:bool) -> Result<CachedUserIn
                 debug!("is_expired:{}", u.id);
                 return Err(MiaoshaError::OpError(EOpError::ActivationCodeExpired(code.to_owned())));
             }
+            access_log::set_current_user_id(u.id);
             Ok(u)
         }
         Err(e) =>{
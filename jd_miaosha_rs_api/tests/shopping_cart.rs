@@ -78,6 +78,7 @@ async fn test_shopping_cart_api() -> tide::Result<()> {
                 "is_stock":1,
                 "ori_price":"12.23",
                 "cur_price":"332.1",
+                "max_price":"400.00",
                 "purchase_type":"normal",
                 "purchase_url":"https://divide.jd.com/user_routing?skuId=100016053734&sn=54e0b3fa205eda63b52ebedb96c71af0&from=pc",
             }
@@ -168,6 +169,46 @@ async fn test_shopping_cart_api() -> tide::Result<()> {
         .await?;
     assert_eq!(req.status(), StatusCode::Ok);
     println!("put api/shopping_cart OP_OUT_OF_STOCK done!");
+    for op in &[11, 10, 12] {
+        // 打开试运行、试运行结束、关闭试运行
+        let mut req = surf::put(connect_addr.as_str())
+            .header("token", code.as_str())
+            .body(json!(
+                {
+                    "op":op,
+                    "yuyue_dt":"",
+                }
+            ))
+            .await?;
+        assert_eq!(req.status(), StatusCode::Ok);
+    }
+    println!("put api/shopping_cart dry_run done!");
+    for body in &[
+        // 设置最高价格、更新价格、价格太高、取消最高价格
+        json!({"op":13, "yuyue_dt":"", "max_price":"300.00"}),
+        json!({"op":14, "yuyue_dt":"", "cur_price":"299.00"}),
+        json!({"op":15, "yuyue_dt":"", "cur_price":"301.00"}),
+        json!({"op":13, "yuyue_dt":""}),
+    ] {
+        let mut req = surf::put(connect_addr.as_str())
+            .header("token", code.as_str())
+            .body(body.clone())
+            .await?;
+        assert_eq!(req.status(), StatusCode::Ok);
+    }
+    for body in &[
+        json!({"op":13, "yuyue_dt":"", "max_price":"abc"}),
+        json!({"op":13, "yuyue_dt":"", "max_price":"-1"}),
+        // 更新价格时必须传价格
+        json!({"op":15, "yuyue_dt":""}),
+    ] {
+        let mut req = surf::put(connect_addr.as_str())
+            .header("token", code.as_str())
+            .body(body.clone())
+            .await?;
+        assert_eq!(req.status(), StatusCode::BadRequest);
+    }
+    println!("put api/shopping_cart max_price done!");
     let connect_addr = format!("http://localhost:48180/api/shopping_cart");
     let mut req = surf::put(connect_addr.as_str())
         .header("token", code.as_str())
@@ -234,4 +275,340 @@ async fn test_shopping_cart_api() -> tide::Result<()> {
     println!("post api/shopping_cart done!");
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[async_std::test]
+async fn test_shopping_cart_claim_api() -> tide::Result<()> {
+    let connect_addr = "http://localhost:48180/api/user/activate/code";
+    let mut req = surf::post(connect_addr)
+        .body(json!(
+            {
+                "level":4
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let v: Value = req.body_json().await?;
+    let code = v["activate_code"].as_str().unwrap_or("").to_string();
+    assert_eq!(code.len(), 32);
+
+    let connect_addr = "http://localhost:48180/api/user/activate";
+    let req = surf::post(connect_addr)
+        .body(json!(
+            {
+                "activate_code":code.as_str(),
+                "mac":"1228A4441A1dffad5B85"
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+
+    let connect_addr = "http://localhost:48180/api/shopping_cart";
+    let sku = format!("claim_{}", Local::now().timestamp_millis());
+    let req = surf::post(connect_addr)
+        .header("token", code.as_str())
+        .body(json!(
+            {
+                "sku":sku.as_str(),
+                "name":"claim_goods",
+                "purchase_num":1,
+                "yuyue_dt":Some(""),
+                "is_stock":1,
+                "ori_price":"12.23",
+                "cur_price":"10.1",
+                "purchase_type":"normal",
+                "purchase_url":"",
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+
+    let connect_addr = format!("http://localhost:48180/api/shopping_cart?key_word={}", sku.as_str());
+    let mut req = surf::get(connect_addr.as_str())
+        .header("token", code.as_str())
+        .await?;
+    let v: Value = req.body_json().await?;
+    let cart_id = v["records"][0]["id"].as_u64().unwrap();
+
+    let connect_addr = format!("http://localhost:48180/api/shopping_cart/claim/{}", cart_id);
+    let req = surf::post(connect_addr.as_str())
+        .header("token", code.as_str())
+        .body(json!({"owner":"client_a", "lease_seconds":60}))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    println!("post api/shopping_cart/claim done!");
+
+    // 同一个客户端可以重复抢占
+    let req = surf::post(connect_addr.as_str())
+        .header("token", code.as_str())
+        .body(json!({"owner":"client_a", "lease_seconds":60}))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+
+    // 其他客户端抢不到
+    let req = surf::post(connect_addr.as_str())
+        .header("token", code.as_str())
+        .body(json!({"owner":"client_b"}))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Conflict);
+
+    let req = surf::put(connect_addr.as_str())
+        .header("token", code.as_str())
+        .body(json!({"owner":"client_a", "lease_seconds":60}))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let req = surf::put(connect_addr.as_str())
+        .header("token", code.as_str())
+        .body(json!({"owner":"client_b"}))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Conflict);
+    println!("put api/shopping_cart/claim done!");
+
+    let req = surf::delete(connect_addr.as_str())
+        .header("token", code.as_str())
+        .body(json!({"owner":"client_a"}))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+
+    let connect_addr = format!("http://localhost:48180/api/shopping_cart?key_word={}", sku.as_str());
+    let mut req = surf::get(connect_addr.as_str())
+        .header("token", code.as_str())
+        .await?;
+    let v: Value = req.body_json().await?;
+    assert_eq!(v["records"][0]["status"].as_str(), Some("ready"));
+    assert_eq!(v["records"][0]["lease_owner"].as_str(), Some(""));
+
+    // 释放后其他客户端就能抢到了
+    let connect_addr = format!("http://localhost:48180/api/shopping_cart/claim/{}", cart_id);
+    let req = surf::post(connect_addr.as_str())
+        .header("token", code.as_str())
+        .body(json!({"owner":"client_b", "lease_seconds":5}))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    println!("delete api/shopping_cart/claim done!");
+
+    // 抢购成功并释放后, 谁都不能再抢
+    let update_addr = format!("http://localhost:48180/api/shopping_cart/{}", cart_id);
+    let req = surf::put(update_addr.as_str())
+        .header("token", code.as_str())
+        .body(json!({"op":5, "yuyue_dt":""}))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let req = surf::delete(connect_addr.as_str())
+        .header("token", code.as_str())
+        .body(json!({"owner":"client_b"}))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    for owner in &["client_a", "client_b"] {
+        let req = surf::post(connect_addr.as_str())
+            .header("token", code.as_str())
+            .body(json!({"owner":owner}))
+            .await?;
+        assert_eq!(req.status(), StatusCode::Conflict);
+    }
+
+    Ok(())
+}
+
+
+#[async_std::test]
+async fn test_shopping_cart_platform_api() -> tide::Result<()> {
+    let connect_addr = "http://localhost:48180/api/user/activate/code";
+    let mut req = surf::post(connect_addr)
+        .body(json!(
+            {
+                "level":4
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let v: Value = req.body_json().await?;
+    let code = v["activate_code"].as_str().unwrap_or("").to_string();
+
+    let connect_addr = "http://localhost:48180/api/user/activate";
+    let req = surf::post(connect_addr)
+        .body(json!(
+            {
+                "activate_code":code.as_str(),
+                "mac":"1228A4441A1dffad5B86"
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+
+    // 未知的平台会被拒绝, 而不是让服务崩掉
+    let connect_addr = "http://localhost:48180/api/shopping_cart";
+    let req = surf::post(connect_addr)
+        .header("token", code.as_str())
+        .body(json!(
+            {
+                "platform":99,
+                "sku":"platform_sku",
+                "name":"platform_goods",
+                "purchase_num":1,
+                "yuyue_dt":Some(""),
+                "is_stock":1,
+                "ori_price":"12.23",
+                "cur_price":"10.1",
+                "purchase_type":"normal",
+                "purchase_url":"",
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::BadRequest);
+
+    let req = surf::post(connect_addr)
+        .header("token", code.as_str())
+        .body(json!(
+            {
+                "platform":0,
+                "sku":"platform_sku",
+                "name":"platform_goods",
+                "purchase_num":1,
+                "yuyue_dt":Some(""),
+                "is_stock":1,
+                "ori_price":"12.23",
+                "cur_price":"10.1",
+                "purchase_type":"normal",
+                "purchase_url":"",
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+
+    let connect_addr = "http://localhost:48180/api/shopping_cart?platform=0";
+    let mut req = surf::get(connect_addr)
+        .header("token", code.as_str())
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let v: Value = req.body_json().await?;
+    assert_eq!(v["records"][0]["platform"].as_u64(), Some(0));
+
+    let connect_addr = "http://localhost:48180/api/shopping_cart?platform=99";
+    let req = surf::get(connect_addr)
+        .header("token", code.as_str())
+        .await?;
+    assert_eq!(req.status(), StatusCode::BadRequest);
+    println!("platform api/shopping_cart done!");
+    Ok(())
+}
+
+#[async_std::test]
+async fn test_shopping_cart_category_api() -> tide::Result<()> {
+    let connect_addr = "http://localhost:48180/api/user/activate/code";
+    let mut req = surf::post(connect_addr)
+        .body(json!(
+            {
+                "level":4
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let v: Value = req.body_json().await?;
+    let code = v["activate_code"].as_str().unwrap_or("").to_string();
+
+    let connect_addr = "http://localhost:48180/api/user/activate";
+    let req = surf::post(connect_addr)
+        .body(json!(
+            {
+                "activate_code":code.as_str(),
+                "mac":"1228A4441A1dffad5B87"
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+
+    let connect_addr = "http://localhost:48180/api/category";
+    let mut req = surf::get(connect_addr)
+        .header("token", code.as_str())
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let v: Value = req.body_json().await?;
+    assert!(v.as_array().map(|a| a.len() > 1).unwrap_or(false));
+
+    // 不存在的分类
+    let connect_addr = "http://localhost:48180/api/shopping_cart";
+    let req = surf::post(connect_addr)
+        .header("token", code.as_str())
+        .body(json!(
+            {
+                "category":200,
+                "sku":"category_sku1",
+                "name":"category_goods",
+                "purchase_num":1,
+                "yuyue_dt":Some(""),
+                "is_stock":1,
+                "ori_price":"12.23",
+                "cur_price":"10.1",
+                "purchase_type":"normal",
+                "purchase_url":"",
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::BadRequest);
+
+    // 根据详情页的分类推断为手机
+    let req = surf::post(connect_addr)
+        .header("token", code.as_str())
+        .body(json!(
+            {
+                "category_hint":"手机通讯,手机,手机",
+                "sku":"category_sku2",
+                "name":"category_goods",
+                "purchase_num":1,
+                "yuyue_dt":Some(""),
+                "is_stock":1,
+                "ori_price":"12.23",
+                "cur_price":"10.1",
+                "purchase_type":"normal",
+                "purchase_url":"",
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+
+    let connect_addr = "http://localhost:48180/api/shopping_cart?category=1";
+    let mut req = surf::get(connect_addr)
+        .header("token", code.as_str())
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let v: Value = req.body_json().await?;
+    assert_eq!(v["records"][0]["sku"].as_str(), Some("category_sku2"));
+    assert_eq!(v["records"][0]["category"].as_u64(), Some(1));
+
+    // 没有详情页的分类时按名称推断, 名称里的 "内存" 不会让手机归到电脑办公
+    let connect_addr = "http://localhost:48180/api/shopping_cart";
+    let req = surf::post(connect_addr)
+        .header("token", code.as_str())
+        .body(json!(
+            {
+                "sku":"category_sku3",
+                "name":"小米12 8GB内存 256GB 5G手机",
+                "purchase_num":1,
+                "yuyue_dt":Some(""),
+                "is_stock":1,
+                "ori_price":"12.23",
+                "cur_price":"10.1",
+                "purchase_type":"normal",
+                "purchase_url":"",
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let connect_addr = "http://localhost:48180/api/shopping_cart?category=1&key_word=category_sku3";
+    let mut req = surf::get(connect_addr)
+        .header("token", code.as_str())
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let v: Value = req.body_json().await?;
+    assert_eq!(v["records"][0]["sku"].as_str(), Some("category_sku3"));
+
+    // 不存在的分类不能用来过滤
+    let connect_addr = "http://localhost:48180/api/shopping_cart?category=200";
+    let req = surf::get(connect_addr)
+        .header("token", code.as_str())
+        .await?;
+    assert_eq!(req.status(), StatusCode::BadRequest);
+    println!("category api/shopping_cart done!");
+    Ok(())
+}
@@ -203,4 +203,26 @@ async fn test_user_heartbeat() -> tide::Result<()> {
     assert_eq!(req.status(), StatusCode::Unauthorized);
     println!("api/user/heartbeat 2 done");
     Ok(())
-}
\ No newline at end of file
+}
+#[async_std::test]
+async fn test_request_id_header() -> tide::Result<()> {
+    // 客户端传的请求id会原样返回, 方便把客户端和服务端的日志对应起来
+    let connect_addr = "http://localhost:48180/api/user/activate/code";
+    let req = surf::post(connect_addr)
+        .header("X-Request-Id", "test-request-id-0001")
+        .body(json!(
+            {
+                "level":4
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    assert_eq!(req.header("X-Request-Id").map(|v| v.last().as_str()), Some("test-request-id-0001"));
+
+    // 没传请求id时由服务端生成
+    let connect_addr = "http://localhost:48180/api/shopping_cart";
+    let req = surf::get(connect_addr).await?;
+    assert_eq!(req.status(), StatusCode::Unauthorized);
+    assert!(req.header("X-Request-Id").map(|v| v.last().as_str().len() > 0).unwrap_or(false));
+    Ok(())
+}
@@ -0,0 +1,230 @@
+use chrono::Local;
+use serde_json::{json, Value, from_slice};
+use tide::StatusCode;
+
+
+async fn new_activated_code(level: u8, mac: &str) -> tide::Result<String> {
+    let connect_addr = "http://localhost:48180/api/user/activate/code";
+    let mut req = surf::post(connect_addr)
+        .body(json!(
+            {
+                "level":level
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let body = req.body_bytes().await?;
+    let v: Value = from_slice(&body)?;
+    let code = v["activate_code"].to_string().trim_matches(|c| c == '\"').to_string();
+    assert_eq!(code.len(), 32);
+
+    let connect_addr = "http://localhost:48180/api/user/activate";
+    let req = surf::post(connect_addr)
+        .body(json!(
+            {
+                "activate_code":code.as_str(),
+                "mac":mac
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    Ok(code)
+}
+
+#[async_std::test]
+async fn test_workspace_api() -> tide::Result<()> {
+    // 两个成员各自使用自己的激活码
+    let owner_code = new_activated_code(4, "1228A4441A1dffad5C01").await?;
+    let member_code = new_activated_code(4, "1228A4441A1dffad5C02").await?;
+
+    let connect_addr = "http://localhost:48180/api/workspace";
+    let req = surf::get(connect_addr)
+        .header("token", owner_code.as_str())
+        .await?;
+    assert_eq!(req.status(), StatusCode::BadRequest);
+    println!("get api/workspace without workspace done!");
+
+    let mut req = surf::post(connect_addr)
+        .header("token", owner_code.as_str())
+        .body(json!(
+            {
+                "name":format!("team_{}", Local::now().timestamp())
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let v: Value = req.body_json().await?;
+    assert!(v["id"].as_u64().unwrap_or(0) > 0);
+    println!("post api/workspace done!");
+
+    let req = surf::post(connect_addr)
+        .header("token", owner_code.as_str())
+        .body(json!(
+            {
+                "name":"another_team"
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::BadRequest);
+    println!("post api/workspace twice done!");
+
+    let connect_addr = "http://localhost:48180/api/workspace/invitation";
+    let mut req = surf::post(connect_addr)
+        .header("token", owner_code.as_str())
+        .body(json!(
+            {
+                "role":2
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let v: Value = req.body_json().await?;
+    let invite_code = v["invite_code"].as_str().unwrap_or("").to_string();
+    assert_eq!(invite_code.len(), 32);
+    println!("post api/workspace/invitation done!");
+
+    let connect_addr = "http://localhost:48180/api/workspace/join";
+    let req = surf::post(connect_addr)
+        .header("token", member_code.as_str())
+        .body(json!(
+            {
+                "invite_code":invite_code.as_str()
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    println!("post api/workspace/join done!");
+
+    // 邀请码只能使用一次
+    let req = surf::post(connect_addr)
+        .header("token", owner_code.as_str())
+        .body(json!(
+            {
+                "invite_code":invite_code.as_str()
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::BadRequest);
+
+    let connect_addr = "http://localhost:48180/api/workspace";
+    let mut req = surf::get(connect_addr)
+        .header("token", member_code.as_str())
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let v: Value = req.body_json().await?;
+    assert_eq!(v["members"].as_array().unwrap().len(), 2);
+    assert_eq!(v["role"].as_u64(), Some(2));
+    let member_id = v["members"][1]["user_id"].as_u64().unwrap();
+    println!("get api/workspace done!");
+
+    // 所有者加的商品，成员也能看到
+    let sku = format!("ws_{}", Local::now().timestamp_millis());
+    let connect_addr = "http://localhost:48180/api/shopping_cart";
+    let req = surf::post(connect_addr)
+        .header("token", owner_code.as_str())
+        .body(json!(
+            {
+                "sku":sku.as_str(),
+                "name":"workspace_goods",
+                "purchase_num":1,
+                "yuyue_dt":Some(""),
+                "is_stock":1,
+                "ori_price":"12.23",
+                "cur_price":"10.1",
+                "purchase_type":"normal",
+                "purchase_url":"",
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+
+    let connect_addr = format!("http://localhost:48180/api/shopping_cart?key_word={}", sku.as_str());
+    let mut req = surf::get(connect_addr.as_str())
+        .header("token", member_code.as_str())
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let v: Value = req.body_json().await?;
+    assert_eq!(v["records"].as_array().unwrap().len(), 1);
+    println!("shared api/shopping_cart done!");
+
+    // 成员在团队里加的商品属于团队
+    let member_sku = format!("ws_member_{}", Local::now().timestamp_millis());
+    let connect_addr = "http://localhost:48180/api/shopping_cart";
+    let req = surf::post(connect_addr)
+        .header("token", member_code.as_str())
+        .body(json!(
+            {
+                "sku":member_sku.as_str(),
+                "name":"workspace_member_goods",
+                "purchase_num":1,
+                "yuyue_dt":Some(""),
+                "is_stock":1,
+                "ori_price":"12.23",
+                "cur_price":"10.1",
+                "purchase_type":"normal",
+                "purchase_url":"",
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+
+    // 只读成员不能修改购物车
+    let connect_addr = format!("http://localhost:48180/api/workspace/member/{}", member_id);
+    let req = surf::put(connect_addr.as_str())
+        .header("token", owner_code.as_str())
+        .body(json!(
+            {
+                "role":3
+            }
+        ))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+
+    let connect_addr = "http://localhost:48180/api/shopping_cart";
+    let req = surf::delete(connect_addr)
+        .header("token", member_code.as_str())
+        .body(json!({
+            "ids":null
+        }))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Forbidden);
+    println!("viewer api/shopping_cart done!");
+
+    // 成员自己退出团队后，就看不到团队的数据了
+    let connect_addr = format!("http://localhost:48180/api/workspace/member/{}", member_id);
+    let req = surf::delete(connect_addr.as_str())
+        .header("token", member_code.as_str())
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+
+    let connect_addr = format!("http://localhost:48180/api/shopping_cart?key_word={}", sku.as_str());
+    let mut req = surf::get(connect_addr.as_str())
+        .header("token", member_code.as_str())
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let v: Value = req.body_json().await?;
+    assert_eq!(v["records"].as_array().unwrap().len(), 0);
+
+    // 自己在团队里加的也看不到、删不掉
+    let connect_addr = format!("http://localhost:48180/api/shopping_cart?key_word={}", member_sku.as_str());
+    let mut req = surf::get(connect_addr.as_str())
+        .header("token", member_code.as_str())
+        .await?;
+    let v: Value = req.body_json().await?;
+    assert_eq!(v["records"].as_array().unwrap().len(), 0);
+    let req = surf::delete("http://localhost:48180/api/shopping_cart")
+        .header("token", member_code.as_str())
+        .body(json!({
+            "ids":null
+        }))
+        .await?;
+    assert_eq!(req.status(), StatusCode::Ok);
+    let mut req = surf::get(connect_addr.as_str())
+        .header("token", owner_code.as_str())
+        .await?;
+    let v: Value = req.body_json().await?;
+    assert_eq!(v["records"].as_array().unwrap().len(), 1);
+    println!("delete api/workspace/member done!");
+
+    Ok(())
+}